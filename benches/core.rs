@@ -0,0 +1,101 @@
+// Benchmarks for the emulation hot paths, one emulated frame (70224 T-cycles)
+// per iteration on synthetic workloads. Run with `cargo bench`.
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+
+use gb_rs::cpu::CPU;
+use gb_rs::mmu::{MMU, Model};
+use gb_rs::ppu::PPU;
+
+const FRAME_CYCLES: u32 = 70224;
+
+// A tight loop of ALU ops, memory stores and branches
+const LOOP: [u8; 18] = [
+    0x3E, 0x01,       // 0x100: LD A,1
+    0x21, 0x00, 0xC0, // 0x102: LD HL,C000
+    0x80,             // 0x105: ADD A,B
+    0x04,             //        INC B
+    0x22,             //        LD (HL+),A
+    0xA9,             //        XOR C
+    0x15,             //        DEC D
+    0x20, 0xF9,       //        JR NZ,0x105
+    0x21, 0x00, 0xC0, // 0x10C: LD HL,C000
+    0xC3, 0x05, 0x01, // 0x10F: JP 0x105
+];
+
+fn machine(program: &[u8]) -> MMU {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x100 + program.len()].copy_from_slice(program);
+    MMU::new(rom, "bench.gb", Model::Dmg)
+}
+
+fn cpu_dispatch(c: &mut Criterion) {
+    let mut cpu = CPU::new(machine(&LOOP));
+    c.bench_function("cpu: one frame of instructions", |b| {
+        b.iter(|| {
+            let mut cycles = 0;
+            while cycles < FRAME_CYCLES {
+                let t = cpu.step().expect("LOOP only uses implemented opcodes");
+                cycles += t as u32;
+            }
+        })
+    });
+}
+
+fn ppu_scanlines(c: &mut Criterion) {
+    let mut mmu = machine(&[]);
+    // Busy tiles and maps, window on, a full OAM of sprites
+    for addr in 0x8000..0xA000u16 {
+        mmu.write_byte(addr, (addr as u8).wrapping_mul(37));
+    }
+    for i in 0..40u16 {
+        let y = 16 + (i as u8 * 13) % 144;
+        let x = 8 + (i as u8 * 29) % 160;
+        for (k, v) in [y, x, i as u8, (i as u8 & 1) << 5].into_iter().enumerate() {
+            mmu.oam[i as usize * 4 + k] = v;
+        }
+    }
+    mmu.write_byte(0xFF40, 0xF3); // LCD, window, BG, sprites on
+    mmu.write_byte(0xFF4A, 72);
+    mmu.write_byte(0xFF4B, 87);
+
+    let mut ppu = PPU::new();
+    c.bench_function("ppu: one frame of scanlines", |b| {
+        b.iter(|| {
+            for _ in 0..FRAME_CYCLES / 4 {
+                ppu.tick(&mut mmu, 4);
+            }
+            black_box(&ppu.frame_buffer);
+        })
+    });
+}
+
+fn apu_tick(c: &mut Criterion) {
+    let mut mmu = machine(&[]);
+    // All four channels playing
+    for (addr, val) in [
+        (0xFF26, 0x80), (0xFF24, 0x77), (0xFF25, 0xFF),
+        (0xFF11, 0x80), (0xFF12, 0xF0), (0xFF13, 0x00), (0xFF14, 0x87),
+        (0xFF16, 0x40), (0xFF17, 0xF0), (0xFF18, 0x80), (0xFF19, 0x86),
+        (0xFF1A, 0x80), (0xFF1C, 0x20), (0xFF1D, 0x00), (0xFF1E, 0x87),
+        (0xFF21, 0xF0), (0xFF22, 0x55), (0xFF23, 0x80),
+    ] {
+        mmu.write_byte(addr, val);
+    }
+    for i in 0..16u16 {
+        mmu.write_byte(0xFF30 + i, (i as u8) * 0x11);
+    }
+
+    c.bench_function("apu: one frame of ticks", |b| {
+        b.iter(|| {
+            for _ in 0..FRAME_CYCLES / 4 {
+                mmu.apu.tick(4);
+            }
+            black_box(mmu.apu.sample_buffer.len());
+            mmu.apu.sample_buffer.clear();
+        })
+    });
+}
+
+criterion_group!(benches, cpu_dispatch, ppu_scanlines, apu_tick);
+criterion_main!(benches);
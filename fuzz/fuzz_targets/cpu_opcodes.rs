@@ -0,0 +1,24 @@
+// Random instruction streams: the input becomes a 32KB ROM starting at the
+// entry point, and the machine runs for a bounded number of steps.
+// Run with `cargo fuzz run cpu_opcodes` (nightly).
+#![no_main]
+
+use gb_rs::cpu::CPU;
+use gb_rs::mmu::{MMU, Model};
+use libfuzzer_sys::fuzz_target;
+
+const MAX_STEPS: usize = 20_000;
+
+fuzz_target!(|data: &[u8]| {
+    let mut rom = vec![0u8; 0x8000];
+    let len = data.len().min(rom.len() - 0x100);
+    rom[0x100..0x100 + len].copy_from_slice(&data[..len]);
+
+    let mut cpu = CPU::new(MMU::new(rom, "fuzz.gb", Model::Dmg));
+    cpu.bus.serial_log = Some(Vec::new());
+    for _ in 0..MAX_STEPS {
+        // An unimplemented opcode is reported, not a crash
+        if cpu.step().is_err() { break }
+        cpu.handle_interrupts();
+    }
+});
@@ -0,0 +1,26 @@
+// Random bus traffic: the input is a list of (address, value) writes, each
+// followed by a read back and a few cycles of timers, APU and PPU, so odd
+// register combinations (LCDC, STAT, NRxx, MBC banking...) get rendered and
+// ticked. Run with `cargo fuzz run mmu_writes` (nightly).
+#![no_main]
+
+use gb_rs::mmu::{MMU, Model};
+use gb_rs::ppu::PPU;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte picks the cartridge type, so MBC paths are covered too
+    let Some((&mbc, writes)) = data.split_first() else { return };
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x147] = mbc;
+
+    let mut mmu = MMU::new(rom, "fuzz.gb", Model::Dmg);
+    let mut ppu = PPU::new();
+    mmu.serial_log = Some(Vec::new());
+    for w in writes.chunks_exact(3) {
+        let addr = u16::from_le_bytes([w[0], w[1]]);
+        mmu.write_byte(addr, w[2]);
+        let _ = mmu.read_byte(addr);
+        mmu.tick(4); mmu.apu.tick(4); ppu.tick(&mut mmu, 4);
+    }
+});
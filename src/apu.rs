@@ -1,4 +1,28 @@
 // apu.rs
+use crate::mmu::Model;
+use crate::savestate::{StateReader, StateWriter};
+use crate::vgm::VgmRecorder;
+
+// Number of samples kept per channel for the oscilloscope
+pub const SCOPE_LEN: usize = 512;
+
+// External audio fed in through the cartridge VIN pin. Implementors return one
+// sample (-1.0..=1.0) per output sample; NR50 bits 7/3 route it left/right.
+// What's heard comes from the audio thread's APU: the frontend hands a source
+// attached here over to it.
+pub trait VinSource: Send {
+    fn next_sample(&mut self) -> f32;
+}
+
+// A register write timestamped in T-cycles since power-on, replayed by the
+// audio thread's synthesizer
+#[derive(Clone, Copy, Debug)]
+pub struct ApuWrite {
+    pub cycle: u64,
+    pub addr: u16,
+    pub val: u8,
+}
+
 pub struct APU {
     // Channel 1: Square wave with sweep
     pub nr10: u8, // 0xFF10 - Sweep
@@ -31,6 +55,8 @@ pub struct APU {
     pub nr50: u8, // 0xFF24 - Master volume
     pub nr51: u8, // 0xFF25 - Sound panning
     pub nr52: u8, // 0xFF26 - Sound on/off
+
+    pub model: Model, // Power/wave RAM quirks differ between DMG and CGB
     
     // Internal state
     ch1_frequency_timer: u16,
@@ -53,6 +79,8 @@ pub struct APU {
     ch3_position: u8,
     ch3_length_counter: u16,
     ch3_enabled: bool,
+    ch3_sample_buffer: u8,       // Last wave RAM byte fetched by the channel
+    ch3_cycles_since_read: u16,  // CPU can only reach wave RAM right after a fetch
     
     ch4_lfsr: u16, // Linear feedback shift register
     ch4_frequency_timer: u16,
@@ -64,17 +92,119 @@ pub struct APU {
     frame_sequencer: u8,
     frame_sequencer_timer: u32,
     
-    // Audio buffer
+    // Audio buffer, only filled (along with the scope) while capture_samples
+    // is set; otherwise just the channels run
     pub sample_buffer: Vec<f32>,
+    pub capture_samples: bool,
+    pub sample_period: f32, // T-cycles per sample: the output rate is 4194304 / this
     sample_timer: f32,
    ch1_sweep_neg_mode: bool, // "Taint" flag
     ch1_sweep_enabled: bool,  // Latch flag
     ch1_sweep_period_was_zero: bool, // Track if period was 0 at trigger
+
+    // VGM capture of register writes (None when not recording)
+    pub vgm: Option<VgmRecorder>,
+
+    // Register write log for the audio thread (see audio.rs)
+    pub record_writes: bool,
+    pending_writes: Vec<ApuWrite>,
+    pub cycle_count: u64, // T-cycles since power-on
+
+    // Cartridge audio input (VIN). None = pin left floating (silent)
+    pub vin: Option<Box<dyn VinSource>>,
+
+    // Oscilloscope history: ch1-ch4 outputs + mixed, ring-buffered at scope_pos
+    pub scope: [[f32; SCOPE_LEN]; 5],
+    pub scope_pos: usize,
+}
+
+// Snapshot of one channel's internal state for the debug panel
+pub struct ChannelState {
+    pub enabled: bool,
+    pub frequency: u16,          // 11-bit period value (ch4: 0)
+    pub timer: u16,              // Frequency timer
+    pub volume: u8,              // Current envelope volume (ch3: output level code)
+    pub envelope_timer: Option<u8>,
+    pub length_counter: u16,
+    pub sweep_shadow: Option<u16>,
+    pub position: u8,            // Duty step (ch1/2), wave position (ch3), LFSR bit 0 (ch4)
+    pub lfsr: Option<u16>,
+    pub registers: [Option<u8>; 5], // NRx0-NRx4 as written (None where the channel has no such register)
+    pub dac_on: bool,
+    pub length_enabled: bool,    // NRx4 bit 6: the length counter silences the channel
+    pub sweep_timer: Option<u8>, // Only while sweep is enabled
 }
 
 impl APU {
-    pub fn new() -> Self {
+    pub fn channel_states(&self) -> [ChannelState; 4] {
+        let freq = |lo: u8, hi: u8| ((hi as u16 & 0x07) << 8) | lo as u16;
+        [
+            ChannelState {
+                enabled: self.ch1_enabled,
+                frequency: freq(self.nr13, self.nr14),
+                timer: self.ch1_frequency_timer,
+                volume: self.ch1_volume,
+                envelope_timer: Some(self.ch1_envelope_timer),
+                length_counter: self.ch1_length_counter as u16,
+                sweep_shadow: Some(self.ch1_sweep_shadow),
+                position: self.ch1_duty_position,
+                lfsr: None,
+                registers: [Some(self.nr10), Some(self.nr11), Some(self.nr12), Some(self.nr13), Some(self.nr14)],
+                dac_on: self.nr12 & 0xF8 != 0,
+                length_enabled: self.nr14 & 0x40 != 0,
+                sweep_timer: self.ch1_sweep_enabled.then_some(self.ch1_sweep_timer),
+            },
+            ChannelState {
+                enabled: self.ch2_enabled,
+                frequency: freq(self.nr23, self.nr24),
+                timer: self.ch2_frequency_timer,
+                volume: self.ch2_volume,
+                envelope_timer: Some(self.ch2_envelope_timer),
+                length_counter: self.ch2_length_counter as u16,
+                sweep_shadow: None,
+                position: self.ch2_duty_position,
+                lfsr: None,
+                registers: [None, Some(self.nr21), Some(self.nr22), Some(self.nr23), Some(self.nr24)],
+                dac_on: self.nr22 & 0xF8 != 0,
+                length_enabled: self.nr24 & 0x40 != 0,
+                sweep_timer: None,
+            },
+            ChannelState {
+                enabled: self.ch3_enabled,
+                frequency: freq(self.nr33, self.nr34),
+                timer: self.ch3_frequency_timer,
+                volume: (self.nr32 >> 5) & 0x03,
+                envelope_timer: None,
+                length_counter: self.ch3_length_counter,
+                sweep_shadow: None,
+                position: self.ch3_position,
+                lfsr: None,
+                registers: [Some(self.nr30), Some(self.nr31), Some(self.nr32), Some(self.nr33), Some(self.nr34)],
+                dac_on: self.nr30 & 0x80 != 0,
+                length_enabled: self.nr34 & 0x40 != 0,
+                sweep_timer: None,
+            },
+            ChannelState {
+                enabled: self.ch4_enabled,
+                frequency: 0,
+                timer: self.ch4_frequency_timer,
+                volume: self.ch4_volume,
+                envelope_timer: Some(self.ch4_envelope_timer),
+                length_counter: self.ch4_length_counter as u16,
+                sweep_shadow: None,
+                position: (self.ch4_lfsr & 0x01) as u8,
+                lfsr: Some(self.ch4_lfsr),
+                registers: [None, Some(self.nr41), Some(self.nr42), Some(self.nr43), Some(self.nr44)],
+                dac_on: self.nr42 & 0xF8 != 0,
+                length_enabled: self.nr44 & 0x40 != 0,
+                sweep_timer: None,
+            },
+        ]
+    }
+
+    pub fn new(model: Model) -> Self {
         Self {
+            model,
             nr10: 0, nr11: 0, nr12: 0, nr13: 0, nr14: 0,
             nr21: 0, nr22: 0, nr23: 0, nr24: 0,
             nr30: 0, nr31: 0, nr32: 0, nr33: 0, nr34: 0,
@@ -102,6 +232,8 @@ impl APU {
             ch3_position: 0,
             ch3_length_counter: 0,
             ch3_enabled: false,
+            ch3_sample_buffer: 0,
+            ch3_cycles_since_read: u16::MAX,
             
             ch4_lfsr: 0x7FFF,
             ch4_frequency_timer: 0,
@@ -114,14 +246,60 @@ impl APU {
             frame_sequencer_timer: 0,
             
             sample_buffer: Vec::with_capacity(4096),
+            capture_samples: false,
+            sample_period: 87.0, // ~48.2 kHz
             sample_timer: 0.0,
            ch1_sweep_neg_mode: false, // Starts clean (not tainted by subtraction)
             ch1_sweep_enabled: false,  // Starts disabled
             ch1_sweep_period_was_zero: false,
+            vgm: None,
+            record_writes: false,
+            pending_writes: Vec::new(),
+            cycle_count: 0,
+            vin: None,
+            scope: [[0.0; SCOPE_LEN]; 5],
+            scope_pos: 0,
+        }
+    }
+
+    pub fn start_vgm_recording(&mut self, path: &str) {
+        let mut vgm = VgmRecorder::new(path);
+
+        // Dump the current register state first so playback starts from the
+        // same point. Trigger bits are masked so nothing restarts on replay.
+        vgm.log_write(0xFF26, self.nr52 & 0x80);
+        for (i, &b) in self.wave_ram.iter().enumerate() {
+            vgm.log_write(0xFF30 + i as u16, b);
+        }
+        let regs = [
+            (0xFF10, self.nr10), (0xFF11, self.nr11), (0xFF12, self.nr12), (0xFF13, self.nr13), (0xFF14, self.nr14 & 0x7F),
+            (0xFF16, self.nr21), (0xFF17, self.nr22), (0xFF18, self.nr23), (0xFF19, self.nr24 & 0x7F),
+            (0xFF1A, self.nr30), (0xFF1B, self.nr31), (0xFF1C, self.nr32), (0xFF1D, self.nr33), (0xFF1E, self.nr34 & 0x7F),
+            (0xFF20, self.nr41), (0xFF21, self.nr42), (0xFF22, self.nr43), (0xFF23, self.nr44 & 0x7F),
+            (0xFF24, self.nr50), (0xFF25, self.nr51),
+        ];
+        for (addr, val) in regs {
+            vgm.log_write(addr, val);
+        }
+
+        println!("● Recording VGM to '{}'", path);
+        self.vgm = Some(vgm);
+    }
+
+    pub fn stop_vgm_recording(&mut self) {
+        if let Some(vgm) = self.vgm.take()
+            && let Err(e) = vgm.finish()
+        {
+            eprintln!("Failed to write VGM: {}", e);
         }
     }
     
     pub fn tick(&mut self, cycles: u8) {
+        self.cycle_count += cycles as u64;
+        if let Some(vgm) = &mut self.vgm {
+            vgm.advance(cycles as u32);
+        }
+
         if (self.nr52 & 0x80) == 0 {
             return; // APU is disabled
         }
@@ -133,21 +311,42 @@ impl APU {
             self.clock_frame_sequencer();
         }
         
-        // Clock all channels
-        for _ in 0..cycles {
-            self.clock_channel1();
-            self.clock_channel2();
-            self.clock_channel3();
-            self.clock_channel4();
-            
-            // Generate sample at ~48kHz (every ~87 cycles)
-            self.sample_timer += 1.0;
-            if self.sample_timer >= 87.0 {
-                self.sample_timer -= 87.0;
+        // Clock all channels in batches: jump straight to the next cycle where a
+        // frequency timer expires or a sample is due instead of stepping every
+        // T-cycle. Each clock_channelN(n) requires n <= its timer + 1.
+        let mut remaining = cycles as u32;
+        while remaining > 0 {
+            let until_sample = if self.capture_samples { (self.sample_period - self.sample_timer).ceil().max(1.0) as u32 } else { u32::MAX };
+            let ch3_active = self.ch3_enabled && (self.nr30 & 0x80) != 0;
+            let step = remaining
+                .min(until_sample)
+                .min(Self::cycles_until_clock(self.ch1_frequency_timer, self.ch1_enabled))
+                .min(Self::cycles_until_clock(self.ch2_frequency_timer, self.ch2_enabled))
+                .min(Self::cycles_until_clock(self.ch3_frequency_timer, ch3_active))
+                .min(Self::cycles_until_clock(self.ch4_frequency_timer, self.ch4_enabled));
+
+            self.clock_channel1(step);
+            self.clock_channel2(step);
+            self.clock_channel3(step);
+            self.clock_channel4(step);
+            remaining -= step;
+
+            // A sample every sample_period cycles, the fraction carried over
+            if !self.capture_samples {
+                continue;
+            }
+            self.sample_timer += step as f32;
+            if self.sample_timer >= self.sample_period {
+                self.sample_timer -= self.sample_period;
                 self.generate_sample();
             }
         }
     }
+
+    // A timer at t reloads on its (t + 1)th clock
+    fn cycles_until_clock(timer: u16, active: bool) -> u32 {
+        if active { timer as u32 + 1 } else { u32::MAX }
+    }
     
     fn clock_frame_sequencer(&mut self) {
         // Frame sequencer steps:
@@ -227,6 +426,27 @@ impl APU {
     }
 }
     
+    // "Zombie mode": writing NRx2 while the channel is playing nudges the
+    // current volume instead of only changing the stored register (DMG).
+    fn zombie_volume(old: u8, new: u8, volume: u8) -> u8 {
+        let old_increasing = (old & 0x08) != 0;
+        let envelope_running = if old_increasing { volume < 15 } else { volume > 0 };
+        let mut vol = volume;
+
+        if (old & 0x07) == 0 && envelope_running {
+            vol = vol.wrapping_add(1);
+        } else if !old_increasing {
+            vol = vol.wrapping_add(2);
+        }
+
+        // Direction flip mirrors the volume around 16
+        if ((old ^ new) & 0x08) != 0 {
+            vol = 16u8.wrapping_sub(vol);
+        }
+
+        vol & 0x0F
+    }
+    
     fn clock_sweep(&mut self) {
     if !self.ch1_sweep_enabled {
         return;
@@ -290,11 +510,11 @@ impl APU {
     }
 }
     
-    fn clock_channel1(&mut self) {
+    fn clock_channel1(&mut self, cycles: u32) {
         if !self.ch1_enabled { return; }
         
-        if self.ch1_frequency_timer > 0 {
-            self.ch1_frequency_timer -= 1;
+        if cycles <= self.ch1_frequency_timer as u32 {
+            self.ch1_frequency_timer -= cycles as u16;
         } else {
             let frequency = ((self.nr14 as u16 & 0x07) << 8) | self.nr13 as u16;
             self.ch1_frequency_timer = (2048 - frequency) * 4;
@@ -302,11 +522,11 @@ impl APU {
         }
     }
     
-    fn clock_channel2(&mut self) {
+    fn clock_channel2(&mut self, cycles: u32) {
         if !self.ch2_enabled { return; }
         
-        if self.ch2_frequency_timer > 0 {
-            self.ch2_frequency_timer -= 1;
+        if cycles <= self.ch2_frequency_timer as u32 {
+            self.ch2_frequency_timer -= cycles as u16;
         } else {
             let frequency = ((self.nr24 as u16 & 0x07) << 8) | self.nr23 as u16;
             self.ch2_frequency_timer = (2048 - frequency) * 4;
@@ -314,24 +534,34 @@ impl APU {
         }
     }
     
-    fn clock_channel3(&mut self) {
+    fn clock_channel3(&mut self, cycles: u32) {
         if !self.ch3_enabled { return; }
         if (self.nr30 & 0x80) == 0 { return; }
         
-        if self.ch3_frequency_timer > 0 {
-            self.ch3_frequency_timer -= 1;
+        if cycles <= self.ch3_frequency_timer as u32 {
+            self.ch3_frequency_timer -= cycles as u16;
         } else {
             let frequency = ((self.nr34 as u16 & 0x07) << 8) | self.nr33 as u16;
             self.ch3_frequency_timer = (2048 - frequency) * 2;
             self.ch3_position = (self.ch3_position + 1) % 32;
+            self.ch3_sample_buffer = self.wave_ram[self.ch3_position as usize / 2];
+            self.ch3_cycles_since_read = 0;
+            return;
         }
+        self.ch3_cycles_since_read = self.ch3_cycles_since_read.saturating_add(cycles as u16);
+    }
+
+    // While channel 3 plays, the DMG only lets the CPU reach wave RAM in the
+    // couple of cycles right after the channel fetched a byte.
+    fn wave_ram_accessible(&self) -> bool {
+        self.model == Model::Cgb || self.ch3_cycles_since_read < 2
     }
     
-    fn clock_channel4(&mut self) {
+    fn clock_channel4(&mut self, cycles: u32) {
         if !self.ch4_enabled { return; }
         
-        if self.ch4_frequency_timer > 0 {
-            self.ch4_frequency_timer -= 1;
+        if cycles <= self.ch4_frequency_timer as u32 {
+            self.ch4_frequency_timer -= cycles as u16;
         } else {
             let divisor = match self.nr43 & 0x07 {
                 0 => 8,
@@ -393,7 +623,7 @@ impl APU {
         
         // Mix channel 3
         let ch3_output = if self.ch3_enabled && (self.nr30 & 0x80) != 0 {
-            let sample_byte = self.wave_ram[self.ch3_position as usize / 2];
+            let sample_byte = self.ch3_sample_buffer;
             let sample = if self.ch3_position % 2 == 0 {
                 (sample_byte >> 4) & 0x0F
             } else {
@@ -432,6 +662,13 @@ impl APU {
         if (self.nr51 & 0x40) != 0 { left += ch3_output; }
         if (self.nr51 & 0x08) != 0 { right += ch4_output; }
         if (self.nr51 & 0x80) != 0 { left += ch4_output; }
+
+        // Mix VIN (NR50 bit 7 = left, bit 3 = right)
+        if let Some(vin) = &mut self.vin {
+            let vin_output = vin.next_sample();
+            if (self.nr50 & 0x80) != 0 { left += vin_output; }
+            if (self.nr50 & 0x08) != 0 { right += vin_output; }
+        }
         
         // Apply master volume (NR50)
         let left_vol = ((self.nr50 >> 4) & 0x07) as f32 / 7.0;
@@ -439,6 +676,15 @@ impl APU {
         
         left *= left_vol * 0.25;
         right *= right_vol * 0.25;
+
+        // Feed the oscilloscope
+        let pos = self.scope_pos;
+        self.scope[0][pos] = ch1_output;
+        self.scope[1][pos] = ch2_output;
+        self.scope[2][pos] = ch3_output;
+        self.scope[3][pos] = ch4_output;
+        self.scope[4][pos] = (left + right) * 0.5;
+        self.scope_pos = (pos + 1) % SCOPE_LEN;
         
         // Interleaved stereo
         self.sample_buffer.push(left);
@@ -446,8 +692,30 @@ impl APU {
     }
     
    pub fn write_register(&mut self, addr: u16, val: u8) {
+        if let Some(vgm) = &mut self.vgm {
+            vgm.log_write(addr, val);
+        }
+        if self.record_writes {
+            self.pending_writes.push(ApuWrite { cycle: self.cycle_count, addr, val });
+        }
+
         if (self.nr52 & 0x80) == 0 && addr != 0xFF26 {
-            return; 
+            match addr {
+                // Wave RAM is always accessible
+                0xFF30..=0xFF3F => {}
+                // DMG keeps the length counters powered, so NRx1 length bits can
+                // still be loaded while the APU is off. CGB ignores these writes.
+                0xFF11 | 0xFF16 | 0xFF1B | 0xFF20 if self.model == Model::Dmg => {
+                    match addr {
+                        0xFF11 => self.ch1_length_counter = 64 - (val & 0x3F),
+                        0xFF16 => self.ch2_length_counter = 64 - (val & 0x3F),
+                        0xFF1B => self.ch3_length_counter = 256 - val as u16,
+                        _ => self.ch4_length_counter = 64 - (val & 0x3F),
+                    }
+                    return;
+                }
+                _ => return,
+            }
         }
         
         match addr {
@@ -478,6 +746,9 @@ impl APU {
 }
             0xFF11 => { self.nr11 = val; self.ch1_length_counter = 64 - (val & 0x3F); }
             0xFF12 => {
+                if self.ch1_enabled {
+                    self.ch1_volume = Self::zombie_volume(self.nr12, val, self.ch1_volume);
+                }
                 self.nr12 = val;
                 if (val & 0xF8) == 0 { self.ch1_enabled = false; self.nr52 &= !0x01; }
             }
@@ -509,6 +780,9 @@ impl APU {
             // ... (0xFF16 - 0xFF18) ...
             0xFF16 => { self.nr21 = val; self.ch2_length_counter = 64 - (val & 0x3F); }
             0xFF17 => {
+                if self.ch2_enabled {
+                    self.ch2_volume = Self::zombie_volume(self.nr22, val, self.ch2_volume);
+                }
                 self.nr22 = val;
                 if (val & 0xF8) == 0 { self.ch2_enabled = false; self.nr52 &= !0x02; }
             }
@@ -565,6 +839,9 @@ impl APU {
             // ... (0xFF20 - 0xFF22) ...
             0xFF20 => { self.nr41 = val; self.ch4_length_counter = 64 - (val & 0x3F); }
             0xFF21 => {
+                if self.ch4_enabled {
+                    self.ch4_volume = Self::zombie_volume(self.nr42, val, self.ch4_volume);
+                }
                 self.nr42 = val;
                 if (val & 0xF8) == 0 { self.ch4_enabled = false; self.nr52 &= !0x08; }
             }
@@ -612,7 +889,13 @@ impl APU {
             self.frame_sequencer_timer = 0;
             
             // IMPORTANT: Do NOT reset chX_length_counter variables here for DMG compliance.
-            // CGB would reset them here.
+            // CGB does reset them.
+            if self.model == Model::Cgb {
+                self.ch1_length_counter = 0;
+                self.ch2_length_counter = 0;
+                self.ch3_length_counter = 0;
+                self.ch4_length_counter = 0;
+            }
         }
     } else {
         if was_on {
@@ -652,9 +935,12 @@ impl APU {
     }
 }
            0xFF30..=0xFF3F => {
-    // If channel 3 is playing, write to the current position instead
+    // If channel 3 is playing, the write lands on the byte being read, and
+    // only if it happens inside the access window
     if self.ch3_enabled && (self.nr30 & 0x80) != 0 {
-        self.wave_ram[self.ch3_position as usize / 2] = val;
+        if self.wave_ram_accessible() {
+            self.wave_ram[self.ch3_position as usize / 2] = val;
+        }
     } else {
         self.wave_ram[(addr - 0xFF30) as usize] = val;
     }
@@ -706,9 +992,14 @@ impl APU {
 }
             
             0xFF30..=0xFF3F => {
-            // If channel 3 is playing, return the sample being read
+            // If channel 3 is playing, return the byte being read (or open bus
+            // outside the access window)
             if self.ch3_enabled && (self.nr30 & 0x80) != 0 {
-                self.wave_ram[self.ch3_position as usize / 2]
+                if self.wave_ram_accessible() {
+                    self.wave_ram[self.ch3_position as usize / 2]
+                } else {
+                    0xFF
+                }
             } else {
                 self.wave_ram[(addr - 0xFF30) as usize]
             }
@@ -817,6 +1108,19 @@ fn trigger_channel2(&mut self) {
 }
 
 fn trigger_channel3(&mut self) {
+    // 0. DMG WAVE RAM CORRUPTION
+    // Retriggering while the channel is fetching a sample byte corrupts the
+    // start of wave RAM with the bytes around the one being read.
+    if self.model == Model::Dmg && self.ch3_enabled && self.ch3_frequency_timer == 0 {
+        let byte = (((self.ch3_position + 1) % 32) / 2) as usize;
+        if byte < 4 {
+            self.wave_ram[0] = self.wave_ram[byte];
+        } else {
+            let block = byte & !0x03;
+            self.wave_ram.copy_within(block..block + 4, 0);
+        }
+    }
+
     // 1. RELOAD LENGTH (Note: Channel 3 is 256 ticks long)
     if self.ch3_length_counter == 0 {
         self.ch3_length_counter = 256;
@@ -829,6 +1133,8 @@ fn trigger_channel3(&mut self) {
     let frequency = ((self.nr34 as u16 & 0x07) << 8) | self.nr33 as u16;
     self.ch3_frequency_timer = (2048 - frequency) * 2;
     self.ch3_position = 0; // Wave channel resets position to 0
+    // The sample buffer is NOT refilled: the first nibble played is stale
+    self.ch3_cycles_since_read = u16::MAX;
 
     // 3. ENABLE CHANNEL (Only if DAC is ON)
     // Channel 3 DAC is controlled by Bit 7 of NR30
@@ -920,9 +1226,74 @@ fn clock_length(&mut self) {
 // NRx4 write handlers - keep the ODD frame check for non-trigger:
 // These stay the same as before with the (frame_sequencer & 1) == 1 check
     
-    pub fn get_samples(&mut self) -> Vec<f32> {
-        let samples = self.sample_buffer.clone();
+    pub fn take_writes(&mut self) -> Vec<ApuWrite> {
+        std::mem::take(&mut self.pending_writes)
+    }
+
+    // Registers + internal channel state. The VGM recorder, write log, VIN
+    // source and scope history are frontend state and aren't saved.
+    pub fn save_state(&self, w: &mut StateWriter) {
+        for r in [
+            self.nr10, self.nr11, self.nr12, self.nr13, self.nr14,
+            self.nr21, self.nr22, self.nr23, self.nr24,
+            self.nr30, self.nr31, self.nr32, self.nr33, self.nr34,
+            self.nr41, self.nr42, self.nr43, self.nr44,
+            self.nr50, self.nr51, self.nr52,
+        ] {
+            w.u8(r);
+        }
+        w.bytes(&self.wave_ram);
+
+        w.u16(self.ch1_frequency_timer); w.u8(self.ch1_duty_position); w.u8(self.ch1_length_counter);
+        w.u8(self.ch1_volume); w.u8(self.ch1_envelope_timer); w.u8(self.ch1_sweep_timer);
+        w.u16(self.ch1_sweep_shadow); w.bool(self.ch1_enabled);
+        w.bool(self.ch1_sweep_neg_mode); w.bool(self.ch1_sweep_enabled); w.bool(self.ch1_sweep_period_was_zero);
+
+        w.u16(self.ch2_frequency_timer); w.u8(self.ch2_duty_position); w.u8(self.ch2_length_counter);
+        w.u8(self.ch2_volume); w.u8(self.ch2_envelope_timer); w.bool(self.ch2_enabled);
+
+        w.u16(self.ch3_frequency_timer); w.u8(self.ch3_position); w.u16(self.ch3_length_counter);
+        w.bool(self.ch3_enabled); w.u8(self.ch3_sample_buffer); w.u16(self.ch3_cycles_since_read);
+
+        w.u16(self.ch4_lfsr); w.u16(self.ch4_frequency_timer); w.u8(self.ch4_length_counter);
+        w.u8(self.ch4_volume); w.u8(self.ch4_envelope_timer); w.bool(self.ch4_enabled);
+
+        w.u8(self.frame_sequencer); w.u32(self.frame_sequencer_timer);
+        w.u32(self.sample_timer.to_bits());
+        w.u64(self.cycle_count);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> std::io::Result<()> {
+        for reg in [
+            &mut self.nr10, &mut self.nr11, &mut self.nr12, &mut self.nr13, &mut self.nr14,
+            &mut self.nr21, &mut self.nr22, &mut self.nr23, &mut self.nr24,
+            &mut self.nr30, &mut self.nr31, &mut self.nr32, &mut self.nr33, &mut self.nr34,
+            &mut self.nr41, &mut self.nr42, &mut self.nr43, &mut self.nr44,
+            &mut self.nr50, &mut self.nr51, &mut self.nr52,
+        ] {
+            *reg = r.u8()?;
+        }
+        r.bytes(&mut self.wave_ram)?;
+
+        self.ch1_frequency_timer = r.u16()?; self.ch1_duty_position = r.u8()?; self.ch1_length_counter = r.u8()?;
+        self.ch1_volume = r.u8()?; self.ch1_envelope_timer = r.u8()?; self.ch1_sweep_timer = r.u8()?;
+        self.ch1_sweep_shadow = r.u16()?; self.ch1_enabled = r.bool()?;
+        self.ch1_sweep_neg_mode = r.bool()?; self.ch1_sweep_enabled = r.bool()?; self.ch1_sweep_period_was_zero = r.bool()?;
+
+        self.ch2_frequency_timer = r.u16()?; self.ch2_duty_position = r.u8()?; self.ch2_length_counter = r.u8()?;
+        self.ch2_volume = r.u8()?; self.ch2_envelope_timer = r.u8()?; self.ch2_enabled = r.bool()?;
+
+        self.ch3_frequency_timer = r.u16()?; self.ch3_position = r.u8()?; self.ch3_length_counter = r.u16()?;
+        self.ch3_enabled = r.bool()?; self.ch3_sample_buffer = r.u8()?; self.ch3_cycles_since_read = r.u16()?;
+
+        self.ch4_lfsr = r.u16()?; self.ch4_frequency_timer = r.u16()?; self.ch4_length_counter = r.u8()?;
+        self.ch4_volume = r.u8()?; self.ch4_envelope_timer = r.u8()?; self.ch4_enabled = r.bool()?;
+
+        self.frame_sequencer = r.u8()?; self.frame_sequencer_timer = r.u32()?;
+        self.sample_timer = f32::from_bits(r.u32()?);
+        self.cycle_count = r.u64()?;
+        self.pending_writes.clear();
         self.sample_buffer.clear();
-        samples
+        Ok(())
     }
 }
\ No newline at end of file
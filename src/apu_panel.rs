@@ -0,0 +1,131 @@
+// apu_panel.rs
+// Debug window listing each APU channel's live internal state, with the
+// pitch the tone channels are playing as a note (for transcribing music and
+// checking frequency emulation). Under each channel: lights for whether it
+// is on, its DAC is powered and NR51 sends it left and right, its registers,
+// and a rolling oscilloscope of its output.
+use minifb::{Window, WindowOptions};
+
+use crate::apu::{APU, SCOPE_LEN};
+use crate::draw_text;
+
+const W: usize = 560;
+const H: usize = 340;
+const COL_W: usize = 136;
+const LINES_TOP: usize = 44;
+const SCOPE_TOP: usize = 264;
+const SCOPE_H: usize = 64;
+const SCOPE_W: usize = COL_W - 12;
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+// Output pitch for an 11-bit period value: square channels step 8 duty
+// positions at 1 MiHz / (2048 - f), the wave channel 32 samples at 2 MiHz
+fn pitch_hz(channel: usize, frequency: u16) -> f64 {
+    let clock = if channel == 2 { 65536.0 } else { 131072.0 };
+    clock / (2048 - frequency.min(2047)) as f64
+}
+
+// Nearest equal-tempered note (A4 = 440 Hz) and how far off it is, e.g. "A4 +3"
+fn note_name(hz: f64) -> String {
+    let semitones = 12.0 * (hz / 440.0).log2() + 69.0; // MIDI note number
+    let nearest = semitones.round();
+    let cents = ((semitones - nearest) * 100.0).round() as i32;
+    let midi = nearest as i32;
+    format!("{}{} {:+}", NOTE_NAMES[midi.rem_euclid(12) as usize], midi.div_euclid(12) - 1, cents)
+}
+
+pub struct ApuPanel {
+    window: Window,
+    buffer: Vec<u32>,
+}
+
+impl ApuPanel {
+    pub fn new() -> Self {
+        let mut window = Window::new(
+            "APU Channels",
+            W, H,
+            WindowOptions { resize: false, ..WindowOptions::default() },
+        ).unwrap();
+        window.limit_update_rate(None);
+        Self { window, buffer: vec![0; W * H] }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    pub fn update(&mut self, apu: &APU) {
+        for p in self.buffer.iter_mut() { *p = 0xFF181818; }
+
+        let names = ["CH1 SQ+SWP", "CH2 SQUARE", "CH3 WAVE", "CH4 NOISE"];
+        for (i, ch) in apu.channel_states().iter().enumerate() {
+            let x = 8 + i * COL_W;
+            let head = if ch.enabled { 0xFF55FF55 } else { 0xFF777777 };
+            draw_text(&mut self.buffer, W, names[i], x, 8, head, 1);
+
+            let lights = [("ON", ch.enabled), ("DAC", ch.dac_on), ("L", apu.nr51 & (0x10 << i) != 0), ("R", apu.nr51 & (0x01 << i) != 0)];
+            let mut lx = x;
+            for (label, on) in lights {
+                draw_text(&mut self.buffer, W, label, lx, 24, if on { 0xFF55FF55 } else { 0xFF555555 }, 1);
+                lx += (label.len() + 1) * 8;
+            }
+
+            let reg = |r: Option<u8>| r.map_or("--".to_string(), |v| format!("{:02X}", v));
+            let mut lines = vec![
+                format!("REG  {} {} {}", reg(ch.registers[0]), reg(ch.registers[1]), reg(ch.registers[2])),
+                format!("     {} {}", reg(ch.registers[3]), reg(ch.registers[4])),
+                format!("FREQ {:04X}", ch.frequency),
+            ];
+            // Noise has no pitch
+            if i < 3 {
+                let hz = pitch_hz(i, ch.frequency);
+                lines.push(format!("HZ   {:.1}", hz));
+                lines.push(format!("NOTE {}", note_name(hz)));
+            }
+            lines.extend([
+                format!("TMR  {}", ch.timer),
+                format!("VOL  {}", ch.volume),
+                format!("LEN  {}{}", ch.length_counter, if ch.length_enabled { " ON" } else { "" }),
+            ]);
+            if let (Some(env), Some(nrx2)) = (ch.envelope_timer, ch.registers[2]) {
+                // Timer, then the direction and period from NRx2
+                let direction = if nrx2 & 0x08 != 0 { '+' } else { '-' };
+                lines.push(format!("ENV  {} {}{}", env, direction, nrx2 & 0x07));
+            }
+            if let Some(shadow) = ch.sweep_shadow {
+                let timer = ch.sweep_timer.map_or("OFF".to_string(), |t| t.to_string());
+                lines.push(format!("SWP  {}", timer));
+                lines.push(format!("SHDW {:04X}", shadow));
+            }
+            match ch.lfsr {
+                Some(lfsr) => lines.push(format!("LFSR {:04X}", lfsr)),
+                None => lines.push(format!("POS  {}", ch.position)),
+            }
+
+            for (row, line) in lines.iter().enumerate() {
+                draw_text(&mut self.buffer, W, line, x, LINES_TOP + row * 14, 0xFFDDDDDD, 1);
+            }
+
+            // Oscilloscope, oldest on the left, a column per few samples
+            for px in 0..SCOPE_W {
+                self.buffer[(SCOPE_TOP + SCOPE_H / 2) * W + x + px] = 0xFF303030;
+            }
+            let mut prev_y = None;
+            for px in 0..SCOPE_W {
+                let sample = apu.scope[i][(apu.scope_pos + px * SCOPE_LEN / SCOPE_W) % SCOPE_LEN].clamp(0.0, 1.0);
+                let y = SCOPE_TOP + ((1.0 - sample) * (SCOPE_H - 1) as f32) as usize;
+                let (y0, y1) = match prev_y {
+                    Some(p) if p < y => (p, y),
+                    Some(p) => (y, p),
+                    None => (y, y),
+                };
+                for yy in y0..=y1 {
+                    self.buffer[yy * W + x + px] = head;
+                }
+                prev_y = Some(y);
+            }
+        }
+
+        self.window.update_with_buffer(&self.buffer, W, H).unwrap();
+    }
+}
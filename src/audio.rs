@@ -0,0 +1,379 @@
+// audio.rs
+// Audio is synthesized on the cpal callback thread. The emulated APU only
+// logs its register writes (timestamped in T-cycles); the callback replays
+// them into its own APU and generates exactly as many samples as the device
+// asks for, so audio pacing no longer depends on emulation pacing.
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, StreamTrait};
+
+use crate::apu::{APU, ApuWrite, VinSource};
+use crate::mmu::Model;
+use crate::savestate::StateReader;
+
+// If emulation gets further ahead than this (fast-forward, a hitch), skip the
+// synth clock forward instead of letting latency build up; the same if the
+// synth gets this far ahead of emulation. Either way it goes back to
+// TARGET_LAG_CYCLES behind.
+pub const MAX_LAG_CYCLES: u64 = 70224 * 4;
+const TARGET_LAG_CYCLES: u64 = 70224;
+const CPU_HZ: f32 = 4_194_304.0;
+// Longest stretch the synth ticks at once (APU::tick takes a u8)
+const MAX_STEP: u64 = 128;
+// Chunk length (~10ms) kept when dropping audio during fast-forward
+const FF_BLOCK_CYCLES: u64 = 87 * 480;
+// How much (~2ms) of what came after a kept chunk is blended into the start
+// of the next one, so the jump between them doesn't click
+const FF_FADE_CYCLES: u64 = 87 * 96;
+
+// Output image applied after NR51 panning
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum StereoMode {
+    Mono,   // L+R summed to both speakers
+    Stereo, // Hardware panning as-is
+    Wide,   // Side signal boosted
+}
+
+impl StereoMode {
+    pub fn next(self) -> Self {
+        match self {
+            StereoMode::Mono => StereoMode::Stereo,
+            StereoMode::Stereo => StereoMode::Wide,
+            StereoMode::Wide => StereoMode::Mono,
+        }
+    }
+
+    fn apply(self, left: f32, right: f32) -> (f32, f32) {
+        match self {
+            StereoMode::Mono => {
+                let mid = (left + right) * 0.5;
+                (mid, mid)
+            }
+            StereoMode::Stereo => (left, right),
+            StereoMode::Wide => {
+                let mid = (left + right) * 0.5;
+                let side = (left - right) * 0.5 * 1.5;
+                ((mid + side).clamp(-1.0, 1.0), (mid - side).clamp(-1.0, 1.0))
+            }
+        }
+    }
+}
+
+// What to do with the surplus audio when emulating faster than 1x
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum FastForwardAudio {
+    Mute, // Output silence, keep the APU state in sync
+    // Play short chunks at normal pitch and skip the rest, crossfading the joins
+    #[serde(alias = "Resample")]
+    Skip,
+}
+
+impl FastForwardAudio {
+    pub fn next(self) -> Self {
+        match self {
+            FastForwardAudio::Mute => FastForwardAudio::Skip,
+            FastForwardAudio::Skip => FastForwardAudio::Mute,
+        }
+    }
+}
+
+// Smallest buffer requested by the low-latency preset (frames)
+const LOW_LATENCY_FRAMES: u32 = 256;
+
+// Device-side statistics shown in the UI
+#[derive(Default)]
+pub struct AudioStats {
+    pub underruns: AtomicU64,
+    pub buffer_frames: AtomicU32, // Frames per callback (0 = not known yet)
+}
+
+pub enum AudioCommand {
+    Reset(Model),     // A new ROM was loaded
+    Write(ApuWrite),
+    SetStereoMode(StereoMode),
+    SetSpeed(u32),
+    SetFastForwardAudio(FastForwardAudio),
+    SetPaused(bool),
+    LoadState(Vec<u8>), // APU snapshot to resume from (see APU::save_state)
+    EmulatedTo(u64), // How far emulation has got in T-cycles, after each batch of frames
+    SetVin(Box<dyn VinSource>), // Cartridge audio input, until the next Reset
+}
+
+pub struct AudioSynth {
+    apu: APU,
+    rx: Receiver<AudioCommand>,
+    queue: VecDeque<ApuWrite>,
+    clock: u64,
+    emulated: u64, // Latest EmulatedTo since the last reset
+    sample_period: f32, // T-cycles per sample at the device's rate
+    samples: VecDeque<f32>,
+    fade: Vec<f32>, // What followed the last fast-forward chunk, faded out into the next
+    stereo_mode: StereoMode,
+    speed: u32,
+    ff_audio: FastForwardAudio,
+    paused: bool,
+    // Synth time published for audio-clocked sync (read by the main loop)
+    shared_clock: Arc<AtomicU64>,
+}
+
+// The synth's APU, the one that makes the samples that get played
+fn synth_apu(model: Model, sample_period: f32) -> APU {
+    let mut apu = APU::new(model);
+    apu.capture_samples = true;
+    apu.sample_period = sample_period;
+    apu
+}
+
+impl AudioSynth {
+    pub fn new(rx: Receiver<AudioCommand>) -> Self {
+        Self {
+            apu: synth_apu(Model::Dmg, 87.0),
+            rx,
+            queue: VecDeque::new(),
+            clock: 0,
+            emulated: 0,
+            sample_period: 87.0,
+            samples: VecDeque::with_capacity(4096),
+            fade: Vec::new(),
+            stereo_mode: StereoMode::Stereo,
+            speed: 1,
+            ff_audio: FastForwardAudio::Skip,
+            paused: false,
+            shared_clock: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    // Samples are made at the device's rate, so a second of them is a
+    // second of emulated time whatever the rate is
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.sample_period = CPU_HZ / rate.max(1) as f32;
+        self.apu.sample_period = self.sample_period;
+    }
+
+    // Handle to the synth's position in emulated T-cycles
+    pub fn clock_handle(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.shared_clock)
+    }
+
+    fn receive(&mut self) {
+        while let Ok(cmd) = self.rx.try_recv() {
+            match cmd {
+                AudioCommand::Reset(model) => {
+                    self.apu = synth_apu(model, self.sample_period);
+                    self.queue.clear();
+                    self.samples.clear();
+                    self.fade.clear();
+                    self.clock = 0;
+                    self.emulated = 0;
+                }
+                AudioCommand::Write(w) => self.queue.push_back(w),
+                AudioCommand::SetStereoMode(mode) => self.stereo_mode = mode,
+                AudioCommand::SetSpeed(speed) => {
+                    self.speed = speed.max(1);
+                    self.fade.clear();
+                }
+                AudioCommand::SetFastForwardAudio(mode) => self.ff_audio = mode,
+                AudioCommand::SetPaused(paused) => self.paused = paused,
+                AudioCommand::EmulatedTo(cycle) => self.emulated = self.emulated.max(cycle),
+                AudioCommand::SetVin(vin) => self.apu.vin = Some(vin),
+                AudioCommand::LoadState(data) => {
+                    let mut apu = synth_apu(self.apu.model, self.sample_period);
+                    match StateReader::new(&data).and_then(|mut r| apu.load_state(&mut r)) {
+                        Ok(()) => {
+                            self.clock = apu.cycle_count;
+                            self.emulated = apu.cycle_count;
+                            apu.vin = self.apu.vin.take();
+                            self.apu = apu;
+                            self.queue.clear();
+                            self.samples.clear();
+                            self.fade.clear();
+                        }
+                        Err(e) => eprintln!("Audio state restore failed: {}", e),
+                    }
+                }
+            }
+        }
+
+        let latest = self.queue.back().map_or(self.emulated, |w| w.cycle.max(self.emulated));
+        if latest > self.clock + MAX_LAG_CYCLES || self.clock > latest + MAX_LAG_CYCLES {
+            self.clock = latest.saturating_sub(TARGET_LAG_CYCLES);
+        }
+    }
+
+    // Run the synth APU forward, replaying due writes on the way. Samples are
+    // kept for output or thrown away (used to skip audio while keeping state).
+    fn advance(&mut self, cycles: u64, keep_samples: bool) {
+        let target = self.clock + cycles;
+        while self.clock < target {
+            // Apply every write that is due at the current synth time
+            while let Some(w) = self.queue.front() {
+                if w.cycle > self.clock { break; }
+                self.apu.write_register(w.addr, w.val);
+                self.queue.pop_front();
+            }
+
+            let until_next = self.queue.front().map_or(MAX_STEP, |w| w.cycle - self.clock);
+            let step = until_next.min(target - self.clock).clamp(1, MAX_STEP);
+            self.apu.tick(step as u8);
+            self.clock += step;
+            if keep_samples {
+                self.samples.extend(self.apu.sample_buffer.drain(..));
+            } else {
+                self.apu.sample_buffer.clear();
+            }
+        }
+    }
+
+    // One FF_BLOCK_CYCLES chunk at normal pitch, its start crossfaded from
+    // where the previous one left off, then the surplus skipped. The first
+    // FF_FADE_CYCLES of the skipped part are kept for the next crossfade.
+    fn fast_forward_block(&mut self) {
+        let start = self.samples.len();
+        self.advance(FF_BLOCK_CYCLES, true);
+        let len = self.fade.len().min(self.samples.len() - start);
+        for i in 0..len {
+            // Per stereo frame, from all the old chunk to all the new one
+            let t = (i / 2 + 1) as f32 / (len / 2 + 1) as f32;
+            let sample = &mut self.samples[start + i];
+            *sample = self.fade[i] * (1.0 - t) + *sample * t;
+        }
+
+        let end = self.samples.len();
+        self.advance(FF_FADE_CYCLES, true);
+        self.fade = self.samples.drain(end..).collect();
+        self.advance(FF_BLOCK_CYCLES * (self.speed as u64 - 1) - FF_FADE_CYCLES, false);
+    }
+
+    // Called from the cpal callback with an interleaved stereo buffer
+    // One synth sample pair per device frame, whatever the channel count:
+    // mono gets the mix, channels past the second get silence
+    pub fn fill(&mut self, out: &mut [f32], channels: usize) {
+        let channels = channels.max(1);
+        self.receive();
+
+        // Hold the synth clock still so emulation resumes in step with it
+        if self.paused {
+            out.fill(0.0);
+            return;
+        }
+
+        if self.speed > 1 && self.ff_audio == FastForwardAudio::Mute {
+            // Catch up with everything emulated so far, silently
+            if let Some(last) = self.queue.back().map(|w| w.cycle)
+                && last > self.clock
+            {
+                self.advance(last - self.clock, false);
+            }
+            self.samples.clear();
+            out.fill(0.0);
+            self.shared_clock.store(self.clock, Ordering::Relaxed);
+            return;
+        }
+
+        while self.samples.len() < out.len() / channels * 2 {
+            if self.speed > 1 {
+                // Keep one block at normal pitch, drop the surplus (speed - 1) blocks
+                self.fast_forward_block();
+            } else {
+                self.advance(MAX_STEP, true);
+            }
+        }
+
+        for frame in out.chunks_exact_mut(channels) {
+            let left = self.samples.pop_front().unwrap_or(0.0);
+            let right = self.samples.pop_front().unwrap_or(0.0);
+            let (l, r) = self.stereo_mode.apply(left, right);
+            if channels == 1 {
+                frame[0] = (l + r) / 2.0;
+            } else {
+                frame[0] = l;
+                frame[1] = r;
+                frame[2..].fill(0.0);
+            }
+        }
+        self.shared_clock.store(self.clock, Ordering::Relaxed);
+    }
+}
+
+// build_stream on the default device, started. The error says why there's
+// no sound, worded for the player.
+pub fn start_stream(
+    device: Option<&cpal::Device>,
+    synth: Arc<Mutex<AudioSynth>>,
+    stats: Arc<AudioStats>,
+    low_latency: bool,
+) -> Result<cpal::Stream, String> {
+    let device = device.ok_or("no audio output device was found")?;
+    let stream = build_stream(device, synth, stats, low_latency).map_err(|e| format!("the audio device couldn't be opened ({})", e))?;
+    stream.play().map_err(|e| format!("the audio device couldn't be started ({})", e))?;
+    Ok(stream)
+}
+
+// Open the output stream feeding `synth`. The low-latency preset asks for the
+// smallest fixed buffer the device allows (but not below LOW_LATENCY_FRAMES),
+// falling back to the default buffer if the device refuses it.
+pub fn build_stream(
+    device: &cpal::Device,
+    synth: Arc<Mutex<AudioSynth>>,
+    stats: Arc<AudioStats>,
+    low_latency: bool,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let supported = device.default_output_config().map_err(|_| cpal::BuildStreamError::DeviceNotAvailable)?;
+    let mut config: cpal::StreamConfig = supported.config();
+    if low_latency {
+        let frames = match supported.buffer_size() {
+            cpal::SupportedBufferSize::Range { min, max } => LOW_LATENCY_FRAMES.clamp(*min, *max),
+            cpal::SupportedBufferSize::Unknown => LOW_LATENCY_FRAMES,
+        };
+        config.buffer_size = cpal::BufferSize::Fixed(frames);
+    }
+
+    let open = |config: cpal::StreamConfig| {
+        let synth = Arc::clone(&synth);
+        let stats = Arc::clone(&stats);
+        let channels = config.channels as usize;
+        let rate = config.sample_rate.0 as f64;
+        let mut last_end: Option<cpal::StreamInstant> = None;
+        stats.underruns.store(0, Ordering::Relaxed);
+        if let Ok(mut synth) = synth.lock() {
+            synth.set_sample_rate(config.sample_rate.0);
+        }
+
+        device.build_output_stream(
+            &config,
+            move |data: &mut [f32], info: &cpal::OutputCallbackInfo| {
+                let frames = data.len() / channels.max(1);
+                stats.buffer_frames.store(frames as u32, Ordering::Relaxed);
+
+                // If the device already finished playing the previous buffer
+                // before asking for this one, it ran dry
+                let ts = info.timestamp();
+                if let Some(end) = last_end
+                    && ts.callback > end
+                {
+                    stats.underruns.fetch_add(1, Ordering::Relaxed);
+                }
+                last_end = ts.playback.add(Duration::from_secs_f64(frames as f64 / rate));
+
+                if let Ok(mut synth) = synth.lock() {
+                    synth.fill(data, channels);
+                }
+            },
+            |err| eprintln!("Err: {}", err),
+            None,
+        )
+    };
+
+    match open(config.clone()) {
+        Err(e) if low_latency => {
+            eprintln!("Low-latency buffer rejected ({}), using default", e);
+            config.buffer_size = cpal::BufferSize::Default;
+            open(config)
+        }
+        result => result,
+    }
+}
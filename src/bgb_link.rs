@@ -0,0 +1,165 @@
+// bgb_link.rs
+// Link cable to another emulator over the network with BGB's protocol
+// (version 1.4), which BGB and several other emulators speak, for trades and
+// versus play across emulators. gb_rs can listen for the other side to
+// connect or connect to it.
+//
+// Every packet is 8 bytes: a command, three parameters, and a u32 timestamp
+// (little-endian, in 2 MiHz clocks, 31 bits). The ones used here:
+//   1    version          1, 4, 0; sent first by both sides
+//   104  sync1            byte sent by the side clocking the transfer
+//   105  sync2            the other side's byte in return
+//   106  sync3            b2=1: sync1 arrived with no transfer waiting for it;
+//                         b2=0: just our timestamp, sent every frame
+//   108  status           bit 0 = running
+//   109  want disconnect
+// Joypad packets (101) from BGB's remote control feature are ignored.
+//
+// Like the local link a byte takes at least a frame to go through, plus the
+// round trip when gb_rs is the one clocking it.
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::mmu::MMU;
+
+const VERSION: [u8; 3] = [1, 4, 0];
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub struct BgbLink {
+    listener: Option<TcpListener>, // Waiting for the other side while set
+    stream: Option<TcpStream>,
+    incoming: Vec<u8>,
+    sent_byte: bool, // Our sync1 is waiting for its answer
+}
+
+impl BgbLink {
+    pub fn listen(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        println!("✓ BGB link: waiting for a connection on port {}", port);
+        Ok(Self { listener: Some(listener), stream: None, incoming: Vec::new(), sent_byte: false })
+    }
+
+    pub fn connect(addr: &str) -> io::Result<Self> {
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| io::Error::new(ErrorKind::NotFound, "no such host"))?;
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        let mut link = Self { listener: None, stream: None, incoming: Vec::new(), sent_byte: false };
+        link.start(stream)?;
+        println!("✓ BGB link: connected to {}", addr);
+        Ok(link)
+    }
+
+    // "host:port" connects, a port alone listens
+    pub fn open(spec: &str) -> io::Result<Self> {
+        match spec.parse::<u16>() {
+            Ok(port) => Self::listen(port),
+            Err(_) => Self::connect(spec),
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    fn start(&mut self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        self.stream = Some(stream);
+        self.incoming.clear();
+        self.sent_byte = false;
+        self.send(1, VERSION, 0);
+        self.send(108, [1, 0, 0], 0);
+        Ok(())
+    }
+
+    fn send(&mut self, command: u8, params: [u8; 3], timestamp: u32) {
+        let mut packet = [command, params[0], params[1], params[2], 0, 0, 0, 0];
+        packet[4..].copy_from_slice(&(timestamp & 0x7FFF_FFFF).to_le_bytes());
+        // The socket is non-blocking, but 8 bytes a few times a frame never
+        // fill its buffer; a failed write means the other side is gone
+        if let Some(stream) = &mut self.stream
+            && stream.write_all(&packet).is_err()
+        {
+            self.stream = None;
+        }
+    }
+
+    // After each emulated frame: picks up a connection, answers the other
+    // side and sends a transfer we clock. Returns a message for the OSD when
+    // the connection comes or goes.
+    pub fn update(&mut self, bus: &mut MMU, frame_count: u64) -> Option<String> {
+        let timestamp = (frame_count * 70224 / 2) as u32;
+        let mut message = None;
+        if self.stream.is_none()
+            && let Some(listener) = &self.listener
+            && let Ok((stream, addr)) = listener.accept()
+        {
+            match self.start(stream) {
+                Ok(()) => message = Some(format!("BGB link: {} connected", addr)),
+                Err(e) => eprintln!("✗ BGB link: {}", e),
+            }
+        }
+        let Some(stream) = &mut self.stream else { return message };
+
+        let mut buf = [0; 256];
+        let mut closed = false;
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => { closed = true; break; }
+                Ok(n) => self.incoming.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => { closed = true; break; }
+            }
+        }
+
+        let packets: Vec<[u8; 8]> = self.incoming.chunks_exact(8).map(|p| p.try_into().unwrap()).collect();
+        self.incoming.drain(..packets.len() * 8);
+        for p in packets {
+            match p[0] {
+                1 if p[1..4] != VERSION => {
+                    eprintln!("✗ BGB link: unsupported protocol version {}.{}", p[1], p[2]);
+                    closed = true;
+                }
+                // The other side clocks a byte: ours goes back if a transfer
+                // is waiting on the external clock
+                104 => {
+                    if bus.sc & 0x81 == 0x80 {
+                        let ours = bus.external_transfer(p[1]);
+                        self.send(105, [ours, 0x80, 1], timestamp);
+                    } else {
+                        self.send(106, [1, 0, 0], timestamp);
+                    }
+                }
+                105 if self.sent_byte => {
+                    bus.external_transfer(p[1]);
+                    self.sent_byte = false;
+                }
+                // Nothing on the other end took the byte, as with no cable
+                106 if p[1] == 1 && self.sent_byte => {
+                    bus.external_transfer(0xFF);
+                    self.sent_byte = false;
+                }
+                109 => closed = true,
+                _ => {}
+            }
+        }
+
+        if !closed && !self.sent_byte && bus.sc & 0x81 == 0x81 {
+            self.send(104, [bus.sb, bus.sc, 0], timestamp);
+            self.sent_byte = true;
+        }
+        self.send(106, [0, 0, 0], timestamp);
+
+        if closed || self.stream.is_none() {
+            self.stream = None;
+            // A transfer we clocked would otherwise wait forever
+            if self.sent_byte {
+                bus.external_transfer(0xFF);
+                self.sent_byte = false;
+            }
+            message = Some("BGB link: disconnected".to_string());
+        }
+        message
+    }
+}
@@ -0,0 +1,88 @@
+// border.rs
+// A picture drawn around the game: a Game Boy shell, an SGB-style frame, any
+// artwork the player supplies. Loaded from an uncompressed 24 or 32-bit BMP
+// (no extra dependencies) at Game Boy pixel size, e.g. 256x224 like a Super
+// Game Boy border. The window's normal contents (menu strip and screen) sit
+// in its middle, covering that part of the picture.
+use std::fs;
+
+pub struct Border {
+    width: usize, // In Game Boy pixels
+    height: usize,
+    scale: usize,
+    pixels: Vec<u32>, // Already scaled up, so each frame is a copy
+}
+
+fn u16_at(data: &[u8], at: usize) -> usize {
+    u16::from_le_bytes([data[at], data[at + 1]]) as usize
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+}
+
+impl Border {
+    // `inner_w` x `inner_h` is what has to fit inside, in Game Boy pixels;
+    // the picture is scaled up `scale` times like the window's contents
+    pub fn load(path: &str, inner_w: usize, inner_h: usize, scale: usize) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|e| format!("Couldn't read '{}': {}", path, e))?;
+        if data.len() < 54 || &data[0..2] != b"BM" {
+            return Err(format!("'{}' isn't a BMP image", path));
+        }
+        let offset = u32_at(&data, 10) as usize;
+        let width = u32_at(&data, 18) as i32;
+        let height = u32_at(&data, 22) as i32;
+        let bpp = u16_at(&data, 28);
+        let compression = u32_at(&data, 30);
+        // 3 is BI_BITFIELDS, which 32-bit BMPs use for plain BGRA too
+        if !(bpp == 24 && compression == 0 || bpp == 32 && (compression == 0 || compression == 3)) {
+            return Err(format!("'{}' must be an uncompressed 24 or 32-bit BMP", path));
+        }
+        let (width, top_down) = (width.unsigned_abs() as usize, height < 0);
+        let height = height.unsigned_abs() as usize;
+        if width < inner_w || height < inner_h {
+            return Err(format!("'{}' is {}x{}, smaller than the {}x{} screen", path, width, height, inner_w, inner_h));
+        }
+
+        let stride = (width * bpp / 8).div_ceil(4) * 4;
+        if data.len() < offset + stride * height {
+            return Err(format!("'{}' is truncated", path));
+        }
+        let mut pixels = vec![0; width * scale * height * scale];
+        for y in 0..height * scale {
+            // Rows are stored bottom-up unless the height is negative
+            let src_y = y / scale;
+            let row = offset + stride * if top_down { src_y } else { height - 1 - src_y };
+            for x in 0..width * scale {
+                let p = row + x / scale * bpp / 8;
+                pixels[y * width * scale + x] = 0xFF000000 | u32::from_le_bytes([data[p], data[p + 1], data[p + 2], 0]);
+            }
+        }
+        println!("✓ Border '{}' ({}x{})", path, width, height);
+        Ok(Self { width, height, scale, pixels })
+    }
+
+    // Size of the bordered picture, scaled up
+    pub fn size(&self) -> (usize, usize) {
+        (self.width * self.scale, self.height * self.scale)
+    }
+
+    // Top-left corner of the window's normal contents, in Game Boy pixels
+    pub fn origin(&self, inner_w: usize, inner_h: usize) -> (usize, usize) {
+        ((self.width - inner_w) / 2, (self.height - inner_h) / 2)
+    }
+
+    // `content` (`content_w` wide, scaled up like the picture) framed by it
+    // into `out`
+    pub fn compose(&self, content: &[u32], content_w: usize, out: &mut Vec<u32>) {
+        let scale = self.scale;
+        let w = self.width * scale;
+        out.clear();
+        out.extend_from_slice(&self.pixels);
+        let (ox, oy) = self.origin(content_w / scale, content.len() / content_w / scale);
+        for (y, row) in content.chunks_exact(content_w).enumerate() {
+            let start = (oy * scale + y) * w + ox * scale;
+            out[start..start + content_w].copy_from_slice(row);
+        }
+    }
+}
@@ -0,0 +1,434 @@
+// breakpoint.rs
+// Breakpoints for the debuggers: an address, optionally in one ROM bank and
+// optionally with a condition in a small expression language that's
+// evaluated each time the address is reached, e.g.
+//
+//   A==0x3F && [0xC123]>5
+//
+// Values are registers (A F B C D E H L AF BC DE HL SP PC), flags (ZF NF HF
+// CF, 0 or 1), IME, memory bytes read through the bus ([addr], any
+// expression inside) and numbers (0x4ABC, $4ABC or decimal). Operators are
+// C's, with C's precedence: || && | ^ & == != < <= > >= << >> + - * / %
+// and the prefixes ! - ~. Anything nonzero is true.
+use std::fmt;
+
+use crate::cpu::CPU;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Var {
+    A, F, B, C, D, E, H, L,
+    AF, BC, DE, HL, SP, PC,
+    ZF, NF, HF, CF,
+    Ime,
+}
+
+impl Var {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_uppercase().as_str() {
+            "A" => Var::A, "F" => Var::F, "B" => Var::B, "C" => Var::C,
+            "D" => Var::D, "E" => Var::E, "H" => Var::H, "L" => Var::L,
+            "AF" => Var::AF, "BC" => Var::BC, "DE" => Var::DE, "HL" => Var::HL,
+            "SP" => Var::SP, "PC" => Var::PC,
+            "ZF" => Var::ZF, "NF" => Var::NF, "HF" => Var::HF, "CF" => Var::CF,
+            "IME" => Var::Ime,
+            _ => return None,
+        })
+    }
+
+    fn value(self, cpu: &CPU) -> i64 {
+        let r = &cpu.registers;
+        let pair = |hi: u8, lo: u8| u16::from_be_bytes([hi, lo]) as i64;
+        match self {
+            Var::A => r.a as i64,
+            Var::F => r.f as i64,
+            Var::B => r.b as i64,
+            Var::C => r.c as i64,
+            Var::D => r.d as i64,
+            Var::E => r.e as i64,
+            Var::H => r.h as i64,
+            Var::L => r.l as i64,
+            Var::AF => pair(r.a, r.f),
+            Var::BC => pair(r.b, r.c),
+            Var::DE => pair(r.d, r.e),
+            Var::HL => pair(r.h, r.l),
+            Var::SP => r.sp as i64,
+            Var::PC => r.pc as i64,
+            Var::ZF => (r.f >> 7 & 1) as i64,
+            Var::NF => (r.f >> 6 & 1) as i64,
+            Var::HF => (r.f >> 5 & 1) as i64,
+            Var::CF => (r.f >> 4 & 1) as i64,
+            Var::Ime => cpu.ime as i64,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Or, And, BitOr, BitXor, BitAnd,
+    Eq, Ne, Lt, Le, Gt, Ge,
+    Shl, Shr, Add, Sub, Mul, Div, Rem,
+}
+
+impl Op {
+    // Higher binds tighter
+    fn precedence(self) -> u8 {
+        match self {
+            Op::Or => 1,
+            Op::And => 2,
+            Op::BitOr => 3,
+            Op::BitXor => 4,
+            Op::BitAnd => 5,
+            Op::Eq | Op::Ne => 6,
+            Op::Lt | Op::Le | Op::Gt | Op::Ge => 7,
+            Op::Shl | Op::Shr => 8,
+            Op::Add | Op::Sub => 9,
+            Op::Mul | Op::Div | Op::Rem => 10,
+        }
+    }
+
+    // Division by zero and oversized shifts give 0 rather than stopping
+    fn apply(self, a: i64, b: i64) -> i64 {
+        match self {
+            Op::Or => (a != 0 || b != 0) as i64,
+            Op::And => (a != 0 && b != 0) as i64,
+            Op::BitOr => a | b,
+            Op::BitXor => a ^ b,
+            Op::BitAnd => a & b,
+            Op::Eq => (a == b) as i64,
+            Op::Ne => (a != b) as i64,
+            Op::Lt => (a < b) as i64,
+            Op::Le => (a <= b) as i64,
+            Op::Gt => (a > b) as i64,
+            Op::Ge => (a >= b) as i64,
+            Op::Shl => u32::try_from(b).ok().and_then(|b| a.checked_shl(b)).unwrap_or(0),
+            Op::Shr => u32::try_from(b).ok().and_then(|b| a.checked_shr(b)).unwrap_or(0),
+            Op::Add => a.wrapping_add(b),
+            Op::Sub => a.wrapping_sub(b),
+            Op::Mul => a.wrapping_mul(b),
+            Op::Div => a.checked_div(b).unwrap_or(0),
+            Op::Rem => a.checked_rem(b).unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Num(i64),
+    Var(Var),
+    Mem(Box<Expr>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Complement(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, cpu: &CPU) -> i64 {
+        match self {
+            Expr::Num(n) => *n,
+            Expr::Var(v) => v.value(cpu),
+            Expr::Mem(addr) => cpu.bus.read_byte(addr.eval(cpu) as u16) as i64,
+            Expr::Not(e) => (e.eval(cpu) == 0) as i64,
+            Expr::Neg(e) => e.eval(cpu).wrapping_neg(),
+            Expr::Complement(e) => !e.eval(cpu),
+            // && and || don't read memory they don't need
+            Expr::Binary(Op::And, a, b) => (a.eval(cpu) != 0 && b.eval(cpu) != 0) as i64,
+            Expr::Binary(Op::Or, a, b) => (a.eval(cpu) != 0 || b.eval(cpu) != 0) as i64,
+            Expr::Binary(op, a, b) => op.apply(a.eval(cpu), b.eval(cpu)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(i64),
+    Name(String),
+    Op(Op),
+    Not,
+    Tilde,
+    Open(char), // ( or [
+    Close(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Num(n) => write!(f, "{}", n),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Op(op) => write!(f, "{}", OPERATORS.iter().find(|(_, o)| o == op).map_or("?", |(s, _)| *s)),
+            Token::Not => write!(f, "!"),
+            Token::Tilde => write!(f, "~"),
+            Token::Open(c) | Token::Close(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+// Two-character operators first so they win over their prefixes
+const OPERATORS: [(&str, Op); 18] = [
+    ("||", Op::Or), ("&&", Op::And), ("==", Op::Eq), ("!=", Op::Ne),
+    ("<=", Op::Le), (">=", Op::Ge), ("<<", Op::Shl), (">>", Op::Shr),
+    ("|", Op::BitOr), ("^", Op::BitXor), ("&", Op::BitAnd), ("<", Op::Lt), (">", Op::Gt),
+    ("+", Op::Add), ("-", Op::Sub), ("*", Op::Mul), ("/", Op::Div), ("%", Op::Rem),
+];
+
+fn parse_number(text: &str) -> Result<i64, String> {
+    let parsed = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")).or_else(|| text.strip_prefix('$')) {
+        i64::from_str_radix(hex, 16)
+    } else {
+        text.parse()
+    };
+    parsed.map_err(|_| format!("bad number '{}'", text))
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_alphanumeric() || c == '$' || c == '_' {
+            let len = rest[1..].find(|c: char| !c.is_ascii_alphanumeric() && c != '_').map_or(rest.len(), |i| i + 1);
+            let word = &rest[..len];
+            tokens.push(if c.is_ascii_digit() || c == '$' { Token::Num(parse_number(word)?) } else { Token::Name(word.to_string()) });
+            rest = &rest[len..];
+        } else if let Some((text, op)) = OPERATORS.iter().find(|(text, _)| rest.starts_with(text)) {
+            tokens.push(Token::Op(*op));
+            rest = &rest[text.len()..];
+        } else {
+            tokens.push(match c {
+                '!' => Token::Not,
+                '~' => Token::Tilde,
+                '(' | '[' => Token::Open(c),
+                ')' | ']' => Token::Close(c),
+                _ => return Err(format!("unexpected '{}'", c)),
+            });
+            rest = &rest[c.len_utf8()..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    // Precedence climbing: operands joined by operators binding at least
+    // as tightly as `min`
+    fn binary(&mut self, min: u8) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while let Some(Token::Op(op)) = self.tokens.get(self.pos)
+            && op.precedence() >= min
+        {
+            let op = *op;
+            self.pos += 1;
+            let right = self.binary(op.precedence() + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Num(n)) => Ok(Expr::Num(n)),
+            Some(Token::Name(name)) => Var::from_name(&name).map(Expr::Var).ok_or(format!("unknown register '{}'", name)),
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Tilde) => Ok(Expr::Complement(Box::new(self.unary()?))),
+            Some(Token::Op(Op::Sub)) => Ok(Expr::Neg(Box::new(self.unary()?))),
+            Some(Token::Open(open)) => {
+                let inner = self.binary(1)?;
+                let close = if open == '(' { ')' } else { ']' };
+                match self.next() {
+                    Some(Token::Close(c)) if c == close => {}
+                    Some(token) => return Err(format!("expected '{}' but found '{}'", close, token)),
+                    None => return Err(format!("missing '{}'", close)),
+                }
+                Ok(if open == '[' { Expr::Mem(Box::new(inner)) } else { inner })
+            }
+            Some(token) => Err(format!("unexpected '{}'", token)),
+            None => Err("expression ends too early".to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parser = Parser { tokens: tokenize(source)?, pos: 0 };
+        let expr = parser.binary(1)?;
+        if let Some(token) = parser.next() {
+            return Err(format!("unexpected '{}'", token));
+        }
+        Ok(Self { source: source.trim().to_string(), expr })
+    }
+
+    pub fn eval(&self, cpu: &CPU) -> i64 {
+        self.expr.eval(cpu)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+// An address in ROM is only one bank's code, so a breakpoint there can be
+// limited to that bank; it's written BANK:ADDR like in instruction traces
+// (bank 0 being 0x0000-0x3FFF)
+#[derive(Clone, Debug)]
+pub struct Breakpoint {
+    pub addr: u16,
+    pub bank: Option<usize>,
+    pub condition: Option<Condition>,
+}
+
+fn parse_hex(text: &str) -> Option<usize> {
+    let text = text.trim();
+    let hex = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")).unwrap_or(text);
+    usize::from_str_radix(hex, 16).ok()
+}
+
+impl Breakpoint {
+    pub fn new(addr: u16) -> Self {
+        Self { addr, bank: None, condition: None }
+    }
+
+    // ADDR or BANK:ADDR in hex, with or without $ or 0x, optionally after
+    // the word "bank": 4F00, $12:$4F00, bank 0x12:0x4F00
+    pub fn parse(location: &str) -> Result<Self, String> {
+        let text = location.trim();
+        let text = text.strip_prefix("bank").unwrap_or(text);
+        let bad = || format!("bad address '{}'", location.trim());
+        let (bank, addr) = match text.split_once(':') {
+            Some((bank, addr)) => (Some(parse_hex(bank).ok_or_else(bad)?), addr),
+            None => (None, text),
+        };
+        let addr = parse_hex(addr).and_then(|a| u16::try_from(a).ok()).ok_or_else(bad)?;
+        match bank {
+            Some(_) if addr >= 0x8000 => Err(format!("${:04X} isn't in ROM, so it has no bank", addr)),
+            Some(b) if b != 0 && addr < 0x4000 => Err(format!("${:04X} is always bank 0", addr)),
+            _ => Ok(Self { addr, bank, condition: None }),
+        }
+    }
+
+    // Whether the CPU, about to run the instruction at PC, should stop
+    pub fn hit(&self, cpu: &CPU) -> bool {
+        let pc = cpu.registers.pc;
+        pc == self.addr
+            && self.bank.is_none_or(|b| cpu.bus.rom_bank_at(pc) == b)
+            && self.condition.as_ref().is_none_or(|c| c.eval(cpu) != 0)
+    }
+
+    // Same place, whatever the condition
+    pub fn same_place(&self, other: &Breakpoint) -> bool {
+        self.addr == other.addr && self.bank == other.bank
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.bank {
+            Some(bank) => write!(f, "{:03X}:{:04X}", bank, self.addr)?,
+            None => write!(f, "${:04X}", self.addr)?,
+        }
+        if let Some(condition) = &self.condition {
+            write!(f, " if {}", condition)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mmu::{MMU, Model};
+
+    fn cpu() -> CPU {
+        CPU::new(MMU::new(vec![0; 0x8000], "test.gb", Model::Dmg))
+    }
+
+    fn eval(source: &str) -> i64 {
+        Condition::parse(source).unwrap_or_else(|e| panic!("'{}': {}", source, e)).eval(&cpu())
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(eval("0x4ABC"), 0x4ABC);
+        assert_eq!(eval("$4abc"), 0x4ABC);
+        assert_eq!(eval("1234"), 1234);
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3"), 7);
+        assert_eq!(eval("(1 + 2) * 3"), 9);
+        assert_eq!(eval("10 - 4 - 3"), 3);
+        assert_eq!(eval("1 << 2 + 1"), 8);
+        assert_eq!(eval("6 & 3 == 3"), 0); // & is looser than ==, as in C
+        assert_eq!(eval("1 | 2 ^ 3 & 1"), 3);
+        assert_eq!(eval("0 || 1 && 0"), 0);
+        assert_eq!(eval("1 < 2 == 1"), 1);
+        assert_eq!(eval("-2 * 3"), -6);
+        assert_eq!(eval("!0 + ~0"), 0);
+    }
+
+    #[test]
+    fn division_by_zero_and_big_shifts_give_zero() {
+        assert_eq!(eval("5 / 0"), 0);
+        assert_eq!(eval("5 % 0"), 0);
+        assert_eq!(eval("1 << 64"), 0);
+        assert_eq!(eval("1 >> -1"), 0);
+    }
+
+    #[test]
+    fn registers_flags_and_memory() {
+        let mut cpu = cpu();
+        cpu.registers.a = 0x3F;
+        cpu.registers.f = 0x90;
+        cpu.registers.h = 0xC1;
+        cpu.registers.l = 0x23;
+        cpu.bus.write_byte(0xC123, 6);
+        let holds = |source: &str| Condition::parse(source).unwrap().eval(&cpu) != 0;
+        assert!(holds("A==0x3F && [0xC123]>5"));
+        assert!(holds("a == 0x3f")); // Names are case-insensitive
+        assert!(holds("ZF && CF && !NF && !HF"));
+        assert!(holds("AF == 0x3F90"));
+        assert!(holds("[HL] == 6"));
+        assert!(holds("[0xC100 + 0x23] == 6"));
+        assert!(!holds("IME"));
+    }
+
+    #[test]
+    fn malformed_conditions() {
+        for source in ["", "A ==", "(1 + 2", "[0xC000)", "1 2", "Q == 1", "0xZZ", "A @ 1", ")"] {
+            assert!(Condition::parse(source).is_err(), "'{}' parsed", source);
+        }
+    }
+
+    #[test]
+    fn locations() {
+        let b = Breakpoint::parse("4F00").unwrap();
+        assert_eq!((b.addr, b.bank), (0x4F00, None));
+        let b = Breakpoint::parse("$12:$4F00").unwrap();
+        assert_eq!((b.addr, b.bank), (0x4F00, Some(0x12)));
+        let b = Breakpoint::parse("bank 0x12:0x4F00").unwrap();
+        assert_eq!((b.addr, b.bank), (0x4F00, Some(0x12)));
+        assert_eq!(b.to_string(), "012:4F00");
+        assert_eq!(Breakpoint::parse("0:0150").unwrap().bank, Some(0));
+        for location in ["", "zz", "10000", "1:8000", "1:0150", "x:4000"] {
+            assert!(Breakpoint::parse(location).is_err(), "'{}' parsed", location);
+        }
+    }
+}
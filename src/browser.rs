@@ -0,0 +1,123 @@
+// browser.rs
+// In-window ROM browser, used instead of the native file dialog when the
+// menu is driven from a controller.
+use std::fs;
+use std::path::PathBuf;
+
+use crate::draw_text;
+use crate::menu::{NavInput, move_focus};
+use crate::theme::Theme;
+
+const ROM_EXTENSIONS: [&str; 3] = ["gb", "gbc", "bin"];
+const LINE_H: usize = 12;
+
+pub enum BrowserResult {
+    Open,
+    Picked(PathBuf),
+    Cancelled,
+}
+
+pub struct RomBrowser {
+    pub dir: PathBuf,
+    entries: Vec<(String, bool)>, // (name, is_dir)
+    focus: usize,
+    scroll: usize,
+}
+
+impl RomBrowser {
+    pub fn new(dir: &str) -> Self {
+        let dir = fs::canonicalize(dir).unwrap_or_else(|_| PathBuf::from("."));
+        let mut browser = Self { dir, entries: Vec::new(), focus: 0, scroll: 0 };
+        browser.refresh();
+        browser
+    }
+
+    fn refresh(&mut self) {
+        let mut dirs = Vec::new();
+        let mut roms = Vec::new();
+        if let Ok(read) = fs::read_dir(&self.dir) {
+            for entry in read.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name.starts_with('.') { continue; }
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(name);
+                } else if path.extension().is_some_and(|e| ROM_EXTENSIONS.contains(&e.to_string_lossy().to_lowercase().as_str())) {
+                    roms.push(name);
+                }
+            }
+        }
+        dirs.sort_by_key(|d| d.to_lowercase());
+        roms.sort_by_key(|r| r.to_lowercase());
+
+        self.entries.clear();
+        if self.dir.parent().is_some() {
+            self.entries.push(("..".to_string(), true));
+        }
+        self.entries.extend(dirs.into_iter().map(|d| (d, true)));
+        self.entries.extend(roms.into_iter().map(|r| (r, false)));
+        self.focus = 0;
+        self.scroll = 0;
+    }
+
+    pub fn nav(&mut self, nav: NavInput, visible: usize) -> BrowserResult {
+        if self.entries.is_empty() {
+            return if nav == NavInput::Cancel { BrowserResult::Cancelled } else { BrowserResult::Open };
+        }
+        match nav {
+            NavInput::Up | NavInput::Down => self.focus = move_focus(self.focus, self.entries.len(), nav),
+            // Left/Right page through long folders
+            NavInput::Left => self.focus = self.focus.saturating_sub(visible),
+            NavInput::Right => self.focus = (self.focus + visible).min(self.entries.len() - 1),
+            NavInput::Cancel => return BrowserResult::Cancelled,
+            NavInput::Confirm => {
+                let (name, is_dir) = self.entries[self.focus].clone();
+                if !is_dir {
+                    return BrowserResult::Picked(self.dir.join(name));
+                }
+                self.dir = if name == ".." {
+                    self.dir.parent().map(PathBuf::from).unwrap_or_else(|| self.dir.clone())
+                } else {
+                    self.dir.join(name)
+                };
+                self.refresh();
+            }
+        }
+        // Keep the focused line on screen
+        if self.focus < self.scroll {
+            self.scroll = self.focus;
+        } else if self.focus >= self.scroll + visible {
+            self.scroll = self.focus + 1 - visible;
+        }
+        BrowserResult::Open
+    }
+
+    // Number of entries that fit in `height` pixels below the title line
+    pub fn visible_lines(height: usize) -> usize {
+        (height.saturating_sub(2 * LINE_H) / LINE_H).max(1)
+    }
+
+    pub fn draw(&self, buffer: &mut [u32], width: usize, top: usize, height: usize, theme: &Theme) {
+        for p in &mut buffer[top * width..(top + height) * width] {
+            *p = theme.panel;
+        }
+        draw_text(buffer, width, &self.dir.to_string_lossy(), 8, top + 4, theme.heading, 1);
+
+        let visible = Self::visible_lines(height);
+        for (row, (name, is_dir)) in self.entries.iter().enumerate().skip(self.scroll).take(visible) {
+            let y = top + 2 * LINE_H + (row - self.scroll) * LINE_H;
+            let focused = row == self.focus;
+            if focused {
+                for p in &mut buffer[(y - 2) * width..(y + LINE_H - 2) * width] {
+                    *p = theme.focus;
+                }
+            }
+            let color = if *is_dir { theme.folder } else { theme.text };
+            let label = if *is_dir { format!("{}/", name) } else { name.clone() };
+            draw_text(buffer, width, &label, 16, y, color, 1);
+        }
+        if self.entries.is_empty() {
+            draw_text(buffer, width, "(no ROMs here)", 16, top + 2 * LINE_H, theme.faint, 1);
+        }
+    }
+}
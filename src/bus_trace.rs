@@ -0,0 +1,46 @@
+// bus_trace.rs
+// The CPU's most recent memory reads and writes, kept in a ring buffer so a
+// debugger can show what touched an address just before something went wrong
+// (garbage in OAM, a clobbered variable) without logging a whole trace.
+// Instruction fetches aren't recorded, only the data the instructions move.
+pub struct Access {
+    pub addr: u16,
+    pub value: u8,
+    pub write: bool,
+    pub pc: u16,    // Instruction that made the access
+    pub cycle: u64, // T-cycles since power-on
+}
+
+pub struct BusTrace {
+    entries: Vec<Access>,
+    capacity: usize,
+    next: usize, // Slot the next access goes in once the buffer is full
+    pub pc: u16, // Set by the CPU at the start of each instruction
+}
+
+impl BusTrace {
+    pub fn new(capacity: usize) -> Self {
+        Self { entries: Vec::with_capacity(capacity), capacity: capacity.max(1), next: 0, pc: 0 }
+    }
+
+    pub fn record(&mut self, addr: u16, value: u8, write: bool, cycle: u64) {
+        let access = Access { addr, value, write, pc: self.pc, cycle };
+        if self.entries.len() < self.capacity {
+            self.entries.push(access);
+        } else {
+            self.entries[self.next] = access;
+        }
+        self.next = (self.next + 1) % self.capacity;
+    }
+
+    // Oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Access> {
+        let (newer, older) = self.entries.split_at(if self.entries.len() < self.capacity { 0 } else { self.next });
+        older.iter().chain(newer)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.next = 0;
+    }
+}
@@ -0,0 +1,244 @@
+// cli.rs
+// Command line options. Without any, gb_rs opens the window with no ROM.
+//
+//   gb_rs [rom.gb] [--state slot3 | --state file.state] [--movie run.txt] [--speed 2] [--watch]
+//       Start straight into a game, optionally from a state or playing an
+//       input log, running at a multiple of normal speed. --watch reloads
+//       the ROM whenever the file is rebuilt (for homebrew development).
+//   gb_rs --headless [--frames N] [--hash] [--serial] [--cgb] rom.gb
+//       Run without video or audio and report on the final frame; exits
+//       with 1 if the game crashed (unknown opcode or lock-up)
+//   gb_rs --debug [rom.gb]
+//       Open the game paused in the debugger on this terminal (see
+//       repl.rs), with the window showing the game
+//   gb_rs --debug-tui [--cgb] rom.gb
+//       Step through the game in a terminal debugger (see debug_tui.rs)
+//   gb_rs --verify-movie run.txt [--cgb] rom.gb
+//       Replay an input log several ways and check every frame matches
+//   gb_rs --test-roms DIR [--frames N] [--cgb]
+//       Run every ROM under DIR as an accuracy test and print a summary
+//   gb_rs --compat-report DIR [--frames N] [--cgb] [--report out.md|out.csv]
+//       Run every ROM under DIR and write a compatibility report
+//   gb_rs --bgb-link PORT | --bgb-link HOST:PORT [rom.gb]
+//       Link cable to BGB or another emulator speaking its protocol:
+//       listen for it on PORT or connect to it (see bgb_link.rs)
+//   gb_rs --gdb PORT [rom.gb]
+//       Let gdb or an IDE attach on localhost:PORT to debug the game (see
+//       gdb.rs)
+//   [--doctor-log FILE]
+//       With a ROM, in the window or headless: log every instruction in
+//       Gameboy Doctor's format (see CPU::start_doctor_log)
+//   [--trace FILE] [--trace-pc START-END] [--trace-bank N]
+//   [--trace-opcodes CD,C4,...] [--trace-ring N]
+//       With a ROM, in the window or headless: log executed instructions
+//       (see instr_trace.rs), only those in the PC range (hex), ROM bank
+//       or with those opcodes (hex) if given, and only the last N if
+//       --trace-ring is given
+//   [--coverage FILE]
+//       With a ROM, in the window or headless: record which ROM bytes run
+//       as code and write a report per bank to FILE on exit (see
+//       coverage.rs)
+//   [--profile FILE]
+//       With a ROM, in the window or headless: count the cycles each
+//       routine takes and write a report to FILE on exit, using the ROM's
+//       .sym file for names if there is one (see profiler.rs)
+//   [--uninit-reads]
+//       With a ROM, in the window or headless: log reads of WRAM and HRAM
+//       the game never wrote, with the PC (see uninit.rs)
+//   [--ram-fill zero|ones|random|pattern] [--ram-seed N]
+//       With any of the above: what RAM holds at power-on (see
+//       mmu::RamFill), instead of the config's setting or zeros
+//   gb_rs --portable | --data-dir DIR [...]
+//       Keep the config, saves, states and screenshots next to the
+//       executable (a portable.txt there does the same) or all in DIR
+use crate::instr_trace::{InstrTrace, TraceFilter};
+use crate::mmu::{Model, RamFill};
+use crate::profiler::Profiler;
+
+pub struct Args {
+    pub rom: Option<String>,
+    pub headless: bool,
+    pub debug: bool,
+    pub debug_tui: bool,
+    pub test_roms: Option<String>, // Directory of test ROMs to run
+    pub compat_report: Option<String>, // Directory of games to report on
+    pub report: Option<String>, // Where the compatibility report goes
+    pub verify_movie: Option<String>, // Input log to check determinism with
+    pub frames: Option<u64>,
+    pub hash: bool,   // Print a hash of the final framebuffer
+    pub serial: bool, // Print what the ROM sent over the serial port
+    pub model: Option<Model>,
+    pub ram_fill: Option<RamFill>,
+    pub ram_seed: Option<u64>,
+    pub doctor_log: Option<String>, // Gameboy Doctor trace file
+    pub trace: Option<String>, // Instruction trace file
+    pub trace_filter: TraceFilter,
+    pub trace_ring: Option<usize>, // Lines kept when only the last ones are wanted
+    pub coverage: Option<String>, // ROM coverage report file
+    pub profile: Option<String>, // Cycle profile report file
+    pub uninit_reads: bool, // Log reads of RAM never written
+    pub state: Option<String>, // "slotN" or a state file
+    pub movie: Option<String>, // Input log to play from power-on
+    pub speed: u32,
+    pub watch: bool, // Reload the ROM when its file changes
+    pub bgb_link: Option<String>, // Port to listen on or host:port to connect to
+    pub gdb: Option<u16>, // Port for the gdb server
+    pub portable: bool,
+    pub data_dir: Option<String>,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, String> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    fn parse_from(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut out = Self {
+            rom: None, headless: false, debug: false, debug_tui: false, test_roms: None, compat_report: None, report: None, verify_movie: None, frames: None, hash: false, serial: false, model: None, ram_fill: None, ram_seed: None, doctor_log: None,
+            trace: None, trace_filter: TraceFilter::default(), trace_ring: None, coverage: None, profile: None, uninit_reads: false,
+            state: None, movie: None, speed: 1, watch: false, bgb_link: None, gdb: None, portable: false, data_dir: None,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => out.headless = true,
+                "--debug" => out.debug = true,
+                "--debug-tui" => out.debug_tui = true,
+                "--hash" => out.hash = true,
+                "--serial" => out.serial = true,
+                "--watch" => out.watch = true,
+                "--portable" => out.portable = true,
+                "--data-dir" => out.data_dir = Some(args.next().ok_or("--data-dir needs a directory")?),
+                "--dmg" => out.model = Some(Model::Dmg),
+                "--cgb" => out.model = Some(Model::Cgb),
+                "--ram-fill" => {
+                    let fill = args.next().ok_or("--ram-fill needs zero, ones, random or pattern")?;
+                    out.ram_fill = Some(match fill.as_str() {
+                        "zero" => RamFill::Zero,
+                        "ones" => RamFill::Ones,
+                        "random" => RamFill::Random,
+                        "pattern" => RamFill::Pattern,
+                        _ => return Err(format!("bad RAM fill '{}'", fill)),
+                    });
+                }
+                "--ram-seed" => {
+                    let n = args.next().ok_or("--ram-seed needs a number")?;
+                    out.ram_seed = Some(n.parse().map_err(|_| format!("bad RAM seed '{}'", n))?);
+                }
+                "--doctor-log" => out.doctor_log = Some(args.next().ok_or("--doctor-log needs a file")?),
+                "--profile" => out.profile = Some(args.next().ok_or("--profile needs a file")?),
+                "--uninit-reads" => out.uninit_reads = true,
+                "--coverage" => out.coverage = Some(args.next().ok_or("--coverage needs a file")?),
+                "--trace" => out.trace = Some(args.next().ok_or("--trace needs a file")?),
+                "--trace-pc" => {
+                    let range = args.next().ok_or("--trace-pc needs a range like 4000-7FFF")?;
+                    let (start, end) = range.split_once('-').unwrap_or((&range, &range));
+                    let hex = |s: &str| u16::from_str_radix(s.trim_start_matches('$'), 16).map_err(|_| format!("bad PC range '{}'", range));
+                    out.trace_filter.pc = Some((hex(start)?, hex(end)?));
+                }
+                "--trace-bank" => {
+                    let n = args.next().ok_or("--trace-bank needs a number")?;
+                    out.trace_filter.bank = Some(n.parse().map_err(|_| format!("bad bank '{}'", n))?);
+                }
+                "--trace-opcodes" => {
+                    let list = args.next().ok_or("--trace-opcodes needs a list like CD,C4")?;
+                    out.trace_filter.opcodes = list
+                        .split(',')
+                        .map(|op| u8::from_str_radix(op.trim().trim_start_matches('$'), 16).map_err(|_| format!("bad opcode '{}'", op)))
+                        .collect::<Result<_, _>>()?;
+                }
+                "--trace-ring" => {
+                    let n = args.next().ok_or("--trace-ring needs a number of lines")?;
+                    out.trace_ring = Some(n.parse().map_err(|_| format!("bad line count '{}'", n))?);
+                }
+                "--frames" => {
+                    let n = args.next().ok_or("--frames needs a number")?;
+                    out.frames = Some(n.parse().map_err(|_| format!("bad frame count '{}'", n))?);
+                }
+                "--test-roms" => out.test_roms = Some(args.next().ok_or("--test-roms needs a directory")?),
+                "--compat-report" => out.compat_report = Some(args.next().ok_or("--compat-report needs a directory")?),
+                "--report" => out.report = Some(args.next().ok_or("--report needs a file")?),
+                "--verify-movie" => out.verify_movie = Some(args.next().ok_or("--verify-movie needs a file")?),
+                "--gdb" => {
+                    let port = args.next().ok_or("--gdb needs a port")?;
+                    out.gdb = Some(port.parse().map_err(|_| format!("bad port '{}'", port))?);
+                }
+                "--bgb-link" => out.bgb_link = Some(args.next().ok_or("--bgb-link needs a port or host:port")?),
+                "--state" => out.state = Some(args.next().ok_or("--state needs a slot or file")?),
+                "--movie" => out.movie = Some(args.next().ok_or("--movie needs a file")?),
+                "--speed" => {
+                    let n = args.next().ok_or("--speed needs a number")?;
+                    out.speed = n.parse().ok().filter(|&s| s > 0).ok_or(format!("bad speed '{}'", n))?;
+                }
+                a if a.starts_with("--") => return Err(format!("unknown option '{}'", a)),
+                _ if out.rom.is_some() => return Err(format!("unexpected argument '{}'", arg)),
+                _ => out.rom = Some(arg),
+            }
+        }
+        if (out.headless || out.debug_tui || out.verify_movie.is_some()) && out.rom.is_none() {
+            return Err("--headless, --debug-tui and --verify-movie need a ROM".to_string());
+        }
+        if (out.trace_filter.pc.is_some() || out.trace_filter.bank.is_some() || !out.trace_filter.opcodes.is_empty() || out.trace_ring.is_some())
+            && out.trace.is_none()
+        {
+            return Err("--trace-pc, --trace-bank, --trace-opcodes and --trace-ring need --trace".to_string());
+        }
+        if (out.state.is_some() || out.movie.is_some()) && out.rom.is_none() {
+            return Err("--state and --movie need a ROM".to_string());
+        }
+        // Movies play from power-on, a state would put them out of sync
+        if out.state.is_some() && out.movie.is_some() {
+            return Err("--state and --movie can't be combined".to_string());
+        }
+        if let Some(rom) = &out.rom
+            && !std::path::Path::new(rom).is_file()
+        {
+            return Err(format!("ROM not found: '{}'", rom));
+        }
+        Ok(out)
+    }
+
+    // Power-on RAM for modes without a config: zeros unless asked otherwise
+    pub fn ram_fill(&self) -> (RamFill, u64) {
+        (self.ram_fill.unwrap_or(RamFill::Zero), self.ram_seed.unwrap_or(0))
+    }
+
+    // The instruction trace asked for, if any
+    pub fn instr_trace(&self) -> Option<std::io::Result<InstrTrace>> {
+        let path = self.trace.as_deref()?;
+        let filter = self.trace_filter.clone();
+        Some(match self.trace_ring {
+            Some(lines) => Ok(InstrTrace::ring(path, filter, lines)),
+            None => InstrTrace::to_file(path, filter),
+        })
+    }
+
+    // The cycle profiler asked for, if any, with the symbols from a .sym
+    // file next to `rom` (as RGBDS and no$gmb name them)
+    pub fn profiler(&self, rom: &str) -> Option<Profiler> {
+        let mut profiler = Profiler::new(self.profile.as_deref()?);
+        let sym = std::path::Path::new(rom).with_extension("sym");
+        if let Ok(text) = std::fs::read_to_string(&sym) {
+            let n = profiler.load_symbols(&text);
+            println!("✓ Profiler: {} symbols from '{}'", n, sym.display());
+        }
+        Some(profiler)
+    }
+
+    // Paths given relative to where we were started, made absolute before
+    // portable mode moves the working directory
+    pub fn absolutize(&mut self) {
+        let abs = |p: &mut String| {
+            if let Ok(full) = std::path::absolute(&*p) {
+                *p = full.to_string_lossy().to_string();
+            }
+        };
+        for path in [&mut self.rom, &mut self.movie, &mut self.doctor_log, &mut self.trace, &mut self.coverage, &mut self.profile].into_iter().flatten() {
+            abs(path);
+        }
+        if let Some(state) = &mut self.state
+            && !state.starts_with("slot")
+        {
+            abs(state);
+        }
+    }
+}
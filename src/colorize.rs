@@ -0,0 +1,68 @@
+// colorize.rs
+// The Game Boy Color's colorization of original Game Boy games. The CGB boot
+// ROM picks a background and two sprite palettes for a DMG-only cartridge:
+// one of twelve by the buttons held while the logo shows, otherwise by a hash
+// of the title for Nintendo's own games, otherwise a default green and red.
+//
+// The boot ROM's title table has about 90 entries; only a few well-known
+// games are listed here, the rest get the default like third-party games.
+
+// Background, OBJ0 and OBJ1 shades for color IDs 0-3
+pub type Colorization = [[u32; 4]; 3];
+
+const fn same(shades: [u32; 4]) -> Colorization {
+    [shades, shades, shades]
+}
+
+const BROWN: [u32; 4] = [0xFFFFFFFF, 0xFFFFAD63, 0xFF843100, 0xFF000000];
+const RED: [u32; 4] = [0xFFFFFFFF, 0xFFFF8484, 0xFF943A3A, 0xFF000000];
+const GREEN: [u32; 4] = [0xFFFFFFFF, 0xFF7BFF31, 0xFF008400, 0xFF000000];
+const BLUE: [u32; 4] = [0xFFFFFFFF, 0xFF63A5FF, 0xFF0000FF, 0xFF000000];
+
+// Held buttons (Right, Left, Up, Down, A, B from bit 0) and what they pick
+pub const COMBOS: [(u8, Colorization); 12] = [
+    (0x04, same(BROWN)),                                                                          // Up
+    (0x14, [RED, GREEN, BLUE]),                                                                   // Up+A
+    (0x24, same([0xFFFFE6C5, 0xFFCE9C84, 0xFF846B29, 0xFF5A3108])),                               // Up+B
+    (0x02, [BLUE, RED, GREEN]),                                                                   // Left
+    (0x12, [[0xFFFFFFFF, 0xFF8C8CDE, 0xFF52528C, 0xFF000000], RED, BROWN]),                       // Left+A
+    (0x22, same([0xFFFFFFFF, 0xFFA5A5A5, 0xFF525252, 0xFF000000])),                               // Left+B
+    (0x08, same([0xFFFFFFA5, 0xFFFF9494, 0xFF9494FF, 0xFF000000])),                               // Down
+    (0x18, same([0xFFFFFFFF, 0xFFFFFF00, 0xFFFF0000, 0xFF000000])),                               // Down+A
+    (0x28, [[0xFFFFFFFF, 0xFFFFFF00, 0xFF7B4A00, 0xFF000000], BLUE, GREEN]),                      // Down+B
+    (0x01, same([0xFFFFFFFF, 0xFF52FF00, 0xFFFF4200, 0xFF000000])),                               // Right
+    (0x11, [[0xFFFFFFFF, 0xFF7BFF31, 0xFF0063C5, 0xFF000000], RED, RED]),                         // Right+A
+    (0x21, same([0xFF000000, 0xFF008484, 0xFFFFDE00, 0xFFFFFFFF])),                               // Right+B
+];
+
+const DEFAULT: usize = 10; // Right+A
+
+// Title hash, the title's 4th letter where the hash alone is ambiguous (0
+// otherwise), and the entry in COMBOS the game gets
+const GAMES: [(u8, u8, usize); 4] = [
+    (0x14, 0, 1),    // POKEMON RED
+    (0x61, b'E', 3), // POKEMON BLUE
+    (0xDB, 0, 7),    // TETRIS
+    (0x49, 0, 6),    // KIRBY DREAM LAND
+];
+
+// True for cartridges that don't use Game Boy Color features, which the CGB
+// runs in compatibility mode
+pub fn is_dmg_only(rom: &[u8]) -> bool {
+    rom[0x143] & 0x80 == 0
+}
+
+// `held` is the buttons held at power on, active high
+pub fn for_game(rom: &[u8], held: u8) -> Colorization {
+    if let Some((_, colors)) = COMBOS.iter().find(|(buttons, _)| *buttons == held & 0x3F) {
+        return *colors;
+    }
+    // Only Nintendo's games are looked up
+    let nintendo = rom[0x14B] == 0x01 || (rom[0x14B] == 0x33 && &rom[0x144..0x146] == b"01");
+    let hash = rom[0x134..0x144].iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+    let game = GAMES.iter().find(|&&(h, letter, _)| h == hash && (letter == 0 || letter == rom[0x137]));
+    match game {
+        Some(&(_, _, i)) if nintendo => COMBOS[i].1,
+        _ => COMBOS[DEFAULT].1,
+    }
+}
@@ -0,0 +1,162 @@
+// compat.rs
+// Compatibility report: runs every ROM under a folder headlessly and records
+// how far each one got, to track progress across a game library. Written as
+// Markdown, or CSV when the report file name ends in .csv.
+//
+// Each ROM ends up as one of:
+//   OK           ran all frames and shows something
+//   BLANK        ran all frames but the screen is a single color
+//   LOCKED       ran an illegal opcode, which hangs the CPU
+//   OPCODE       hit an opcode the CPU doesn't implement
+//   CRASH        any other panic
+//   MAPPER       its cartridge type isn't emulated (not run)
+//   LOAD ERROR   couldn't be loaded at all
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use crate::EmulatorState;
+use crate::cli::Args;
+use crate::mmu::Model;
+use crate::test_roms::find_roms;
+use gb_rs::crash;
+use gb_rs::error::CoreError;
+
+const DEFAULT_FRAMES: u64 = 60 * 30;
+
+struct Entry {
+    path: PathBuf,
+    title: String,
+    mapper: Option<u8>,
+    status: &'static str,
+    frames: u64,
+    detail: String, // Panic message or load error
+}
+
+fn run_one(path: &Path, model: Model, frames: u64) -> Entry {
+    let rom = path.to_string_lossy().to_string();
+    let mut entry = Entry { path: path.to_path_buf(), title: String::new(), mapper: None, status: "OK", frames: 0, detail: String::new() };
+    let mut emu = match EmulatorState::load_rom(&rom, model, crate::ppu::PPU::new().palette) {
+        Ok(emu) => emu,
+        Err(e) => {
+            entry.status = "LOAD ERROR";
+            entry.detail = e.to_string().lines().next().unwrap_or_default().to_string();
+            return entry;
+        }
+    };
+    entry.title = emu.title();
+    entry.mapper = Some(emu.cpu.bus.mbc_type);
+    if !emu.cpu.bus.mapper_supported() {
+        entry.status = "MAPPER";
+        return entry;
+    }
+    emu.detach_battery();
+    emu.cpu.bus.serial_log = Some(Vec::new());
+    emu.cpu.bus.debug_log = Some(Vec::new());
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        for frame in 1..=frames {
+            emu.run_frame(1, &mut || 0xFF);
+            emu.cpu.bus.apu.sample_buffer.clear();
+            entry.frames = frame;
+            if emu.cpu.locked || emu.fault.is_some() {
+                break;
+            }
+        }
+    }));
+    match result {
+        Ok(()) if let Some(e @ CoreError::UnknownOpcode { .. }) = &emu.fault => {
+            entry.status = "OPCODE";
+            entry.detail = e.to_string().lines().next().unwrap_or_default().to_string();
+        }
+        // EmulatorState catches panics in the core itself
+        Ok(()) if let Some(CoreError::Internal { message, .. }) = &emu.fault => {
+            entry.status = "CRASH";
+            entry.detail = message.clone();
+        }
+        Ok(()) if emu.cpu.locked => {
+            entry.status = "LOCKED";
+            entry.detail = emu.cpu.lock_message().unwrap_or_default();
+        }
+        Ok(()) => {
+            let first = emu.cpu.ppu.frame_buffer[0];
+            if emu.cpu.ppu.frame_buffer.iter().all(|&p| p == first) {
+                entry.status = "BLANK";
+            }
+        }
+        Err(payload) => {
+            entry.status = "CRASH";
+            entry.detail = crash::panic_message(&*payload);
+        }
+    }
+    entry
+}
+
+fn markdown(entries: &[Entry], frames: u64) -> String {
+    let mut out = format!("# gb_rs compatibility report\n\n{} ROMs, {} frames each.\n\n", entries.len(), frames);
+    out.push_str("| ROM | Title | Mapper | Result | Frames | Details |\n|---|---|---|---|---|---|\n");
+    for e in entries {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} |\n",
+            e.path.display(),
+            e.title,
+            e.mapper.map(|m| format!("{:#04X}", m)).unwrap_or_default(),
+            e.status,
+            e.frames,
+            e.detail.replace('|', "\\|"),
+        ));
+    }
+    out
+}
+
+fn csv(entries: &[Entry]) -> String {
+    let quote = |s: &str| format!("\"{}\"", s.replace('"', "\"\""));
+    let mut out = String::from("rom,title,mapper,result,frames,details\n");
+    for e in entries {
+        out.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            quote(&e.path.to_string_lossy()),
+            quote(&e.title),
+            e.mapper.map(|m| format!("{:#04X}", m)).unwrap_or_default(),
+            e.status,
+            e.frames,
+            quote(&e.detail),
+        ));
+    }
+    out
+}
+
+pub fn run(args: &Args) -> i32 {
+    let Some(dir) = &args.compat_report else { return 2 };
+    let mut roms = Vec::new();
+    find_roms(Path::new(dir), &mut roms);
+    if roms.is_empty() {
+        eprintln!("✗ No ROMs found in '{}'", dir);
+        return 1;
+    }
+
+    let model = args.model.unwrap_or(Model::Dmg);
+    let frames = args.frames.unwrap_or(DEFAULT_FRAMES);
+    // Panics are expected here and end up in the report, not on the console
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let entries: Vec<Entry> = roms
+        .iter()
+        .map(|path| {
+            let entry = run_one(path, model, frames);
+            println!("{:<10}  {}", entry.status, path.display());
+            entry
+        })
+        .collect();
+    panic::set_hook(hook);
+
+    let out = args.report.clone().unwrap_or_else(|| "compat_report.md".to_string());
+    let text = if out.ends_with(".csv") { csv(&entries) } else { markdown(&entries, frames) };
+    if let Err(e) = fs::write(&out, text) {
+        eprintln!("✗ Couldn't write '{}': {}", out, e);
+        return 1;
+    }
+    let ok = entries.iter().filter(|e| e.status == "OK").count();
+    println!("\n{}/{} OK, report written to '{}'", ok, entries.len(), out);
+    0
+}
@@ -0,0 +1,483 @@
+// config.rs
+// Persistent settings, stored as TOML in the platform config directory
+// (e.g. ~/.config/gb_rs/config.toml). Missing fields fall back to defaults so
+// old config files keep loading as new settings are added.
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use gilrs::Gamepad;
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio::{FastForwardAudio, StereoMode};
+use crate::hotkeys::{HoldMode, HotkeyMapping};
+use crate::macros::InputMacro;
+use crate::mmu::{Model, RamFill};
+use crate::ram_watch::RamWatch;
+use crate::{ComboBinding, InputMapping, SyncMode};
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub model: Model,
+    #[serde(with = "hex_colors")]
+    pub palette: [u32; 4],  // Shades for color IDs 0-3 (lightest first), see theme::PALETTE_PRESETS
+    pub high_contrast_ui: bool, // Menus and overlays in high-contrast colors
+    pub border: String, // BMP drawn around the screen, see border.rs ("" = none)
+    pub ram_fill: RamFill, // Power-on WRAM/HRAM/cartridge RAM contents
+    pub ram_seed: u64,     // Seed for RamFill::Random
+    pub cgb_colorization: bool, // Color DMG games on the CGB model like its boot ROM does (see colorize.rs)
+    pub window_scale: usize,
+    pub display_hz: f64, // Monitor refresh rate to present at (0 = once per Game Boy frame)
+    pub audio: AudioConfig,
+    pub paths: PathsConfig,
+    pub auto_state: AutoStateConfig,
+    pub spectator: SpectatorConfig,
+    pub remote_debug: RemoteDebugConfig,
+    pub discord: DiscordConfig,
+    pub livesplit: LiveSplitConfig,
+    pub input: InputMapping,
+    pub turbo_frames: u32, // Turbo holds each pressed/released phase this many frames
+    pub pause_on_disconnect: bool, // Pause when the pad in use drops out
+    pub pause_when_unfocused: bool, // Pause (and mute) while another window has focus
+    pub resume_on_launch: bool, // Save a state on exit and offer it when the ROM is loaded again
+    pub block_opposite_dirs: bool, // Left+Right / Up+Down read as neither, like a real D-pad
+    pub input_polls_per_frame: u32, // Joypad reads per emulated frame (1 = once at frame start)
+    pub watch_rom: bool, // Reload the ROM whenever its file is rewritten (homebrew development)
+    // Player 1 keys/pad inputs that press several buttons, e.g.
+    //   [[combos]]
+    //   key = "C"
+    //   pad = "RightTrigger"
+    //   buttons = ["A", "B"]
+    pub combos: Vec<ComboBinding>,
+    pub macros: Vec<InputMacro>, // Recorded with the macro hotkey, see macros.rs
+    // Per-game overrides, keyed by game_key, e.g.
+    //   [games."TETRIS 16BF"]
+    //   palette = [...]
+    //   speed = 2
+    //   border = "borders/tetris.bmp"
+    //   [[games."TETRIS 16BF".watches]]
+    //   label = "LINES"
+    //   addr = 0xFF9E
+    pub games: BTreeMap<String, GameOverrides>,
+    pub hotkeys: HotkeyMapping,
+    pub fast_forward_mode: HoldMode,
+    // Controller bindings per pad, keyed by Config::pad_key. Pads without a
+    // profile use the controller half of `input`.
+    pub pad_profiles: BTreeMap<String, PadProfile>,
+    // Pad (by pad_key) that drives the joypad. None or not connected = all pads.
+    pub active_pad: Option<String>,
+    // Second local player for link play: own keys, pad bindings and pad
+    pub player2: InputMapping,
+    pub player2_pad: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    pub stereo_mode: StereoMode,
+    pub fast_forward: FastForwardAudio,
+    pub low_latency: bool,
+    pub sync_mode: SyncMode,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PadProfile {
+    pub name: String, // Human-readable, for people editing the file
+    pub mapping: InputMapping,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PathsConfig {
+    pub rom_dir: String, // Where the ROM picker opens
+}
+
+// Periodic save states, written in turn to `slots` files per game
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoStateConfig {
+    pub interval_minutes: u32, // 0 = off
+    pub slots: u32,
+    // Interval overrides by ROM file name (without extension), e.g. `tetris = 5`
+    pub games: BTreeMap<String, u32>,
+}
+
+impl AutoStateConfig {
+    pub fn interval_for(&self, game: &str) -> u32 {
+        self.games.get(game).copied().unwrap_or(self.interval_minutes)
+    }
+}
+
+// Settings a game uses instead of the global ones; unset fields follow the
+// global setting. Edited from the pause menu (see game_settings.rs).
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")] pub model: Option<Model>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub palette: Option<[u32; 4]>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub speed: Option<u32>,
+    // Set in the config file only; "" for no border in this game
+    #[serde(skip_serializing_if = "Option::is_none")] pub border: Option<String>,
+    // Set in the config file only, see ram_watch.rs
+    #[serde(skip_serializing_if = "Vec::is_empty")] pub watches: Vec<RamWatch>,
+}
+
+// Identifies a game for GameOverrides: the header title and global checksum,
+// so renamed files keep their settings and different revisions don't share them
+pub fn game_key(rom: &[u8]) -> String {
+    let title: String = rom[0x134..0x144].iter().take_while(|&&b| b != 0).map(|&b| b as char).collect();
+    format!("{} {:02X}{:02X}", title.trim(), rom[0x14E], rom[0x14F])
+}
+
+// Let viewers watch over TCP (see stream.rs for the protocol)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpectatorConfig {
+    pub enabled: bool,
+    pub port: u16,
+    pub audio: bool, // Send the game's audio along with the frames
+}
+
+// Serve the browser debugger (see remote_debug.rs). Anyone who can reach
+// the port can pause and inspect the game, so it's only open to this
+// machine unless allow_remote is set.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteDebugConfig {
+    pub enabled: bool,
+    pub port: u16,
+    pub allow_remote: bool, // Listen on every interface, not just localhost
+}
+
+// Auto-split with the LiveSplit Server component for games that have split
+// definitions (see livesplit.rs)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LiveSplitConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+}
+
+// Show the game being played as Discord Rich Presence. Needs the ID of a
+// Discord application (its name is what Discord shows as "Playing ...").
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiscordConfig {
+    pub enabled: bool,
+    pub client_id: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            model: Model::Dmg,
+            palette: [0xFFFFFFFF, 0xFFAAAAAA, 0xFF555555, 0xFF000000],
+            high_contrast_ui: false,
+            border: String::new(),
+            ram_fill: RamFill::Zero,
+            ram_seed: 0,
+            cgb_colorization: true,
+            window_scale: 3,
+            display_hz: 0.0,
+            audio: AudioConfig::default(),
+            paths: PathsConfig::default(),
+            auto_state: AutoStateConfig::default(),
+            spectator: SpectatorConfig::default(),
+            remote_debug: RemoteDebugConfig::default(),
+            discord: DiscordConfig::default(),
+            livesplit: LiveSplitConfig::default(),
+            input: InputMapping::default(),
+            turbo_frames: 2,
+            pause_on_disconnect: true,
+            pause_when_unfocused: true,
+            resume_on_launch: true,
+            block_opposite_dirs: true,
+            input_polls_per_frame: 2,
+            watch_rom: false,
+            combos: Vec::new(),
+            macros: Vec::new(),
+            games: BTreeMap::new(),
+            hotkeys: HotkeyMapping::default(),
+            fast_forward_mode: HoldMode::Hold,
+            pad_profiles: BTreeMap::new(),
+            active_pad: None,
+            player2: InputMapping::player2(),
+            player2_pad: None,
+        }
+    }
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            stereo_mode: StereoMode::Stereo,
+            fast_forward: FastForwardAudio::Skip,
+            low_latency: false,
+            sync_mode: SyncMode::Video,
+        }
+    }
+}
+
+impl Default for AutoStateConfig {
+    fn default() -> Self {
+        Self { interval_minutes: 0, slots: 3, games: BTreeMap::new() }
+    }
+}
+
+impl Default for SpectatorConfig {
+    fn default() -> Self {
+        Self { enabled: false, port: 7860, audio: false }
+    }
+}
+
+impl Default for LiveSplitConfig {
+    fn default() -> Self {
+        Self { enabled: false, host: "127.0.0.1".to_string(), port: 16834 }
+    }
+}
+
+impl Default for RemoteDebugConfig {
+    fn default() -> Self {
+        Self { enabled: false, port: 7861, allow_remote: false }
+    }
+}
+
+impl Default for PathsConfig {
+    fn default() -> Self {
+        Self { rom_dir: ".".to_string() }
+    }
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        match DATA_DIR.get() {
+            Some(dir) => dir.join("config.toml"),
+            None => config_dir().join("gb_rs").join("config.toml"),
+        }
+    }
+
+    // Folders inside the portable data folder are kept relative, so the
+    // setup still works when the folder moves (another drive letter, a
+    // different machine syncing it)
+    pub fn set_rom_dir(&mut self, dir: &Path) {
+        let dir = DATA_DIR.get().and_then(|d| dir.strip_prefix(d).ok()).unwrap_or(dir);
+        self.paths.rom_dir = if dir.as_os_str().is_empty() { ".".to_string() } else { dir.to_string_lossy().to_string() };
+    }
+
+    pub fn load() -> Self {
+        let path = Self::path();
+        match fs::read_to_string(&path) {
+            Ok(text) => match toml::from_str(&text) {
+                Ok(config) => {
+                    println!("✓ Loaded config '{}'", path.display());
+                    config
+                }
+                Err(e) => {
+                    eprintln!("✗ Invalid config '{}': {} (using defaults)", path.display(), e);
+                    Self::default()
+                }
+            },
+            Err(_) => Self::default(),
+        }
+    }
+
+    // Pads are identified by their SDL-style UUID; backends that don't
+    // report one (all zeroes) fall back to the device name
+    pub fn pad_key(gamepad: &Gamepad) -> String {
+        let uuid = gamepad.uuid();
+        if uuid.iter().all(|&b| b == 0) {
+            gamepad.name().to_string()
+        } else {
+            uuid.iter().map(|b| format!("{:02x}", b)).collect()
+        }
+    }
+
+    pub fn model_for(&self, game: &str) -> Model {
+        self.games.get(game).and_then(|g| g.model).unwrap_or(self.model)
+    }
+
+    pub fn palette_for(&self, game: &str) -> [u32; 4] {
+        self.games.get(game).and_then(|g| g.palette).unwrap_or(self.palette)
+    }
+
+    pub fn border_for(&self, game: &str) -> &str {
+        self.games.get(game).and_then(|g| g.border.as_deref()).unwrap_or(&self.border)
+    }
+
+    pub fn speed_for(&self, game: &str) -> u32 {
+        self.games.get(game).and_then(|g| g.speed).unwrap_or(1)
+    }
+
+    pub fn pad_mapping(&self, gamepad: &Gamepad) -> &InputMapping {
+        self.pad_profiles.get(&Self::pad_key(gamepad)).map_or(&self.input, |p| &p.mapping)
+    }
+
+    // Creates the pad's profile (copied from the defaults) on first edit
+    pub fn pad_mapping_mut(&mut self, gamepad: &Gamepad) -> &mut InputMapping {
+        let input = self.input;
+        &mut self.pad_profiles
+            .entry(Self::pad_key(gamepad))
+            .or_insert_with(|| PadProfile { name: gamepad.name().to_string(), mapping: input })
+            .mapping
+    }
+
+    pub fn save(&self) {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        match toml::to_string_pretty(self) {
+            Ok(text) => {
+                if let Err(e) = fs::write(&path, text) {
+                    eprintln!("Failed to save config '{}': {}", path.display(), e);
+                }
+            }
+            Err(e) => eprintln!("Failed to serialize config: {}", e),
+        }
+    }
+}
+
+// Portable mode: the config lives in one folder and the working directory
+// moves there too, so battery saves, states, screenshots and recordings
+// (all written relative to it) end up alongside
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn use_data_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let dir = fs::canonicalize(dir)?;
+    std::env::set_current_dir(&dir)?;
+    println!("✓ Portable mode, data in '{}'", dir.display());
+    let _ = DATA_DIR.set(dir);
+    Ok(())
+}
+
+// Folder holding the executable
+pub fn exe_dir() -> Option<PathBuf> {
+    std::env::current_exe().ok()?.parent().map(PathBuf::from)
+}
+
+// The executable's folder if it's marked portable with a portable.txt
+pub fn portable_marker_dir() -> Option<PathBuf> {
+    exe_dir().filter(|dir| dir.join("portable.txt").is_file())
+}
+
+// Per-platform user config directory
+fn config_dir() -> PathBuf {
+    let env = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    if cfg!(target_os = "windows") {
+        env("APPDATA").unwrap_or_else(|| PathBuf::from("."))
+    } else if cfg!(target_os = "macos") {
+        env("HOME").map(|h| h.join("Library/Application Support")).unwrap_or_else(|| PathBuf::from("."))
+    } else {
+        env("XDG_CONFIG_HOME")
+            .or_else(|| env("HOME").map(|h| h.join(".config")))
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+}
+
+// Colors are written as "#RRGGBB" so the file stays hand-editable
+mod hex_colors {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error, ser::SerializeSeq};
+
+    pub fn serialize<S: Serializer>(colors: &[u32; 4], s: S) -> Result<S::Ok, S::Error> {
+        let mut seq = s.serialize_seq(Some(4))?;
+        for c in colors {
+            seq.serialize_element(&format!("#{:06X}", c & 0xFFFFFF))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u32; 4], D::Error> {
+        let names: Vec<String> = Vec::deserialize(d)?;
+        if names.len() != 4 {
+            return Err(D::Error::custom("palette needs exactly 4 colors"));
+        }
+        let mut out = [0u32; 4];
+        for (slot, name) in out.iter_mut().zip(&names) {
+            let hex = name.trim_start_matches('#');
+            let rgb = u32::from_str_radix(hex, 16).map_err(|_| D::Error::custom(format!("bad color '{}'", name)))?;
+            *slot = 0xFF000000 | (rgb & 0xFFFFFF);
+        }
+        Ok(out)
+    }
+}
+
+// minifb keys and gilrs buttons/axes don't implement serde, so they are stored by name
+pub mod key_serde {
+    use minifb::Key;
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub const ALL_KEYS: [Key; 107] = [
+        Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+        Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J, Key::K, Key::L, Key::M,
+        Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+        Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10, Key::F11,
+        Key::F12, Key::F13, Key::F14, Key::F15,
+        Key::Down, Key::Left, Key::Right, Key::Up,
+        Key::Apostrophe, Key::Backquote, Key::Backslash, Key::Comma, Key::Equal, Key::LeftBracket, Key::Minus,
+        Key::Period, Key::RightBracket, Key::Semicolon, Key::Slash, Key::Backspace, Key::Delete, Key::End,
+        Key::Enter, Key::Escape, Key::Home, Key::Insert, Key::Menu, Key::PageDown, Key::PageUp, Key::Pause,
+        Key::Space, Key::Tab, Key::NumLock, Key::CapsLock, Key::ScrollLock, Key::LeftShift, Key::RightShift,
+        Key::LeftCtrl, Key::RightCtrl, Key::NumPad0, Key::NumPad1, Key::NumPad2, Key::NumPad3, Key::NumPad4,
+        Key::NumPad5, Key::NumPad6, Key::NumPad7, Key::NumPad8, Key::NumPad9, Key::NumPadDot, Key::NumPadSlash,
+        Key::NumPadAsterisk, Key::NumPadMinus, Key::NumPadPlus, Key::NumPadEnter, Key::LeftAlt, Key::RightAlt,
+        Key::LeftSuper, Key::RightSuper, Key::Unknown,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Key> {
+        ALL_KEYS.iter().copied().find(|k| format!("{:?}", k) == name)
+    }
+
+    pub fn serialize<S: Serializer>(key: &Key, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&format!("{:?}", key))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Key, D::Error> {
+        let name = String::deserialize(d)?;
+        from_name(&name).ok_or_else(|| D::Error::custom(format!("unknown key '{}'", name)))
+    }
+}
+
+pub mod pad_serde {
+    use gilrs::{Axis, Button};
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    use crate::PadInput;
+
+    const ALL_BUTTONS: [Button; 20] = [
+        Button::South, Button::East, Button::North, Button::West, Button::C, Button::Z,
+        Button::LeftTrigger, Button::LeftTrigger2, Button::RightTrigger, Button::RightTrigger2,
+        Button::Select, Button::Start, Button::Mode, Button::LeftThumb, Button::RightThumb,
+        Button::DPadUp, Button::DPadDown, Button::DPadLeft, Button::DPadRight, Button::Unknown,
+    ];
+
+    const ALL_AXES: [Axis; 9] = [
+        Axis::LeftStickX, Axis::LeftStickY, Axis::LeftZ, Axis::RightStickX, Axis::RightStickY,
+        Axis::RightZ, Axis::DPadX, Axis::DPadY, Axis::Unknown,
+    ];
+
+    pub fn from_name(name: &str) -> Option<PadInput> {
+        // Axis directions end in +/-, everything else is a button
+        if let Some(axis) = name.strip_suffix('+') {
+            return ALL_AXES.iter().copied().find(|a| format!("{:?}", a) == axis).map(|a| PadInput::Axis(a, true));
+        }
+        if let Some(axis) = name.strip_suffix('-') {
+            return ALL_AXES.iter().copied().find(|a| format!("{:?}", a) == axis).map(|a| PadInput::Axis(a, false));
+        }
+        ALL_BUTTONS.iter().copied().find(|b| format!("{:?}", b) == name).map(PadInput::Button)
+    }
+
+    pub fn serialize<S: Serializer>(input: &PadInput, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&input.name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<PadInput, D::Error> {
+        let name = String::deserialize(d)?;
+        from_name(&name).ok_or_else(|| D::Error::custom(format!("unknown pad input '{}'", name)))
+    }
+}
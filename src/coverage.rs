@@ -0,0 +1,89 @@
+// coverage.rs
+// ROM execution coverage: which bytes of the ROM the CPU has fetched as code
+// (opcodes and their operands), by where they are in the file so each bank
+// counts separately however the game maps it. The report says how much of
+// each bank ran and lists the ranges that never did:
+//
+//   bank  executed     of  percent
+//    000      9123  16384    55.7%
+//   ...
+//   Never executed:
+//   000:0000-00FF    256 bytes
+//   001:6A00-7FFF   5632 bytes
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+
+const BANK_SIZE: usize = 0x4000;
+
+pub struct Coverage {
+    path: String, // Where the report goes
+    executed: Vec<bool>, // One per ROM byte
+}
+
+impl Coverage {
+    pub fn new(path: &str, rom_len: usize) -> Self {
+        Self { path: path.to_string(), executed: vec![false; rom_len] }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    // The CPU fetched a byte of code at `addr` (below 0x8000) with `bank`
+    // mapped there
+    pub fn record(&mut self, bank: usize, addr: u16) {
+        let offset = bank * BANK_SIZE + (addr as usize & (BANK_SIZE - 1));
+        if let Some(byte) = self.executed.get_mut(offset) {
+            *byte = true;
+        }
+    }
+
+    fn banks(&self) -> impl Iterator<Item = (usize, &[bool])> {
+        self.executed.chunks(BANK_SIZE).enumerate()
+    }
+
+    // Runs of bytes in a bank that never ran, as inclusive CPU addresses
+    // (bank 0 at 0x0000, the others at 0x4000)
+    fn unvisited(bank: usize, bytes: &[bool]) -> Vec<(u16, u16)> {
+        let base = if bank == 0 { 0 } else { BANK_SIZE as u16 };
+        let mut ranges = Vec::new();
+        let mut start = None;
+        for (i, &executed) in bytes.iter().chain([&true]).enumerate() {
+            match (start, executed) {
+                (None, false) => start = Some(i),
+                (Some(s), true) => {
+                    ranges.push((base + s as u16, base + i as u16 - 1));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        ranges
+    }
+
+    pub fn report(&self) -> String {
+        let total = self.executed.iter().filter(|&&b| b).count();
+        let percent = |n: usize, of: usize| 100.0 * n as f64 / of.max(1) as f64;
+        let mut out = String::new();
+        let _ = writeln!(out, "Executed {} of {} ROM bytes ({:.1}%)", total, self.executed.len(), percent(total, self.executed.len()));
+        let _ = writeln!(out);
+        let _ = writeln!(out, "bank  executed     of  percent");
+        for (bank, bytes) in self.banks() {
+            let n = bytes.iter().filter(|&&b| b).count();
+            let _ = writeln!(out, " {:03X}  {:>8}  {:>5}  {:>6.1}%", bank, n, bytes.len(), percent(n, bytes.len()));
+        }
+        let _ = writeln!(out);
+        let _ = writeln!(out, "Never executed:");
+        for (bank, bytes) in self.banks() {
+            for (start, end) in Self::unvisited(bank, bytes) {
+                let _ = writeln!(out, "{:03X}:{:04X}-{:04X}  {:>5} bytes", bank, start, end, end - start + 1);
+            }
+        }
+        out
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        fs::write(&self.path, self.report())
+    }
+}
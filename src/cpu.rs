@@ -1,5 +1,18 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::coverage::Coverage;
+use crate::disasm::disassemble;
+use crate::error::CoreError;
+use crate::events::Event;
+use crate::history::{Executed, History};
+use crate::instr_trace::InstrTrace;
 use crate::mmu::MMU;
+use crate::ppu::PPU;
+use crate::profiler::Profiler;
+use crate::savestate::{StateReader, StateWriter};
 
+#[derive(Clone, Copy, Default)]
 pub struct Registers {
     pub a: u8, pub f: u8,
     pub b: u8, pub c: u8,
@@ -12,34 +25,21 @@ pub struct Registers {
 pub struct CPU {
     pub registers: Registers,
     pub bus: MMU,
+    pub ppu: PPU, // Ticked along with the bus, so it's never behind a memory access
     pub ime: bool, // Interrupt Master Enable
     pub halted: bool, // 2. Add this too (you'll need it for the HALT instruction soon)
     pub interrupt_enable_delay: bool, // Shadow flag for EI delay
+    pub locked: bool, // Hit an illegal opcode; only a reset gets it going again
+    branch_taken: bool, // Set by a conditional jump, call or return that was taken
+    doctor_log: Option<BufWriter<File>>,
+    instr_trace: Option<InstrTrace>,
+    coverage: Option<Coverage>,
+    profiler: Option<Profiler>,
+    pub history: History, // For crash reports
+    cycles_done: u8, // T-cycles the bus has been advanced by in the current step
 }
 
 impl CPU {
-    fn sra_8bit(&mut self, val: u8) -> u8 {
-    let carry = (val & 0x01) != 0;       // Check bit 0
-    let res = (val >> 1) | (val & 0x80); // Shift right, preserve bit 7
-
-    self.registers.f = 0;
-    if res == 0 { self.registers.f |= 0x80; } // Z flag
-    if carry { self.registers.f |= 0x10; }    // C flag
-    // N and H are already 0
-
-    res
-}
-    fn sla_8bit(&mut self, val: u8) -> u8 {
-    let carry = (val & 0x80) != 0; // Check bit 7
-    let res = val << 1;            // Shift left (bit 0 becomes 0)
-
-    self.registers.f = 0;
-    if res == 0 { self.registers.f |= 0x80; } // Z
-    if carry { self.registers.f |= 0x10; }    // C
-    // N and H are already 0
-
-    res
-}
     fn sbc_8bit(&mut self, val: u8) {
     let a = self.registers.a;
     let c_in = if (self.registers.f & 0x10) != 0 { 1 } else { 0 };
@@ -63,11 +63,12 @@ impl CPU {
 
     self.registers.a = res;
 }
-    fn rst(&mut self, address: u16) -> u8 {
+    // RST n: a call to the address in bits 3-5
+    fn rst(&mut self, opcode: u8) {
     let pc = self.registers.pc;
+    self.advance(); // Internal cycle before the push
     self.push_u16(pc);
-    self.registers.pc = address;
-    16 // RST always takes 16 cycles
+    self.registers.pc = (opcode & 0x38) as u16;
 }
     // cpu.rs
 
@@ -77,8 +78,20 @@ impl CPU {
 // 1. Change return type to u8
 // In cpu.rs
 
-// 1. Change signature to return u8
+// Dispatches a pending interrupt, in cycles (0 if none). Like step, the bus
+// is advanced as it runs.
 pub fn handle_interrupts(&mut self) -> u8 {
+    self.cycles_done = 0;
+    let cycles = self.dispatch_interrupt();
+    self.finish(cycles)
+}
+
+fn dispatch_interrupt(&mut self) -> u8 {
+    // A locked-up CPU doesn't take interrupts either
+    if self.locked {
+        return 0;
+    }
+
     // Check for enabled interrupts
     let fired = self.bus.interrupt_flag & self.bus.interrupt_enable & 0x1F;
 
@@ -102,6 +115,10 @@ pub fn handle_interrupts(&mut self) -> u8 {
     for i in 0..5 {
         if (fired & (1 << i)) != 0 {
             self.bus.interrupt_flag &= !(1 << i);
+            self.bus.event(Event::Interrupt(i));
+            // Two internal cycles, the push, then one more to jump
+            self.advance();
+            self.advance();
             let pc = self.registers.pc;
             self.push_u16(pc);
             
@@ -188,157 +205,6 @@ fn daa(&mut self) {
         self.registers.f |= 0x10;
     }
     
-    self.registers.a = res;
-}
-    fn execute_cb(&mut self, cb_opcode: u8) -> u8 {
-    let bit = (cb_opcode >> 3) & 0x07; // Which bit (0-7)
-    let reg_idx = cb_opcode & 0x07;    // Which register index
-    let val = self.get_reg_by_index(reg_idx);
-
-    match cb_opcode {
-        // Inside execute_cb match
-0x28 => { self.registers.b = self.sra_8bit(self.registers.b); }
-0x29 => { self.registers.c = self.sra_8bit(self.registers.c); }
-0x2A => { self.registers.d = self.sra_8bit(self.registers.d); }
-0x2B => { self.registers.e = self.sra_8bit(self.registers.e); }
-0x2C => { self.registers.h = self.sra_8bit(self.registers.h); }
-0x2D => { self.registers.l = self.sra_8bit(self.registers.l); }
-0x2E => {
-    let addr = self.get_hl();
-    let val = self.bus.read_byte(addr);
-    let res = self.sra_8bit(val);
-    self.bus.write_byte(addr, res);
-}
-0x2F => { self.registers.a = self.sra_8bit(self.registers.a); }
-       0x20 => { self.registers.b = self.sla_8bit(self.registers.b); }
-    0x21 => { self.registers.c = self.sla_8bit(self.registers.c); }
-    0x22 => { self.registers.d = self.sla_8bit(self.registers.d); }
-    0x23 => { self.registers.e = self.sla_8bit(self.registers.e); }
-    0x24 => { self.registers.h = self.sla_8bit(self.registers.h); }
-    0x25 => { self.registers.l = self.sla_8bit(self.registers.l); }
-    0x26 => {
-        let addr = self.get_hl();
-        let val = self.bus.read_byte(addr);
-        let res = self.sla_8bit(val);
-        self.bus.write_byte(addr, res);
-    }
-    0x27 => { self.registers.a = self.sla_8bit(self.registers.a); }
-        0x00..=0x07 => {
-        let carry = (val & 0x80) >> 7;
-        let res = (val << 1) | carry;
-        self.registers.f = if carry == 1 { 0x10 } else { 0 };
-        if res == 0 { self.registers.f |= 0x80; }
-        self.set_reg_by_index(reg_idx, res);
-    },
-
-    // 0x08 - 0x0F: RRC r (Rotate Right)
-    0x08..=0x0F => {
-        let carry = val & 0x01;
-        let res = (val >> 1) | (carry << 7);
-        self.registers.f = if carry == 1 { 0x10 } else { 0 };
-        if res == 0 { self.registers.f |= 0x80; }
-        self.set_reg_by_index(reg_idx, res);
-    },
-
-    // 0x10 - 0x17: RL r (Rotate Left through Carry)
-    0x10..=0x17 => {
-        let old_carry = if (self.registers.f & 0x10) != 0 { 1 } else { 0 };
-        let new_carry = (val & 0x80) >> 7;
-        let res = (val << 1) | old_carry;
-        self.registers.f = if new_carry == 1 { 0x10 } else { 0 };
-        if res == 0 { self.registers.f |= 0x80; }
-        self.set_reg_by_index(reg_idx, res);
-    },
-
-    // 0x18 - 0x1F: RR r (Rotate Right through Carry)
-    0x18..=0x1F => {
-        let old_carry = if (self.registers.f & 0x10) != 0 { 1 } else { 0 };
-        let new_carry = val & 0x01;
-        let res = (val >> 1) | (old_carry << 7);
-        self.registers.f = if new_carry == 1 { 0x10 } else { 0 };
-        if res == 0 { self.registers.f |= 0x80; }
-        self.set_reg_by_index(reg_idx, res);
-    },
-
-    // 0x38 - 0x3F: SRL r (Shift Right Logical - The one you hit!)
-    0x38..=0x3F => {
-        let carry = val & 0x01;
-        let res = val >> 1; // High bit always becomes 0
-        self.registers.f = if carry == 1 { 0x10 } else { 0 };
-        if res == 0 { self.registers.f |= 0x80; }
-        self.set_reg_by_index(reg_idx, res);
-    },
-        // 0x40..=0x7F: BIT n, r (Test bit n in register r)
-        0x40..=0x7F => {
-            let is_set = (val & (1 << bit)) != 0;
-            self.registers.f &= 0x10; // Keep Carry, clear others
-            self.registers.f |= 0x20; // H flag is ALWAYS set for BIT
-            if !is_set { self.registers.f |= 0x80; } // Set Z if bit is 0
-        },
-
-        // 0x80..=0xBF: RES n, r (Reset bit n)
-        0x80..=0xBF => {
-            let res = val & !(1 << bit);
-            self.set_reg_by_index(reg_idx, res);
-        },
-
-        // 0xC0..=0xFF: SET n, r (Set bit n)
-        0xC0..=0xFF => {
-            let res = val | (1 << bit);
-            self.set_reg_by_index(reg_idx, res);
-        },
-
-        // 0x10..=0x17: RL r (Rotate Left through Carry)
-        0x10..=0x17 => {
-            let old_carry = if (self.registers.f & 0x10) != 0 { 1 } else { 0 };
-            let new_carry = (val & 0x80) >> 7;
-            let res = (val << 1) | old_carry;
-            
-            self.registers.f = 0;
-            if res == 0 { self.registers.f |= 0x80; }
-            if new_carry == 1 { self.registers.f |= 0x10; }
-            self.set_reg_by_index(reg_idx, res);
-        },
-        0x30..=0x37 => {
-        let low_nibble = val & 0x0F;
-        let high_nibble = (val & 0xF0) >> 4;
-        let res = (low_nibble << 4) | high_nibble;
-        
-        // Flags: Z 0 0 0
-        self.registers.f = if res == 0 { 0x80 } else { 0 };
-        self.set_reg_by_index(reg_idx, res);
-    },
-
-        _ => {
-            println!("CB Opcode not yet implemented: {:#04X}", cb_opcode);
-            panic!("CB CRASH");
-        }
-    }
-
-    // Timing: (HL) takes more cycles
-    if reg_idx == 6 {
-        if (0x40..=0x7F).contains(&cb_opcode) { 12 } else { 16 }
-    } else { 8 }
-}
-    fn sbc_a(&mut self, value: u8) {
-    let a = self.registers.a;
-    let c = if (self.registers.f & 0x10) != 0 { 1 } else { 0 };
-    let res = a.wrapping_sub(value).wrapping_sub(c);
-    
-    self.registers.f = 0x40; // Set N
-    
-    if res == 0 { self.registers.f |= 0x80; } // Z
-    
-    // H: Set if (a & 0xf) - (value & 0xf) - c < 0
-    if (a as i32 & 0x0F) - (value as i32 & 0x0F) - (c as i32) < 0 {
-        self.registers.f |= 0x20;
-    }
-    
-    // C: Set if a - value - c < 0
-    if (a as i32) - (value as i32) - (c as i32) < 0 {
-        self.registers.f |= 0x10;
-    }
-    
     self.registers.a = res;
 }
     fn sub_a(&mut self, value: u8) {
@@ -398,21 +264,205 @@ fn and_a(&mut self, value: u8) {
     
     self.registers.a = res;
 }
+    // Register values the boot ROM leaves behind when it jumps to 0x100
+    fn boot_registers() -> Registers {
+        Registers {
+            a: 0x01, f: 0xB0,
+            b: 0x00, c: 0x13,
+            d: 0x00, e: 0xD8,
+            h: 0x01, l: 0x4D,
+            pc: 0x100,
+            sp: 0xFFFE,
+        }
+    }
+
     pub fn new(bus: MMU) -> Self {
         Self {
-            registers: Registers {
-                a: 0x01, f: 0xB0,
-                b: 0x00, c: 0x13,
-                d: 0x00, e: 0xD8,
-                h: 0x01, l: 0x4D,
-                pc: 0x100,
-                sp: 0xFFFE,
-            },
+            registers: Self::boot_registers(),
             bus,
+            ppu: PPU::new(),
             ime: false,
             interrupt_enable_delay: false,
             halted: false, // Usually starts disabled
+            locked: false,
+            branch_taken: false,
+            doctor_log: None,
+            instr_trace: None,
+            coverage: None,
+            profiler: None,
+            history: History::default(),
+            cycles_done: 0,
+        }
+    }
+
+    // Restart at the entry point with interrupts off, like a reset that skips
+    // the boot ROM. RAM, VRAM and the cartridge are left as they are.
+    pub fn soft_reset(&mut self) {
+        self.registers = Self::boot_registers();
+        self.ime = false;
+        self.interrupt_enable_delay = false;
+        self.halted = false;
+        self.locked = false;
+        self.history.clear();
+        self.bus.interrupt_enable = 0;
+        self.bus.interrupt_flag = 0;
+    }
+
+    // What the CPU locked up on, for the UI
+    pub fn lock_message(&self) -> Option<String> {
+        let pc = self.registers.pc;
+        self.locked.then(|| format!("CPU locked up: illegal opcode ${:02X} at ${:04X}", self.bus.read_byte(pc), pc))
+    }
+
+    // Gameboy Doctor trace: the registers and the four bytes at PC before
+    // every instruction, in the tool's exact format, to diff against its
+    // reference logs. LY reads 0x90 meanwhile, like in those logs.
+    pub fn start_doctor_log(&mut self, path: &str) -> io::Result<()> {
+        self.doctor_log = Some(BufWriter::new(File::create(path)?));
+        self.bus.ly_stub = true;
+        Ok(())
+    }
+
+    pub fn stop_doctor_log(&mut self) {
+        if let Some(mut log) = self.doctor_log.take() {
+            let _ = log.flush();
         }
+        self.bus.ly_stub = false;
+    }
+
+    pub fn doctor_logging(&self) -> bool {
+        self.doctor_log.is_some()
+    }
+
+    fn log_doctor_line(&mut self) {
+        let r = &self.registers;
+        let mem = |i: u16| self.bus.read_byte(r.pc.wrapping_add(i));
+        let line = format!(
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}\n",
+            r.a, r.f, r.b, r.c, r.d, r.e, r.h, r.l, r.sp, r.pc, mem(0), mem(1), mem(2), mem(3)
+        );
+        if let Some(log) = &mut self.doctor_log
+            && let Err(e) = log.write_all(line.as_bytes())
+        {
+            eprintln!("✗ Gameboy Doctor log stopped: {}", e);
+            self.stop_doctor_log();
+        }
+    }
+
+    // Starts logging instructions, replacing any trace already running
+    pub fn start_trace(&mut self, trace: InstrTrace) {
+        if let Some(old) = self.instr_trace.replace(trace) {
+            let _ = old.finish();
+        }
+    }
+
+    // Ends the trace and writes out what's left; returns where it went
+    pub fn stop_trace(&mut self) -> Option<io::Result<String>> {
+        let trace = self.instr_trace.take()?;
+        let path = trace.path().to_string();
+        Some(trace.finish().map(|_| path))
+    }
+
+    // Starts recording which ROM bytes run as code, replacing any coverage
+    // already being recorded
+    pub fn start_coverage(&mut self, coverage: Coverage) {
+        if let Some(old) = self.coverage.replace(coverage) {
+            let _ = old.finish();
+        }
+    }
+
+    // Ends the coverage and writes its report; returns where it went
+    pub fn stop_coverage(&mut self) -> Option<io::Result<String>> {
+        let coverage = self.coverage.take()?;
+        let path = coverage.path().to_string();
+        Some(coverage.finish().map(|_| path))
+    }
+
+    // Starts counting cycles per instruction, replacing any profile already
+    // running
+    pub fn start_profile(&mut self, profiler: Profiler) {
+        if let Some(old) = self.profiler.replace(profiler) {
+            let _ = old.finish();
+        }
+    }
+
+    // Ends the profile and writes its report; returns where it went
+    pub fn stop_profile(&mut self) -> Option<io::Result<String>> {
+        let profiler = self.profiler.take()?;
+        let path = profiler.path().to_string();
+        Some(profiler.finish().map(|_| path))
+    }
+
+    pub fn tracing(&self) -> bool {
+        self.instr_trace.is_some()
+    }
+
+    fn log_instruction(&mut self) {
+        let pc = self.registers.pc;
+        let bank = if pc < 0x8000 { self.bus.rom_bank_at(pc) } else { 0 };
+        let Some(trace) = &self.instr_trace else { return };
+        if !trace.filter.matches(pc, bank, self.bus.read_byte(pc)) {
+            return;
+        }
+        let r = &self.registers;
+        let flags: String = [(0x80, 'Z'), (0x40, 'N'), (0x20, 'H'), (0x10, 'C')]
+            .iter()
+            .map(|&(bit, c)| if r.f & bit != 0 { c } else { '-' })
+            .collect();
+        let line = format!(
+            "{:>12}  {:03X}:{:04X}  {:<16}  A:{:02X} F:{} BC:{:04X} DE:{:04X} HL:{:04X} SP:{:04X}",
+            self.bus.apu.cycle_count, bank, pc, disassemble(&self.bus, pc).0, r.a, flags, self.get_bc(), self.get_de(), self.get_hl(), r.sp
+        );
+        if let Some(trace) = &mut self.instr_trace
+            && let Err(e) = trace.log(line)
+        {
+            eprintln!("✗ Instruction trace stopped: {}", e);
+            self.instr_trace = None;
+        }
+    }
+
+    // BGB/Emulicious debug message, right after an LD D,D:
+    //   ld d,d / jr .end / dw $6464 / dw $0000 / db "text" / .end:
+    // The JR skips the text, so on hardware the whole thing does nothing.
+    fn debug_message(&mut self) {
+        let pc = self.registers.pc;
+        let byte = |i: u16| self.bus.read_byte(pc.wrapping_add(i));
+        if byte(0) != 0x18 || byte(2) != 0x64 || byte(3) != 0x64 || byte(4) != 0x00 || byte(5) != 0x00 {
+            return;
+        }
+        let len = byte(1).saturating_sub(4) as u16;
+        let text: Vec<u8> = (0..len).map(|i| byte(6 + i)).collect();
+        self.bus.debug_print(&text);
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        let r = &self.registers;
+        for v in [r.a, r.f, r.b, r.c, r.d, r.e, r.h, r.l] {
+            w.u8(v);
+        }
+        w.u16(r.pc);
+        w.u16(r.sp);
+        w.bool(self.ime);
+        w.bool(self.halted);
+        w.bool(self.interrupt_enable_delay);
+        self.bus.save_state(w);
+        self.ppu.save_state(w);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> std::io::Result<()> {
+        let regs = &mut self.registers;
+        for v in [&mut regs.a, &mut regs.f, &mut regs.b, &mut regs.c, &mut regs.d, &mut regs.e, &mut regs.h, &mut regs.l] {
+            *v = r.u8()?;
+        }
+        regs.pc = r.u16()?;
+        regs.sp = r.u16()?;
+        self.ime = r.bool()?;
+        self.halted = r.bool()?;
+        self.locked = false;
+        self.history.clear();
+        self.interrupt_enable_delay = r.bool()?;
+        self.bus.load_state(r)?;
+        self.ppu.load_state(r)
     }
 
     fn dec_8bit(&mut self, val: u8) -> u8 {
@@ -444,17 +494,17 @@ fn and_a(&mut self, value: u8) {
     let lo = (value & 0xFF) as u8;
     
     self.registers.sp = self.registers.sp.wrapping_sub(1);
-    self.bus.write_byte(self.registers.sp, hi);
+    self.write(self.registers.sp, hi);
     
     self.registers.sp = self.registers.sp.wrapping_sub(1);
-    self.bus.write_byte(self.registers.sp, lo);
+    self.write(self.registers.sp, lo);
 }
 
 fn pop_u16(&mut self) -> u16 {
-    let lo = self.bus.read_byte(self.registers.sp) as u16;
+    let lo = self.read(self.registers.sp) as u16;
     self.registers.sp = self.registers.sp.wrapping_add(1);
     
-    let hi = self.bus.read_byte(self.registers.sp) as u16;
+    let hi = self.read(self.registers.sp) as u16;
     self.registers.sp = self.registers.sp.wrapping_add(1);
     
     (hi << 8) | lo
@@ -467,7 +517,7 @@ fn pop_u16(&mut self) -> u16 {
         3 => self.registers.e,
         4 => self.registers.h,
         5 => self.registers.l,
-        6 => self.bus.read_byte(self.get_hl()), // Memory access at address HL
+        6 => self.read(self.get_hl()), // Memory access at address HL
         7 => self.registers.a,
         _ => unreachable!(),
     }
@@ -481,7 +531,7 @@ fn set_reg_by_index(&mut self, index: u8, val: u8) {
         3 => self.registers.e = val,
         4 => self.registers.h = val,
         5 => self.registers.l = val,
-        6 => self.bus.write_byte(self.get_hl(), val), // Write to memory at address HL
+        6 => self.write(self.get_hl(), val), // Write to memory at address HL
         7 => self.registers.a = val,
         _ => unreachable!(),
     }
@@ -520,968 +570,459 @@ fn set_de(&mut self, value: u16) {
     if (val & 0x0F) == 0x0F { self.registers.f |= 0x20; } // Set Half-Carry
     res
 }
-    pub fn step(&mut self) -> u8 {
+    // One instruction, in T-cycles. The timer, serial port and APU (everything
+    // in the MMU) and the PPU are advanced as it runs, one M-cycle before
+    // each memory access and for each internal cycle that precedes one, so
+    // reads and writes land on the cycle they do on hardware; internal cycles
+    // after the last access are made up at the end. An opcode the CPU doesn't
+    // know is an error rather than a panic, so the frontend can report it and
+    // carry on.
+    pub fn step(&mut self) -> Result<u8, CoreError> {
+        self.cycles_done = 0;
+        let cycles = self.execute()?;
+        Ok(self.finish(cycles))
+    }
+
+    // The bus up to `cycles` into the step
+    fn finish(&mut self, cycles: u8) -> u8 {
+        let rest = cycles.saturating_sub(self.cycles_done);
+        if rest > 0 {
+            self.bus.tick(rest);
+            self.bus.apu.tick(rest);
+            self.ppu.tick(&mut self.bus, rest);
+        }
+        cycles
+    }
+
+    // One M-cycle on the bus: ahead of a memory access, or an internal cycle
+    // that comes before one
+    fn advance(&mut self) {
+        self.bus.tick(4);
+        self.bus.apu.tick(4);
+        self.ppu.tick(&mut self.bus, 4);
+        self.cycles_done += 4;
+    }
+
+    fn read(&mut self, addr: u16) -> u8 {
+        self.advance();
+        self.bus.read_traced(addr)
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        self.advance();
+        self.bus.write_byte(addr, val);
+    }
+
+    fn execute(&mut self) -> Result<u8, CoreError> {
         
         if self.halted {
         // While halted, we just return 4 cycles (the smallest unit of time)
         // so the MMU timer can continue to tick.
-        return 4; 
+        if let Some(profiler) = &mut self.profiler { profiler.record_halted(4); }
+        return Ok(4); 
     }
-        
-       
-
-    let opcode = self.fetch_byte();
-  
-        
-        // We'll return cycles (u8) to sync with the PPU/Timer later
-       let cycles= match opcode {
-        
-        
-        0x9A => { self.sbc_8bit(self.registers.d); 4 }
-0x9B => { self.sbc_8bit(self.registers.e); 4 }
-0x9C => { self.sbc_8bit(self.registers.h); 4 }
-0x9D => { self.sbc_8bit(self.registers.l); 4 }
-0x9E => { 
-    let val = self.bus.read_byte(self.get_hl()); 
-    self.sbc_8bit(val); 
-    8 
-}
-0x9F => { self.sbc_8bit(self.registers.a); 4 }
-        0x99 => {
-    let val = self.registers.c;
-    self.sbc_8bit(val);
-    4
-},
-        0x98 => {
-    let b_val = self.registers.b;
-    self.sbc_8bit(b_val);
-    4
-},
-        // Helper logic (you can do this inline or as a function)
-// Note: self.registers.pc is already pointing at the NEXT instruction 
-// because fetch_byte() incremented it. This is the correct value to push.
-
-0xC7 => self.rst(0x0000), // RST 00H
-0xCF => self.rst(0x0008), // RST 08H
-0xD7 => self.rst(0x0010), // RST 10H
-0xDF => self.rst(0x0018), // RST 18H
-0xE7 => self.rst(0x0020), // RST 20H
-0xEF => self.rst(0x0028), // RST 28H
-0xF7 => self.rst(0x0030), // RST 30H
-0xFF => self.rst(0x0038), // RST 38H
-        0xD9 => {
-    // 1. Pop the return address from the stack
-    let low = self.bus.read_byte(self.registers.sp) as u16;
-    self.registers.sp = self.registers.sp.wrapping_add(1);
-    let high = self.bus.read_byte(self.registers.sp) as u16;
-    self.registers.sp = self.registers.sp.wrapping_add(1);
-    
-    self.registers.pc = (high << 8) | low;
-
-    // 2. Enable interrupts immediately
-    self.ime = true;
-
-    16
-},
-        0xCE => {
-    let n = self.fetch_byte();
-    let a = self.registers.a;
-    let c_in = if (self.registers.f & 0x10) != 0 { 1 } else { 0 };
-    
-    let res_full = a as u16 + n as u16 + c_in as u16;
-    let res = res_full as u8;
+        // Locked up: nothing runs, but time (and the screen) goes on
+        if self.locked {
+            return Ok(4);
+        }
 
-    self.registers.f = 0;
-    if res == 0 { self.registers.f |= 0x80; }
-    // H: Carry from bit 3 to 4
-    if (a & 0xF) + (n & 0xF) + c_in > 0xF { self.registers.f |= 0x20; }
-    // C: Carry from bit 7 to 8
-    if res_full > 0xFF { self.registers.f |= 0x10; }
+        if self.doctor_log.is_some() { self.log_doctor_line(); }
+        if self.instr_trace.is_some() { self.log_instruction(); }
+        if let Some(trace) = &mut self.bus.trace { trace.pc = self.registers.pc; }
+        if let Some(uninit) = &mut self.bus.uninit { uninit.pc = self.registers.pc; }
+        let before = self.registers;
+        let opcode = self.fetch_byte();
+        let bank = if before.pc < 0x8000 { self.bus.rom_bank_at(before.pc) } else { 0 };
+        self.history.record(Executed { bank, opcode, registers: before });
+        let (entry, code) = if opcode == 0xCB {
+            let cb_opcode = self.fetch_byte();
+            (Some(CB_OPCODES[cb_opcode as usize]), cb_opcode)
+        } else {
+            (OPCODES[opcode as usize], opcode)
+        };
+        let Some(entry) = entry else {
+            // Left on the opcode, so stepping again reports it again
+            let pc = self.registers.pc.wrapping_sub(1);
+            self.registers.pc = pc;
+            return Err(CoreError::UnknownOpcode { opcode, pc });
+        };
 
-    self.registers.a = res;
-    8
-},
-        0xDE => {
-    let n = self.fetch_byte();
-    let a = self.registers.a;
-    let c_in = if (self.registers.f & 0x10) != 0 { 1 } else { 0 };
-    
-    // Perform subtraction with the incoming carry
-    let res = a.wrapping_sub(n).wrapping_sub(c_in);
+        self.branch_taken = false;
+        (entry.exec)(self, code);
+        let cycles = if self.branch_taken { entry.taken } else { entry.cycles };
+        if let Some(profiler) = &mut self.profiler { profiler.record(bank, before.pc, cycles); }
 
-    // Update Flags
-    self.registers.f = 0x40; // Set N flag
-    if res == 0 { self.registers.f |= 0x80; } // Z
-    
-    // Half-Carry: borrow from bit 4
-    if (a as i32 & 0xF) - (n as i32 & 0xF) - (c_in as i32) < 0 {
-        self.registers.f |= 0x20;
-    }
-    
-    // Carry: borrow from bit 8
-    if (a as i32) - (n as i32) - (c_in as i32) < 0 {
-        self.registers.f |= 0x10;
+        if self.interrupt_enable_delay {
+            self.ime = true;
+            self.interrupt_enable_delay = false;
+        }
+        Ok(cycles)
     }
 
-    self.registers.a = res;
-    8
-},
-        0x36 => {
-    // 1. Fetch the 8-bit value from the ROM
-    let val = self.fetch_byte();
-    
-    // 2. Get the address from HL
-    let addr = self.get_hl();
-    
-    // 3. Write the value to the bus
-    self.bus.write_byte(addr, val);
-    
-    12
-},
-        0xF8 => {
-    let offset = self.fetch_byte() as i8;
-    let sp = self.registers.sp;
+    fn fetch_byte(&mut self) -> u8 {
+        self.advance();
+        if let Some(coverage) = &mut self.coverage
+            && self.registers.pc < 0x8000
+        {
+            coverage.record(self.bus.rom_bank_at(self.registers.pc), self.registers.pc);
+        }
+        let byte = self.bus.read_byte(self.registers.pc);
+        self.registers.pc = self.registers.pc.wrapping_add(1);
+        byte
+    }
 
-    let h_flag = (sp & 0xF) + (offset as u16 & 0xF) > 0xF;
-    let c_flag = (sp & 0xFF) + (offset as u16 & 0xFF) > 0xFF;
+    fn fetch_u16(&mut self) -> u16 {
+        let low = self.fetch_byte() as u16;
+        let high = self.fetch_byte() as u16;
+        (high << 8) | low
+    }
 
-    let res = sp.wrapping_add(offset as i16 as u16);
-    self.set_hl(res);
+    // --- Opcode handlers ---
+    // Each gets its own opcode and decodes the registers or condition from
+    // its bits, so one handler covers a whole row or column of the table.
 
-    self.registers.f = 0;
-    if h_flag { self.registers.f |= 0x20; }
-    if c_flag { self.registers.f |= 0x10; }
+    // cc in bits 3-4: NZ, Z, NC, C
+    fn condition(&self, opcode: u8) -> bool {
+        let flag = if opcode & 0x10 == 0 { 0x80 } else { 0x10 };
+        (self.registers.f & flag != 0) == (opcode & 0x08 != 0)
+    }
 
-    12 // LD HL, SP+r8 takes 12 cycles
-}
-        0xE8 => {
-    let offset = self.fetch_byte() as i8; // Signed 8-bit value
-    let sp = self.registers.sp;
-    
-    // Half-carry: check carry from bit 3
-    let h_flag = (sp & 0xF) + (offset as u16 & 0xF) > 0xF;
-    
-    // Carry: check carry from bit 7
-    let c_flag = (sp & 0xFF) + (offset as u16 & 0xFF) > 0xFF;
+    // rr in bits 4-5: BC, DE, HL, SP
+    fn get_rr(&self, opcode: u8) -> u16 {
+        match (opcode >> 4) & 3 {
+            0 => self.get_bc(),
+            1 => self.get_de(),
+            2 => self.get_hl(),
+            _ => self.registers.sp,
+        }
+    }
 
-    // Actual 16-bit addition (must wrap)
-    self.registers.sp = sp.wrapping_add(offset as i16 as u16);
+    fn set_rr(&mut self, opcode: u8, value: u16) {
+        match (opcode >> 4) & 3 {
+            0 => self.set_bc(value),
+            1 => self.set_de(value),
+            2 => self.set_hl(value),
+            _ => self.registers.sp = value,
+        }
+    }
 
-    // Update Flags: Z=0, N=0, H, C
-    self.registers.f = 0;
-    if h_flag { self.registers.f |= 0x20; }
-    if c_flag { self.registers.f |= 0x10; }
+    // The eight ALU operations in bits 3-5, on A
+    fn alu(&mut self, opcode: u8, value: u8) {
+        match (opcode >> 3) & 7 {
+            0 => self.add_a(value),
+            1 => self.adc_a(value),
+            2 => self.sub_a(value),
+            3 => self.sbc_8bit(value),
+            4 => self.and_a(value),
+            5 => self.xor_a(value),
+            6 => self.or_a(value),
+            _ => self.compare(value),
+        }
+    }
 
-    16
-}
-        0x0B => {
-    let val = self.get_bc().wrapping_sub(1);
-    self.set_bc(val);
-    8
-},
-0x1B => {
-    let val = self.get_de().wrapping_sub(1);
-    self.set_de(val);
-    8
-},
-0x2B => {
-    let val = self.get_hl().wrapping_sub(1);
-    self.set_hl(val);
-    8
-},
-        0x3B => {
-    self.registers.sp = self.registers.sp.wrapping_sub(1);
-    8
-},
-        0xF9 => {
-    // Assuming you have a helper get_hl() that combines H and L
-    self.registers.sp = self.get_hl();
-    8
-},
-        0x08 => {
-    // 1. Fetch the 16-bit address (nn) from the next two bytes
-    let low_addr = self.fetch_byte() as u16;
-    let high_addr = self.fetch_byte() as u16;
-    let addr = (high_addr << 8) | low_addr;
-
-    // 2. Get the current Stack Pointer value
-    let sp_val = self.registers.sp;
-
-    // 3. Store the Low Byte of SP into addr, and High Byte into addr + 1
-    self.bus.write_byte(addr, (sp_val & 0xFF) as u8);
-    self.bus.write_byte(addr.wrapping_add(1), (sp_val >> 8) as u8);
-
-    20 // This instruction takes 20 cycles
-}
-        0x76 => {
-            self.halted = true;
-            4 // It takes 4 cycles to enter the halt state
-        },
-            // 0xF3: DI (Disable Interrupts)
-0xF3 => {
-    self.ime = false;
-    self.interrupt_enable_delay = false; // Cancel any pending EI delay
-    4
-},
-            // 0xF8: LD HL, SP+e8
-0xF8 => {
-    let offset = self.fetch_byte() as i8 as i16 as u16;
-    let sp = self.registers.sp;
-    
-    // Calculate flags based on the lower 8 bits (u8)
-    let low_sp = (sp & 0xFF) as u8;
-    let low_offset = (offset & 0xFF) as u8;
-    
-    self.registers.f = 0; // Z and N are always 0
-    
-    // H: Carry from bit 3 to 4
-    if (low_sp & 0x0F) + (low_offset & 0x0F) > 0x0F {
-        self.registers.f |= 0x20;
+    // The CB rotates and shifts, by bits 3-5: RLC, RRC, RL, RR, SLA, SRA,
+    // SWAP, SRL. Flags: Z 0 0 C
+    fn rotate_shift(&mut self, opcode: u8, val: u8) -> u8 {
+        let carry_in = (self.registers.f & 0x10) >> 4;
+        let (res, carry) = match (opcode >> 3) & 7 {
+            0 => (val.rotate_left(1), val >> 7),
+            1 => (val.rotate_right(1), val & 1),
+            2 => ((val << 1) | carry_in, val >> 7),
+            3 => ((val >> 1) | (carry_in << 7), val & 1),
+            4 => (val << 1, val >> 7),
+            5 => ((val >> 1) | (val & 0x80), val & 1), // Bit 7 stays
+            6 => (val.rotate_left(4), 0),
+            _ => (val >> 1, val & 1),
+        };
+        self.registers.f = if res == 0 { 0x80 } else { 0 };
+        if carry == 1 { self.registers.f |= 0x10; }
+        res
     }
-    
-    // C: Carry from bit 7 to 8
-    if (low_sp as u16) + (low_offset as u16) > 0xFF {
-        self.registers.f |= 0x10;
+
+    // SP plus a signed byte, for ADD SP,e8 and LD HL,SP+e8. Flags: 0 0 H C,
+    // from the low byte
+    fn sp_offset(&mut self) -> u16 {
+        let offset = self.fetch_byte() as i8;
+        let sp = self.registers.sp;
+        self.registers.f = 0;
+        if (sp & 0xF) + (offset as u16 & 0xF) > 0xF { self.registers.f |= 0x20; }
+        if (sp & 0xFF) + (offset as u16 & 0xFF) > 0xFF { self.registers.f |= 0x10; }
+        sp.wrapping_add(offset as i16 as u16)
     }
-    
-    let res = sp.wrapping_add(offset);
-    self.set_hl(res);
-    12
-},
-            // 0x2F: CPL (Complement A - flip all bits)
-0x2F => {
-    self.registers.a = !self.registers.a;
-    self.registers.f |= 0x60; // Set N and H flags
-    4
-},
-
-// 0x37: SCF (Set Carry Flag)
-0x37 => {
-    self.registers.f &= 0x80; // Keep Z, clear N and H
-    self.registers.f |= 0x10; // Set Carry
-    4
-},
-
-// 0x3F: CCF (Complement Carry Flag)
-0x3F => {
-    let carry = (self.registers.f & 0x10) != 0;
-    self.registers.f &= 0x80; // Keep Z, clear N and H
-    if !carry { self.registers.f |= 0x10; } // Flip Carry
-    4
-},
-            // 0x27: DAA (Decimal Adjust Accumulator)
-0x27 => {
-    self.daa();
-    4
-},
-            // 0xC2: JP NZ, nn (Jump if Not Zero)
-0xC2 => {
-    let addr = self.fetch_u16();
-    if (self.registers.f & 0x80) == 0 { // Check if Z flag is 0
-        self.registers.pc = addr;
-        16 // Takes 16 cycles if jump is taken
-    } else {
-        12 // Takes 12 cycles if jump is ignored
+
+    fn ld_r_r(&mut self, opcode: u8) {
+        let val = self.get_reg_by_index(opcode & 7);
+        self.set_reg_by_index((opcode >> 3) & 7, val);
     }
-},
 
-// 0xCA: JP Z, nn (Jump if Zero)
-0xCA => {
-    let addr = self.fetch_u16();
-    if (self.registers.f & 0x80) != 0 { // Check if Z flag is 1
-        self.registers.pc = addr;
-        16
-    } else {
-        12
+    fn ld_r_d8(&mut self, opcode: u8) {
+        let val = self.fetch_byte();
+        self.set_reg_by_index((opcode >> 3) & 7, val);
     }
-},
 
-// 0xD2: JP NC, nn (Jump if No Carry)
-0xD2 => {
-    let addr = self.fetch_u16();
-    if (self.registers.f & 0x10) == 0 { // Check if C flag is 0
-        self.registers.pc = addr;
-        16
-    } else {
-        12
+    fn inc_r(&mut self, opcode: u8) {
+        let val = self.get_reg_by_index((opcode >> 3) & 7);
+        let res = self.inc_8bit(val);
+        self.set_reg_by_index((opcode >> 3) & 7, res);
     }
-},
 
-// 0xDA: JP C, nn (Jump if Carry)
-0xDA => {
-    let addr = self.fetch_u16();
-    if (self.registers.f & 0x10) != 0 { // Check if C flag is 1
-        self.registers.pc = addr;
-        16
-    } else {
-        12
-    }
-},
-            // 0xE2: LD (C), A (Store A into address 0xFF00 + C)
-0xE2 => {
-    let addr = 0xFF00 | (self.registers.c as u16);
-    self.bus.write_byte(addr, self.registers.a);
-    8
-},
-
-// 0xF2: LD A, (C) (Load from 0xFF00 + C into A)
-0xF2 => {
-    let addr = 0xFF00 | (self.registers.c as u16);
-    self.registers.a = self.bus.read_byte(addr);
-    8
-},
-            // 0xE9: JP (HL) (Jump to the address currently in HL)
-0xE9 => {
-    self.registers.pc = self.get_hl();
-    4 // This is a very fast jump, taking only 4 cycles
-},
-            // 0x09: ADD HL, BC
-0x09 => { let val = self.get_bc(); self.add_hl(val); 8 },
-
-// 0x19: ADD HL, DE
-0x19 => { let val = self.get_de(); self.add_hl(val); 8 },
-
-// 0x29: ADD HL, HL (The one you just hit!)
-0x29 => { let val = self.get_hl(); self.add_hl(val); 8 },
-
-// 0x39: ADD HL, SP
-0x39 => { let val = self.registers.sp; self.add_hl(val); 8 },
-            // 0xCE: ADC A, d8 (The one you just hit!)
-0xCE => {
-    let val = self.fetch_byte();
-    self.adc_a(val);
-    8
-},
-
-// 0x88..=0x8F: ADC A, r (Add register + carry to A)
-0x88..=0x8F => {
-    let idx = opcode & 0x07;
-    let val = self.get_reg_by_index(idx);
-    self.adc_a(val);
-    if idx == 6 { 8 } else { 4 }
-},
-            // 0x07: RLCA (Rotate Left Accumulator)
-0x07 => {
-    let a = self.registers.a;
-    let carry = (a & 0x80) >> 7;
-    self.registers.a = (a << 1) | carry;
-    // Flags: 0 0 0 C
-    self.registers.f = if carry == 1 { 0x10 } else { 0 };
-    4
-},
-
-// 0x0F: RRCA (Rotate Right Accumulator)
-0x0F => {
-    let a = self.registers.a;
-    let carry = a & 0x01;
-    self.registers.a = (a >> 1) | (carry << 7);
-    // Flags: 0 0 0 C
-    self.registers.f = if carry == 1 { 0x10 } else { 0 };
-    4
-},
-
-// 0x17: RLA (Rotate Left Accumulator through Carry)
-0x17 => {
-    let a = self.registers.a;
-    let old_carry = if (self.registers.f & 0x10) != 0 { 1 } else { 0 };
-    let new_carry = (a & 0x80) >> 7;
-    self.registers.a = (a << 1) | old_carry;
-    // Flags: 0 0 0 C
-    self.registers.f = if new_carry == 1 { 0x10 } else { 0 };
-    4
-},
-
-// 0x1F: RRA (The one you just hit!)
-0x1F => {
-    let a = self.registers.a;
-    let old_carry = if (self.registers.f & 0x10) != 0 { 1 } else { 0 };
-    let new_carry = a & 0x01;
-    self.registers.a = (a >> 1) | (old_carry << 7);
-    // Flags: 0 0 0 C
-    self.registers.f = if new_carry == 1 { 0x10 } else { 0 };
-    4
-},
-            0xCB => {
-        let cb_opcode = self.fetch_byte();
-        self.execute_cb(cb_opcode) // Returns the cycles taken
-    },
-            // 0xD6: SUB d8 (Subtract immediate 8-bit from A)
-0xD6 => {
-    let val = self.fetch_byte();
-    self.sub_a(val);
-    8
-},
-
-// 0x90..=0x97: SUB r (Subtract register r from A)
-0x90..=0x97 => {
-    let idx = opcode & 0x07;
-    let val = self.get_reg_by_index(idx);
-    self.sub_a(val);
-    if idx == 6 { 8 } else { 4 }
-},
-            // 0xC4: CALL NZ, nn (Call if Not Zero)
-0xC4 => {
-    let dest = self.fetch_u16();
-    if (self.registers.f & 0x80) == 0 {
-        let ret = self.registers.pc;
-        self.push_u16(ret);
-        self.registers.pc = dest;
-        24
-    } else {
-        12
+    fn dec_r(&mut self, opcode: u8) {
+        let val = self.get_reg_by_index((opcode >> 3) & 7);
+        let res = self.dec_8bit(val);
+        self.set_reg_by_index((opcode >> 3) & 7, res);
     }
-},
 
-// 0xCC: CALL Z, nn (Call if Zero)
-0xCC => {
-    let dest = self.fetch_u16();
-    if (self.registers.f & 0x80) != 0 {
-        let ret = self.registers.pc;
-        self.push_u16(ret);
-        self.registers.pc = dest;
-        24
-    } else {
-        12
+    // LD (BC),A / LD (DE),A / LD (HL+),A / LD (HL-),A
+    fn ld_ind_a(&mut self, opcode: u8) {
+        let addr = self.indirect(opcode);
+        self.write(addr, self.registers.a);
     }
-},
 
-// 0xD4: CALL NC, nn (Call if No Carry)
-0xD4 => {
-    let dest = self.fetch_u16();
-    if (self.registers.f & 0x10) == 0 {
-        let ret = self.registers.pc;
-        self.push_u16(ret);
-        self.registers.pc = dest;
-        24
-    } else {
-        12
+    fn ld_a_ind(&mut self, opcode: u8) {
+        let addr = self.indirect(opcode);
+        self.registers.a = self.read(addr);
     }
-},
 
-// 0xDC: CALL C, nn (Call if Carry)
-0xDC => {
-    let dest = self.fetch_u16();
-    if (self.registers.f & 0x10) != 0 {
-        let ret = self.registers.pc;
-        self.push_u16(ret);
-        self.registers.pc = dest;
-        24
-    } else {
-        12
-    }
-},
-// 0xC0: RET NZ (Return if Not Zero)
-0xC0 => {
-    if (self.registers.f & 0x80) == 0 {
-        self.registers.pc = self.pop_u16();
-        20
-    } else {
-        8
+    // Address for the (BC), (DE), (HL+), (HL-) loads, stepping HL
+    fn indirect(&mut self, opcode: u8) -> u16 {
+        match (opcode >> 4) & 3 {
+            0 => self.get_bc(),
+            1 => self.get_de(),
+            2 => { let hl = self.get_hl(); self.set_hl(hl.wrapping_add(1)); hl }
+            _ => { let hl = self.get_hl(); self.set_hl(hl.wrapping_sub(1)); hl }
+        }
     }
-},
 
-// 0xC8: RET Z (Return if Zero)
-0xC8 => {
-    if (self.registers.f & 0x80) != 0 {
-        self.registers.pc = self.pop_u16();
-        20
-    } else {
-        8
+    // RLCA, RRCA, RLA, RRA: the CB rotates on A, with Z always cleared
+    fn rotate_a(&mut self, opcode: u8) {
+        self.registers.a = self.rotate_shift(opcode, self.registers.a);
+        self.registers.f &= 0x10;
     }
-},
 
-// 0xD0: RET NC (Return if No Carry)
-0xD0 => {
-    if (self.registers.f & 0x10) == 0 {
-        self.registers.pc = self.pop_u16();
-        20
-    } else {
-        8
+    fn jr(&mut self, _: u8) {
+        let offset = self.fetch_byte() as i8;
+        self.registers.pc = self.registers.pc.wrapping_add(offset as i16 as u16);
     }
-},
 
-// 0xD8: RET C (Return if Carry)
-0xD8 => {
-    if (self.registers.f & 0x10) != 0 {
-        self.registers.pc = self.pop_u16();
-        20
-    } else {
-        8
-    }
-},
-            // 0xE6: AND d8 (Bitwise AND A with immediate byte)
-0xE6 => {
-    let val = self.fetch_byte();
-    self.and_a(val);
-    8
-},
-
-// 0xEE: XOR d8 (Bitwise XOR A with immediate byte)
-0xEE => {
-    let val = self.fetch_byte();
-    self.xor_a(val);
-    8
-},
-
-// 0xF6: OR d8 (Bitwise OR A with immediate byte)
-0xF6 => {
-    let val = self.fetch_byte();
-    self.or_a(val);
-    8
-},
-            // 0xB0..=0xB7: OR r
-0xB0..=0xB7 => {
-    let val = self.get_reg_by_index(opcode & 0x07);
-    self.or_a(val);
-    if (opcode & 0x07) == 6 { 8 } else { 4 }
-},
-
-// 0xA0..=0xA7: AND r
-0xA0..=0xA7 => {
-    let val = self.get_reg_by_index(opcode & 0x07);
-    self.and_a(val);
-    if (opcode & 0x07) == 6 { 8 } else { 4 }
-},
-
-// 0xA8..=0xAF: XOR r
-0xA8..=0xAF => {
-    let val = self.get_reg_by_index(opcode & 0x07);
-    self.xor_a(val);
-    if (opcode & 0x07) == 6 { 8 } else { 4 }
-},
-            // 0xC1: POP BC
-0xC1 => {
-    let val = self.pop_u16();
-    self.set_bc(val);
-    12
-},
-
-// 0xD1: POP DE
-0xD1 => {
-    let val = self.pop_u16();
-    self.set_de(val);
-    12
-},
-
-// 0xE1: POP HL
-0xE1 => {
-    let val = self.pop_u16();
-    self.set_hl(val);
-    12
-},
-
-// 0xF1: POP AF
-0xF1 => {
-    let val = self.pop_u16();
-    self.registers.a = (val >> 8) as u8;
-    self.registers.f = (val & 0xF0) as u8; // Force lower 4 bits to 0
-    12
-},
-            // 0xC5: PUSH BC
-0xC5 => {
-    let val = self.get_bc();
-    self.push_u16(val);
-    16
-},
-
-// 0xD5: PUSH DE
-0xD5 => {
-    let val = self.get_de();
-    self.push_u16(val);
-    16
-},
-
-// 0xE5: PUSH HL (The one you just hit!)
-0xE5 => {
-    let val = self.get_hl();
-    self.push_u16(val);
-    16
-},
-
-// 0xF5: PUSH AF
-0xF5 => {
-    let val = ((self.registers.a as u16) << 8) | (self.registers.f as u16);
-    self.push_u16(val);
-    16
-},
-            // 0xFE: CP d8 (Compare A with immediate 8-bit value)
-0xFE => {
-    let val = self.fetch_byte();
-    self.compare(val);
-    8
-},
-
-// 0xB8..=0xBF: CP r (Compare A with register r)
-0xB8..=0xBF => {
-    let idx = opcode & 0x07;
-    let val = self.get_reg_by_index(idx);
-    self.compare(val);
-    if idx == 6 { 8 } else { 4 }
-},
-            // 0x18: JR n (Unconditional Relative Jump)
-0x18 => {
-    let offset = self.fetch_byte() as i8; // Fetch the signed 8-bit offset
-    // Cast to i16 to preserve the sign, then to u16 to add to PC
-    self.registers.pc = self.registers.pc.wrapping_add(offset as i16 as u16);
-    12 // This instruction always takes 12 cycles
-},
-            // 0xC6: ADD A, d8
-    0xC6 => {
-        let val = self.fetch_byte();
-        self.add_a(val);
-        8
-    },
-
-    // 0x80..0x87: ADD A, r
-    0x80..=0x87 => {
-        let idx = opcode & 0x07;
-        let val = self.get_reg_by_index(idx);
-        self.add_a(val);
-        if idx == 6 { 8 } else { 4 }
-    },
-            // 0xE0: LDH (n), A (Store A into 0xFF00 + n)
-    0xE0 => {
-        let n = self.fetch_byte() as u16;
-        let addr = 0xFF00 | n;
-        self.bus.write_byte(addr, self.registers.a);
-        12
-    },
-
-    // 0xF0: LDH A, (n) (Load A from 0xFF00 + n)
-    0xF0 => {
-        let n = self.fetch_byte() as u16;
-        let addr = 0xFF00 | n;
-        self.registers.a = self.bus.read_byte(addr);
-        12
-    },
-    
-    // 0xE2: LD (C), A (Store A into 0xFF00 + Register C)
-    0xE2 => {
-        let addr = 0xFF00 | (self.registers.c as u16);
-        self.bus.write_byte(addr, self.registers.a);
-        8
-    },
-
-    // 0xF2: LD A, (C) (Load A from 0xFF00 + Register C)
-    0xF2 => {
-        let addr = 0xFF00 | (self.registers.c as u16);
-        self.registers.a = self.bus.read_byte(addr);
-        8
-    },
-            // 0x03: INC BC
-    0x03 => {
-        let val = self.get_bc().wrapping_add(1);
-        self.set_bc(val);
-        8
-    },
-
-    // 0x13: INC DE
-    0x13 => {
-        let val = self.get_de().wrapping_add(1);
-        self.set_de(val);
-        8
-    },
-
-    // 0x23: INC HL
-    0x23 => {
-        let val = self.get_hl().wrapping_add(1);
-        self.set_hl(val);
-        8
-    },
-
-    // 0x33: INC SP
-    0x33 => {
-        self.registers.sp = self.registers.sp.wrapping_add(1);
-        8
-    },
-            // 0xEA: LD (nn), A (Load A into absolute 16-bit address)
-    0xEA => {
-        let addr = self.fetch_u16();
-        self.bus.write_byte(addr, self.registers.a);
-        16 // Takes 16 cycles
-    },
+    fn jr_cc(&mut self, opcode: u8) {
+        let offset = self.fetch_byte() as i8;
+        if self.condition(opcode) {
+            self.registers.pc = self.registers.pc.wrapping_add(offset as i16 as u16);
+            self.branch_taken = true;
+        }
+    }
 
-    // 0xFA: LD A, (nn) (Load A from absolute 16-bit address)
-    0xFA => {
+    fn jp_cc(&mut self, opcode: u8) {
         let addr = self.fetch_u16();
-        self.registers.a = self.bus.read_byte(addr);
-        16
-    },
-            // 0xF3: DI (Disable Interrupts)
-    0xF3 => {
-        self.ime = false;
-        4
-    },
-
-    // 0xFB: EI (Enable Interrupts)
-    0xFB => {
-        // Note: On a real Game Boy, EI takes effect AFTER the next instruction.
-        // For now, setting it immediately is usually fine for Blargg tests.
-self.interrupt_enable_delay = true;
-    4
-    },
-            0x00 => 4, // NOP
-
-    // LD SP, d16 (Load immediate 16-bit value into Stack Pointer)
-    0x31 => {
-        self.registers.sp = self.fetch_u16();
-        12 // Takes 12 cycles
-    },
-
-    // JP nn (Jump to 16-bit address)
-    0xC3 => {
-        self.registers.pc = self.fetch_u16();
-        16
-    },
-
-    // XOR A (XOR Register A with itself - effectively sets A to 0 and updates flags)
-    0xAF => {
-        self.registers.a = 0;
-        self.registers.f = 0x80; // Set Zero flag, clear others
-        4
-    },
-    0x21 => {
-        let val = self.fetch_u16();
-        self.set_hl(val);
-        12
-    },   
-    0x20 => {
-        let offset = self.fetch_byte() as i8; // This is a signed jump!
-        if (self.registers.f & 0x80) == 0 {
-            self.registers.pc = self.registers.pc.wrapping_add(offset as i16 as u16);
-            12 // Takes more cycles if it jumps
-        } else {
-            8
+        if self.condition(opcode) {
+            self.registers.pc = addr;
+            self.branch_taken = true;
         }
-    },
-    // 0x40 - 0x7F: LD r1, r2 (excluding 0x76 which is HALT)
-0x40..=0x75 | 0x77..=0x7F => {
-    let dest_idx = (opcode >> 3) & 0x07; // Bits 3, 4, 5 define destination
-    let src_idx = opcode & 0x07;        // Bits 0, 1, 2 define source
-    
-    let val = self.get_reg_by_index(src_idx);
-    self.set_reg_by_index(dest_idx, val);
-    
-    // Most LD r,r take 4 cycles, but if it involves (HL), it takes 8
-    if dest_idx == 6 || src_idx == 6 { 8 } else { 4 }
-},
-0x01 => {
-        let val = self.fetch_u16();
-        self.set_bc(val);
-        12
-    },
-
-    // 0x11: LD DE, d16 (The one you just hit!)
-    0x11 => {
-        let val = self.fetch_u16();
-        self.set_de(val);
-        12
-    },
-
-    // 0x06: LD B, d8 (8-bit immediate load)
-    0x06 => {
-        self.registers.b = self.fetch_byte();
-        8
-    },
-
-    // 0x0E: LD C, d8
-    0x0E => {
-        self.registers.c = self.fetch_byte();
-        8
-    },
-
-    // 0x16: LD D, d8
-    0x16 => {
-        self.registers.d = self.fetch_byte();
-        8
-    },
-
-    // 0x1E: LD E, d8
-    0x1E => {
-        self.registers.e = self.fetch_byte();
-        8
-    },
-
-    // 0x26: LD H, d8
-    0x26 => {
-        self.registers.h = self.fetch_byte();
-        8
-    },
-
-    // 0x2E: LD L, d8
-    0x2E => {
-        self.registers.l = self.fetch_byte();
-        8
-    },
-
-    // 0x3E: LD A, d8
-    0x3E => {
-        self.registers.a = self.fetch_byte();
-        8
-    },
-    0x22 => {
-        let addr = self.get_hl();
-        self.bus.write_byte(addr, self.registers.a);
-        self.set_hl(addr.wrapping_add(1));
-        8
-    },
-
-    // 0x2A: LD A, (HL+) (Read (HL) into A, then Increment HL)
-    0x2A => {
-        let addr = self.get_hl();
-        self.registers.a = self.bus.read_byte(addr);
-        self.set_hl(addr.wrapping_add(1));
-        8
-    },
-
-    // 0x32: LD (HL-), A (Write A to (HL), then Decrement HL)
-    0x32 => {
-        let addr = self.get_hl();
-        self.bus.write_byte(addr, self.registers.a);
-        self.set_hl(addr.wrapping_sub(1));
-        8
-    },
-
-    // 0x3A: LD A, (HL-) (Read (HL) into A, then Decrement HL)
-    0x3A => {
-        let addr = self.get_hl();
-        self.registers.a = self.bus.read_byte(addr);
-        self.set_hl(addr.wrapping_sub(1));
-        8
-    },
-    0x02 => {
-        let addr = self.get_bc();
-        self.bus.write_byte(addr, self.registers.a);
-        8
-    },
-
-    // 0x12: LD (DE), A (Store A into memory address pointed to by DE)
-    0x12 => {
-        let addr = self.get_de();
-        self.bus.write_byte(addr, self.registers.a);
-        8
-    },
-
-    // 0x0A: LD A, (BC) (Load A from memory address pointed to by BC)
-    0x0A => {
-        let addr = self.get_bc();
-        self.registers.a = self.bus.read_byte(addr);
-        8
-    },
-
-    // 0x1A: LD A, (DE) (Load A from memory address pointed to by DE)
-    0x1A => {
-        let addr = self.get_de();
-        self.registers.a = self.bus.read_byte(addr);
-        8
-    },
-    // 0xCD: CALL nn (Call function at 16-bit address)
-    0xCD => {
+    }
+
+    fn call(&mut self, _: u8) {
         let dest = self.fetch_u16();
-        // Push the address of the NEXT instruction (the current PC) onto the stack
-        let return_addr = self.registers.pc;
-        self.push_u16(return_addr);
-        // Jump to the destination
+        // The return address is the next instruction, where PC already is
+        self.advance(); // Internal cycle before the push
+        self.push_u16(self.registers.pc);
         self.registers.pc = dest;
-        24 // This is a heavy instruction, takes 24 cycles
-    },
-
-    // 0xC9: RET (Return from function)
-    0xC9 => {
-        self.registers.pc = self.pop_u16();
-        16
-    },
-    0x04 => { self.registers.b = self.inc_8bit(self.registers.b); 4 },
-    0x0C => { self.registers.c = self.inc_8bit(self.registers.c); 4 },
-    0x14 => { self.registers.d = self.inc_8bit(self.registers.d); 4 },
-    0x1C => { self.registers.e = self.inc_8bit(self.registers.e); 4 }, // The one you hit!
-    0x24 => { self.registers.h = self.inc_8bit(self.registers.h); 4 },
-    0x2C => { self.registers.l = self.inc_8bit(self.registers.l); 4 },
-    0x3C => { self.registers.a = self.inc_8bit(self.registers.a); 4 },
-    0x34 => { 
-        let val = self.bus.read_byte(self.get_hl());
-        let res = self.inc_8bit(val);
-        self.bus.write_byte(self.get_hl(), res);
-        12 
-    },
-    0x05 => { self.registers.b = self.dec_8bit(self.registers.b); 4 },
-    0x0D => { self.registers.c = self.dec_8bit(self.registers.c); 4 }, // The one you hit!
-    0x15 => { self.registers.d = self.dec_8bit(self.registers.d); 4 },
-    0x1D => { self.registers.e = self.dec_8bit(self.registers.e); 4 },
-    0x25 => { self.registers.h = self.dec_8bit(self.registers.h); 4 },
-    0x2D => { self.registers.l = self.dec_8bit(self.registers.l); 4 },
-    0x3D => { self.registers.a = self.dec_8bit(self.registers.a); 4 },
-    0x35 => { 
-        let val = self.bus.read_byte(self.get_hl());
-        let res = self.dec_8bit(val);
-        self.bus.write_byte(self.get_hl(), res);
-        12 
-    },
-    // 0x20: JR NZ, r8 (Jump Relative if Not Zero)
-    0x20 => {
-        let offset = self.fetch_byte() as i8;
-        if (self.registers.f & 0x80) == 0 { // Check if Z flag is 0
-            self.registers.pc = self.registers.pc.wrapping_add(offset as i16 as u16);
-            12 // Takes 12 cycles if jump is taken
-        } else {
-            8  // Takes 8 cycles if jump is ignored
-        }
-    },
+    }
 
-    // 0x28: JR Z, r8 (Jump Relative if Zero)
-    0x28 => {
-        let offset = self.fetch_byte() as i8;
-        if (self.registers.f & 0x80) != 0 { // Check if Z flag is 1
-            self.registers.pc = self.registers.pc.wrapping_add(offset as i16 as u16);
-            12
-        } else {
-            8
+    fn call_cc(&mut self, opcode: u8) {
+        let dest = self.fetch_u16();
+        if self.condition(opcode) {
+            self.advance();
+            self.push_u16(self.registers.pc);
+            self.registers.pc = dest;
+            self.branch_taken = true;
         }
-    },
+    }
 
-    // 0x30: JR NC, r8 (Jump Relative if No Carry)
-    0x30 => {
-        let offset = self.fetch_byte() as i8;
-        if (self.registers.f & 0x10) == 0 { // Check if C flag is 0
-            self.registers.pc = self.registers.pc.wrapping_add(offset as i16 as u16);
-            12
-        } else {
-            8
+    fn ret_cc(&mut self, opcode: u8) {
+        self.advance(); // Checking the condition takes a cycle, taken or not
+        if self.condition(opcode) {
+            self.registers.pc = self.pop_u16();
+            self.branch_taken = true;
         }
-    },
+    }
 
-    // 0x38: JR C, r8 (Jump Relative if Carry)
-    0x38 => {
-        let offset = self.fetch_byte() as i8;
-        if (self.registers.f & 0x10) != 0 { // Check if C flag is 1
-            self.registers.pc = self.registers.pc.wrapping_add(offset as i16 as u16);
-            12
+    // POP/PUSH use AF instead of SP
+    fn pop(&mut self, opcode: u8) {
+        let val = self.pop_u16();
+        if opcode == 0xF1 {
+            self.registers.a = (val >> 8) as u8;
+            self.registers.f = (val & 0xF0) as u8; // Low 4 bits are always 0
         } else {
-            8
+            self.set_rr(opcode, val);
         }
-    },
-            _ => {
-                println!("Unknown Opcode: {:#04X} at PC: {:#06X}", opcode, self.registers.pc.wrapping_sub(1));
-                panic!("CPU CRASHED");
-            }
-        };
-         if self.interrupt_enable_delay {
-        self.ime = true;
-        self.interrupt_enable_delay = false;
     }
-        cycles
+
+    fn push(&mut self, opcode: u8) {
+        let val = if opcode == 0xF5 { u16::from_be_bytes([self.registers.a, self.registers.f]) } else { self.get_rr(opcode) };
+        self.advance(); // Internal cycle before the push
+        self.push_u16(val);
     }
 
-    fn fetch_byte(&mut self) -> u8 {
-        let byte = self.bus.read_byte(self.registers.pc);
-        self.registers.pc = self.registers.pc.wrapping_add(1);
-        byte
+    // Illegal opcodes: the real CPU hangs until it's reset. PC stays on the
+    // opcode so the debugger and the UI can show which one it was.
+    fn lock_up(&mut self, _: u8) {
+        self.registers.pc = self.registers.pc.wrapping_sub(1);
+        self.locked = true;
     }
 
-    fn fetch_u16(&mut self) -> u16 {
-        let low = self.fetch_byte() as u16;
-        let high = self.fetch_byte() as u16;
-        (high << 8) | low
+    fn cb_bit(&mut self, opcode: u8) {
+        let val = self.get_reg_by_index(opcode & 7);
+        self.registers.f &= 0x10; // Keep Carry, clear others
+        self.registers.f |= 0x20; // H flag is ALWAYS set for BIT
+        if val & (1 << ((opcode >> 3) & 7)) == 0 { self.registers.f |= 0x80; }
     }
-        
-}
\ No newline at end of file
+
+    fn cb_rotate_shift(&mut self, opcode: u8) {
+        let val = self.get_reg_by_index(opcode & 7);
+        let res = self.rotate_shift(opcode, val);
+        self.set_reg_by_index(opcode & 7, res);
+    }
+
+    fn cb_res(&mut self, opcode: u8) {
+        let val = self.get_reg_by_index(opcode & 7);
+        self.set_reg_by_index(opcode & 7, val & !(1 << ((opcode >> 3) & 7)));
+    }
+
+    fn cb_set(&mut self, opcode: u8) {
+        let val = self.get_reg_by_index(opcode & 7);
+        self.set_reg_by_index(opcode & 7, val | (1 << ((opcode >> 3) & 7)));
+    }
+}
+
+// Runs one opcode; it gets the opcode itself to decode its operands
+type Handler = fn(&mut CPU, u8);
+
+#[derive(Clone, Copy)]
+struct Opcode {
+    exec: Handler,
+    cycles: u8, // T-cycles, including the fetch
+    taken: u8,  // T-cycles when a conditional jump, call or return is taken
+}
+
+const fn op(exec: Handler, cycles: u8) -> Option<Opcode> {
+    Some(Opcode { exec, cycles, taken: cycles })
+}
+
+const fn branch(exec: Handler, cycles: u8, taken: u8) -> Option<Opcode> {
+    Some(Opcode { exec, cycles, taken })
+}
+
+// The table entry for an opcode, from its bit fields: y is bits 3-5 and z
+// bits 0-2, which pick the register (6 is (HL), a memory access) or the
+// operation. None for opcodes that aren't emulated yet (STOP); 0xCB is the
+// prefix for CB_OPCODES.
+const fn decode(opcode: u8) -> Option<Opcode> {
+    let (y, z) = ((opcode >> 3) & 7, opcode & 7);
+    match opcode {
+        0x00 => op(|_, _| {}, 4), // NOP
+        0x08 => op(|cpu, _| { // LD (nn),SP
+            let addr = cpu.fetch_u16();
+            let sp = cpu.registers.sp;
+            cpu.write(addr, sp as u8);
+            cpu.write(addr.wrapping_add(1), (sp >> 8) as u8);
+        }, 20),
+        0x10 => None, // STOP
+        0x18 => op(CPU::jr, 12),
+        0x20 | 0x28 | 0x30 | 0x38 => branch(CPU::jr_cc, 8, 12),
+        0x01 | 0x11 | 0x21 | 0x31 => op(|cpu, opcode| { let val = cpu.fetch_u16(); cpu.set_rr(opcode, val) }, 12),
+        0x09 | 0x19 | 0x29 | 0x39 => op(|cpu, opcode| { cpu.advance(); cpu.add_hl(cpu.get_rr(opcode)) }, 8),
+        0x02 | 0x12 | 0x22 | 0x32 => op(CPU::ld_ind_a, 8),
+        0x0A | 0x1A | 0x2A | 0x3A => op(CPU::ld_a_ind, 8),
+        0x03 | 0x13 | 0x23 | 0x33 => op(|cpu, opcode| { cpu.advance(); cpu.set_rr(opcode, cpu.get_rr(opcode).wrapping_add(1)) }, 8),
+        0x0B | 0x1B | 0x2B | 0x3B => op(|cpu, opcode| { cpu.advance(); cpu.set_rr(opcode, cpu.get_rr(opcode).wrapping_sub(1)) }, 8),
+        0x00..=0x3F if z == 4 => op(CPU::inc_r, if y == 6 { 12 } else { 4 }),
+        0x00..=0x3F if z == 5 => op(CPU::dec_r, if y == 6 { 12 } else { 4 }),
+        0x00..=0x3F if z == 6 => op(CPU::ld_r_d8, if y == 6 { 12 } else { 8 }),
+        0x07 | 0x0F | 0x17 | 0x1F => op(CPU::rotate_a, 4),
+        0x27 => op(|cpu, _| cpu.daa(), 4),
+        0x2F => op(|cpu, _| { // CPL
+            cpu.registers.a = !cpu.registers.a;
+            cpu.registers.f |= 0x60; // Set N and H
+        }, 4),
+        0x37 => op(|cpu, _| cpu.registers.f = (cpu.registers.f & 0x80) | 0x10, 4), // SCF
+        0x3F => op(|cpu, _| cpu.registers.f = (cpu.registers.f & 0x90) ^ 0x10, 4), // CCF
+
+        0x76 => op(|cpu, _| cpu.halted = true, 4), // HALT
+        // LD D,D is a no-op, also used to mark homebrew debug messages
+        0x52 => op(|cpu, _| cpu.debug_message(), 4),
+        0x40..=0x7F => op(CPU::ld_r_r, if y == 6 || z == 6 { 8 } else { 4 }),
+        0x80..=0xBF => op(|cpu, opcode| { let val = cpu.get_reg_by_index(opcode & 7); cpu.alu(opcode, val) }, if z == 6 { 8 } else { 4 }),
+
+        0xC0 | 0xC8 | 0xD0 | 0xD8 => branch(CPU::ret_cc, 8, 20),
+        0xC9 => op(|cpu, _| cpu.registers.pc = cpu.pop_u16(), 16), // RET
+        0xD9 => op(|cpu, _| { // RETI: interrupts back on straight away
+            cpu.registers.pc = cpu.pop_u16();
+            cpu.ime = true;
+        }, 16),
+        0xC1 | 0xD1 | 0xE1 | 0xF1 => op(CPU::pop, 12),
+        0xC5 | 0xD5 | 0xE5 | 0xF5 => op(CPU::push, 16),
+        0xC2 | 0xCA | 0xD2 | 0xDA => branch(CPU::jp_cc, 12, 16),
+        0xC3 => op(|cpu, _| cpu.registers.pc = cpu.fetch_u16(), 16), // JP nn
+        0xE9 => op(|cpu, _| cpu.registers.pc = cpu.get_hl(), 4), // JP HL
+        0xC4 | 0xCC | 0xD4 | 0xDC => branch(CPU::call_cc, 12, 24),
+        0xCD => op(CPU::call, 24),
+        0xC0..=0xFF if z == 6 => op(|cpu, opcode| { let val = cpu.fetch_byte(); cpu.alu(opcode, val) }, 8),
+        0xC0..=0xFF if z == 7 => op(|cpu, opcode| cpu.rst(opcode), 16),
+
+        0xE0 => op(|cpu, _| { let addr = 0xFF00 | cpu.fetch_byte() as u16; cpu.write(addr, cpu.registers.a) }, 12), // LDH (n),A
+        0xF0 => op(|cpu, _| { let addr = 0xFF00 | cpu.fetch_byte() as u16; cpu.registers.a = cpu.read(addr) }, 12), // LDH A,(n)
+        0xE2 => op(|cpu, _| cpu.write(0xFF00 | cpu.registers.c as u16, cpu.registers.a), 8), // LD (C),A
+        0xF2 => op(|cpu, _| cpu.registers.a = cpu.read(0xFF00 | cpu.registers.c as u16), 8), // LD A,(C)
+        0xEA => op(|cpu, _| { let addr = cpu.fetch_u16(); cpu.write(addr, cpu.registers.a) }, 16), // LD (nn),A
+        0xFA => op(|cpu, _| { let addr = cpu.fetch_u16(); cpu.registers.a = cpu.read(addr) }, 16), // LD A,(nn)
+        0xE8 => op(|cpu, _| { // ADD SP,e8
+            let res = cpu.sp_offset();
+            cpu.advance();
+            cpu.advance();
+            cpu.registers.sp = res;
+        }, 16),
+        0xF8 => op(|cpu, _| { let res = cpu.sp_offset(); cpu.advance(); cpu.set_hl(res) }, 12), // LD HL,SP+e8
+        0xF9 => op(|cpu, _| { cpu.advance(); cpu.registers.sp = cpu.get_hl() }, 8), // LD SP,HL
+        0xF3 => op(|cpu, _| { // DI, which also cancels a pending EI
+            cpu.ime = false;
+            cpu.interrupt_enable_delay = false;
+        }, 4),
+        0xFB => op(|cpu, _| cpu.interrupt_enable_delay = true, 4), // EI
+        0xCB => None,
+        // D3, DB, DD, E3, E4, EB, EC, ED, F4, FC, FD
+        _ => op(CPU::lock_up, 4),
+    }
+}
+
+// CB-prefixed opcodes, cycles including the prefix
+const fn decode_cb(opcode: u8) -> Opcode {
+    let (exec, cycles): (Handler, u8) = match opcode {
+        0x00..=0x3F => (CPU::cb_rotate_shift, 16),
+        0x40..=0x7F => (CPU::cb_bit, 12),
+        0x80..=0xBF => (CPU::cb_res, 16),
+        _ => (CPU::cb_set, 16),
+    };
+    // (HL) is the only operand that isn't a register
+    let cycles = if opcode & 7 == 6 { cycles } else { 8 };
+    Opcode { exec, cycles, taken: cycles }
+}
+
+static OPCODES: [Option<Opcode>; 256] = {
+    let mut table = [None; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = decode(i as u8);
+        i += 1;
+    }
+    table
+};
+
+static CB_OPCODES: [Opcode; 256] = {
+    let mut table = [decode_cb(0); 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = decode_cb(i as u8);
+        i += 1;
+    }
+    table
+};
\ No newline at end of file
@@ -0,0 +1,63 @@
+// crash.rs
+// Crash reports: when the CPU stops on something it can't run (or the core
+// itself fails), the state needed to work out why goes into a text file
+// rather than a one-line message: the registers, interrupt state, mapped
+// banks, the memory around PC and SP, and the instructions that led there
+// (see history.rs).
+use std::any::Any;
+use std::fmt::Write;
+
+use crate::cpu::CPU;
+use crate::memdump;
+
+const INTERRUPTS: [&str; 5] = ["VBLANK", "STAT", "TIMER", "SERIAL", "JOYPAD"];
+
+// The interrupts whose bits are set in an IE/IF value
+fn interrupt_names(bits: u8) -> String {
+    let names: Vec<&str> = (0..5).filter(|i| bits & (1 << i) != 0).map(|i| INTERRUPTS[i]).collect();
+    if names.is_empty() { "none".to_string() } else { names.join(" ") }
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ")
+}
+
+// The text a caught panic was raised with
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<String>()
+        .cloned()
+        .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+        .unwrap_or_default()
+}
+
+// `reason` is what stopped the CPU, in the words shown to the player
+pub fn report(cpu: &CPU, reason: &str) -> String {
+    let r = &cpu.registers;
+    let bus = &cpu.bus;
+    let flag = |bit: u8, name: char| if r.f & bit != 0 { name } else { '-' };
+    let mut out = format!("{}\n\n", reason);
+    let _ = writeln!(out, "Registers");
+    let _ = writeln!(
+        out,
+        "  AF {:02X}{:02X}  BC {:02X}{:02X}  DE {:02X}{:02X}  HL {:02X}{:02X}  SP {:04X}  PC {:04X}",
+        r.a, r.f, r.b, r.c, r.d, r.e, r.h, r.l, r.sp, r.pc
+    );
+    let _ = writeln!(out, "  Flags {}{}{}{}", flag(0x80, 'Z'), flag(0x40, 'N'), flag(0x20, 'H'), flag(0x10, 'C'));
+    let _ = writeln!(
+        out,
+        "  IME {}  EI pending {}  HALT {}  Locked {}",
+        cpu.ime as u8, cpu.interrupt_enable_delay as u8, cpu.halted as u8, cpu.locked as u8
+    );
+    let _ = writeln!(out, "Interrupts");
+    let _ = writeln!(out, "  IE {:02X} ({})", bus.interrupt_enable, interrupt_names(bus.interrupt_enable));
+    let _ = writeln!(out, "  IF {:02X} ({})", bus.interrupt_flag, interrupt_names(bus.interrupt_flag));
+    let _ = writeln!(out, "Banks");
+    let _ = writeln!(out, "  ROM {:02X} at 4000  RAM {:02X}", bus.rom_bank_at(0x4000), bus.ram_bank);
+    let _ = writeln!(out, "Memory");
+    let _ = writeln!(out, "  PC {:04X}: {}", r.pc, hex_bytes(&memdump::range(bus, r.pc, 16)));
+    let _ = writeln!(out, "  SP {:04X}: {}", r.sp, hex_bytes(&memdump::range(bus, r.sp, 16)));
+    out.push('\n');
+    out.push_str(&cpu.history.dump());
+    out
+}
@@ -0,0 +1,358 @@
+// debug_tui.rs
+// `--debug-tui`: a debugger in the terminal, for working over SSH or without
+// a display. No window or audio; the screen shows disassembly from PC, the
+// registers, a memory view, breakpoints and watches, the latest memory
+// accesses, and a command line.
+//
+// Commands (an empty line repeats the last one):
+//   s [N]     step N instructions (default 1)
+//   f         run one frame
+//   c         continue until a breakpoint or watch hits (Esc pauses)
+//   b ADDR    toggle a breakpoint at ADDR
+//   w ADDR    toggle a watch: stop when the byte at ADDR changes
+//   m ADDR    show memory from ADDR
+//   t [RANGE] show only accesses to ADDR or ADDR-ADDR in the bus trace
+//             (no range shows them all)
+//   q         quit
+// Addresses are hex, with or without a $ or 0x prefix.
+use std::time::{Duration, Instant};
+
+use gb_rs::bus_trace::BusTrace;
+use gb_rs::disasm::disassemble;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::EmulatorState;
+use crate::cli::Args;
+use crate::mmu::Model;
+
+const FRAME_CYCLES: u32 = 70224;
+const FRAME_TIME: Duration = Duration::from_nanos(16_742_706);
+const LOG_LINES: usize = 200;
+const TRACE_LEN: usize = 4096; // Accesses kept for the bus trace pane
+
+struct Debugger {
+    emu: EmulatorState,
+    running: bool,
+    breakpoints: Vec<u16>,
+    watches: Vec<(u16, u8)>, // Address and the value it last had
+    mem_addr: u16,
+    trace_range: (u16, u16), // Accesses shown in the bus trace pane
+    input: String,
+    last_command: String,
+    log: Vec<String>,
+    serial_line: Vec<u8>,
+}
+
+fn parse_addr(arg: Option<&str>) -> Result<u16, String> {
+    let arg = arg.ok_or("needs an address")?;
+    let hex = arg.trim_start_matches('$').trim_start_matches("0x");
+    u16::from_str_radix(hex, 16).map_err(|_| format!("bad address '{}'", arg))
+}
+
+impl Debugger {
+    fn new(emu: EmulatorState) -> Self {
+        Self {
+            emu,
+            running: false,
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+            mem_addr: 0xC000,
+            trace_range: (0x0000, 0xFFFF),
+            input: String::new(),
+            last_command: String::new(),
+            log: vec!["Paused at power-on. Type a command; q quits.".to_string()],
+            serial_line: Vec::new(),
+        }
+    }
+
+    fn print(&mut self, line: String) {
+        self.log.push(line);
+        if self.log.len() > LOG_LINES {
+            self.log.remove(0);
+        }
+    }
+
+    // Picks up debug messages and serial text the game produced
+    fn collect_output(&mut self) {
+        let bus = &mut self.emu.cpu.bus;
+        let messages = bus.debug_log.as_mut().map(std::mem::take).unwrap_or_default();
+        let serial = bus.serial_log.as_mut().map(std::mem::take).unwrap_or_default();
+        for message in messages {
+            self.print(format!("[debug] {}", message));
+        }
+        for byte in serial {
+            if byte == b'\n' {
+                let line = std::mem::take(&mut self.serial_line);
+                self.print(format!("[serial] {}", String::from_utf8_lossy(&line)));
+            } else {
+                self.serial_line.push(byte);
+            }
+        }
+    }
+
+    // One instruction; an error is kept for check_stop to report
+    fn step(&mut self) -> u32 {
+        let cycles = self.emu.step().unwrap_or_else(|e| {
+            self.emu.fault = Some(e);
+            0
+        });
+        // Nothing plays the samples here
+        self.emu.cpu.bus.apu.sample_buffer.clear();
+        cycles
+    }
+
+    // Up to a frame's worth of instructions while running
+    fn run_frame(&mut self) {
+        let mut cycles = 0;
+        while cycles < FRAME_CYCLES {
+            cycles += self.step();
+            if let Some(hit) = self.check_stop() {
+                self.running = false;
+                self.print(hit);
+                break;
+            }
+        }
+    }
+
+    // After each instruction: why execution should stop, if it should
+    fn check_stop(&mut self) -> Option<String> {
+        let bus = &self.emu.cpu.bus;
+        for (addr, value) in &mut self.watches {
+            let now = bus.read_byte(*addr);
+            if now != *value {
+                let hit = format!("Watch ${:04X}: ${:02X} -> ${:02X}", addr, value, now);
+                *value = now;
+                return Some(hit);
+            }
+        }
+        if let Some(e) = &self.emu.fault {
+            return Some(format!("✗ {}", e.to_string().lines().next().unwrap_or_default()));
+        }
+        if let Some(message) = self.emu.cpu.lock_message() {
+            return Some(message);
+        }
+        let pc = self.emu.cpu.registers.pc;
+        self.breakpoints.contains(&pc).then(|| format!("Breakpoint at ${:04X}", pc))
+    }
+
+    // False once the user quits
+    fn command(&mut self, line: &str) -> bool {
+        let mut words = line.split_whitespace();
+        let Some(cmd) = words.next() else { return true };
+        let arg = words.next();
+        let result = match cmd {
+            "q" | "quit" => return false,
+            "s" | "step" => match arg.map(str::parse::<u32>).unwrap_or(Ok(1)) {
+                Ok(n) => {
+                    for _ in 0..n {
+                        self.step();
+                        if let Some(hit) = self.check_stop() {
+                            self.print(hit);
+                            break;
+                        }
+                    }
+                    Ok(())
+                }
+                Err(_) => Err(format!("bad count '{}'", arg.unwrap_or_default())),
+            },
+            "f" | "frame" => {
+                self.run_frame();
+                Ok(())
+            }
+            "c" | "continue" => {
+                // Get off a breakpoint we're sitting on first
+                self.step();
+                self.running = true;
+                Ok(())
+            }
+            "b" | "break" => parse_addr(arg).map(|addr| {
+                if let Some(i) = self.breakpoints.iter().position(|&b| b == addr) {
+                    self.breakpoints.remove(i);
+                } else {
+                    self.breakpoints.push(addr);
+                }
+            }),
+            "w" | "watch" => parse_addr(arg).map(|addr| {
+                if let Some(i) = self.watches.iter().position(|&(w, _)| w == addr) {
+                    self.watches.remove(i);
+                } else {
+                    self.watches.push((addr, self.emu.cpu.bus.read_byte(addr)));
+                }
+            }),
+            "m" | "mem" => parse_addr(arg).map(|addr| self.mem_addr = addr & 0xFFF0),
+            "t" | "trace" => match arg {
+                None => {
+                    self.trace_range = (0x0000, 0xFFFF);
+                    Ok(())
+                }
+                Some(range) => {
+                    let (start, end) = range.split_once('-').unwrap_or((range, range));
+                    parse_addr(Some(start)).and_then(|s| Ok((s, parse_addr(Some(end))?))).map(|r| self.trace_range = r)
+                }
+            },
+            _ => Err(format!("unknown command '{}'", cmd)),
+        };
+        if let Err(e) = result {
+            self.print(format!("✗ {}: {}", cmd, e));
+        }
+        true
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [top, memory, log, input] =
+            Layout::vertical([Constraint::Min(12), Constraint::Length(10), Constraint::Length(8), Constraint::Length(3)]).areas(frame.area());
+        let [code, side] = Layout::horizontal([Constraint::Min(30), Constraint::Length(36)]).areas(top);
+        let [regs, points, trace] = Layout::vertical([Constraint::Length(12), Constraint::Length(6), Constraint::Min(3)]).areas(side);
+        let title = if self.running { " Disassembly (running, Esc pauses) " } else { " Disassembly " };
+
+        // Disassembly from PC onwards
+        let bus = &self.emu.cpu.bus;
+        let r = &self.emu.cpu.registers;
+        let mut addr = r.pc;
+        let mut lines = Vec::new();
+        for _ in 0..code.height.saturating_sub(2) {
+            let (text, len) = disassemble(bus, addr);
+            let bytes: Vec<String> = (0..len).map(|i| format!("{:02X}", bus.read_byte(addr.wrapping_add(i)))).collect();
+            let mark = if self.breakpoints.contains(&addr) { "●" } else { " " };
+            let style = if addr == r.pc { Style::new().add_modifier(Modifier::REVERSED) } else { Style::new() };
+            lines.push(Line::styled(format!("{}{:04X}  {:<9} {}", mark, addr, bytes.join(" "), text), style));
+            addr = addr.wrapping_add(len);
+        }
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(title)), code);
+
+        let flags: String = ["Z", "N", "H", "C"].iter().enumerate()
+            .map(|(i, f)| if r.f & (0x80 >> i) != 0 { *f } else { "-" })
+            .collect();
+        let reg_lines = vec![
+            Line::raw(format!("AF {:02X}{:02X}  BC {:02X}{:02X}", r.a, r.f, r.b, r.c)),
+            Line::raw(format!("DE {:02X}{:02X}  HL {:02X}{:02X}", r.d, r.e, r.h, r.l)),
+            Line::raw(format!("SP {:04X}  PC {:04X}", r.sp, r.pc)),
+            Line::raw(format!("Flags {}", flags)),
+            Line::raw(format!("IME {}  {}", self.emu.cpu.ime as u8, if self.emu.cpu.locked { "LOCKED" } else if self.emu.cpu.halted { "HALTED" } else { "" })),
+            Line::raw(format!("IE {:02X}  IF {:02X}", bus.interrupt_enable, bus.interrupt_flag)),
+            Line::raw(format!("LY {:3}  ROM bank {}", bus.ly, bus.rom_bank)),
+            Line::raw(format!("Frame {}", self.emu.frame_count)),
+        ];
+        frame.render_widget(Paragraph::new(reg_lines).block(Block::bordered().title(" Registers ")), regs);
+
+        let mut point_lines: Vec<Line> = self.breakpoints.iter().map(|b| Line::raw(format!("break ${:04X}", b))).collect();
+        point_lines.extend(self.watches.iter().map(|(w, v)| Line::raw(format!("watch ${:04X} = ${:02X}", w, v))));
+        frame.render_widget(Paragraph::new(point_lines).block(Block::bordered().title(" Breakpoints ")), points);
+
+        // Newest access first, with how many T-cycles ago it happened
+        let (start, end) = self.trace_range;
+        let now = bus.apu.cycle_count;
+        let trace_lines: Vec<Line> = bus.trace.iter().flat_map(|t| t.iter().rev())
+            .filter(|a| (start..=end).contains(&a.addr))
+            .take(trace.height.saturating_sub(2) as usize)
+            .map(|a| {
+                let kind = if a.write { Span::styled("W", Style::new().fg(Color::Red)) } else { Span::raw("R") };
+                Line::from(vec![kind, Span::raw(format!(" {:04X}={:02X}  pc {:04X}  -{}", a.addr, a.value, a.pc, now - a.cycle))])
+            })
+            .collect();
+        let trace_title = if self.trace_range == (0x0000, 0xFFFF) { " Bus trace ".to_string() } else { format!(" Bus trace {:04X}-{:04X} ", start, end) };
+        frame.render_widget(Paragraph::new(trace_lines).block(Block::bordered().title(trace_title)), trace);
+
+        let mem_lines: Vec<Line> = (0..memory.height.saturating_sub(2))
+            .map(|row| {
+                let base = self.mem_addr.wrapping_add(row * 16);
+                let bytes: Vec<u8> = (0..16).map(|i| bus.read_byte(base.wrapping_add(i))).collect();
+                let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+                let ascii: String = bytes.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+                Line::from(vec![
+                    Span::styled(format!("{:04X}  ", base), Style::new().fg(Color::DarkGray)),
+                    Span::raw(format!("{}  {}", hex.join(" "), ascii)),
+                ])
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(mem_lines).block(Block::bordered().title(" Memory ")), memory);
+
+        let shown = log.height.saturating_sub(2) as usize;
+        let log_lines: Vec<Line> = self.log[self.log.len().saturating_sub(shown)..].iter().map(|l| Line::raw(l.as_str())).collect();
+        frame.render_widget(Paragraph::new(log_lines).block(Block::bordered().title(" Log ")), log);
+
+        frame.render_widget(Paragraph::new(format!("> {}", self.input)).block(Block::bordered()), input);
+        frame.set_cursor_position((input.x + 3 + self.input.len() as u16, input.y + 1));
+    }
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, debugger: &mut Debugger) -> std::io::Result<()> {
+    let mut next_frame = Instant::now();
+    loop {
+        if debugger.running {
+            debugger.run_frame();
+            next_frame += FRAME_TIME;
+        } else {
+            next_frame = Instant::now();
+        }
+        debugger.collect_output();
+        terminal.draw(|frame| debugger.draw(frame))?;
+
+        let timeout = if debugger.running { next_frame.saturating_duration_since(Instant::now()) } else { Duration::from_millis(250) };
+        if !event::poll(timeout)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else { continue };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+            KeyCode::Esc if debugger.running => {
+                debugger.running = false;
+                debugger.print(format!("Paused at ${:04X}", debugger.emu.cpu.registers.pc));
+            }
+            KeyCode::Char(c) => debugger.input.push(c),
+            KeyCode::Backspace => {
+                debugger.input.pop();
+            }
+            KeyCode::Enter if !debugger.running => {
+                let mut line = std::mem::take(&mut debugger.input);
+                if line.trim().is_empty() {
+                    line = debugger.last_command.clone();
+                } else {
+                    debugger.last_command = line.clone();
+                }
+                if !debugger.command(&line) {
+                    return Ok(());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn run(args: &Args) -> i32 {
+    let Some(rom) = &args.rom else { return 2 };
+    let mut emu = match EmulatorState::load_rom(rom, args.model.unwrap_or(Model::Dmg), crate::ppu::PPU::new().palette) {
+        Ok(emu) => emu,
+        Err(e) => {
+            eprintln!("✗ {}", e);
+            return 1;
+        }
+    };
+    // Anything printed would end up on top of the interface
+    emu.cpu.bus.debug_log = Some(Vec::new());
+    emu.cpu.bus.serial_log = Some(Vec::new());
+    emu.cpu.bus.trace = Some(BusTrace::new(TRACE_LEN));
+    let (fill, seed) = args.ram_fill();
+    emu.cpu.bus.fill_ram(fill, seed);
+
+    let mut debugger = Debugger::new(emu);
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut debugger);
+    ratatui::restore();
+    debugger.emu.close(false);
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("✗ Terminal error: {}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// disasm.rs
+// SM83 disassembler for the debuggers: one instruction at a time, read
+// through the bus so banked ROM shows what's currently mapped.
+use crate::mmu::MMU;
+
+const R8: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+const R16: [&str; 4] = ["BC", "DE", "HL", "SP"];
+const R16_STACK: [&str; 4] = ["BC", "DE", "HL", "AF"];
+const R16_MEM: [&str; 4] = ["(BC)", "(DE)", "(HL+)", "(HL-)"];
+const COND: [&str; 4] = ["NZ", "Z", "NC", "C"];
+const ALU: [&str; 8] = ["ADD A,", "ADC A,", "SUB ", "SBC A,", "AND ", "XOR ", "OR ", "CP "];
+const ROT: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+
+// The instruction at `addr` and its length in bytes
+pub fn disassemble(bus: &MMU, addr: u16) -> (String, u16) {
+    let byte = |i: u16| bus.read_byte(addr.wrapping_add(i));
+    let n8 = || format!("${:02X}", byte(1));
+    let n16 = || format!("${:04X}", u16::from_le_bytes([byte(1), byte(2)]));
+    // JR target, relative to the next instruction
+    let rel = || format!("${:04X}", addr.wrapping_add(2).wrapping_add(byte(1) as i8 as u16));
+
+    let op = byte(0);
+    let (x, y, z) = ((op >> 6) as usize, ((op >> 3) & 7) as usize, (op & 7) as usize);
+    let (p, q) = (y >> 1, y & 1);
+    let (text, len) = match op {
+        0x00 => ("NOP".to_string(), 1),
+        0x08 => (format!("LD ({}),SP", n16()), 3),
+        0x10 => ("STOP".to_string(), 2),
+        0x18 => (format!("JR {}", rel()), 2),
+        0x20 | 0x28 | 0x30 | 0x38 => (format!("JR {},{}", COND[y - 4], rel()), 2),
+        0x76 => ("HALT".to_string(), 1),
+        0xC3 => (format!("JP {}", n16()), 3),
+        0xC9 => ("RET".to_string(), 1),
+        0xCB => {
+            let cb = byte(1);
+            let (op_x, op_y, reg) = (cb >> 6, ((cb >> 3) & 7) as usize, R8[(cb & 7) as usize]);
+            let text = match op_x {
+                0 => format!("{} {}", ROT[op_y], reg),
+                1 => format!("BIT {},{}", op_y, reg),
+                2 => format!("RES {},{}", op_y, reg),
+                _ => format!("SET {},{}", op_y, reg),
+            };
+            (text, 2)
+        }
+        0xCD => (format!("CALL {}", n16()), 3),
+        0xD9 => ("RETI".to_string(), 1),
+        0xE0 => (format!("LDH (${:04X}),A", 0xFF00 | byte(1) as u16), 2),
+        0xE2 => ("LD ($FF00+C),A".to_string(), 1),
+        0xE8 => (format!("ADD SP,{}", byte(1) as i8), 2),
+        0xE9 => ("JP HL".to_string(), 1),
+        0xEA => (format!("LD ({}),A", n16()), 3),
+        0xF0 => (format!("LDH A,(${:04X})", 0xFF00 | byte(1) as u16), 2),
+        0xF2 => ("LD A,($FF00+C)".to_string(), 1),
+        0xF3 => ("DI".to_string(), 1),
+        0xF8 => (format!("LD HL,SP{:+}", byte(1) as i8), 2),
+        0xF9 => ("LD SP,HL".to_string(), 1),
+        0xFA => (format!("LD A,({})", n16()), 3),
+        0xFB => ("EI".to_string(), 1),
+        0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD => (format!("DB ${:02X}", op), 1),
+        _ => match (x, z) {
+            (0, 1) if q == 0 => (format!("LD {},{}", R16[p], n16()), 3),
+            (0, 1) => (format!("ADD HL,{}", R16[p]), 1),
+            (0, 2) if q == 0 => (format!("LD {},A", R16_MEM[p]), 1),
+            (0, 2) => (format!("LD A,{}", R16_MEM[p]), 1),
+            (0, 3) if q == 0 => (format!("INC {}", R16[p]), 1),
+            (0, 3) => (format!("DEC {}", R16[p]), 1),
+            (0, 4) => (format!("INC {}", R8[y]), 1),
+            (0, 5) => (format!("DEC {}", R8[y]), 1),
+            (0, 6) => (format!("LD {},{}", R8[y], n8()), 2),
+            (0, _) => (["RLCA", "RRCA", "RLA", "RRA", "DAA", "CPL", "SCF", "CCF"][y].to_string(), 1),
+            (1, _) => (format!("LD {},{}", R8[y], R8[z]), 1),
+            (2, _) => (format!("{}{}", ALU[y], R8[z]), 1),
+            (_, 0) => (format!("RET {}", COND[y]), 1),
+            (_, 1) => (format!("POP {}", R16_STACK[p]), 1),
+            (_, 2) => (format!("JP {},{}", COND[y], n16()), 3),
+            (_, 4) => (format!("CALL {},{}", COND[y], n16()), 3),
+            (_, 5) => (format!("PUSH {}", R16_STACK[p]), 1),
+            (_, 6) => (format!("{}{}", ALU[y], n8()), 2),
+            _ => (format!("RST ${:02X}", y * 8), 1),
+        },
+    };
+    (text, len)
+}
@@ -0,0 +1,168 @@
+// environment.rs
+// Reinforcement-learning environment: a headless Game Boy driven one step
+// (a few frames) at a time, for training agents from Rust directly.
+//
+//   let mut env = Environment::new(rom, Model::Dmg);
+//   env.ram_addresses = vec![0xD362, 0xD363]; // e.g. player X/Y
+//   env.seed(42);
+//   let mut obs = env.reset();
+//   obs = env.step(Buttons::A | Buttons::RIGHT);
+//
+// Emulation is deterministic, so episodes only differ by the seeded number
+// of idle frames run after each reset (see `noop_max`). Battery RAM is
+// neither loaded nor written. An opcode the CPU can't run ends the episode:
+// `fault` says which, and no more frames run until the next reset.
+use std::ops::BitOr;
+
+use crate::cpu::CPU;
+use crate::error::CoreError;
+use crate::mmu::{MMU, Model};
+use crate::savestate::{StateReader, StateWriter};
+
+const FRAME_CYCLES: u32 = 70224;
+
+// Buttons held for a step, combined with `|`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Buttons(pub u8);
+
+impl Buttons {
+    pub const NONE: Self = Self(0);
+    pub const RIGHT: Self = Self(1 << 0);
+    pub const LEFT: Self = Self(1 << 1);
+    pub const UP: Self = Self(1 << 2);
+    pub const DOWN: Self = Self(1 << 3);
+    pub const A: Self = Self(1 << 4);
+    pub const B: Self = Self(1 << 5);
+    pub const SELECT: Self = Self(1 << 6);
+    pub const START: Self = Self(1 << 7);
+}
+
+impl BitOr for Buttons {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+pub struct Observation {
+    pub screen: Vec<u8>,   // 160x144 shades, 0 (lightest) to 3, row by row
+    pub ram: Vec<u8>,      // Bytes at `ram_addresses`, in that order
+    pub frame_count: u64,  // Frames since the last reset
+}
+
+pub struct Environment {
+    cpu: CPU,
+    start: Vec<u8>, // Machine state reset() returns to
+    rng: u64,
+    frame_count: u64,
+    pub frame_skip: u32, // Frames per step, the buttons held for all of them
+    pub noop_max: u32,   // reset() runs 0..=noop_max idle frames, picked by the seed
+    pub ram_addresses: Vec<u16>,
+    pub fault: Option<CoreError>,
+}
+
+impl Environment {
+    // `rom` is a whole ROM image, header included
+    pub fn new(rom: Vec<u8>, model: Model) -> Self {
+        let mut mmu = MMU::new(rom, "environment.gb", model);
+        mmu.eram = [0; 0x8000];
+        mmu.has_battery = false;
+        mmu.serial_log = Some(Vec::new());
+        mmu.debug_log = Some(Vec::new());
+        let mut env = Self {
+            cpu: CPU::new(mmu),
+            start: Vec::new(),
+            rng: 0,
+            frame_count: 0,
+            frame_skip: 4,
+            noop_max: 30,
+            ram_addresses: Vec::new(),
+            fault: None,
+        };
+        env.start = env.snapshot();
+        env
+    }
+
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = seed;
+    }
+
+    // Makes the current machine state the one reset() returns to, e.g. after
+    // getting past the title screen
+    pub fn set_start(&mut self) {
+        self.start = self.snapshot();
+    }
+
+    pub fn reset(&mut self) -> Observation {
+        let mut r = StateReader::new(&self.start).expect("start state is written by this environment");
+        self.cpu.load_state(&mut r).expect("start state is written by this environment");
+        self.fault = None;
+        let noops = self.next_random() % (self.noop_max as u64 + 1);
+        for _ in 0..noops {
+            self.run_frame(Buttons::NONE);
+        }
+        self.frame_count = 0;
+        self.observe()
+    }
+
+    pub fn step(&mut self, buttons: Buttons) -> Observation {
+        for _ in 0..self.frame_skip.max(1) {
+            self.run_frame(buttons);
+        }
+        self.observe()
+    }
+
+    fn run_frame(&mut self, buttons: Buttons) {
+        if self.fault.is_some() {
+            return;
+        }
+        let bus = &mut self.cpu.bus;
+        if buttons != Buttons::NONE {
+            bus.interrupt_flag |= 0x10; // Joypad interrupt, as the frontend raises it
+        }
+        bus.joypad_state = !buttons.0;
+        let mut cycles = 0;
+        while cycles < FRAME_CYCLES {
+            let c = match self.cpu.step() {
+                Ok(c) => c,
+                Err(e) => {
+                    self.fault = Some(e);
+                    break;
+                }
+            };
+            let i = self.cpu.handle_interrupts();
+            cycles += c as u32 + i as u32;
+        }
+        // Nothing listens to these here
+        let bus = &mut self.cpu.bus;
+        bus.apu.sample_buffer.clear();
+        bus.apu.take_writes();
+        if let Some(log) = &mut bus.serial_log { log.clear(); }
+        if let Some(log) = &mut bus.debug_log { log.clear(); }
+        self.frame_count += 1;
+    }
+
+    fn observe(&self) -> Observation {
+        let palette = &self.cpu.ppu.palette;
+        Observation {
+            screen: self.cpu.ppu.frame_buffer.iter().map(|p| palette.iter().position(|c| c == p).unwrap_or(0) as u8).collect(),
+            ram: self.ram_addresses.iter().map(|&a| self.cpu.bus.read_byte(a)).collect(),
+            frame_count: self.frame_count,
+        }
+    }
+
+    fn snapshot(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        self.cpu.save_state(&mut w);
+        w.data
+    }
+
+    // SplitMix64: any seed, 0 included, gives a good sequence
+    fn next_random(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+}
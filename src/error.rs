@@ -0,0 +1,48 @@
+// error.rs
+// What the core reports instead of panicking: an opcode the CPU can't run, a
+// ROM that can't be loaded, or a bug in gb_rs itself. The frontend shows these to the player, so
+// Display is worded for them: what went wrong and what to try.
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CoreError {
+    // `pc` is the opcode's address; the CPU is left there
+    UnknownOpcode { opcode: u8, pc: u16 },
+    RomUnreadable { path: String, reason: String },
+    RomTooSmall { path: String, len: usize },
+    // A panic while running the game, caught by the frontend; `pc` is where
+    // the instruction that hit it started
+    Internal { message: String, pc: u16 },
+}
+
+impl fmt::Display for CoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CoreError::UnknownOpcode { opcode, pc } => {
+                write!(f, "The CPU hit opcode ${:02X} at ${:04X}, which gb_rs doesn't emulate yet.\n\nReset the game or load a save state.", opcode, pc)
+            }
+            CoreError::RomUnreadable { path, reason } => {
+                write!(f, "Couldn't read '{}': {}.\n\nCheck that the file exists and that you have permission to read it.", path, reason)
+            }
+            CoreError::RomTooSmall { path, len } => write!(
+                f,
+                "'{}' is too small to be a Game Boy ROM ({} bytes).\n\nThe file may be damaged or only partly downloaded; try dumping or downloading it again.",
+                path, len
+            ),
+            CoreError::Internal { message, pc } => write!(
+                f,
+                "gb_rs hit an internal error at ${:04X}: {}.\n\nThis is a bug in the emulator; please report it with the crash report. Reset the game or load a save state.",
+                pc, message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CoreError {}
+
+// Lets `?` pass it on from functions that report errors as text
+impl From<CoreError> for String {
+    fn from(e: CoreError) -> Self {
+        e.to_string()
+    }
+}
@@ -0,0 +1,98 @@
+// event_viewer.rs
+// Debug window in the style of Mesen's event viewer: the last frame's LCD
+// register writes as marks on a 456x154 grid of dots by scanlines, over the
+// PPU modes. Hovering a mark shows the write. While the window is open the
+// bus records the writes (see timeline.rs).
+use minifb::{MouseMode, Window, WindowOptions};
+
+use gb_rs::timeline::{RegWrite, Timeline, register_name};
+
+use crate::draw_text;
+
+const SCALE: usize = 2;
+const GRID_W: usize = 456 * SCALE;
+const GRID_H: usize = 154 * SCALE;
+const W: usize = GRID_W;
+const H: usize = GRID_H + 52;
+
+// Per register, 0xFF40 first
+const COLORS: [u32; 12] = [
+    0xFFFF5555, 0xFFFFAA55, 0xFF55FF55, 0xFF55FFFF, 0xFF888888, 0xFFFF55FF,
+    0xFFFFFFFF, 0xFFFFFF55, 0xFFAAAAFF, 0xFF5599FF, 0xFFAAFF55, 0xFFFF88AA,
+];
+
+// Background for a dot: OAM scan, drawing, HBlank or VBlank
+fn mode_color(line: usize, dot: usize) -> u32 {
+    if line >= 144 {
+        0xFF201828
+    } else if dot < 80 {
+        0xFF182838
+    } else if dot < 80 + 172 {
+        0xFF203020
+    } else {
+        0xFF181818
+    }
+}
+
+pub struct EventViewer {
+    window: Window,
+    buffer: Vec<u32>,
+}
+
+impl EventViewer {
+    pub fn new() -> Self {
+        let mut window = Window::new(
+            "Event Viewer",
+            W, H,
+            WindowOptions { resize: false, ..WindowOptions::default() },
+        ).unwrap();
+        window.limit_update_rate(None);
+        Self { window, buffer: vec![0; W * H] }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    pub fn update(&mut self, timeline: &Timeline) {
+        for y in 0..GRID_H {
+            for x in 0..GRID_W {
+                self.buffer[y * W + x] = mode_color(y / SCALE, x / SCALE);
+            }
+        }
+        for p in self.buffer[GRID_H * W..].iter_mut() { *p = 0xFF101010; }
+
+        let writes = &timeline.last_frame;
+        for w in writes {
+            let color = COLORS[(w.addr - 0xFF40) as usize];
+            let (x, y) = (w.dot as usize * SCALE, w.line as usize * SCALE);
+            for yy in y..(y + 3).min(GRID_H) {
+                for xx in x..(x + 3).min(GRID_W) {
+                    self.buffer[yy * W + xx] = color;
+                }
+            }
+        }
+
+        // Legend, then the write under the mouse (the closest within a few dots)
+        for (i, &color) in COLORS.iter().enumerate() {
+            draw_text(&mut self.buffer, W, register_name(0xFF40 + i as u16), 4 + i * 72, GRID_H + 6, color, 1);
+        }
+        let mouse = self.window.get_mouse_pos(MouseMode::Discard)
+            .map(|(mx, my)| (mx as usize / SCALE, my as usize / SCALE))
+            .filter(|&(dot, line)| dot < 456 && line < 154);
+        let status = match mouse {
+            Some((dot, line)) => {
+                let near = |w: &&RegWrite| w.dot.abs_diff(dot as u16);
+                match writes.iter().filter(|w| w.line as usize == line && near(w) <= 3).min_by_key(near) {
+                    Some(w) => format!("LY {:3}  DOT {:3}  {} = {:02X}", w.line, w.dot, register_name(w.addr), w.value),
+                    None => format!("LY {:3}  DOT {:3}", line, dot),
+                }
+            }
+            None => String::new(),
+        };
+        draw_text(&mut self.buffer, W, &status, 4, GRID_H + 22, 0xFFCCCCCC, 1);
+        draw_text(&mut self.buffer, W, &format!("{} writes last frame", writes.len()), 4, GRID_H + 36, 0xFF888888, 1);
+
+        self.window.update_with_buffer(&self.buffer, W, H).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+// events.rs
+// Event breakpoints: stop when something happens rather than when code at
+// an address runs. The CPU reports interrupts as it dispatches them, the PPU
+// its mode changes and new scanlines, and the MBC ROM bank switches; the MMU
+// keeps the ones a debugger asked for (see MMU::event).
+//
+// Written the same way the debuggers take them:
+//   int vblank|stat|timer|serial|joypad   the interrupt is dispatched
+//   mode N [line L]                       the PPU enters mode N (on line L)
+//   line L                                LY becomes L
+//   bank N                                ROM bank N gets mapped at 0x4000
+// Numbers are decimal except bank, which is hex like elsewhere.
+use std::fmt;
+
+const INTERRUPTS: [&str; 5] = ["vblank", "stat", "timer", "serial", "joypad"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    Interrupt(u8), // Bit in IF, 0 = VBlank
+    Mode { mode: u8, ly: u8 },
+    Line(u8),
+    RomBank(usize),
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Interrupt(bit) => write!(f, "{} interrupt", INTERRUPTS[*bit as usize]),
+            Event::Mode { mode, ly } => write!(f, "PPU mode {} on line {}", mode, ly),
+            Event::Line(ly) => write!(f, "line {}", ly),
+            Event::RomBank(bank) => write!(f, "ROM bank ${:02X}", bank),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventBreak {
+    Interrupt(u8),
+    Mode { mode: u8, ly: Option<u8> },
+    Line(u8),
+    RomBank(usize),
+}
+
+impl EventBreak {
+    pub fn matches(&self, event: &Event) -> bool {
+        match (*self, *event) {
+            (EventBreak::Interrupt(a), Event::Interrupt(b)) => a == b,
+            (EventBreak::Mode { mode, ly }, Event::Mode { mode: m, ly: l }) => mode == m && ly.is_none_or(|ly| ly == l),
+            (EventBreak::Line(a), Event::Line(b)) => a == b,
+            (EventBreak::RomBank(a), Event::RomBank(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let number = |word: Option<&&str>, max: u8| -> Result<u8, String> {
+            let word = word.ok_or("needs a number")?;
+            word.parse().ok().filter(|&n| n <= max).ok_or(format!("bad number '{}' (0-{})", word, max))
+        };
+        match words.as_slice() {
+            ["int", name] => INTERRUPTS
+                .iter()
+                .position(|i| i.eq_ignore_ascii_case(name))
+                .map(|bit| EventBreak::Interrupt(bit as u8))
+                .ok_or(format!("unknown interrupt '{}' ({})", name, INTERRUPTS.join(", "))),
+            ["mode", rest @ ..] => {
+                let mode = number(rest.first(), 3)?;
+                let ly = match rest.get(1..) {
+                    Some(["line", ly]) => Some(number(Some(ly), 153)?),
+                    Some([]) => None,
+                    _ => return Err("expected mode N or mode N line L".to_string()),
+                };
+                Ok(EventBreak::Mode { mode, ly })
+            }
+            ["line", ly] => Ok(EventBreak::Line(number(Some(ly), 153)?)),
+            ["bank", bank] => {
+                let hex = bank.trim_start_matches('$').trim_start_matches("0x");
+                usize::from_str_radix(hex, 16).map(EventBreak::RomBank).map_err(|_| format!("bad bank '{}'", bank))
+            }
+            _ => Err("expected int NAME, mode N [line L], line L or bank N".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for EventBreak {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventBreak::Interrupt(bit) => write!(f, "int {}", INTERRUPTS[*bit as usize]),
+            EventBreak::Mode { mode, ly: Some(ly) } => write!(f, "mode {} line {}", mode, ly),
+            EventBreak::Mode { mode, ly: None } => write!(f, "mode {}", mode),
+            EventBreak::Line(ly) => write!(f, "line {}", ly),
+            EventBreak::RomBank(bank) => write!(f, "bank {:02X}", bank),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// game_settings.rs
+// The pause menu's GAME SETTINGS page: overrides for the running game only
+// (see config::GameOverrides). Up/Down picks a setting, Left/Right changes
+// it, Enter or Esc goes back.
+use crate::config::GameOverrides;
+use crate::draw_text;
+use crate::menu::{NavInput, move_focus};
+use crate::mmu::Model;
+use crate::theme::{PALETTE_PRESETS, Theme};
+
+const ROWS: usize = 3;
+const MODELS: [Option<Model>; 3] = [None, Some(Model::Dmg), Some(Model::Cgb)];
+const SPEEDS: [Option<u32>; 5] = [None, Some(1), Some(2), Some(3), Some(4)];
+const SCALE: usize = 2;
+const LINE_H: usize = 8 * SCALE + 8;
+const WIDTH: usize = 30 * 8 * SCALE;
+
+pub struct GameSettingsMenu {
+    focus: usize,
+}
+
+// The entry after (or before) `current` in `options`
+fn cycle<T: PartialEq + Copy>(options: &[T], current: T, nav: NavInput) -> T {
+    let i = options.iter().position(|&o| o == current).unwrap_or(0);
+    let len = options.len();
+    options[if nav == NavInput::Left { (i + len - 1) % len } else { (i + 1) % len }]
+}
+
+impl GameSettingsMenu {
+    pub fn new() -> Self {
+        Self { focus: 0 }
+    }
+
+    // Edits `game` in place; true when the page is closed
+    pub fn nav(&mut self, nav: NavInput, game: &mut GameOverrides) -> bool {
+        match nav {
+            NavInput::Confirm | NavInput::Cancel => return true,
+            NavInput::Left | NavInput::Right => match self.focus {
+                0 => game.model = cycle(&MODELS, game.model, nav),
+                1 => {
+                    let palettes: Vec<Option<[u32; 4]>> =
+                        std::iter::once(None).chain(PALETTE_PRESETS.iter().map(|(_, p)| Some(*p))).collect();
+                    game.palette = cycle(&palettes, game.palette, nav);
+                }
+                _ => game.speed = cycle(&SPEEDS, game.speed, nav),
+            },
+            _ => self.focus = move_focus(self.focus, ROWS, nav),
+        }
+        false
+    }
+
+    // Centered in the `width` wide area starting at row `top`
+    pub fn draw(&self, buffer: &mut [u32], width: usize, top: usize, game: &GameOverrides, theme: &Theme) {
+        let model = match game.model {
+            None => "DEFAULT",
+            Some(Model::Dmg) => "DMG",
+            Some(Model::Cgb) => "CGB",
+        };
+        let palette = match game.palette {
+            None => "DEFAULT",
+            Some(p) => PALETTE_PRESETS.iter().find(|(_, preset)| *preset == p).map_or("CUSTOM", |(name, _)| name),
+        };
+        let speed = game.speed.map_or("DEFAULT".to_string(), |s| format!("{}X", s));
+        let rows = [
+            format!("MODEL    < {} >", model),
+            format!("PALETTE  < {} >", palette),
+            format!("SPEED    < {} >", speed),
+        ];
+
+        let x0 = (width - WIDTH) / 2;
+        let height = (ROWS + 2) * LINE_H + 8;
+        for y in top..top + height {
+            for p in &mut buffer[y * width + x0..y * width + x0 + WIDTH] {
+                *p = theme.panel;
+            }
+        }
+        draw_text(buffer, width, "GAME SETTINGS", x0 + 16, top + 8, theme.heading, SCALE);
+        for (i, row) in rows.iter().enumerate() {
+            let y = top + 4 + (i + 1) * LINE_H;
+            let focused = i == self.focus;
+            if focused {
+                for row_y in y..y + LINE_H {
+                    for p in &mut buffer[row_y * width + x0..row_y * width + x0 + WIDTH] {
+                        *p = theme.focus;
+                    }
+                }
+            }
+            draw_text(buffer, width, row, x0 + 16, y + 4, if focused { theme.accent } else { theme.text }, SCALE);
+        }
+        // The model can't change under a running game
+        draw_text(buffer, width, "MODEL APPLIES ON NEXT RESET", x0 + 16, top + 8 + (ROWS + 1) * LINE_H, theme.dim, 1);
+    }
+}
@@ -0,0 +1,320 @@
+// gdb.rs
+// GDB remote protocol server: `--gdb PORT` lets gdb or an IDE attach to the
+// running game (`target remote localhost:PORT`) to set breakpoints, step,
+// and read or write registers and memory.
+//
+// The game pauses when a client attaches and whenever it stops the target;
+// it runs while the client has it continuing. Registers are sent as af, bc,
+// de, hl, sp, pc, 16 bits each and little-endian, the first six registers
+// of gdb's z80 layout. Memory goes through the bus like the game's own
+// accesses, so ROM shows the banks currently mapped.
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read};
+use std::net::{TcpListener, TcpStream};
+
+use gdbstub::arch::{Arch, Registers};
+use gdbstub::common::Signal;
+use gdbstub::stub::state_machine::GdbStubStateMachine;
+use gdbstub::stub::{GdbStub, GdbStubError, SingleThreadStopReason};
+use gdbstub::target::ext::base::BaseOps;
+use gdbstub::target::ext::base::singlethread::{SingleThreadBase, SingleThreadResume, SingleThreadResumeOps, SingleThreadSingleStep, SingleThreadSingleStepOps};
+use gdbstub::target::ext::breakpoints::{Breakpoints, BreakpointsOps, SwBreakpoint, SwBreakpointOps};
+use gdbstub::target::{Target, TargetResult};
+
+use gb_rs::breakpoint::Breakpoint;
+
+use crate::EmulatorState;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <architecture>z80</architecture>
+  <feature name="org.gnu.gdb.z80.cpu">
+    <reg name="af" bitsize="16" type="int"/>
+    <reg name="bc" bitsize="16" type="int"/>
+    <reg name="de" bitsize="16" type="data_ptr"/>
+    <reg name="hl" bitsize="16" type="data_ptr"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>"#;
+
+pub enum Sm83 {}
+
+impl Arch for Sm83 {
+    type Usize = u16;
+    type Registers = Sm83Registers;
+    type BreakpointKind = usize;
+    type RegId = ();
+
+    fn target_description_xml() -> Option<&'static str> {
+        Some(TARGET_XML)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sm83Registers {
+    pub af: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub sp: u16,
+    pub pc: u16,
+}
+
+impl Registers for Sm83Registers {
+    type ProgramCounter = u16;
+
+    fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    fn gdb_serialize(&self, mut write_byte: impl FnMut(Option<u8>)) {
+        for reg in [self.af, self.bc, self.de, self.hl, self.sp, self.pc] {
+            reg.to_le_bytes().into_iter().for_each(|b| write_byte(Some(b)));
+        }
+    }
+
+    fn gdb_deserialize(&mut self, bytes: &[u8]) -> Result<(), ()> {
+        if bytes.len() != 12 {
+            return Err(());
+        }
+        let mut regs = bytes.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+        for reg in [&mut self.af, &mut self.bc, &mut self.de, &mut self.hl, &mut self.sp, &mut self.pc] {
+            *reg = regs.next().unwrap();
+        }
+        Ok(())
+    }
+}
+
+impl Target for EmulatorState {
+    type Arch = Sm83;
+    type Error = &'static str;
+
+    fn base_ops(&mut self) -> BaseOps<'_, Sm83, Self::Error> {
+        BaseOps::SingleThread(self)
+    }
+
+    fn support_breakpoints(&mut self) -> Option<BreakpointsOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl SingleThreadBase for EmulatorState {
+    fn read_registers(&mut self, regs: &mut Sm83Registers) -> TargetResult<(), Self> {
+        let r = &self.cpu.registers;
+        *regs = Sm83Registers {
+            af: u16::from_be_bytes([r.a, r.f]),
+            bc: u16::from_be_bytes([r.b, r.c]),
+            de: u16::from_be_bytes([r.d, r.e]),
+            hl: u16::from_be_bytes([r.h, r.l]),
+            sp: r.sp,
+            pc: r.pc,
+        };
+        Ok(())
+    }
+
+    fn write_registers(&mut self, regs: &Sm83Registers) -> TargetResult<(), Self> {
+        let r = &mut self.cpu.registers;
+        [r.a, r.f] = regs.af.to_be_bytes();
+        r.f &= 0xF0; // The low nibble of F is always 0
+        [r.b, r.c] = regs.bc.to_be_bytes();
+        [r.d, r.e] = regs.de.to_be_bytes();
+        [r.h, r.l] = regs.hl.to_be_bytes();
+        r.sp = regs.sp;
+        r.pc = regs.pc;
+        Ok(())
+    }
+
+    fn read_addrs(&mut self, start_addr: u16, data: &mut [u8]) -> TargetResult<usize, Self> {
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = self.cpu.bus.read_byte(start_addr.wrapping_add(i as u16));
+        }
+        Ok(data.len())
+    }
+
+    fn write_addrs(&mut self, start_addr: u16, data: &[u8]) -> TargetResult<(), Self> {
+        for (i, &byte) in data.iter().enumerate() {
+            self.cpu.bus.write_byte(start_addr.wrapping_add(i as u16), byte);
+        }
+        Ok(())
+    }
+
+    fn support_resume(&mut self) -> Option<SingleThreadResumeOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl SingleThreadResume for EmulatorState {
+    fn resume(&mut self, signal: Option<Signal>) -> Result<(), Self::Error> {
+        if signal.is_some() {
+            return Err("the Game Boy has no signals to deliver");
+        }
+        self.single_step = false;
+        Ok(())
+    }
+
+    fn support_single_step(&mut self) -> Option<SingleThreadSingleStepOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl SingleThreadSingleStep for EmulatorState {
+    fn step(&mut self, signal: Option<Signal>) -> Result<(), Self::Error> {
+        if signal.is_some() {
+            return Err("the Game Boy has no signals to deliver");
+        }
+        self.single_step = true;
+        Ok(())
+    }
+}
+
+impl Breakpoints for EmulatorState {
+    fn support_sw_breakpoint(&mut self) -> Option<SwBreakpointOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl SwBreakpoint for EmulatorState {
+    fn add_sw_breakpoint(&mut self, addr: u16, _kind: usize) -> TargetResult<bool, Self> {
+        if !self.breakpoints.iter().any(|b| b.addr == addr) {
+            self.breakpoints.push(Breakpoint::new(addr));
+        }
+        Ok(true)
+    }
+
+    fn remove_sw_breakpoint(&mut self, addr: u16, _kind: usize) -> TargetResult<bool, Self> {
+        let Some(i) = self.breakpoints.iter().position(|b| b.addr == addr) else { return Ok(false) };
+        self.breakpoints.remove(i);
+        Ok(true)
+    }
+}
+
+type Session = GdbStubStateMachine<'static, EmulatorState, TcpStream>;
+type SessionError = GdbStubError<&'static str, io::Error>;
+
+pub struct GdbServer {
+    listener: TcpListener,
+    session: Option<Session>, // The attached client, one at a time
+    incoming: VecDeque<u8>,
+    pub paused: bool,
+}
+
+impl GdbServer {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        println!("✓ gdb server: target remote localhost:{}", port);
+        Ok(Self { listener, session: None, incoming: VecDeque::new(), paused: false })
+    }
+
+    // Whether a client is attached. Its breakpoints go over to whatever
+    // game is loaded next, since gdb still thinks they're set.
+    pub fn attached(&self) -> bool {
+        self.session.is_some()
+    }
+
+    // Once per frame: takes a client, answers its packets and tells it when
+    // the game stopped. Returns a message for the OSD when a client comes
+    // or goes.
+    pub fn update(&mut self, emu: &mut EmulatorState) -> Option<String> {
+        let mut message = None;
+        if self.session.is_none() {
+            let Ok((stream, addr)) = self.listener.accept() else { return None };
+            let _ = stream.set_nonblocking(false);
+            let _ = stream.set_nodelay(true);
+            emu.single_step = false;
+            match GdbStub::new(stream).run_state_machine(emu) {
+                Ok(session) => {
+                    self.session = Some(session);
+                    self.incoming.clear();
+                    message = Some(format!("gdb: {} attached", addr));
+                }
+                Err(e) => {
+                    eprintln!("✗ gdb: {}", e);
+                    return None;
+                }
+            }
+        }
+
+        let mut session = self.session.take()?;
+        if let Err(e) = self.receive(&mut session) {
+            eprintln!("✗ gdb: {}", e);
+            self.paused = false;
+            return Some("gdb: connection lost".to_string());
+        }
+        match self.drive(session, emu) {
+            Ok(Some(session)) => {
+                self.paused = !matches!(session, GdbStubStateMachine::Running(_));
+                self.session = Some(session);
+                message
+            }
+            Ok(None) => {
+                self.paused = false;
+                Some("gdb: detached".to_string())
+            }
+            Err(e) => {
+                eprintln!("✗ gdb: {}", e);
+                self.paused = false;
+                Some("gdb: connection lost".to_string())
+            }
+        }
+    }
+
+    // Whatever the client sent since the last frame, without waiting for more
+    fn receive(&mut self, session: &mut Session) -> io::Result<()> {
+        let stream = match session {
+            GdbStubStateMachine::Idle(s) => s.borrow_conn(),
+            GdbStubStateMachine::Running(s) => s.borrow_conn(),
+            GdbStubStateMachine::CtrlCInterrupt(s) => s.borrow_conn(),
+            GdbStubStateMachine::Disconnected(s) => s.borrow_conn(),
+        };
+        stream.set_nonblocking(true)?;
+        let mut buf = [0; 1024];
+        let result = loop {
+            match stream.read(&mut buf) {
+                Ok(0) => break Err(io::Error::from(ErrorKind::UnexpectedEof)),
+                Ok(n) => self.incoming.extend(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        // Replies are written blocking, they're small
+        stream.set_nonblocking(false)?;
+        result
+    }
+
+    // Feeds the client's bytes through the stub, running a single step and
+    // reporting why a continuing game stopped. None once the client is gone.
+    fn drive(&mut self, mut session: Session, emu: &mut EmulatorState) -> Result<Option<Session>, SessionError> {
+        loop {
+            session = match session {
+                GdbStubStateMachine::Idle(s) => match self.incoming.pop_front() {
+                    Some(byte) => s.incoming_data(emu, byte)?,
+                    None => return Ok(Some(GdbStubStateMachine::Idle(s))),
+                },
+                GdbStubStateMachine::Running(s) => {
+                    if let Some(byte) = self.incoming.pop_front() {
+                        s.incoming_data(emu, byte)?
+                    } else if emu.single_step {
+                        emu.single_step = false;
+                        if let Err(e) = emu.step() {
+                            emu.fault = Some(e);
+                        }
+                        s.report_stop(emu, SingleThreadStopReason::DoneStep)?
+                    } else if emu.fault.is_some() || emu.cpu.locked {
+                        s.report_stop(emu, SingleThreadStopReason::Signal(Signal::SIGILL))?
+                    } else if emu.break_hit.is_some() {
+                        s.report_stop(emu, SingleThreadStopReason::SwBreak(()))?
+                    } else {
+                        return Ok(Some(GdbStubStateMachine::Running(s)));
+                    }
+                }
+                GdbStubStateMachine::CtrlCInterrupt(s) => {
+                    s.interrupt_handled(emu, Some(SingleThreadStopReason::Signal(Signal::SIGINT)))?
+                }
+                GdbStubStateMachine::Disconnected(_) => return Ok(None),
+            };
+        }
+    }
+}
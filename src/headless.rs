@@ -0,0 +1,176 @@
+// headless.rs
+// Runs a ROM for a fixed number of frames with no window, audio or input,
+// for scripted regression checks: the framebuffer hash only changes when the
+// emulation does. Battery RAM is neither loaded nor written, so repeated runs
+// start from the same power-on state. Also home to the movie replay check
+// that catches nondeterminism (host time, ordering, state gaps).
+use crate::EmulatorState;
+use crate::cli::Args;
+use crate::mmu::{Model, RamFill};
+use crate::movie::Movie;
+use gb_rs::coverage::Coverage;
+use gb_rs::uninit::UninitReads;
+
+// Determinism check: state round trips happen this often (in frames)
+const ROUNDTRIP_EVERY: u64 = 600;
+
+// FNV-1a, 64 bit: tiny and stable across platforms and Rust versions
+fn fnv1a(mut hash: u64, bytes: impl Iterator<Item = u8>) -> u64 {
+    for b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+pub fn hash_frame(frame: &[u32]) -> u64 {
+    fnv1a(0xcbf29ce484222325, frame.iter().flat_map(|p| p.to_le_bytes()))
+}
+
+// A fresh machine for headless runs: no battery RAM, serial kept in memory
+fn power_on(rom: &str, model: Model, (fill, seed): (RamFill, u64)) -> Result<EmulatorState, String> {
+    // The default palette, not the configured one, so hashes are comparable
+    let mut emu = EmulatorState::load_rom(rom, model, crate::ppu::PPU::new().palette)?;
+    emu.detach_battery();
+    emu.cpu.bus.fill_ram(fill, seed);
+    emu.cpu.bus.serial_log = Some(Vec::new());
+    Ok(emu)
+}
+
+pub fn run(args: &Args) -> i32 {
+    let Some(rom) = &args.rom else { return 2 };
+
+    let mut emu = match power_on(rom, args.model.unwrap_or(Model::Dmg), args.ram_fill()) {
+        Ok(emu) => emu,
+        Err(e) => {
+            eprintln!("✗ {}", e);
+            return 1;
+        }
+    };
+    if let Some(path) = &args.doctor_log
+        && let Err(e) = emu.cpu.start_doctor_log(path)
+    {
+        eprintln!("✗ Couldn't create '{}': {}", path, e);
+        return 1;
+    }
+    match args.instr_trace() {
+        Some(Ok(trace)) => emu.cpu.start_trace(trace),
+        Some(Err(e)) => {
+            eprintln!("✗ Couldn't create the trace file: {}", e);
+            return 1;
+        }
+        None => {}
+    }
+    if let Some(path) = &args.coverage {
+        emu.cpu.start_coverage(Coverage::new(path, emu.cpu.bus.rom.len()));
+    }
+    if let Some(profiler) = args.profiler(rom) {
+        emu.cpu.start_profile(profiler);
+    }
+    if args.uninit_reads {
+        emu.cpu.bus.uninit = Some(UninitReads::default());
+    }
+    for _ in 0..args.frames.unwrap_or(600) {
+        emu.run_frame(1, &mut || 0xFF);
+        emu.cpu.bus.apu.sample_buffer.clear();
+    }
+    // The game crashed along the way: what it ran up to the crash
+    let crash = emu.fault.as_ref().map(|e| e.to_string()).or_else(|| emu.cpu.lock_message());
+    if let Some(message) = &crash {
+        eprintln!("✗ {}\n{}", message, emu.write_crash_report(message));
+    }
+
+    if args.serial {
+        let log = emu.cpu.bus.serial_log.as_deref().unwrap_or_default();
+        println!("{}", String::from_utf8_lossy(log));
+    }
+    if args.hash {
+        println!("{:016x}  {}", hash_frame(&emu.cpu.ppu.frame_buffer), rom);
+    }
+    emu.cpu.stop_doctor_log();
+    if let Some(Err(e)) = emu.cpu.stop_trace() {
+        eprintln!("✗ Couldn't write the trace: {}", e);
+    }
+    if let Some(Err(e)) = emu.cpu.stop_coverage() {
+        eprintln!("✗ Couldn't write the coverage report: {}", e);
+    }
+    if let Some(Err(e)) = emu.cpu.stop_profile() {
+        eprintln!("✗ Couldn't write the profile: {}", e);
+    }
+    if let Some(uninit) = &emu.cpu.bus.uninit {
+        match uninit.count() {
+            0 => println!("✓ No uninitialized RAM reads"),
+            n => println!("✗ {} uninitialized RAM read(s)", n),
+        }
+    }
+    // The reports above still get written, but a crash fails the run
+    if crash.is_some() { 1 } else { 0 }
+}
+
+// Plays the movie and hashes every frame (picture and audio). With
+// `roundtrip`, the machine is saved and restored into a brand new one every
+// ROUNDTRIP_EVERY frames along the way.
+fn replay(rom: &str, model: Model, fill: (RamFill, u64), log: &str, roundtrip: bool) -> Result<Vec<u64>, String> {
+    let mut emu = power_on(rom, model, fill)?;
+    emu.cpu.bus.apu.capture_samples = true; // The samples are hashed too
+    emu.start_movie(Movie::from_input_log(log)?);
+    let mut hashes = Vec::new();
+    while emu.movie.is_some() {
+        emu.run_frame(1, &mut || 0xFF);
+        let samples = emu.cpu.bus.apu.sample_buffer.drain(..).flat_map(|s| s.to_le_bytes());
+        hashes.push(fnv1a(hash_frame(&emu.cpu.ppu.frame_buffer), samples));
+
+        if roundtrip && emu.frame_count.is_multiple_of(ROUNDTRIP_EVERY) {
+            let state = emu.save_state();
+            let mut fresh = power_on(rom, model, fill)?;
+            fresh.cpu.bus.apu.capture_samples = true;
+            fresh.load_state(&state).map_err(|e| format!("state round trip failed: {}", e))?;
+            fresh.movie = emu.movie.take();
+            fresh.frame_count = emu.frame_count;
+            emu = fresh;
+        }
+    }
+    Ok(hashes)
+}
+
+// Replays an input log twice, then once more with save-state round trips,
+// and reports the first frame where a run differs from the first one
+pub fn verify_movie(args: &Args) -> i32 {
+    let (Some(rom), Some(movie)) = (&args.rom, &args.verify_movie) else { return 2 };
+    let log = match std::fs::read_to_string(movie) {
+        Ok(log) => log,
+        Err(e) => {
+            eprintln!("✗ Can't read movie '{}': {}", movie, e);
+            return 1;
+        }
+    };
+    let model = args.model.unwrap_or(Model::Dmg);
+
+    let runs = [("replay", false), ("replay with state round trips", true)];
+    let reference = match replay(rom, model, args.ram_fill(), &log, false) {
+        Ok(hashes) => hashes,
+        Err(e) => {
+            eprintln!("✗ {}", e);
+            return 1;
+        }
+    };
+    for (name, roundtrip) in runs {
+        let hashes = match replay(rom, model, args.ram_fill(), &log, roundtrip) {
+            Ok(hashes) => hashes,
+            Err(e) => {
+                eprintln!("✗ {}: {}", name, e);
+                return 1;
+            }
+        };
+        if let Some(frame) = reference.iter().zip(&hashes).position(|(a, b)| a != b) {
+            eprintln!("✗ {} diverged at frame {}", name, frame + 1);
+            return 1;
+        }
+        if hashes.len() != reference.len() {
+            eprintln!("✗ {} ran {} frames instead of {}", name, hashes.len(), reference.len());
+            return 1;
+        }
+    }
+    println!("✓ Deterministic over {} frames (state round trip every {} frames)", reference.len(), ROUNDTRIP_EVERY);
+    0
+}
@@ -0,0 +1,62 @@
+// history.rs
+// The last instructions the CPU ran, always kept, so a crash (an unknown
+// opcode or a lock-up) can be reported with the path that led to it rather
+// than just the address it ended at.
+use std::fmt::Write;
+
+use crate::cpu::Registers;
+
+pub const HISTORY_LEN: usize = 200;
+
+#[derive(Clone, Copy, Default)]
+pub struct Executed {
+    pub bank: usize, // ROM bank the code ran from, 0 outside 0x4000-0x7FFF
+    pub opcode: u8,
+    pub registers: Registers, // Before it ran, PC on the opcode
+}
+
+pub struct History {
+    entries: [Executed; HISTORY_LEN],
+    len: usize,
+    next: usize, // Slot the next instruction goes in
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self { entries: [Executed::default(); HISTORY_LEN], len: 0, next: 0 }
+    }
+}
+
+impl History {
+    pub fn record(&mut self, executed: Executed) {
+        self.entries[self.next] = executed;
+        self.next = (self.next + 1) % HISTORY_LEN;
+        self.len = (self.len + 1).min(HISTORY_LEN);
+    }
+
+    // Oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Executed> {
+        let start = (self.next + HISTORY_LEN - self.len) % HISTORY_LEN;
+        (0..self.len).map(move |i| &self.entries[(start + i) % HISTORY_LEN])
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+
+    // One line per instruction, oldest first, the last one being where it
+    // stopped
+    pub fn dump(&self) -> String {
+        let mut out = format!("Last {} instructions, oldest first:\n", self.len);
+        for e in self.iter() {
+            let r = &e.registers;
+            let _ = writeln!(
+                out,
+                "  {:03X}:{:04X}  {:02X}  A:{:02X} F:{:02X} BC:{:02X}{:02X} DE:{:02X}{:02X} HL:{:02X}{:02X} SP:{:04X}",
+                e.bank, r.pc, e.opcode, r.a, r.f, r.b, r.c, r.d, r.e, r.h, r.l, r.sp
+            );
+        }
+        out
+    }
+}
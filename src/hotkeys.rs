@@ -0,0 +1,255 @@
+// hotkeys.rs
+// Emulator (frontend) actions bound to keyboard keys. Kept apart from
+// InputMapping, which only covers the eight Game Boy buttons.
+//
+// There's no fullscreen action: minifb can't switch a window to fullscreen
+// or tell how big the screen is, so the window's own maximize button (it
+// scales to fit) is the way to fill the screen.
+use minifb::{Key, KeyRepeat, Window};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hotkey {
+    SaveState,
+    LoadState,
+    StatePicker,
+    UndoLoad,
+    UndoSave,
+    FastForward, // Held or toggled, see Config::fast_forward_mode
+    Pause,
+    Screenshot,
+    RomInfo,
+    Palette, // Next of theme::PALETTE_PRESETS
+    Reset, // Hard: power cycle, keeping battery RAM
+    SoftReset, // Back to the entry point, memory untouched
+    RecordVgm,
+    Oscilloscope,
+    ApuPanel,
+    StereoMode,
+    FastForwardAudio,
+    SyncMode,
+    LowLatency,
+    NextPad,
+    RecordMovie,
+    PlayMovie,
+    LinkCable, // Second Game Boy on the link port
+    RecordMacro, // Start/stop recording an input macro, then bind it to a key
+    DoctorLog, // Start/stop a Gameboy Doctor trace
+    Debugger, // Open/close the debugger on the terminal (see repl.rs)
+    EventViewer,
+    MemoryEditor,
+    TilemapViewer,
+    RamWatch, // Show/hide the game's RAM watches (see ram_watch.rs)
+    CoreDump, // Write all memory to files (see memdump.rs)
+    ExportGraphics, // Tiles, BG map and sprites as PNGs
+    PerfOverlay, // FPS, host frame time and audio queue (see perf_overlay.rs)
+}
+
+impl Hotkey {
+    pub const ALL: [Hotkey; 33] = [
+        Hotkey::SaveState, Hotkey::LoadState, Hotkey::StatePicker, Hotkey::UndoLoad, Hotkey::UndoSave,
+        Hotkey::FastForward, Hotkey::Pause, Hotkey::Screenshot, Hotkey::RomInfo, Hotkey::Palette, Hotkey::Reset, Hotkey::SoftReset,
+        Hotkey::RecordVgm, Hotkey::Oscilloscope, Hotkey::ApuPanel, Hotkey::StereoMode,
+        Hotkey::FastForwardAudio, Hotkey::SyncMode, Hotkey::LowLatency, Hotkey::NextPad,
+        Hotkey::RecordMovie, Hotkey::PlayMovie, Hotkey::LinkCable, Hotkey::RecordMacro,
+        Hotkey::DoctorLog, Hotkey::Debugger, Hotkey::EventViewer, Hotkey::MemoryEditor,
+        Hotkey::TilemapViewer, Hotkey::RamWatch, Hotkey::CoreDump, Hotkey::ExportGraphics,
+        Hotkey::PerfOverlay,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Hotkey::SaveState => "SAVE STATE",
+            Hotkey::LoadState => "LOAD STATE",
+            Hotkey::StatePicker => "STATE LIST",
+            Hotkey::UndoLoad => "UNDO LOAD",
+            Hotkey::UndoSave => "UNDO SAVE",
+            Hotkey::FastForward => "FAST FWD",
+            Hotkey::Pause => "PAUSE",
+            Hotkey::Screenshot => "SCREENSHOT",
+            Hotkey::RomInfo => "ROM INFO",
+            Hotkey::Palette => "PALETTE",
+            Hotkey::Reset => "HARD RESET",
+            Hotkey::SoftReset => "SOFT RESET",
+            Hotkey::RecordVgm => "REC VGM",
+            Hotkey::Oscilloscope => "SCOPE",
+            Hotkey::ApuPanel => "APU PANEL",
+            Hotkey::StereoMode => "STEREO",
+            Hotkey::FastForwardAudio => "FF AUDIO",
+            Hotkey::SyncMode => "SYNC MODE",
+            Hotkey::LowLatency => "LOW LAT",
+            Hotkey::NextPad => "NEXT PAD",
+            Hotkey::RecordMovie => "REC MOVIE",
+            Hotkey::PlayMovie => "PLAY MOVIE",
+            Hotkey::LinkCable => "LINK CABLE",
+            Hotkey::RecordMacro => "REC MACRO",
+            Hotkey::DoctorLog => "DOCTOR LOG",
+            Hotkey::Debugger => "DEBUGGER",
+            Hotkey::EventViewer => "EVENTS",
+            Hotkey::MemoryEditor => "MEMORY",
+            Hotkey::TilemapViewer => "TILE MAPS",
+            Hotkey::RamWatch => "RAM WATCH",
+            Hotkey::CoreDump => "CORE DUMP",
+            Hotkey::ExportGraphics => "EXPORT GFX",
+            Hotkey::PerfOverlay => "PERF",
+        }
+    }
+}
+
+// Save-state slots 1-10 on the number row (Shift+number saves)
+pub const SLOT_KEYS: [Key; 10] = [
+    Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5,
+    Key::Key6, Key::Key7, Key::Key8, Key::Key9, Key::Key0,
+];
+
+// How a speed control key behaves: active while held, or flipped on each press.
+// Fast-forward is the only speed control so far (Config::fast_forward_mode);
+// there's no slow motion or rewind yet to give one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HoldMode {
+    Hold,
+    Toggle,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotkeyMapping {
+    #[serde(with = "crate::config::key_serde")] pub save_state: Key,
+    #[serde(with = "crate::config::key_serde")] pub load_state: Key,
+    #[serde(with = "crate::config::key_serde")] pub state_picker: Key,
+    #[serde(with = "crate::config::key_serde")] pub undo_load: Key,
+    #[serde(with = "crate::config::key_serde")] pub undo_save: Key,
+    #[serde(with = "crate::config::key_serde")] pub fast_forward: Key,
+    #[serde(with = "crate::config::key_serde")] pub pause: Key,
+    #[serde(with = "crate::config::key_serde")] pub screenshot: Key,
+    #[serde(with = "crate::config::key_serde")] pub rom_info: Key,
+    #[serde(with = "crate::config::key_serde")] pub palette: Key,
+    #[serde(with = "crate::config::key_serde")] pub reset: Key,
+    #[serde(with = "crate::config::key_serde")] pub soft_reset: Key,
+    #[serde(with = "crate::config::key_serde")] pub record_vgm: Key,
+    #[serde(with = "crate::config::key_serde")] pub oscilloscope: Key,
+    #[serde(with = "crate::config::key_serde")] pub apu_panel: Key,
+    #[serde(with = "crate::config::key_serde")] pub stereo_mode: Key,
+    #[serde(with = "crate::config::key_serde")] pub fast_forward_audio: Key,
+    #[serde(with = "crate::config::key_serde")] pub sync_mode: Key,
+    #[serde(with = "crate::config::key_serde")] pub low_latency: Key,
+    #[serde(with = "crate::config::key_serde")] pub next_pad: Key,
+    #[serde(with = "crate::config::key_serde")] pub record_movie: Key,
+    #[serde(with = "crate::config::key_serde")] pub play_movie: Key,
+    #[serde(with = "crate::config::key_serde")] pub link_cable: Key,
+    #[serde(with = "crate::config::key_serde")] pub record_macro: Key,
+    #[serde(with = "crate::config::key_serde")] pub doctor_log: Key,
+    #[serde(with = "crate::config::key_serde")] pub debugger: Key,
+    #[serde(with = "crate::config::key_serde")] pub event_viewer: Key,
+    #[serde(with = "crate::config::key_serde")] pub memory_editor: Key,
+    #[serde(with = "crate::config::key_serde")] pub tilemap_viewer: Key,
+    #[serde(with = "crate::config::key_serde")] pub ram_watch: Key,
+    #[serde(with = "crate::config::key_serde")] pub core_dump: Key,
+    #[serde(with = "crate::config::key_serde")] pub export_graphics: Key,
+    #[serde(with = "crate::config::key_serde")] pub perf_overlay: Key,
+}
+
+impl Default for HotkeyMapping {
+    fn default() -> Self {
+        Self {
+            save_state: Key::F1,
+            load_state: Key::F2,
+            state_picker: Key::Backquote,
+            undo_load: Key::Minus,
+            undo_save: Key::Equal,
+            fast_forward: Key::Tab,
+            pause: Key::P,
+            screenshot: Key::F12,
+            rom_info: Key::Home,
+            palette: Key::PageDown,
+            reset: Key::F3,
+            soft_reset: Key::Backspace,
+            record_vgm: Key::F9,
+            oscilloscope: Key::F10,
+            apu_panel: Key::F11,
+            stereo_mode: Key::F8,
+            fast_forward_audio: Key::F7,
+            sync_mode: Key::F6,
+            low_latency: Key::F5,
+            next_pad: Key::F4,
+            record_movie: Key::R,
+            play_movie: Key::M,
+            link_cable: Key::Insert,
+            record_macro: Key::End,
+            doctor_log: Key::PageUp,
+            debugger: Key::Delete,
+            event_viewer: Key::LeftBracket,
+            memory_editor: Key::RightBracket,
+            tilemap_viewer: Key::Backslash,
+            ram_watch: Key::Apostrophe,
+            core_dump: Key::ScrollLock,
+            export_graphics: Key::Pause,
+            perf_overlay: Key::F,
+        }
+    }
+}
+
+impl HotkeyMapping {
+    pub fn key_mut(&mut self, hotkey: Hotkey) -> &mut Key {
+        match hotkey {
+            Hotkey::SaveState => &mut self.save_state,
+            Hotkey::LoadState => &mut self.load_state,
+            Hotkey::StatePicker => &mut self.state_picker,
+            Hotkey::UndoLoad => &mut self.undo_load,
+            Hotkey::UndoSave => &mut self.undo_save,
+            Hotkey::FastForward => &mut self.fast_forward,
+            Hotkey::Pause => &mut self.pause,
+            Hotkey::Screenshot => &mut self.screenshot,
+            Hotkey::RomInfo => &mut self.rom_info,
+            Hotkey::Palette => &mut self.palette,
+            Hotkey::Reset => &mut self.reset,
+            Hotkey::SoftReset => &mut self.soft_reset,
+            Hotkey::RecordVgm => &mut self.record_vgm,
+            Hotkey::Oscilloscope => &mut self.oscilloscope,
+            Hotkey::ApuPanel => &mut self.apu_panel,
+            Hotkey::StereoMode => &mut self.stereo_mode,
+            Hotkey::FastForwardAudio => &mut self.fast_forward_audio,
+            Hotkey::SyncMode => &mut self.sync_mode,
+            Hotkey::LowLatency => &mut self.low_latency,
+            Hotkey::NextPad => &mut self.next_pad,
+            Hotkey::RecordMovie => &mut self.record_movie,
+            Hotkey::PlayMovie => &mut self.play_movie,
+            Hotkey::LinkCable => &mut self.link_cable,
+            Hotkey::RecordMacro => &mut self.record_macro,
+            Hotkey::DoctorLog => &mut self.doctor_log,
+            Hotkey::Debugger => &mut self.debugger,
+            Hotkey::EventViewer => &mut self.event_viewer,
+            Hotkey::MemoryEditor => &mut self.memory_editor,
+            Hotkey::TilemapViewer => &mut self.tilemap_viewer,
+            Hotkey::RamWatch => &mut self.ram_watch,
+            Hotkey::CoreDump => &mut self.core_dump,
+            Hotkey::ExportGraphics => &mut self.export_graphics,
+            Hotkey::PerfOverlay => &mut self.perf_overlay,
+        }
+    }
+
+    pub fn key(&self, hotkey: Hotkey) -> Key {
+        let mut copy = *self;
+        *copy.key_mut(hotkey)
+    }
+
+    pub fn pressed(&self, window: &Window, hotkey: Hotkey) -> bool {
+        window.is_key_pressed(self.key(hotkey), KeyRepeat::No)
+    }
+
+    pub fn down(&self, window: &Window, hotkey: Hotkey) -> bool {
+        window.is_key_down(self.key(hotkey))
+    }
+
+    // Whether a hold/toggle control is on. `latched` keeps the toggle state
+    // between frames.
+    pub fn active(&self, window: &Window, hotkey: Hotkey, mode: HoldMode, latched: &mut bool) -> bool {
+        match mode {
+            HoldMode::Hold => self.down(window, hotkey),
+            HoldMode::Toggle => {
+                if self.pressed(window, hotkey) { *latched = !*latched; }
+                *latched
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// instr_trace.rs
+// Instruction trace: one line per executed instruction with its ROM bank,
+// the registers and the cycle count, for following a game that hangs or
+// goes wrong. Filters keep the log to the code in question, and ring mode
+// keeps only the last lines in memory, written out when the trace stops,
+// so a long run doesn't leave a log gigabytes long.
+//
+//   cycle        bank:pc   instruction       registers before it
+//       1234567  001:4A3C  LD A,($FF44)      A:01 F:Z-HC BC:0013 DE:00D8 HL:014D SP:FFFE
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+#[derive(Clone, Debug, Default)]
+pub struct TraceFilter {
+    pub pc: Option<(u16, u16)>, // Inclusive range
+    pub bank: Option<usize>,    // ROM bank the code runs from, 0 for the fixed one
+    pub opcodes: Vec<u8>,       // First byte of the instruction; empty for all
+}
+
+impl TraceFilter {
+    pub fn matches(&self, pc: u16, bank: usize, opcode: u8) -> bool {
+        self.pc.is_none_or(|(start, end)| (start..=end).contains(&pc))
+            && self.bank.is_none_or(|b| b == bank)
+            && (self.opcodes.is_empty() || self.opcodes.contains(&opcode))
+    }
+}
+
+enum Sink {
+    File(BufWriter<File>),
+    Ring(VecDeque<String>, usize), // Lines and how many to keep
+}
+
+pub struct InstrTrace {
+    pub filter: TraceFilter,
+    path: String,
+    sink: Sink,
+}
+
+impl InstrTrace {
+    // Every matching instruction, written as it runs
+    pub fn to_file(path: &str, filter: TraceFilter) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(Self { filter, path: path.to_string(), sink: Sink::File(file) })
+    }
+
+    // Only the last `lines` matching instructions, written by finish()
+    pub fn ring(path: &str, filter: TraceFilter, lines: usize) -> Self {
+        let lines = lines.max(1);
+        Self { filter, path: path.to_string(), sink: Sink::Ring(VecDeque::with_capacity(lines), lines) }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn log(&mut self, line: String) -> io::Result<()> {
+        match &mut self.sink {
+            Sink::File(file) => writeln!(file, "{}", line),
+            Sink::Ring(lines, capacity) => {
+                if lines.len() == *capacity {
+                    lines.pop_front();
+                }
+                lines.push_back(line);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        let mut file = match self.sink {
+            Sink::File(file) => file,
+            Sink::Ring(lines, _) => {
+                let mut file = BufWriter::new(File::create(&self.path)?);
+                for line in lines {
+                    writeln!(file, "{}", line)?;
+                }
+                file
+            }
+        };
+        file.flush()
+    }
+}
@@ -0,0 +1,27 @@
+// lib.rs
+// The emulation core (CPU, memory, PPU, APU and save states). The frontend in
+// main.rs builds on it; fuzz targets and benchmarks drive it directly, and
+// Environment wraps it for reinforcement learning.
+pub mod cpu;
+pub mod error;
+pub mod mmu;
+pub mod ppu;
+pub mod colorize;
+pub mod apu;
+pub mod vgm;
+pub mod savestate;
+pub mod checksum;
+pub mod disasm;
+pub mod breakpoint;
+pub mod events;
+pub mod bus_trace;
+pub mod history;
+pub mod crash;
+pub mod instr_trace;
+pub mod coverage;
+pub mod profiler;
+pub mod timeline;
+pub mod memdump;
+pub mod png;
+pub mod uninit;
+pub mod environment;
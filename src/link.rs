@@ -0,0 +1,61 @@
+// link.rs
+// A second Game Boy in its own window for local link play (two-player
+// Tetris, Pokemon trades). Its serial port is wired to the main one and it
+// runs frame for frame alongside it, driven by player 2's bindings.
+use std::path::Path;
+
+use minifb::{Scale, ScaleMode, Window, WindowOptions};
+
+use crate::mmu::{MMU, Model};
+use crate::EmulatorState;
+
+pub struct LinkedGameBoy {
+    pub emu: EmulatorState,
+    pub window: Window,
+}
+
+impl LinkedGameBoy {
+    // `main_rom` is the ROM player 1 runs: the same game gets its own .sav
+    // so the two copies don't overwrite each other's battery RAM
+    pub fn open(path: &str, main_rom: &str, model: Model, palette: [u32; 4]) -> Result<Self, String> {
+        let mut emu = EmulatorState::load_rom(path, model, palette)?;
+        if path == main_rom && emu.cpu.bus.has_battery {
+            let bus = &mut emu.cpu.bus;
+            bus.save_filename = Path::new(&bus.save_filename).with_extension("p2.sav").to_string_lossy().to_string();
+            bus.eram = [0; 0x8000];
+            bus.load_save();
+        }
+        emu.cpu.bus.link_connected = true;
+
+        let mut window = Window::new(
+            &format!("Rust Game Boy (Player 2) - {}", path),
+            160, 144,
+            WindowOptions {
+                resize: true,
+                scale: Scale::X4,
+                scale_mode: ScaleMode::AspectRatioStretch,
+                ..WindowOptions::default()
+            },
+        ).unwrap();
+        window.limit_update_rate(None);
+        Ok(Self { emu, window })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    // One frame on this side, then a byte each way over the cable. Only
+    // player 1's Game Boy is heard.
+    pub fn run_frame(&mut self, other: &mut MMU, pad: u8) {
+        self.emu.run_frame(1, &mut || pad);
+        let bus = &mut self.emu.cpu.bus;
+        other.serial_exchange(bus);
+        bus.serial_exchange(other);
+        bus.apu.sample_buffer.clear();
+    }
+
+    pub fn present(&mut self) {
+        let _ = self.window.update_with_buffer(&self.emu.cpu.ppu.frame_buffer, 160, 144);
+    }
+}
@@ -0,0 +1,193 @@
+// livesplit.rs
+// Auto-splitting for speedruns: watches game memory and drives a LiveSplit
+// timer through its LiveSplit Server component (TCP, one command per line).
+//
+// Splits are defined per game in splits/<rom name>.toml, e.g.
+//
+//   start = { addr = 0xD35E, equals = 0x26 }
+//   reset = { addr = 0xFF40, equals = 0x00 }
+//   [[split]]
+//   name = "Boulder Badge"
+//   addr = 0xD356
+//   mask = 0x01
+//   equals = 0x01
+//
+// A condition holds when (byte at addr & mask) == equals (mask defaults to
+// 0xFF) and fires on the frame it becomes true. Splits fire in order. Game
+// time is taken from emulated frames, so pauses, fast forward and loading
+// states don't distort it.
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::config::LiveSplitConfig;
+use crate::mmu::MMU;
+
+const GAME_TIME_EVERY: u64 = 30; // Frames between game time updates
+const FRAME_RATE: f64 = 4_194_304.0 / 70224.0;
+
+#[derive(Deserialize)]
+struct Condition {
+    addr: u16,
+    #[serde(default = "full_mask")]
+    mask: u8,
+    equals: u8,
+}
+
+fn full_mask() -> u8 {
+    0xFF
+}
+
+impl Condition {
+    fn holds(&self, bus: &MMU) -> bool {
+        bus.read_byte(self.addr) & self.mask == self.equals
+    }
+}
+
+#[derive(Deserialize)]
+struct Split {
+    name: String,
+    #[serde(flatten)]
+    when: Condition,
+}
+
+#[derive(Deserialize)]
+struct SplitFile {
+    start: Condition,
+    reset: Option<Condition>,
+    #[serde(default)]
+    split: Vec<Split>,
+}
+
+// Rising-edge detector. Starts out "held" so a condition that's already true
+// when the game loads doesn't fire until it has been false once.
+struct Edge(bool);
+
+impl Edge {
+    fn rose(&mut self, now: bool) -> bool {
+        let rose = now && !self.0;
+        self.0 = now;
+        rose
+    }
+}
+
+pub struct AutoSplitter {
+    defs: SplitFile,
+    commands: Sender<String>,
+    running: bool,
+    next: usize, // Split being waited for
+    start_frame: u64,
+    time_sent: u64, // Frame of the last game time update
+    start_edge: Edge,
+    reset_edge: Edge,
+    split_edge: Edge,
+}
+
+impl AutoSplitter {
+    // None when the game has no (valid) split definitions
+    pub fn load(rom_stem: &str, config: &LiveSplitConfig) -> Option<Self> {
+        let path = std::path::Path::new("splits").join(format!("{}.toml", rom_stem));
+        let text = std::fs::read_to_string(&path).ok()?;
+        let defs: SplitFile = match toml::from_str(&text) {
+            Ok(defs) => defs,
+            Err(e) => {
+                eprintln!("✗ Invalid splits '{}': {}", path.display(), e);
+                return None;
+            }
+        };
+        println!("✓ Auto-splitter: {} splits from '{}'", defs.split.len(), path.display());
+
+        // The connection lives on its own thread so a missing LiveSplit never
+        // holds up the game; commands sent while it can't connect are dropped
+        let (commands, rx) = mpsc::channel::<String>();
+        let addr = (config.host.clone(), config.port);
+        std::thread::spawn(move || {
+            let mut conn: Option<TcpStream> = None;
+            for command in rx {
+                if conn.is_none() {
+                    // Host names resolve here too, each address tried in turn
+                    conn = addr.to_socket_addrs().ok().and_then(|mut addrs| {
+                        addrs.find_map(|a| TcpStream::connect_timeout(&a, Duration::from_millis(500)).ok())
+                    });
+                    if conn.is_none() {
+                        eprintln!("✗ LiveSplit Server not reachable at {}:{}", addr.0, addr.1);
+                    }
+                }
+                if let Some(stream) = &mut conn
+                    && stream.write_all(format!("{}\r\n", command).as_bytes()).is_err()
+                {
+                    conn = None;
+                }
+            }
+        });
+
+        Some(Self {
+            defs,
+            commands,
+            running: false,
+            next: 0,
+            start_frame: 0,
+            time_sent: 0,
+            start_edge: Edge(true),
+            reset_edge: Edge(true),
+            split_edge: Edge(true),
+        })
+    }
+
+    fn send(&self, command: &str) {
+        let _ = self.commands.send(command.to_string());
+    }
+
+    fn send_game_time(&mut self, frame_count: u64) {
+        self.time_sent = frame_count;
+        let secs = frame_count.saturating_sub(self.start_frame) as f64 / FRAME_RATE;
+        let (h, m, s) = ((secs / 3600.0) as u64, (secs / 60.0) as u64 % 60, secs % 60.0);
+        self.send(&format!("setgametime {}:{:02}:{:05.2}", h, m, s));
+    }
+
+    // After each emulated frame; returns a message for the OSD
+    pub fn update(&mut self, bus: &MMU, frame_count: u64) -> Option<String> {
+        if let Some(reset) = &self.defs.reset
+            && self.reset_edge.rose(reset.holds(bus))
+            && self.running
+        {
+            self.running = false;
+            self.send("reset");
+            return Some("Timer reset".to_string());
+        }
+
+        if !self.running {
+            if self.start_edge.rose(self.defs.start.holds(bus)) {
+                self.running = true;
+                self.next = 0;
+                self.start_frame = frame_count;
+                self.split_edge = Edge(true);
+                // Game time only moves when we set it
+                self.send("starttimer");
+                self.send("pausegametime");
+                self.send_game_time(frame_count);
+                return Some("Timer started".to_string());
+            }
+            return None;
+        }
+
+        let split = self.defs.split.get(self.next)?;
+        if self.split_edge.rose(split.when.holds(bus)) {
+            let message = format!("Split: {}", split.name);
+            self.send_game_time(frame_count);
+            self.send("split");
+            self.next += 1;
+            self.split_edge = Edge(true);
+            // The last split ends the run
+            self.running = self.next < self.defs.split.len();
+            return Some(message);
+        }
+        if frame_count >= self.time_sent + GAME_TIME_EVERY {
+            self.send_game_time(frame_count);
+        }
+        None
+    }
+}
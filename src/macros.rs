@@ -0,0 +1,77 @@
+// macros.rs
+// Input macros: a short recorded run of joypad input (a mash pattern, a menu
+// combo) played back frame for frame from a key, once or repeating. While one
+// plays, its buttons are held on top of whatever the player presses.
+//
+// Stored in the config, one frame per word using the movie log letters
+// (UDLRSsBA, '.' for nothing held):
+//
+//   [[macros]]
+//   key = "Q"
+//   inputs = "A . A . A ."
+use minifb::Key;
+use serde::{Deserialize, Serialize};
+
+use crate::movie::BUTTONS;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputMacro {
+    #[serde(with = "crate::config::key_serde")] pub key: Key,
+    pub inputs: String,
+}
+
+impl Default for InputMacro {
+    fn default() -> Self {
+        Self { key: Key::Unknown, inputs: String::new() }
+    }
+}
+
+impl InputMacro {
+    pub fn new(key: Key, frames: &[u8]) -> Self {
+        let words: Vec<String> = frames
+            .iter()
+            .map(|&pad| {
+                let held: String = BUTTONS.iter().filter(|(_, _, bit)| pad & (1 << bit) == 0).map(|(_, c, _)| *c).collect();
+                if held.is_empty() { ".".to_string() } else { held }
+            })
+            .collect();
+        Self { key, inputs: words.join(" ") }
+    }
+
+    // Joypad bytes (active low) per frame; unknown letters are ignored
+    pub fn frames(&self) -> Vec<u8> {
+        self.inputs
+            .split_whitespace()
+            .map(|word| {
+                word.chars().fold(0xFF, |pad, c| match BUTTONS.iter().find(|(_, b, _)| *b == c) {
+                    Some((_, _, bit)) => pad & !(1 << bit),
+                    None => pad,
+                })
+            })
+            .collect()
+    }
+}
+
+pub struct MacroPlayer {
+    pub key: Key, // The macro's key, which also stops it
+    frames: Vec<u8>,
+    pos: usize,
+    repeat: bool,
+}
+
+impl MacroPlayer {
+    pub fn new(input_macro: &InputMacro, repeat: bool) -> Self {
+        Self { key: input_macro.key, frames: input_macro.frames(), pos: 0, repeat }
+    }
+
+    // Buttons for the next frame, None once it's done
+    pub fn next_frame(&mut self) -> Option<u8> {
+        if self.pos == self.frames.len() && self.repeat {
+            self.pos = 0;
+        }
+        let pad = self.frames.get(self.pos).copied();
+        self.pos += 1;
+        pad
+    }
+}
@@ -1,59 +1,227 @@
-mod cpu;
-mod mmu;
-mod ppu;
-mod apu;
+use gb_rs::{apu, checksum, colorize, cpu, crash, error, instr_trace, memdump, mmu, png, ppu, profiler, savestate};
+
+mod scope;
+mod audio;
+mod apu_panel;
+mod event_viewer;
+mod memory_editor;
+mod tilemap_viewer;
+mod ram_watch;
+mod perf_overlay;
+mod config;
+mod hotkeys;
+mod osd;
+mod movie;
+mod menu;
+mod browser;
+mod pacer;
+mod state_picker;
+mod cli;
+mod headless;
+mod test_roms;
+mod link;
+mod bgb_link;
+mod stream;
+mod presence;
+mod theme;
+mod quick_menu;
+mod watch;
+mod debug_tui;
+mod remote_debug;
+mod livesplit;
+mod macros;
+mod game_settings;
+mod compat;
+mod border;
+mod repl;
+mod gdb;
 
 use std::fs;
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant, SystemTime};
 
-use ppu::PPU;
 use cpu::CPU;
-use mmu::MMU;
+use error::CoreError;
+use gb_rs::breakpoint::Breakpoint;
+use gb_rs::coverage::Coverage;
+use gb_rs::timeline::Timeline;
+use gb_rs::uninit::UninitReads;
+use mmu::{MMU, Model};
+use scope::ScopeWindow;
+use apu_panel::ApuPanel;
+use event_viewer::EventViewer;
+use memory_editor::MemoryEditor;
+use tilemap_viewer::TilemapViewer;
+use perf_overlay::PerfOverlay;
+use config::Config;
+use hotkeys::{Hotkey, HotkeyMapping, SLOT_KEYS};
+use osd::Osd;
+use movie::{Movie, MovieMode};
+use menu::{NavInput, PadNav, move_focus};
+use browser::{BrowserResult, RomBrowser};
+use pacer::FramePacer;
+use state_picker::{PickerResult, StatePicker};
+use cli::Args;
+use link::LinkedGameBoy;
+use bgb_link::BgbLink;
+use stream::SpectatorServer;
+use remote_debug::DebugServer;
+use repl::DebugRepl;
+use gdb::GdbServer;
+use livesplit::AutoSplitter;
+use macros::{InputMacro, MacroPlayer};
+use border::Border;
+use game_settings::GameSettingsMenu;
+use presence::Presence;
+use theme::Theme;
+use quick_menu::{QuickAction, QuickMenu};
+use watch::RomWatcher;
+use savestate::{Preview, StateReader, StateWriter};
+use audio::{AudioCommand, AudioStats, AudioSynth};
 
 use minifb::{Window, WindowOptions, Key, Scale, ScaleMode, MouseMode, MouseButton, KeyRepeat};
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use gilrs::{Gilrs, Event, EventType, Button as GamepadButton};
+use cpal::traits::HostTrait;
+use gilrs::{Gilrs, Gamepad, GamepadId, Event, EventType, Axis, Button as GamepadButton};
 use font8x8::{BASIC_FONTS, UnicodeFonts};
 use rfd::FileDialog;
+use serde::{Deserialize, Serialize};
 
 // --- 1. CONFIGURATION STRUCTS ---
 
-#[derive(Clone, Copy, Debug)]
+// A controller input bound to a Game Boy button: a regular button, or one
+// direction of an axis (hats, sticks and analog triggers on some pads)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PadInput {
+    Button(GamepadButton),
+    Axis(Axis, bool), // true = positive direction
+}
+
+// How far an axis has to move to count as pressed
+const AXIS_THRESHOLD: f32 = 0.5;
+
+impl PadInput {
+    pub fn is_active(&self, gamepad: &Gamepad) -> bool {
+        match *self {
+            PadInput::Button(btn) => gamepad.is_pressed(btn),
+            PadInput::Axis(axis, positive) => {
+                let v = gamepad.value(axis);
+                if positive { v > AXIS_THRESHOLD } else { v < -AXIS_THRESHOLD }
+            }
+        }
+    }
+
+    // "South", "LeftStickX+", "DPadY-" ... (also the config file format)
+    pub fn name(&self) -> String {
+        match self {
+            PadInput::Button(btn) => format!("{:?}", btn),
+            PadInput::Axis(axis, positive) => format!("{:?}{}", axis, if *positive { '+' } else { '-' }),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct InputMapping {
     // Controller
-    pub up_btn: GamepadButton,
-    pub down_btn: GamepadButton,
-    pub left_btn: GamepadButton,
-    pub right_btn: GamepadButton,
-    pub a_btn: GamepadButton,
-    pub b_btn: GamepadButton,
-    pub start_btn: GamepadButton,
-    pub select_btn: GamepadButton,
+    #[serde(with = "config::pad_serde")] pub up_btn: PadInput,
+    #[serde(with = "config::pad_serde")] pub down_btn: PadInput,
+    #[serde(with = "config::pad_serde")] pub left_btn: PadInput,
+    #[serde(with = "config::pad_serde")] pub right_btn: PadInput,
+    #[serde(with = "config::pad_serde")] pub a_btn: PadInput,
+    #[serde(with = "config::pad_serde")] pub b_btn: PadInput,
+    #[serde(with = "config::pad_serde")] pub start_btn: PadInput,
+    #[serde(with = "config::pad_serde")] pub select_btn: PadInput,
+    #[serde(with = "config::pad_serde")] pub turbo_a_btn: PadInput,
+    #[serde(with = "config::pad_serde")] pub turbo_b_btn: PadInput,
     
     // Keyboard
-    pub up_key: Key,
-    pub down_key: Key,
-    pub left_key: Key,
-    pub right_key: Key,
-    pub a_key: Key,
-    pub b_key: Key,
-    pub start_key: Key,
-    pub select_key: Key,
+    #[serde(with = "config::key_serde")] pub up_key: Key,
+    #[serde(with = "config::key_serde")] pub down_key: Key,
+    #[serde(with = "config::key_serde")] pub left_key: Key,
+    #[serde(with = "config::key_serde")] pub right_key: Key,
+    #[serde(with = "config::key_serde")] pub a_key: Key,
+    #[serde(with = "config::key_serde")] pub b_key: Key,
+    #[serde(with = "config::key_serde")] pub start_key: Key,
+    #[serde(with = "config::key_serde")] pub select_key: Key,
+    #[serde(with = "config::key_serde")] pub turbo_a_key: Key,
+    #[serde(with = "config::key_serde")] pub turbo_b_key: Key,
 }
 
 impl InputMapping {
-    pub fn default() -> Self {
+    // Rows of the config window: UP, DOWN, LEFT, RIGHT, A, B, START, SELECT, TURBO A, TURBO B
+    pub fn key_mut(&mut self, row: usize) -> &mut Key {
+        match row {
+            0 => &mut self.up_key,
+            1 => &mut self.down_key,
+            2 => &mut self.left_key,
+            3 => &mut self.right_key,
+            4 => &mut self.a_key,
+            5 => &mut self.b_key,
+            6 => &mut self.start_key,
+            7 => &mut self.select_key,
+            8 => &mut self.turbo_a_key,
+            _ => &mut self.turbo_b_key,
+        }
+    }
+
+    pub fn pad_mut(&mut self, row: usize) -> &mut PadInput {
+        match row {
+            0 => &mut self.up_btn,
+            1 => &mut self.down_btn,
+            2 => &mut self.left_btn,
+            3 => &mut self.right_btn,
+            4 => &mut self.a_btn,
+            5 => &mut self.b_btn,
+            6 => &mut self.start_btn,
+            7 => &mut self.select_btn,
+            8 => &mut self.turbo_a_btn,
+            _ => &mut self.turbo_b_btn,
+        }
+    }
+
+    pub fn key(&self, row: usize) -> Key {
+        let mut copy = *self;
+        *copy.key_mut(row)
+    }
+
+    pub fn pad(&self, row: usize) -> PadInput {
+        let mut copy = *self;
+        *copy.pad_mut(row)
+    }
+
+    // Player 2 defaults: keys on the right-hand side of the keyboard
+    pub fn player2() -> Self {
+        Self {
+            up_key: Key::I,
+            down_key: Key::K,
+            left_key: Key::J,
+            right_key: Key::L,
+            a_key: Key::Period,
+            b_key: Key::Comma,
+            start_key: Key::RightShift,
+            select_key: Key::RightCtrl,
+            turbo_a_key: Key::Slash,
+            turbo_b_key: Key::Semicolon,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for InputMapping {
+    fn default() -> Self {
         Self {
             // Default Controller (Xbox/PS Standard)
-            up_btn: GamepadButton::DPadUp,
-            down_btn: GamepadButton::DPadDown,
-            left_btn: GamepadButton::DPadLeft,
-            right_btn: GamepadButton::DPadRight,
-            a_btn: GamepadButton::East,
-            b_btn: GamepadButton::South,
-            start_btn: GamepadButton::Start,
-            select_btn: GamepadButton::Select,
+            up_btn: PadInput::Button(GamepadButton::DPadUp),
+            down_btn: PadInput::Button(GamepadButton::DPadDown),
+            left_btn: PadInput::Button(GamepadButton::DPadLeft),
+            right_btn: PadInput::Button(GamepadButton::DPadRight),
+            a_btn: PadInput::Button(GamepadButton::East),
+            b_btn: PadInput::Button(GamepadButton::South),
+            start_btn: PadInput::Button(GamepadButton::Start),
+            select_btn: PadInput::Button(GamepadButton::Select),
+            turbo_a_btn: PadInput::Button(GamepadButton::North),
+            turbo_b_btn: PadInput::Button(GamepadButton::West),
 
             // Default Keyboard
             up_key: Key::Up,
@@ -64,26 +232,646 @@ impl InputMapping {
             b_key: Key::X,
             start_key: Key::Enter,
             select_key: Key::Space,
+            turbo_a_key: Key::A,
+            turbo_b_key: Key::S,
         }
     }
 }
 
+// Game Boy buttons, for bindings that press several at once
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GbButton {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl GbButton {
+    // Bit in MMU::joypad_state
+    fn bit(self) -> u8 {
+        self as u8
+    }
+}
+
+// One key or pad input that holds a combination of buttons (A+B,
+// Start+Select, ...), for one-handed play and common combos
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ComboBinding {
+    #[serde(with = "config::key_serde")] pub key: Key,
+    #[serde(with = "config::pad_serde")] pub pad: PadInput,
+    pub buttons: Vec<GbButton>,
+}
+
+impl Default for ComboBinding {
+    fn default() -> Self {
+        Self { key: Key::Unknown, pad: PadInput::Button(GamepadButton::Unknown), buttons: Vec::new() }
+    }
+}
+
 // --- 2. HELPER STRUCTS & FUNCTIONS ---
 
+// What paces emulation: the frame pacer (video) or the audio device
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncMode {
+    Video,
+    Audio,
+}
+
+// 4194304 / 70224 frames per second
+const FRAMES_PER_MINUTE: u64 = 3584;
+
+// How far (in T-cycles) emulation may run ahead of the audio device in audio
+// sync. The synth clock moves 4194304 / rate cycles per device frame, so this
+// is two video frames of real time at any device rate.
+const AUDIO_SYNC_LEAD: u64 = 70224 * 2;
+
 struct EmulatorState {
     cpu: CPU,
-    ppu: PPU,
     mmu_filename: String,
+    movie: Option<Movie>,
+    frame_count: u64, // Frames run since the ROM was loaded
+    undo_load: Option<Vec<u8>>, // Machine state from before the last slot load
+    undo_save: Option<(String, Option<Vec<u8>>)>, // Last slot saved and what it held before
+    crc32: u32, // Of the ROM file, to check dumps against No-Intro
+    sha1: [u8; 20],
+    breakpoints: Vec<Breakpoint>, // Set by the debuggers; run_frame stops at these
+    break_hit: Option<u16>, // Where run_frame last stopped early
+    single_step: bool, // gdb asked for one instruction, which gdb.rs runs
+    fault: Option<CoreError>, // What stopped the CPU; nothing runs until a reset or state load
+    macro_player: Option<MacroPlayer>,
+    macro_pad: u8, // The playing macro's buttons this frame, held on top of live input
+    macro_recording: Option<Vec<u8>>, // Live input per frame while recording a macro
 }
 
 impl EmulatorState {
-    fn load_rom(path: &str) -> Self {
-        let rom_data = fs::read(path).expect("Failed to read ROM");
-        let mmu = MMU::new(rom_data, path);
-        let cpu = CPU::new(mmu);
-        let ppu = PPU::new();
+    // Errors are worded for the player: what went wrong and what to try
+    fn load_rom(path: &str, model: Model, palette: [u32; 4]) -> Result<Self, CoreError> {
+        let rom_data = mmu::read_rom(path)?;
+        let crc32 = checksum::crc32(&rom_data);
+        let sha1 = checksum::sha1(&rom_data);
+        println!("CRC32 {:08x}  SHA-1 {}", crc32, checksum::hex(&sha1));
+        let mmu = MMU::new(rom_data, path, model);
+        let mut cpu = CPU::new(mmu);
+        cpu.ppu.set_palette(palette);
         println!("Loaded ROM: {}", path);
-        Self { cpu, ppu, mmu_filename: path.to_string() }
+        Ok(Self { cpu, mmu_filename: path.to_string(), movie: None, frame_count: 0, undo_load: None, undo_save: None, crc32, sha1, breakpoints: Vec::new(), break_hit: None, single_step: false, fault: None,
+            macro_player: None, macro_pad: 0xFF, macro_recording: None })
+    }
+
+    // Game title from the cartridge header
+    fn title(&self) -> String {
+        self.cpu.bus.rom[0x134..0x144].iter().take_while(|&&b| b != 0).map(|&b| b as char).collect()
+    }
+
+    // Key for the game's config::GameOverrides
+    fn game_key(&self) -> String {
+        config::game_key(&self.cpu.bus.rom)
+    }
+
+    // Cartridge header and checksums for the ROM info panel
+    fn rom_info(&self) -> Vec<String> {
+        let rom = &self.cpu.bus.rom;
+        vec![
+            format!("TITLE  {}", self.title()),
+            format!("FILE   {}", self.file_stem()),
+            format!("MBC    {:#04X}", self.cpu.bus.mbc_type),
+            format!("SIZE   {} KB", rom.len() / 1024),
+            format!("CRC32  {:08x}", self.crc32),
+            format!("SHA-1  {}", checksum::hex(&self.sha1)),
+        ]
+    }
+
+    // Leaving the game: finalize any in-progress VGM so the file is valid,
+    // flush battery RAM and write the exit state. Movies are tied to their
+    // own timeline, so they don't leave an exit state.
+    fn close(&mut self, exit_state: bool) {
+        self.cpu.bus.apu.stop_vgm_recording();
+        match self.cpu.stop_trace() {
+            Some(Ok(path)) => println!("✓ Instruction trace written to '{}'", path),
+            Some(Err(e)) => eprintln!("✗ Couldn't write the trace: {}", e),
+            None => {}
+        }
+        match self.cpu.stop_coverage() {
+            Some(Ok(path)) => println!("✓ Coverage report written to '{}'", path),
+            Some(Err(e)) => eprintln!("✗ Couldn't write the coverage report: {}", e),
+            None => {}
+        }
+        match self.cpu.stop_profile() {
+            Some(Ok(path)) => println!("✓ Profile written to '{}'", path),
+            Some(Err(e)) => eprintln!("✗ Couldn't write the profile: {}", e),
+            None => {}
+        }
+        self.cpu.bus.save_ram();
+        if exit_state && self.movie.is_none() {
+            self.save_state_file(&self.exit_state_path());
+        }
+    }
+
+    // Blank cartridge RAM with battery saving off, so the real .sav is left alone
+    fn detach_battery(&mut self) {
+        self.cpu.bus.eram = [0; 0x8000];
+        self.cpu.bus.has_battery = false;
+    }
+
+    // Movies run from power-on with blank cartridge RAM
+    fn start_movie(&mut self, movie: Movie) {
+        self.detach_battery();
+        self.movie = Some(movie);
+    }
+
+    // Run one full frame (70224 T-cycles) of emulation
+    // `poll` reads the live joypad. It's called `polls` times, spread evenly
+    // over the frame, so a press reaches the game without waiting for the
+    // whole 70224 cycles to finish.
+    fn run_frame(&mut self, polls: u32, poll: &mut dyn FnMut() -> u8) {
+        // Stopped on an error: the last frame stays up
+        if self.fault.is_some() {
+            return;
+        }
+        // Movies hold one input per frame
+        let polls = if self.movie.is_some() { 1 } else { polls.max(1) };
+        self.macro_pad = 0xFF;
+        match self.macro_player.as_mut().map(|p| p.next_frame()) {
+            Some(Some(pad)) => self.macro_pad = pad,
+            Some(None) => self.macro_player = None, // Played out
+            None => {}
+        }
+        let pad = poll();
+        if let Some(frames) = &mut self.macro_recording {
+            frames.push(pad);
+        }
+        self.latch_joypad(pad);
+
+        if let Some(movie) = &mut self.movie {
+            match movie.next_frame(self.cpu.bus.joypad_state) {
+                Some(pad) => {
+                    if movie.mode == MovieMode::Playing && pad != 0xFF { self.cpu.bus.interrupt_flag |= 0x10; }
+                    self.cpu.bus.joypad_state = pad;
+                }
+                None => {
+                    println!("Movie playback finished ({} frames)", movie.frames.len());
+                    self.movie = None;
+                }
+            }
+        }
+
+        self.frame_count += 1;
+        let mut cycles = 0;
+        let mut next_poll = 1;
+        while cycles < 70224 {
+            if next_poll < polls && cycles >= 70224 * next_poll / polls {
+                self.latch_joypad(poll());
+                next_poll += 1;
+            }
+            // A panic in the core stops the game like an unknown opcode
+            // would, rather than taking the emulator down with it
+            let pc = self.cpu.registers.pc;
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| self.step()))
+                .unwrap_or_else(|payload| Err(CoreError::Internal { message: crash::panic_message(&*payload), pc }));
+            match result {
+                Ok(c) => cycles += c,
+                Err(e) => {
+                    self.fault = Some(e);
+                    break;
+                }
+            }
+            if self.at_breakpoint() {
+                self.break_hit = Some(self.cpu.registers.pc);
+                break;
+            }
+        }
+    }
+
+    // The next instruction is on a breakpoint whose condition (if any) holds,
+    // or an event a debugger breaks on just happened
+    fn at_breakpoint(&self) -> bool {
+        self.cpu.bus.event_hit.is_some() || self.breakpoints.iter().any(|b| b.hit(&self.cpu))
+    }
+
+    // Why run_frame stopped early, for the debuggers to show
+    fn stop_reason(&self) -> String {
+        let pc = self.cpu.registers.pc;
+        match self.cpu.bus.event_hit {
+            Some(event) => format!("{} at ${:04X}", event, pc),
+            None => format!("breakpoint at ${:04X}", pc),
+        }
+    }
+
+    // One instruction (and any interrupt dispatch after it), in cycles
+    fn step(&mut self) -> Result<u32, CoreError> {
+        // The CPU advances the bus and the PPU itself
+        let c = self.cpu.step()?;
+        let i = self.cpu.handle_interrupts();
+        Ok(c as u32 + i as u32)
+    }
+
+    fn latch_joypad(&mut self, pad: u8) {
+        let pad = pad & self.macro_pad;
+        self.cpu.bus.joypad_state = pad;
+        if pad != 0xFF { self.cpu.bus.interrupt_flag |= 0x10; }
+    }
+
+    // ROM file name without extension, used to name per-game output files
+    fn file_stem(&self) -> String {
+        std::path::Path::new(&self.mmu_filename)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "game".to_string())
+    }
+
+    // Gameboy Doctor trace of every instruction, to <rom name>.doctor.log.
+    // Returns what happened, for the OSD.
+    fn toggle_doctor_log(&mut self) -> String {
+        if self.cpu.doctor_logging() {
+            self.cpu.stop_doctor_log();
+            return "Doctor log stopped".to_string();
+        }
+        let path = format!("{}.doctor.log", self.file_stem());
+        match self.cpu.start_doctor_log(&path) {
+            Ok(()) => format!("Doctor log: {}", path),
+            Err(e) => format!("Couldn't create '{}': {}", path, e),
+        }
+    }
+
+    fn toggle_vgm_recording(&mut self) {
+        let stem = self.file_stem();
+        let apu = &mut self.cpu.bus.apu;
+        if apu.vgm.is_some() {
+            apu.stop_vgm_recording();
+        } else {
+            apu.start_vgm_recording(&format!("{}.vgm", stem));
+        }
+    }
+
+    // All of a game's states live in states/<rom name>/
+    fn state_dir(&self) -> std::path::PathBuf {
+        std::path::Path::new("states").join(self.file_stem())
+    }
+
+    fn slot_path(&self, slot: u32) -> String {
+        self.state_dir().join(format!("slot{}.state", slot)).to_string_lossy().to_string()
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        Preview::write(&mut w, &self.cpu.ppu.frame_buffer);
+        self.cpu.save_state(&mut w);
+        w.data
+    }
+
+    // On a bad or truncated file the machine is rolled back to where it was
+    fn load_state(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let backup = self.save_state();
+        let result = StateReader::new(data).and_then(|mut r| {
+            Preview::read(&mut r)?;
+            self.cpu.load_state(&mut r)
+        });
+        if result.is_err() {
+            let mut r = StateReader::new(&backup)?;
+            Preview::read(&mut r)?;
+            self.cpu.load_state(&mut r)?;
+        } else {
+            self.fault = None;
+            // RAM as the state has it, whoever wrote it
+            if let Some(uninit) = &mut self.cpu.bus.uninit { uninit.initialize_all(); }
+        }
+        result
+    }
+
+    // Written when the window closes, offered back the next time the ROM loads
+    fn exit_state_path(&self) -> String {
+        self.state_dir().join("exit.state").to_string_lossy().to_string()
+    }
+
+    // Slot saves and loads keep what they replace, so one fat-fingered key
+    // can be taken back with undo_save/undo_load
+    fn save_slot(&mut self, slot: u32) -> bool {
+        let path = self.slot_path(slot);
+        let previous = fs::read(&path).ok();
+        let ok = self.save_state_file(&path);
+        if ok { self.undo_save = Some((path, previous)); }
+        ok
+    }
+
+    fn load_slot(&mut self, slot: u32) -> bool {
+        let current = self.save_state();
+        let ok = self.load_state_file(&self.slot_path(slot));
+        if ok { self.undo_load = Some(current); }
+        ok
+    }
+
+    // Back to the state before the last load; undoing again redoes it
+    fn undo_load(&mut self) -> bool {
+        let Some(data) = self.undo_load.take() else { return false };
+        let current = self.save_state();
+        if self.load_state(&data).is_err() { return false; }
+        self.undo_load = Some(current);
+        true
+    }
+
+    // Puts back the slot file the last save overwrote (or removes it if it was new)
+    fn undo_save(&mut self) -> Option<String> {
+        let (path, previous) = self.undo_save.take()?;
+        let result = match previous {
+            Some(data) => fs::write(&path, data),
+            None => fs::remove_file(&path),
+        };
+        match result {
+            Ok(()) => Some(path),
+            Err(e) => {
+                eprintln!("✗ Failed to restore '{}': {}", path, e);
+                None
+            }
+        }
+    }
+
+    // Auto-states rotate through `slots` files, replacing the oldest
+    fn save_auto_state(&self, slots: u32) {
+        let path = (1..=slots.max(1))
+            .map(|n| self.state_dir().join(format!("auto{}.state", n)))
+            .min_by_key(|p| fs::metadata(p).and_then(|m| m.modified()).ok())
+            .unwrap();
+        self.save_state_file(&path.to_string_lossy());
+    }
+
+    fn save_state_file(&self, path: &str) -> bool {
+        if let Some(dir) = std::path::Path::new(path).parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        match fs::write(path, self.save_state()) {
+            Ok(()) => {
+                println!("✓ State saved to '{}'", path);
+                true
+            }
+            Err(e) => {
+                eprintln!("✗ Failed to save state '{}': {}", path, e);
+                false
+            }
+        }
+    }
+
+    // Exported states made with another ROM are refused
+    fn load_state_file(&mut self, path: &str) -> bool {
+        let result = fs::read(path).and_then(|data| match savestate::import_portable(&data)? {
+            Some((info, _)) if info.rom_sha1 != self.sha1 => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("it was saved with a different ROM (SHA-1 {})", checksum::hex(&info.rom_sha1)),
+            )),
+            Some((_, state)) => self.load_state(state),
+            None => self.load_state(&data),
+        });
+        match result {
+            Ok(()) => {
+                println!("✓ State loaded from '{}'", path);
+                true
+            }
+            Err(e) => {
+                eprintln!("✗ Failed to load state '{}': {}", path, e);
+                false
+            }
+        }
+    }
+
+    // APU state for AudioCommand::LoadState, so the audio thread's synth
+    // restarts from a loaded state
+    fn apu_snapshot(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        self.cpu.bus.apu.save_state(&mut w);
+        w.data
+    }
+
+    // The CPU's state and recent history into <rom name>_crash_NNN.txt, for
+    // when it stops on `reason`. Returns what happened, for the log.
+    fn write_crash_report(&self, reason: &str) -> String {
+        let stem = self.file_stem();
+        let path = (1..)
+            .map(|n| format!("{}_crash_{:03}.txt", stem, n))
+            .find(|p| !std::path::Path::new(p).exists())
+            .unwrap();
+        let header = format!("gb_rs crash report
+ROM: {} (CRC32 {:08x})
+Frame: {}
+
+", self.mmu_filename, self.crc32, self.frame_count);
+        match fs::write(&path, header + &crash::report(&self.cpu, reason)) {
+            Ok(()) => format!("Crash report written to '{}'", path),
+            Err(e) => format!("Couldn't write the crash report to '{}': {}
+{}", path, e, self.cpu.history.dump()),
+        }
+    }
+
+    // Every memory region and the registers into <rom name>_dump_NNN/, for
+    // offline analysis. Returns what happened, for the OSD.
+    fn core_dump(&self) -> String {
+        let stem = self.file_stem();
+        let dir = (1..)
+            .map(|n| format!("{}_dump_{:03}", stem, n))
+            .find(|p| !std::path::Path::new(p).exists())
+            .unwrap();
+        match memdump::core_dump(&self.cpu, std::path::Path::new(&dir)) {
+            Ok(()) => format!("Memory dumped to '{}'", dir),
+            Err(e) => format!("Couldn't dump memory to '{}': {}", dir, e),
+        }
+    }
+
+    // VRAM's tiles, the BG map in use and the OAM sprites as PNGs, to
+    // <rom name>_tiles/bgmap/sprites_NNN.png. Returns what happened, for the OSD.
+    fn export_graphics(&self) -> String {
+        let stem = self.file_stem();
+        let n = (1..)
+            .find(|n| ["tiles", "bgmap", "sprites"].iter().all(|kind| !std::path::Path::new(&format!("{}_{}_{:03}.png", stem, kind, n)).exists()))
+            .unwrap();
+        let bus = &self.cpu.bus;
+        let bg_map = if bus.lcdc & 0x08 != 0 { 0x9C00 } else { 0x9800 };
+        let images = [
+            ("tiles", 128, 192, self.cpu.ppu.render_tileset(bus)),
+            ("bgmap", 256, 256, self.cpu.ppu.render_tile_map(bus, bg_map)),
+            ("sprites", 80, 90, self.cpu.ppu.render_sprite_sheet(bus)),
+        ];
+        for (kind, width, height, pixels) in images {
+            let path = format!("{}_{}_{:03}.png", stem, kind, n);
+            if let Err(e) = std::fs::write(&path, png::encode(width, height, &pixels)) {
+                return format!("Couldn't write '{}': {}", path, e);
+            }
+        }
+        format!("Graphics exported to {}_*_{:03}.png", stem, n)
+    }
+
+    // Writes the current frame as a 24-bit BMP (no extra dependencies needed)
+    fn save_screenshot(&self) {
+        let stem = self.file_stem();
+        let path = (1..)
+            .map(|n| format!("{}_{:03}.bmp", stem, n))
+            .find(|p| !std::path::Path::new(p).exists())
+            .unwrap();
+
+        const W: usize = 160;
+        const H: usize = 144;
+        let row_size = W * 3; // Already a multiple of 4, no padding needed
+        let pixel_bytes = row_size * H;
+        let mut file = Vec::with_capacity(54 + pixel_bytes);
+        file.extend_from_slice(b"BM");
+        file.extend_from_slice(&((54 + pixel_bytes) as u32).to_le_bytes());
+        file.extend_from_slice(&[0; 4]);
+        file.extend_from_slice(&54u32.to_le_bytes());
+        file.extend_from_slice(&40u32.to_le_bytes()); // BITMAPINFOHEADER
+        file.extend_from_slice(&(W as i32).to_le_bytes());
+        file.extend_from_slice(&(H as i32).to_le_bytes()); // Positive = bottom-up rows
+        file.extend_from_slice(&1u16.to_le_bytes());
+        file.extend_from_slice(&24u16.to_le_bytes());
+        file.extend_from_slice(&[0; 24]);
+        for y in (0..H).rev() {
+            for x in 0..W {
+                let p = self.cpu.ppu.frame_buffer[y * W + x];
+                file.extend_from_slice(&[p as u8, (p >> 8) as u8, (p >> 16) as u8]);
+            }
+        }
+
+        match fs::write(&path, &file) {
+            Ok(()) => println!("✓ Screenshot saved to '{}'", path),
+            Err(e) => eprintln!("✗ Failed to save screenshot '{}': {}", path, e),
+        }
+    }
+}
+
+// Build a joypad byte (active low) from the keyboard plus every pad that
+// `pad_mapping` returns bindings for
+fn read_joypad(
+    window: &Window,
+    gilrs: &Gilrs,
+    keys: &InputMapping,
+    combos: &[ComboBinding],
+    turbo_on: bool,
+    pad_mapping: impl Fn(GamepadId, &Gamepad) -> Option<InputMapping>,
+) -> u8 {
+    let mut pad = 0xFF;
+    
+    // KEYBOARD (Dynamic Mapping)
+    if window.is_key_down(keys.right_key) { pad &= !(1 << 0); }
+    if window.is_key_down(keys.left_key)  { pad &= !(1 << 1); }
+    if window.is_key_down(keys.up_key)    { pad &= !(1 << 2); }
+    if window.is_key_down(keys.down_key)  { pad &= !(1 << 3); }
+    if window.is_key_down(keys.a_key)     { pad &= !(1 << 4); }
+    if window.is_key_down(keys.b_key)     { pad &= !(1 << 5); }
+    if window.is_key_down(keys.select_key){ pad &= !(1 << 6); }
+    if window.is_key_down(keys.start_key) { pad &= !(1 << 7); }
+    let mut turbo_a = window.is_key_down(keys.turbo_a_key);
+    let mut turbo_b = window.is_key_down(keys.turbo_b_key);
+    
+    // CONTROLLER (Mapped)
+    for (id, gamepad) in gilrs.gamepads() {
+        let Some(mapping) = pad_mapping(id, &gamepad) else { continue };
+        if mapping.right_btn.is_active(&gamepad) { pad &= !(1 << 0); }
+        if mapping.left_btn.is_active(&gamepad)  { pad &= !(1 << 1); }
+        if mapping.up_btn.is_active(&gamepad)    { pad &= !(1 << 2); }
+        if mapping.down_btn.is_active(&gamepad)  { pad &= !(1 << 3); }
+        if mapping.a_btn.is_active(&gamepad)     { pad &= !(1 << 4); }
+        if mapping.b_btn.is_active(&gamepad)     { pad &= !(1 << 5); }
+        if mapping.select_btn.is_active(&gamepad){ pad &= !(1 << 6); }
+        if mapping.start_btn.is_active(&gamepad) { pad &= !(1 << 7); }
+        turbo_a |= mapping.turbo_a_btn.is_active(&gamepad);
+        turbo_b |= mapping.turbo_b_btn.is_active(&gamepad);
+    }
+    // Combos: any of their key or (allowed) pad inputs holds all their buttons
+    for combo in combos {
+        let held = window.is_key_down(combo.key)
+            || gilrs.gamepads().any(|(id, gamepad)| pad_mapping(id, &gamepad).is_some() && combo.pad.is_active(&gamepad));
+        if held {
+            for b in &combo.buttons { pad &= !(1 << b.bit()); }
+        }
+    }
+    if turbo_on && turbo_a { pad &= !(1 << 4); }
+    if turbo_on && turbo_b { pad &= !(1 << 5); }
+    pad
+}
+
+// A real D-pad can't press both opposites at once and some games misbehave
+// when it happens: treat Left+Right and Up+Down as neither being held
+fn block_opposite_dirs(pad: u8) -> u8 {
+    let mut pad = pad;
+    if pad & 0b0011 == 0 { pad |= 0b0011; }
+    if pad & 0b1100 == 0 { pad |= 0b1100; }
+    pad
+}
+
+// Recoverable problems get a dialog, since most players never see the console
+// Loads a state file picked by the player, undoable like a slot load. An
+// exported state made with another ROM revision is only loaded if the player
+// says so. Ok(false) when they didn't.
+fn import_state(emu: &mut EmulatorState, path: &std::path::Path) -> Result<bool, String> {
+    let data = fs::read(path).map_err(|e| format!("Couldn't read '{}': {}.", path.display(), e))?;
+    let portable = savestate::import_portable(&data).map_err(|e| format!("'{}' isn't a usable save state: {}.", path.display(), e))?;
+    let (state, core_version) = match portable {
+        Some((info, state)) => {
+            if info.rom_sha1 != emu.sha1 {
+                let load_anyway = rfd::MessageDialog::new()
+                    .set_level(rfd::MessageLevel::Warning)
+                    .set_title("Different ROM")
+                    .set_description(format!(
+                        "This state was saved with a different ROM than the one running:\n\n  state  {}\n  ROM    {}\n\nIt's probably another revision or region of the game, and may crash or glitch. Load it anyway?",
+                        checksum::hex(&info.rom_sha1),
+                        checksum::hex(&emu.sha1),
+                    ))
+                    .set_buttons(rfd::MessageButtons::YesNo)
+                    .show();
+                if load_anyway != rfd::MessageDialogResult::Yes {
+                    return Ok(false);
+                }
+            }
+            (state, Some(info.core_version))
+        }
+        None => (&data[..], None),
+    };
+    let current = emu.save_state();
+    emu.load_state(state).map_err(|e| match core_version {
+        Some(v) if v != env!("CARGO_PKG_VERSION") => format!("The state was saved by gb_rs {}, which this version can't load ({}).", v, e),
+        _ => format!("'{}' isn't a usable save state: {}.", path.display(), e),
+    })?;
+    emu.undo_load = Some(current);
+    println!("✓ State loaded from '{}'", path.display());
+    Ok(true)
+}
+
+fn show_dialog(level: rfd::MessageLevel, title: &str, text: &str) {
+    eprintln!("✗ {}: {}", title, text);
+    rfd::MessageDialog::new()
+        .set_level(level)
+        .set_title(title)
+        .set_description(text)
+        .set_buttons(rfd::MessageButtons::Ok)
+        .show();
+}
+
+// Arrow keys and Enter drive the in-window menus like a pad's D-pad
+fn key_nav(window: &Window) -> Option<NavInput> {
+    window.get_keys_pressed(KeyRepeat::No).iter().find_map(|k| match k {
+        Key::Up => Some(NavInput::Up),
+        Key::Down => Some(NavInput::Down),
+        Key::Left => Some(NavInput::Left),
+        Key::Right => Some(NavInput::Right),
+        Key::Enter => Some(NavInput::Confirm),
+        _ => None,
+    })
+}
+
+// Integer upscale of a 160 pixel wide frame: each source row is widened
+// once, then copied down for the rest of its `scale` rows
+fn scale_frame(frame: &[u32], dest: &mut [u32], scale: usize) {
+    let width = 160 * scale;
+    for (y, row) in frame.chunks_exact(160).enumerate() {
+        let top = y * scale * width;
+        for (out, &pixel) in dest[top..top + width].chunks_exact_mut(scale).zip(row) {
+            out.fill(pixel);
+        }
+        for dy in 1..scale {
+            dest.copy_within(top..top + width, top + dy * width);
+        }
     }
 }
 
@@ -111,9 +899,28 @@ fn draw_text(buffer: &mut [u32], width: usize, text: &str, x: usize, y: usize, c
 }
 
 // --- CONTROLLER & KEYBOARD CONFIG WINDOW ---
-fn open_controller_config(mut current_mapping: InputMapping, gilrs: &mut Gilrs) -> InputMapping {
-    const W: usize = 500; // Slightly wider for text
-    const H: usize = 350;
+const INPUT_ROWS: usize = 10;
+const INPUT_LABELS: [&str; INPUT_ROWS] = ["UP", "DOWN", "LEFT", "RIGHT", "A", "B", "START", "SELECT", "TURBO A", "TURBO B"];
+
+// Mapping that controller bindings edit: player 2's own mapping, or the
+// profile of the pad in use (the shared defaults when no pad is connected)
+fn pad_target<'a>(config: &'a mut Config, gilrs: &Gilrs, pad: Option<GamepadId>, player2: bool) -> &'a mut InputMapping {
+    match pad {
+        _ if player2 => &mut config.player2,
+        Some(id) => config.pad_mapping_mut(&gilrs.gamepad(id)),
+        None => &mut config.input,
+    }
+}
+
+// Keyboard bindings and hotkeys are global; controller bindings go to the
+// profile of the pad that was used last (or the first connected one).
+// Player 2 has a single mapping, and the pad used to bind it becomes player
+// 2's pad. Escape cancels a pending bind, or closes the window.
+fn open_controller_config(config: &mut Config, gilrs: &mut Gilrs) {
+    const W: usize = 600;
+    const H: usize = 846;
+    const CLEAR_X: usize = 500;
+    const RESET_X: usize = 540;
     
     let mut config_window = Window::new(
         "Bind Controls (Press Key OR Button)",
@@ -123,20 +930,44 @@ fn open_controller_config(mut current_mapping: InputMapping, gilrs: &mut Gilrs)
 
     let mut buffer = vec![0; W * H];
     let mut binding_target: Option<usize> = None;
-    let row_height = 30;
-    let start_y = 50;
+    let row_height = 22;
+    let start_y = 40;
+    // Hotkey rows follow the game button rows under their own header
+    let hotkey_y = start_y + INPUT_ROWS * row_height + 24;
+    let total_rows = INPUT_ROWS + Hotkey::ALL.len();
+    let mut pad: Option<GamepadId> = gilrs.gamepads().next().map(|(id, _)| id);
+    let mut player2 = false;
+    let mut mouse_was_down = false;
+    // Controller navigation: row 0 is the player header, then game rows, then hotkeys
+    let mut pad_nav = PadNav::new();
+    let mut focus: usize = 0;
 
-    while config_window.is_open() && !config_window.is_key_down(Key::Escape) {
-        for p in buffer.iter_mut() { *p = 0xFF202020; } 
+    while config_window.is_open() {
+        let theme = Theme::get(config.high_contrast_ui);
+        for p in buffer.iter_mut() { *p = theme.window; } 
 
         // 1. POLL INPUTS (Keyboard + Controller)
         let mut pressed_btn = None;
         let mut pressed_key = None;
 
-        // Check Controller
-        while let Some(Event { event, .. }) = gilrs.next_event() {
-            if let EventType::ButtonPressed(btn, _) = event {
-                pressed_btn = Some(btn);
+        // Check Controller (buttons, analog triggers and axes/hats)
+        let mut nav = None;
+        while let Some(Event { id, event, .. }) = gilrs.next_event() {
+            if binding_target.is_none() && let Some(n) = pad_nav.translate(&event) {
+                nav = Some(n);
+                continue;
+            }
+            let input = match event {
+                EventType::ButtonPressed(btn, _) => Some(PadInput::Button(btn)),
+                EventType::ButtonChanged(btn, value, _) if value > AXIS_THRESHOLD => Some(PadInput::Button(btn)),
+                EventType::AxisChanged(axis, value, _) if value.abs() > AXIS_THRESHOLD => {
+                    Some(PadInput::Axis(axis, value > 0.0))
+                }
+                _ => None,
+            };
+            if input.is_some() {
+                pressed_btn = input;
+                pad = Some(id);
             }
         }
         
@@ -146,89 +977,175 @@ fn open_controller_config(mut current_mapping: InputMapping, gilrs: &mut Gilrs)
             pressed_key = Some(keys[0]);
         }
 
+        // Escape never gets bound: it cancels the pending bind or closes
+        if pressed_key == Some(Key::Escape) {
+            if binding_target.is_none() { break; }
+            binding_target = None;
+            pressed_key = None;
+        }
+
         // 2. BINDING LOGIC
         if let Some(idx) = binding_target {
             // We are waiting for input...
             
-            if let Some(btn) = pressed_btn {
+            if idx >= INPUT_ROWS {
+                // Hotkeys are keyboard only
+                if let Some(key) = pressed_key {
+                    *config.hotkeys.key_mut(Hotkey::ALL[idx - INPUT_ROWS]) = key;
+                    binding_target = None;
+                }
+            } else if let Some(btn) = pressed_btn {
                 // User pressed a CONTROLLER BUTTON
-                match idx {
-                    0 => current_mapping.up_btn = btn,
-                    1 => current_mapping.down_btn = btn,
-                    2 => current_mapping.left_btn = btn,
-                    3 => current_mapping.right_btn = btn,
-                    4 => current_mapping.a_btn = btn,
-                    5 => current_mapping.b_btn = btn,
-                    6 => current_mapping.start_btn = btn,
-                    7 => current_mapping.select_btn = btn,
-                    _ => {}
+                if player2 && let Some(id) = pad {
+                    config.player2_pad = Some(Config::pad_key(&gilrs.gamepad(id)));
                 }
+                *pad_target(config, gilrs, pad, player2).pad_mut(idx) = btn;
                 binding_target = None;
             } else if let Some(key) = pressed_key {
                 // User pressed a KEYBOARD KEY
-                match idx {
-                    0 => current_mapping.up_key = key,
-                    1 => current_mapping.down_key = key,
-                    2 => current_mapping.left_key = key,
-                    3 => current_mapping.right_key = key,
-                    4 => current_mapping.a_key = key,
-                    5 => current_mapping.b_key = key,
-                    6 => current_mapping.start_key = key,
-                    7 => current_mapping.select_key = key,
-                    _ => {}
-                }
+                let current_mapping = if player2 { &mut config.player2 } else { &mut config.input };
+                *current_mapping.key_mut(idx) = key;
                 binding_target = None;
             }
         } else {
-            // Waiting for mouse click to select row
-            if config_window.get_mouse_down(MouseButton::Left) {
-                if let Some((_, my)) = config_window.get_mouse_pos(MouseMode::Clamp) {
-                    if my >= start_y as f32 {
-                        let row = ((my as usize) - start_y) / row_height;
-                        if row < 8 { binding_target = Some(row); }
-                    }
+            // Mouse: click a row to bind it, CLR/DEF to clear or reset it
+            let mouse_down = config_window.get_mouse_down(MouseButton::Left);
+            let clicked = mouse_down && !mouse_was_down;
+            if mouse_down && let Some((mx, my)) = config_window.get_mouse_pos(MouseMode::Clamp) {
+                let (mx, my) = (mx as usize, my as usize);
+                let row = if my >= hotkey_y {
+                    Some(INPUT_ROWS + (my - hotkey_y) / row_height).filter(|&r| r < total_rows)
+                } else if my >= start_y {
+                    Some((my - start_y) / row_height).filter(|&r| r < INPUT_ROWS)
+                } else {
+                    None
+                };
+                match row {
+                    // Header click switches between player 1 and 2
+                    None if clicked && my < start_y => player2 = !player2,
+                    Some(r) if clicked && mx >= RESET_X => reset_binding(config, gilrs, pad, player2, r),
+                    Some(r) if clicked && mx >= CLEAR_X => clear_binding(config, gilrs, pad, player2, r),
+                    Some(r) if mx < CLEAR_X => binding_target = Some(r),
+                    _ => {}
                 }
             }
+            mouse_was_down = mouse_down;
+
+            // Controller: confirm binds the focused row
+            match nav {
+                Some(NavInput::Confirm) if focus == 0 => player2 = !player2,
+                Some(NavInput::Confirm) => binding_target = Some(focus - 1),
+                Some(NavInput::Cancel) => break,
+                Some(n) => focus = move_focus(focus, total_rows + 1, n),
+                None => {}
+            }
         }
 
         // 3. DRAW UI
-        draw_text(&mut buffer, W, "CLICK LINE THEN PRESS KEY/BTN", 10, 10, 0xFFFFFF00, 1);
-        
-        let labels = ["UP", "DOWN", "LEFT", "RIGHT", "A", "B", "START", "SELECT"];
-        
-        for i in 0..8 {
+        // Focus highlight for controller navigation
+        let focus_y = match focus {
+            0 => 20,
+            f if f <= INPUT_ROWS => start_y + (f - 1) * row_height - 2,
+            f => hotkey_y + (f - 1 - INPUT_ROWS) * row_height - 2,
+        };
+        for p in &mut buffer[focus_y * W..(focus_y + 12) * W] { *p = theme.focus; }
+
+        draw_text(&mut buffer, W, "CLICK LINE THEN PRESS KEY/BTN (ESC CANCELS)", 10, 10, theme.heading, 1);
+        let key_mapping = if player2 { config.player2 } else { config.input };
+        let other_keys = if player2 { config.input } else { config.player2 };
+        let (pad_name, pad_mapping) = if player2 {
+            let name = gilrs.gamepads()
+                .find(|(_, g)| config.player2_pad.as_ref() == Some(&Config::pad_key(g)))
+                .map_or("NO PAD".to_string(), |(_, g)| g.name().to_string());
+            (name, config.player2)
+        } else {
+            match pad {
+                Some(id) => {
+                    let gamepad = gilrs.gamepad(id);
+                    (gamepad.name().to_string(), *config.pad_mapping(&gamepad))
+                }
+                None => ("NO PAD".to_string(), config.input),
+            }
+        };
+        let player = if player2 { "P2" } else { "P1" };
+        draw_text(&mut buffer, W, &format!("[{}]  PAD: {}", player, pad_name), 10, 22, theme.dim, 1);
+
+        // Conflicts: a key used by two rows (game buttons of either player or
+        // hotkeys), or a pad input used twice in the shown mapping
+        let row_key = |r: usize| if r < INPUT_ROWS { key_mapping.key(r) } else { config.hotkeys.key(Hotkey::ALL[r - INPUT_ROWS]) };
+        let key_conflict = |r: usize| {
+            let k = row_key(r);
+            k != Key::Unknown
+                && ((0..total_rows).any(|o| o != r && row_key(o) == k) || (0..INPUT_ROWS).any(|o| other_keys.key(o) == k))
+        };
+        let pad_conflict = |r: usize| {
+            let b = pad_mapping.pad(r);
+            b != PadInput::Button(GamepadButton::Unknown) && (0..INPUT_ROWS).any(|o| o != r && pad_mapping.pad(o) == b)
+        };
+        let mut any_conflict = false;
+
+        for (i, label) in INPUT_LABELS.iter().enumerate() {
             let y = start_y + (i * row_height);
             let is_binding = binding_target == Some(i);
-            let color = if is_binding { 0xFFFF0000 } else { 0xFFFFFFFF };
-            
-            // Get current values to display
-            let (btn, key) = match i {
-                0 => (current_mapping.up_btn, current_mapping.up_key),
-                1 => (current_mapping.down_btn, current_mapping.down_key),
-                2 => (current_mapping.left_btn, current_mapping.left_key),
-                3 => (current_mapping.right_btn, current_mapping.right_key),
-                4 => (current_mapping.a_btn, current_mapping.a_key),
-                5 => (current_mapping.b_btn, current_mapping.b_key),
-                6 => (current_mapping.start_btn, current_mapping.start_key),
-                7 => (current_mapping.select_btn, current_mapping.select_key),
-                _ => (GamepadButton::Unknown, Key::Unknown),
-            };
+            let conflict = key_conflict(i) || pad_conflict(i);
+            any_conflict |= conflict;
+            let color = if is_binding { 0xFFFF0000 } else if conflict { 0xFFFFAA00 } else { theme.text };
 
             let val_str = if is_binding { 
                 "Waiting for Input...".to_string() 
             } else { 
-                // Show "Btn: X  Key: Z"
-                format!("Btn:{:?}  Key:{:?}", btn, key) 
+                // Show "Pad: X  Key: Z"
+                format!("Pad:{}  Key:{:?}", pad_mapping.pad(i).name(), key_mapping.key(i)) 
             };
             
-            draw_text(&mut buffer, W, labels[i], 20, y, color, 1);
+            draw_text(&mut buffer, W, label, 20, y, color, 1);
             draw_text(&mut buffer, W, &val_str, 100, y, color, 1);
+            draw_text(&mut buffer, W, "CLR", CLEAR_X, y, theme.faint, 1);
+            draw_text(&mut buffer, W, "DEF", RESET_X, y, theme.faint, 1);
+        }
+
+        draw_text(&mut buffer, W, "HOTKEYS (KEYBOARD)", 10, hotkey_y - 18, theme.heading, 1);
+        for (i, hk) in Hotkey::ALL.iter().enumerate() {
+            let y = hotkey_y + (i * row_height);
+            let is_binding = binding_target == Some(INPUT_ROWS + i);
+            let conflict = key_conflict(INPUT_ROWS + i);
+            any_conflict |= conflict;
+            let color = if is_binding { 0xFFFF0000 } else if conflict { 0xFFFFAA00 } else { theme.text };
+            let val_str = if is_binding { "Waiting for Key...".to_string() } else { format!("Key:{:?}", config.hotkeys.key(*hk)) };
+            draw_text(&mut buffer, W, hk.label(), 20, y, color, 1);
+            draw_text(&mut buffer, W, &val_str, 140, y, color, 1);
+            draw_text(&mut buffer, W, "CLR", CLEAR_X, y, theme.faint, 1);
+            draw_text(&mut buffer, W, "DEF", RESET_X, y, theme.faint, 1);
+        }
+
+        if any_conflict {
+            draw_text(&mut buffer, W, "ORANGE ROWS SHARE A KEY OR BUTTON", 10, H - 16, 0xFFFFAA00, 1);
         }
 
         config_window.update_with_buffer(&buffer, W, H).unwrap();
     }
-    
-    current_mapping
+}
+
+fn clear_binding(config: &mut Config, gilrs: &Gilrs, pad: Option<GamepadId>, player2: bool, row: usize) {
+    if row >= INPUT_ROWS {
+        *config.hotkeys.key_mut(Hotkey::ALL[row - INPUT_ROWS]) = Key::Unknown;
+        return;
+    }
+    *pad_target(config, gilrs, pad, player2).pad_mut(row) = PadInput::Button(GamepadButton::Unknown);
+    let keys = if player2 { &mut config.player2 } else { &mut config.input };
+    *keys.key_mut(row) = Key::Unknown;
+}
+
+fn reset_binding(config: &mut Config, gilrs: &Gilrs, pad: Option<GamepadId>, player2: bool, row: usize) {
+    if row >= INPUT_ROWS {
+        let hk = Hotkey::ALL[row - INPUT_ROWS];
+        *config.hotkeys.key_mut(hk) = HotkeyMapping::default().key(hk);
+        return;
+    }
+    let defaults = if player2 { InputMapping::player2() } else { InputMapping::default() };
+    *pad_target(config, gilrs, pad, player2).pad_mut(row) = defaults.pad(row);
+    let keys = if player2 { &mut config.player2 } else { &mut config.input };
+    *keys.key_mut(row) = defaults.key(row);
 }
 
 fn main() {
@@ -244,11 +1161,63 @@ fn main() {
     const SS_WIDTH: usize = TOTAL_WIDTH * SS_SCALE;
     const SS_HEIGHT: usize = TOTAL_HEIGHT * SS_SCALE;
 
+    let mut args = match Args::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("✗ {}", e);
+            std::process::exit(2);
+        }
+    };
+    if args.test_roms.is_some() {
+        std::process::exit(test_roms::run(&args));
+    }
+    if args.compat_report.is_some() {
+        std::process::exit(compat::run(&args));
+    }
+    if args.verify_movie.is_some() {
+        std::process::exit(headless::verify_movie(&args));
+    }
+    if args.headless {
+        std::process::exit(headless::run(&args));
+    }
+    if args.debug_tui {
+        std::process::exit(debug_tui::run(&args));
+    }
+
+    let data_dir = match &args.data_dir {
+        Some(dir) => Some(std::path::PathBuf::from(dir)),
+        None if args.portable => config::exe_dir(),
+        None => config::portable_marker_dir(),
+    };
+    if let Some(dir) = data_dir {
+        args.absolutize();
+        if let Err(e) = config::use_data_dir(&dir) {
+            eprintln!("✗ Can't use data folder '{}': {}", dir.display(), e);
+            std::process::exit(2);
+        }
+    }
+
+    // Session requested on the command line, picked up by the first frame
+    let mut startup_rom = args.rom.clone();
+    let mut startup_state = args.state.clone();
+    let mut startup_movie = match &args.movie {
+        Some(path) => match fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|t| Movie::from_input_log(&t)) {
+            Ok(movie) => Some(movie),
+            Err(e) => {
+                eprintln!("✗ Bad movie '{}': {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let mut config = Config::load();
+
     // --- WINDOW SETUP ---
     let mut window = Window::new(
         "Rust Game Boy",
-        160 * 3, 
-        (144 + MENU_HEIGHT) * 3,
+        160 * config.window_scale, 
+        (144 + MENU_HEIGHT) * config.window_scale,
         WindowOptions {
             resize: true,
             scale: Scale::FitScreen,
@@ -257,40 +1226,131 @@ fn main() {
         },
     ).unwrap();
 
-    window.limit_update_rate(Some(Duration::from_micros(16600)));
+    // Pacing is done by FramePacer, not minifb's (16.6ms, 60.2 Hz) limiter
+    window.limit_update_rate(None);
+    let mut pacer = FramePacer::new();
+    let mut display_pacer = FramePacer::with_hz(config.display_hz);
 
     let mut window_buffer: Vec<u32> = vec![0; SS_WIDTH * SS_HEIGHT];
+    // The last game frame shown and its upscale, so an unchanged frame (paused,
+    // menus, a game waiting on input) is copied instead of scaled again
+    let mut shown_frame: Vec<u32> = Vec::new();
+    let mut scaled_frame: Vec<u32> = vec![0; SS_WIDTH * GB_HEIGHT * SS_SCALE];
+    // The running game's border (or the global one), and the window's
+    // contents framed by it
+    let load_border = |path: &str| {
+        if path.is_empty() { return None; }
+        Border::load(path, TOTAL_WIDTH, TOTAL_HEIGHT, SS_SCALE).map_err(|e| eprintln!("✗ Border: {}", e)).ok()
+    };
+    let mut border = load_border(&config.border);
+    let mut framed_buffer: Vec<u32> = Vec::new();
 
     // --- INIT ---
     let mut current_emulator: Option<EmulatorState> = None;
     let mut gilrs = Gilrs::new().unwrap(); 
-    let mut mapping = InputMapping::default(); 
+    let mut scope_window: Option<ScopeWindow> = None;
+    let mut apu_panel: Option<ApuPanel> = None;
+    let mut event_viewer: Option<EventViewer> = None;
+    let mut memory_editor: Option<MemoryEditor> = None;
+    let mut tilemap_viewer: Option<TilemapViewer> = None;
+    let mut link: Option<LinkedGameBoy> = None;
+    let mut bgb_link = args.bgb_link.as_ref().and_then(|spec| {
+        BgbLink::open(spec).map_err(|e| eprintln!("✗ BGB link '{}': {}", spec, e)).ok()
+    });
+    let mut spectators = if config.spectator.enabled {
+        SpectatorServer::bind(config.spectator.port, config.spectator.audio)
+            .map_err(|e| eprintln!("✗ Spectator stream: {}", e))
+            .ok()
+    } else {
+        None
+    };
+    let mut debug_server = if config.remote_debug.enabled {
+        DebugServer::bind(config.remote_debug.port, config.remote_debug.allow_remote)
+            .map_err(|e| eprintln!("✗ Remote debugger: {}", e))
+            .ok()
+    } else {
+        None
+    };
+    let mut gdb_server = args.gdb.and_then(|port| {
+        GdbServer::bind(port)
+            .map_err(|e| eprintln!("✗ gdb server: {}", e))
+            .ok()
+    });
+    // An attached gdb's breakpoints while no game is loaded
+    let mut gdb_breakpoints = Vec::new();
+    // Made on first use: it reads standard input from then on
+    let mut debug_repl: Option<DebugRepl> = None;
+    let mut open_debugger = args.debug;
+    let mut presence = match &config.discord {
+        d if d.enabled && d.client_id.is_empty() => {
+            eprintln!("✗ Discord presence needs discord.client_id in the config");
+            None
+        }
+        d if d.enabled => Some(Presence::new(&d.client_id)),
+        _ => None,
+    };
+    let mut game_started = SystemTime::now();
+    let mut splitter: Option<AutoSplitter> = None;
+    let mut macro_to_bind: Option<Vec<u8>> = None; // Recorded, waiting for its key
 
     // Audio
     let host = cpal::default_host();
-    let device = host.default_output_device().expect("No output device found");
-    let config = device.default_output_config().unwrap();
-    let audio_buffer = Arc::new(Mutex::new(Vec::<f32>::new()));
-    let cb_buffer = Arc::clone(&audio_buffer);
-    let stream = device.build_output_stream(
-        &config.into(),
-        move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-            let mut buffer = cb_buffer.lock().unwrap();
-            for sample in data.iter_mut() {
-                *sample = if buffer.len() > 0 { buffer.remove(0) } else { 0.0 };
-            }
-        },
-        |err| eprintln!("Err: {}", err),
-        None
-    ).unwrap();
-    stream.play().unwrap();
+    let device = host.default_output_device();
+    let (audio_tx, audio_rx) = mpsc::channel::<AudioCommand>();
+    let synth = AudioSynth::new(audio_rx);
+    let audio_clock = synth.clock_handle();
+    let synth = Arc::new(Mutex::new(synth));
+    let audio_stats = Arc::new(AudioStats::default());
+    let mut speed: u32 = 1;
+    let mut fast_forward_latched = false;
+    // Without sound the game still runs, silent and paced by video sync
+    let no_audio = |e: String| {
+        show_dialog(
+            rfd::MessageLevel::Warning,
+            "No sound",
+            &format!("Audio is unavailable: {}.\n\nGames will run without sound. Check that speakers or headphones are connected and not in exclusive use by another program, then restart gb_rs.", e),
+        );
+    };
+    let mut stream = audio::start_stream(device.as_ref(), Arc::clone(&synth), Arc::clone(&audio_stats), config.audio.low_latency)
+        .map_err(no_audio)
+        .ok();
+    let _ = audio_tx.send(AudioCommand::SetStereoMode(config.audio.stereo_mode));
+    let _ = audio_tx.send(AudioCommand::SetFastForwardAudio(config.audio.fast_forward));
 
     let mut last_save = Instant::now();
+    let mut save_error_shown = false;
+    let mut paused = false;
+    let mut resume_offer = false;
+    let mut state_slot: u32 = 1; // 1-10, picked with the number keys
+    let mut last_auto_state: u64 = 0; // frame_count at the last auto-state
+    let mut osd = Osd::new();
+    let mut turbo_frame: u32 = 0;
+    let mut pad_events: Vec<Event> = Vec::new();
+    let mut audio_paused = false;
+    let mut lock_reported = false; // The current lock-up has been shown
+    let mut fault_reported = false; // Likewise for the current CPU error
+    let mut pad_nav = PadNav::new();
+    let mut menu_focus: Option<usize> = None; // Menu strip row focused from a controller
+    let mut browser: Option<RomBrowser> = None;
+    let mut state_picker: Option<StatePicker> = None;
+    let mut show_rom_info = false;
+    let mut show_ram_watch = true;
+    let mut perf_overlay: Option<PerfOverlay> = None; // Measuring only while shown
+    let mut quick_menu = QuickMenu::new();
+    let mut game_settings: Option<GameSettingsMenu> = None; // Pause menu page
+    let mut game_speed: u32 = 1; // The running game's speed override
+    let mut watcher: Option<RomWatcher> = None; // With --watch / watch_rom, follows the loaded ROM file
+    // Kept alive: on X11 copied text is only served while this exists
+    let mut clipboard = arboard::Clipboard::new().ok();
+    let mut input_pad: Option<GamepadId> = None; // Pad that last sent player 1 input
+    let mut pad_lost = false; // Paused because input_pad disconnected
 
     // --- MAIN LOOP ---
     while window.is_open() && !window.is_key_down(Key::Escape) {
+        if let Some(perf) = &mut perf_overlay { perf.start(); }
         
-        let mut rom_to_load: Option<String> = None;
+        let mut rom_to_load: Option<String> = startup_rom.take();
+        let mut pending_movie: Option<Movie> = startup_movie.take();
         let mut is_hovering_load = false;
         let mut is_hovering_config = false;
         let mut open_config_requested = false;
@@ -298,14 +1358,18 @@ fn main() {
         // --- ROBUST MOUSE LOGIC ---
         if let Some((mx, my)) = window.get_mouse_pos(MouseMode::Discard) {
             
-            // 1. Geometry
+            // 1. Geometry (a border moves the strip into its middle)
             let (win_w, win_h) = window.get_size();
-            let scale_x = win_w as f32 / TOTAL_WIDTH as f32;
-            let scale_y = win_h as f32 / TOTAL_HEIGHT as f32;
+            let (total_w, total_h) = border.as_ref().map_or((TOTAL_WIDTH, TOTAL_HEIGHT), |b| (b.size().0 / SS_SCALE, b.size().1 / SS_SCALE));
+            let (origin_x, origin_y) = border.as_ref().map_or((0, 0), |b| b.origin(TOTAL_WIDTH, TOTAL_HEIGHT));
+            let scale_x = win_w as f32 / total_w as f32;
+            let scale_y = win_h as f32 / total_h as f32;
             let scale = scale_x.min(scale_y); 
 
-            let draw_h = TOTAL_HEIGHT as f32 * scale;
-            let offset_y = (win_h as f32 - draw_h) / 2.0;
+            let draw_h = total_h as f32 * scale;
+            let offset_y = (win_h as f32 - draw_h) / 2.0 + origin_y as f32 * scale;
+            let strip_left = (win_w as f32 - total_w as f32 * scale) / 2.0 + origin_x as f32 * scale;
+            let in_strip = border.is_none() || (mx >= strip_left && mx < strip_left + TOTAL_WIDTH as f32 * scale);
 
             // 2. Define Strips using manual 3.00 scaling (as requested)
             let strip1_top = offset_y;
@@ -315,16 +1379,20 @@ fn main() {
             let strip2_bot = offset_y + (4.0 * 3.00); 
 
             // 3. Logic
-            if mx < (win_w as f32 * 1.00) {
+            if mx < (win_w as f32 * 1.00) && in_strip {
                 // Check Row 1 (Load)
                 if my >= strip1_top && my < strip1_bot {
                     is_hovering_load = true;
                     if window.get_mouse_down(MouseButton::Left) {
                         let file = FileDialog::new()
                             .add_filter("Game Boy", &["gb", "gbc", "bin"])
-                            .set_directory(".")
+                            .set_directory(&config.paths.rom_dir)
                             .pick_file();
                         if let Some(path) = file {
+                            if let Some(dir) = path.parent() {
+                                config.set_rom_dir(dir);
+                                config.save();
+                            }
                             rom_to_load = Some(path.to_string_lossy().to_string());
                         }
                     }
@@ -339,99 +1407,1033 @@ fn main() {
             }
         }
 
+        // --- GAMEPADS ---
+        // Hot-plug notifications and menu navigation
+        let mut nav: Option<NavInput> = None;
+        let mut menu_button = false;
+        let mut resume_after_pad_lost = false;
+        let player2_pad_key = config.player2_pad.clone();
+        while let Some(ev) = gilrs.next_event() { pad_events.push(ev); }
+        for Event { id, event, .. } in pad_events.drain(..) {
+            let gamepad = gilrs.gamepad(id);
+            match event {
+                EventType::Connected => {
+                    let saved = config.pad_profiles.contains_key(&Config::pad_key(&gamepad));
+                    osd.show(&format!("Pad connected: {}{}", gamepad.name(), if saved { " (profile)" } else { "" }));
+                }
+                EventType::Disconnected => {
+                    osd.show(&format!("Pad disconnected: {}", gamepad.name()));
+                    // Auto-pause if this was the pad playing the game
+                    if config.pause_on_disconnect && input_pad == Some(id) && current_emulator.is_some() && !paused {
+                        paused = true;
+                        pad_lost = true;
+                        osd.prompt = Some("RECONNECT PAD OR PRESS A KEY".to_string());
+                    }
+                }
+                EventType::ButtonPressed(..) | EventType::AxisChanged(..) if player2_pad_key.as_ref() != Some(&Config::pad_key(&gamepad)) => {
+                    input_pad = Some(id);
+                }
+                EventType::ButtonPressed(GamepadButton::Mode, _) => menu_button = true,
+                _ => {}
+            }
+            if let Some(n) = pad_nav.translate(&event) { nav = Some(n); }
+            if pad_lost && event == EventType::Connected {
+                resume_after_pad_lost = true;
+            }
+        }
+        if pad_lost && !window.get_keys_pressed(KeyRepeat::No).is_empty() {
+            resume_after_pad_lost = true;
+        }
+        if resume_after_pad_lost {
+            paused = false;
+            pad_lost = false;
+            osd.prompt = None;
+        }
+
+        // The guide button (or any D-pad/confirm input while no game runs)
+        // moves focus to the menu strip; the game is paused while it's there
+        if let Some(b) = &mut browser {
+            if let Some(nav) = nav {
+                match b.nav(nav, RomBrowser::visible_lines(GB_HEIGHT * SS_SCALE)) {
+                    BrowserResult::Picked(path) => {
+                        config.set_rom_dir(&b.dir);
+                        config.save();
+                        rom_to_load = Some(path.to_string_lossy().to_string());
+                        browser = None;
+                        menu_focus = None;
+                    }
+                    BrowserResult::Cancelled => browser = None,
+                    BrowserResult::Open => {}
+                }
+            }
+        } else if let Some(focus) = menu_focus {
+            match nav {
+                Some(NavInput::Confirm) if focus == 0 => browser = Some(RomBrowser::new(&config.paths.rom_dir)),
+                Some(NavInput::Confirm) => open_config_requested = true,
+                Some(NavInput::Cancel) => menu_focus = None,
+                Some(n) => menu_focus = Some(move_focus(focus, 2, n)),
+                None => {}
+            }
+            if menu_button { menu_focus = None; }
+        } else if menu_button && current_emulator.is_some() && !paused {
+            // Pause into the quick menu; pressing it again there goes on to the strip
+            paused = true;
+            quick_menu = QuickMenu::new();
+        } else if menu_button || (current_emulator.is_none() && nav.is_some()) {
+            menu_focus = Some(0);
+        }
+
+        // Pause menu: its actions stand in for the matching hotkeys below
+        let mut quick_hotkey = None;
+        if !paused {
+            game_settings = None;
+        }
+        if paused
+            && !pad_lost
+            && let Some(page) = &mut game_settings
+            && let Some(emu) = &mut current_emulator
+        {
+            // Overrides take effect right away, except the model
+            if let Some(n) = key_nav(&window).or(nav) {
+                let key = emu.game_key();
+                let mut game = config.games.get(&key).cloned().unwrap_or_default();
+                if page.nav(n, &mut game) {
+                    game_settings = None;
+                }
+                if config.games.get(&key).cloned().unwrap_or_default() != game {
+                    if game == config::GameOverrides::default() {
+                        config.games.remove(&key);
+                    } else {
+                        config.games.insert(key.clone(), game);
+                    }
+                    config.save();
+                    emu.cpu.ppu.set_palette(config.palette_for(&key));
+                    game_speed = config.speed_for(&key);
+                }
+            }
+        } else if paused && !pad_lost && menu_focus.is_none() && browser.is_none() && state_picker.is_none() {
+            match key_nav(&window).or(nav).and_then(|n| quick_menu.nav(n)) {
+                Some(QuickAction::Resume) => paused = false,
+                Some(QuickAction::GameSettings) => game_settings = Some(GameSettingsMenu::new()),
+                // Games read battery RAM at boot, so an import restarts the game
+                Some(QuickAction::ImportSave) => {
+                    if let Some(emu) = &mut current_emulator {
+                        let file = FileDialog::new().add_filter("Save file", &["sav", "srm", "sa1"]).pick_file();
+                        match file.map(fs::read) {
+                            _ if !emu.cpu.bus.has_battery => osd.show("This game has no battery save"),
+                            Some(Ok(data)) => {
+                                let note = emu.cpu.bus.import_save(&data);
+                                emu.cpu.bus.save_ram();
+                                rom_to_load = Some(emu.mmu_filename.clone());
+                                osd.show(&format!("Save imported ({})", note));
+                            }
+                            Some(Err(e)) => show_dialog(rfd::MessageLevel::Error, "Can't import save", &format!("Couldn't read the file: {}.", e)),
+                            None => {}
+                        }
+                    }
+                }
+                Some(QuickAction::ImportState) => {
+                    if let Some(emu) = &mut current_emulator
+                        && let Some(path) = FileDialog::new().add_filter("Save state", &["state"]).pick_file()
+                    {
+                        match import_state(emu, &path) {
+                            Ok(true) => {
+                                let _ = audio_tx.send(AudioCommand::LoadState(emu.apu_snapshot()));
+                                osd.show("State imported");
+                            }
+                            Ok(false) => {}
+                            Err(e) => show_dialog(rfd::MessageLevel::Error, "Can't import state", &e),
+                        }
+                    }
+                }
+                Some(QuickAction::ExportState) => {
+                    if let Some(emu) = &current_emulator {
+                        let file = FileDialog::new()
+                            .add_filter("Save state", &["state"])
+                            .set_file_name(format!("{}.state", emu.file_stem()))
+                            .save_file();
+                        if let Some(path) = file {
+                            match fs::write(&path, savestate::export_portable(&emu.save_state(), &emu.sha1)) {
+                                Ok(()) => osd.show("State exported"),
+                                Err(e) => show_dialog(rfd::MessageLevel::Error, "Can't export state", &format!("Couldn't write '{}': {}.", path.display(), e)),
+                            }
+                        }
+                    }
+                }
+                Some(QuickAction::ExportSave) => {
+                    if let Some(emu) = &current_emulator {
+                        let file = FileDialog::new()
+                            .add_filter("Save file", &["sav"])
+                            .set_file_name(format!("{}.sav", emu.file_stem()))
+                            .save_file();
+                        if let Some(path) = file {
+                            match fs::write(&path, emu.cpu.bus.export_save()) {
+                                Ok(()) => osd.show("Save exported"),
+                                Err(e) => show_dialog(rfd::MessageLevel::Error, "Can't export save", &format!("Couldn't write '{}': {}.", path.display(), e)),
+                            }
+                        }
+                    }
+                }
+                Some(QuickAction::Hotkey(hotkey)) => quick_hotkey = Some(hotkey),
+                Some(QuickAction::QuitToLibrary) => {
+                    if let Some(emu) = &mut current_emulator {
+                        emu.close(config.resume_on_launch);
+                        if gdb_server.as_ref().is_some_and(|g| g.attached()) {
+                            gdb_breakpoints = std::mem::take(&mut emu.breakpoints);
+                        }
+                    }
+                    if let Some(mut p2) = link.take() {
+                        p2.emu.cpu.bus.save_ram();
+                    }
+                    current_emulator = None;
+                    watcher = None;
+                    splitter = None;
+                    border = load_border(&config.border);
+                    paused = false;
+                    show_rom_info = false;
+                    let _ = audio_tx.send(AudioCommand::Reset(config.model));
+                    window.set_title("Rust Game Boy");
+                    browser = Some(RomBrowser::new(&config.paths.rom_dir));
+                }
+                None => {}
+            }
+        }
+
+        // Cycle the shade presets (the game's colors change right away)
+        // A game with its own palette cycles that one instead.
+        if config.hotkeys.pressed(&window, Hotkey::Palette) || quick_hotkey == Some(Hotkey::Palette) {
+            let game = current_emulator.as_ref().map(|e| e.game_key()).and_then(|key| config.games.get_mut(&key));
+            let palette = match game {
+                Some(config::GameOverrides { palette: Some(p), .. }) => p,
+                _ => &mut config.palette,
+            };
+            let (name, next) = theme::next_palette(*palette);
+            *palette = next;
+            let palette = next;
+            config.save();
+            if let Some(emu) = &mut current_emulator { emu.cpu.ppu.set_palette(palette); }
+            if let Some(p2) = &mut link { p2.emu.cpu.ppu.set_palette(palette); }
+            osd.show(&format!("Palette: {}", name));
+        }
+
+        if config.hotkeys.pressed(&window, Hotkey::RamWatch) {
+            show_ram_watch = !show_ram_watch;
+            osd.show(if show_ram_watch { "RAM watch: on" } else { "RAM watch: off" });
+        }
+        if config.hotkeys.pressed(&window, Hotkey::PerfOverlay) {
+            perf_overlay = if perf_overlay.is_some() { None } else { Some(PerfOverlay::new()) };
+        }
+
+        // ROM info panel; C copies the checksums while it's up
+        if config.hotkeys.pressed(&window, Hotkey::RomInfo) {
+            show_rom_info = !show_rom_info && current_emulator.is_some();
+        }
+        if show_rom_info
+            && window.is_key_pressed(Key::C, KeyRepeat::No)
+            && let Some(emu) = &current_emulator
+        {
+            let text = format!("CRC32: {:08x}\nSHA-1: {}", emu.crc32, checksum::hex(&emu.sha1));
+            match clipboard.as_mut().map(|c| c.set_text(text)) {
+                Some(Ok(())) => osd.show("Checksums copied"),
+                _ => osd.show("Clipboard unavailable"),
+            }
+        }
+
+        // Save-state picker: its hotkey opens and closes it, arrows/Enter or
+        // the pad choose a slot to load
+        let mut load_slot = false;
+        if config.hotkeys.pressed(&window, Hotkey::StatePicker) {
+            state_picker = match (&state_picker, &current_emulator) {
+                (None, Some(emu)) => Some(StatePicker::new((1..=10).map(|n| emu.slot_path(n)), state_slot)),
+                _ => None,
+            };
+        }
+        if let Some(picker) = &mut state_picker {
+            match key_nav(&window).or(nav).map(|n| picker.nav(n)) {
+                Some(PickerResult::Picked(slot)) => {
+                    state_slot = slot;
+                    load_slot = true;
+                    state_picker = None;
+                }
+                Some(PickerResult::Cancelled) => state_picker = None,
+                _ => {}
+            }
+        }
+        let menu_open = menu_focus.is_some() || browser.is_some() || state_picker.is_some();
+
         // --- HANDLE CONFIG OPEN ---
         if open_config_requested {
-            mapping = open_controller_config(mapping, &mut gilrs);
+            open_controller_config(&mut config, &mut gilrs);
+            config.save();
             // Must clear inputs to prevent stuck keys after closing window
             while let Some(_) = gilrs.next_event() {} 
         }
 
+        // Cycle mono / stereo / wide output
+        if config.hotkeys.pressed(&window, Hotkey::StereoMode) {
+            config.audio.stereo_mode = config.audio.stereo_mode.next();
+            let _ = audio_tx.send(AudioCommand::SetStereoMode(config.audio.stereo_mode));
+            println!("Audio output: {:?}", config.audio.stereo_mode);
+            config.save();
+        }
+
+        // Mute or skip audio while fast-forwarding
+        if config.hotkeys.pressed(&window, Hotkey::FastForwardAudio) {
+            config.audio.fast_forward = config.audio.fast_forward.next();
+            let _ = audio_tx.send(AudioCommand::SetFastForwardAudio(config.audio.fast_forward));
+            println!("Fast-forward audio: {:?}", config.audio.fast_forward);
+            config.save();
+        }
+
+        // Toggle the low-latency audio preset (rebuilds the stream)
+        if config.hotkeys.pressed(&window, Hotkey::LowLatency) {
+            config.audio.low_latency = !config.audio.low_latency;
+            drop(stream);
+            stream = audio::start_stream(device.as_ref(), Arc::clone(&synth), Arc::clone(&audio_stats), config.audio.low_latency)
+                .map_err(no_audio)
+                .ok();
+            println!("Low-latency audio: {}", if config.audio.low_latency { "ON" } else { "OFF" });
+            config.save();
+        }
+
+        // Switch between video-paced and audio-clocked emulation
+        if config.hotkeys.pressed(&window, Hotkey::SyncMode) {
+            config.audio.sync_mode = if config.audio.sync_mode == SyncMode::Video { SyncMode::Audio } else { SyncMode::Video };
+            pacer.reset();
+            println!("Sync mode: {:?}", config.audio.sync_mode);
+            config.save();
+        }
+
+        // Pause / resume
+        if config.hotkeys.pressed(&window, Hotkey::Pause) && current_emulator.is_some() {
+            paused = !paused;
+            quick_menu = QuickMenu::new();
+        }
+
+        // Fast-forward at 4x while held (or until pressed again in toggle mode),
+        // on top of any --speed
+        let fast_forward = config.hotkeys.active(&window, Hotkey::FastForward, config.fast_forward_mode, &mut fast_forward_latched);
+        let new_speed = args.speed * game_speed * if fast_forward { 4 } else { 1 };
+        if new_speed != speed {
+            speed = new_speed;
+            let _ = audio_tx.send(AudioCommand::SetSpeed(speed));
+        }
+
+        // Which pad drives the joypad (None = all of them)
+        let active_pad = config.active_pad.as_ref().and_then(|key| {
+            gilrs.gamepads().find(|(_, g)| Config::pad_key(g) == *key).map(|(id, _)| id)
+        });
+
+        let player2_pad = config.player2_pad.as_ref().and_then(|key| {
+            gilrs.gamepads().find(|(_, g)| Config::pad_key(g) == *key).map(|(id, _)| id)
+        });
+
+        // Cycle All pads -> pad 1 -> pad 2 -> ... -> All pads
+        if config.hotkeys.pressed(&window, Hotkey::NextPad) {
+            let pads: Vec<_> = gilrs.gamepads().collect();
+            let next = match active_pad.and_then(|id| pads.iter().position(|(p, _)| *p == id)) {
+                Some(i) => pads.get(i + 1),
+                None => pads.first(),
+            };
+            match next {
+                Some((_, g)) => {
+                    config.active_pad = Some(Config::pad_key(g));
+                    osd.show(&format!("Input: {}", g.name()));
+                }
+                None => {
+                    config.active_pad = None;
+                    osd.show("Input: all pads");
+                }
+            }
+            config.save();
+        }
+
+        // Start/stop movie recording (restarts the game), export on stop
+        if config.hotkeys.pressed(&window, Hotkey::RecordMovie)
+            && let Some(emu) = &mut current_emulator
+        {
+            match emu.movie.take() {
+                Some(movie) if movie.mode == MovieMode::Recording => {
+                    let file = FileDialog::new()
+                        .add_filter("Input log", &["txt"])
+                        .set_file_name(format!("{} Input Log.txt", emu.file_stem()))
+                        .save_file();
+                    if let Some(path) = file {
+                        match fs::write(&path, movie.to_input_log()) {
+                            Ok(()) => osd.show(&format!("Movie saved ({} frames)", movie.frames.len())),
+                            Err(e) => osd.show(&format!("Movie save failed: {}", e)),
+                        }
+                    }
+                }
+                _ => {
+                    emu.cpu.bus.save_ram();
+                    pending_movie = Some(Movie::recording());
+                    rom_to_load = Some(emu.mmu_filename.clone());
+                    osd.show("Recording movie");
+                }
+            }
+        }
+
+        // Import an input log and play it from power-on
+        if config.hotkeys.pressed(&window, Hotkey::PlayMovie)
+            && let Some(emu) = &mut current_emulator
+        {
+            let file = FileDialog::new().add_filter("Input log", &["txt"]).pick_file();
+            if let Some(path) = file {
+                match fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|t| Movie::from_input_log(&t)) {
+                    Ok(movie) => {
+                        osd.show(&format!("Playing movie ({} frames)", movie.frames.len()));
+                        emu.cpu.bus.save_ram();
+                        pending_movie = Some(movie);
+                        rom_to_load = Some(emu.mmu_filename.clone());
+                    }
+                    Err(e) => osd.show(&format!("Bad movie: {}", e)),
+                }
+            }
+        }
+
+        // Input macros: the record hotkey starts and stops a recording, then
+        // the next key pressed becomes the macro's key (Esc discards it). A
+        // macro's key plays it once, Shift+key repeats it; either stops it.
+        if let Some(frames) = &macro_to_bind
+            && let Some(key) = window.get_keys_pressed(KeyRepeat::No).into_iter().find(|k| !matches!(k, Key::LeftShift | Key::RightShift))
+        {
+            let i = &config.input;
+            let game_keys = [i.up_key, i.down_key, i.left_key, i.right_key, i.a_key, i.b_key, i.start_key, i.select_key, i.turbo_a_key, i.turbo_b_key];
+            if key == Key::Escape {
+                osd.show("Macro discarded");
+            } else if Hotkey::ALL.iter().any(|&h| config.hotkeys.key(h) == key) || game_keys.contains(&key) {
+                osd.show(&format!("{:?} is already in use, macro discarded", key));
+            } else {
+                osd.show(&format!("Macro bound to {:?} ({} frames)", key, frames.len()));
+                config.macros.retain(|m| m.key != key);
+                config.macros.push(InputMacro::new(key, frames));
+                config.save();
+            }
+            osd.prompt = None;
+            macro_to_bind = None;
+        } else if let Some(emu) = &mut current_emulator {
+            if config.hotkeys.pressed(&window, Hotkey::RecordMacro) {
+                match emu.macro_recording.take() {
+                    Some(frames) if frames.is_empty() => osd.show("Macro empty"),
+                    Some(frames) => {
+                        osd.prompt = Some("PRESS A KEY FOR THE MACRO (ESC DISCARDS)".to_string());
+                        macro_to_bind = Some(frames);
+                    }
+                    None => {
+                        emu.macro_recording = Some(Vec::new());
+                        osd.show("Recording macro");
+                    }
+                }
+            }
+            let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+            for m in &config.macros {
+                if window.is_key_pressed(m.key, KeyRepeat::No) {
+                    if emu.macro_player.as_ref().is_some_and(|p| p.key == m.key) {
+                        emu.macro_player = None;
+                    } else {
+                        emu.macro_player = Some(MacroPlayer::new(m, shift));
+                    }
+                }
+            }
+        }
+
+        // Plug a second Game Boy into the link port, or unplug it. Closing
+        // player 2's window unplugs it too.
+        if (config.hotkeys.pressed(&window, Hotkey::LinkCable) || link.as_ref().is_some_and(|l| !l.is_open()))
+            && let Some(emu) = &current_emulator
+        {
+            if let Some(mut p2) = link.take() {
+                p2.emu.cpu.bus.save_ram();
+                osd.show("Link cable unplugged");
+            } else {
+                let file = FileDialog::new()
+                    .add_filter("Game Boy", &["gb", "gbc", "bin"])
+                    .set_directory(&config.paths.rom_dir)
+                    .pick_file();
+                if let Some(path) = file {
+                    match LinkedGameBoy::open(&path.to_string_lossy(), &emu.mmu_filename, config.model, config.palette) {
+                        Ok(p2) => {
+                            link = Some(p2);
+                            osd.show("Player 2 linked");
+                        }
+                        Err(e) => show_dialog(rfd::MessageLevel::Error, "Can't load ROM", &e),
+                    }
+                }
+            }
+        }
+
+        // Reset by reloading the current ROM (battery RAM is flushed first)
+        // Hard reset: reload the ROM from scratch (battery RAM is saved first and read back)
+        if (config.hotkeys.pressed(&window, Hotkey::Reset) || quick_hotkey == Some(Hotkey::Reset))
+            && let Some(emu) = &mut current_emulator
+        {
+            emu.cpu.bus.save_ram();
+            rom_to_load = Some(emu.mmu_filename.clone());
+            osd.show("Hard reset");
+        }
+        if config.hotkeys.pressed(&window, Hotkey::SoftReset)
+            && let Some(emu) = &mut current_emulator
+        {
+            emu.cpu.soft_reset();
+            emu.fault = None;
+            osd.show("Soft reset");
+        }
+
+        // Answer the "continue where you left off" offer. The exit state is
+        // used up either way.
+        if resume_offer && let Some(emu) = &mut current_emulator {
+            let keys = window.get_keys_pressed(KeyRepeat::No);
+            let yes = keys.contains(&Key::Enter) || nav == Some(NavInput::Confirm);
+            if yes || !keys.is_empty() || nav.is_some() {
+                let path = emu.exit_state_path();
+                if yes && emu.load_state_file(&path) {
+                    let _ = audio_tx.send(AudioCommand::LoadState(emu.apu_snapshot()));
+                }
+                let _ = fs::remove_file(&path);
+                resume_offer = false;
+                osd.prompt = None;
+            }
+        }
+
+        // Homebrew hot reload: a rebuilt ROM restarts straight away (battery
+        // RAM is saved first, no offer to continue)
+        let mut hot_reload = false;
+        if rom_to_load.is_none()
+            && let Some(w) = &mut watcher
+            && w.changed()
+        {
+            if let Some(emu) = &mut current_emulator {
+                emu.cpu.bus.save_ram();
+            }
+            osd.show("ROM changed, reloading");
+            rom_to_load = Some(w.path.clone());
+            hot_reload = true;
+        }
+
         // --- EMULATOR UPDATE ---
         if let Some(path) = rom_to_load {
-            current_emulator = Some(EmulatorState::load_rom(&path));
-            if let Some(emu) = &current_emulator {
-                window.set_title(&format!("Rust Game Boy - {}", emu.mmu_filename));
+            // A ROM that can't be loaded leaves the current game running
+            // Per-game overrides are looked up from the header before loading
+            let game = fs::read(&path).ok().filter(|rom| rom.len() >= 0x150).map(|rom| config::game_key(&rom)).unwrap_or_default();
+            match EmulatorState::load_rom(&path, config.model_for(&game), config.palette_for(&game)) {
+                Err(e) => {
+                    show_dialog(rfd::MessageLevel::Error, "Can't load ROM", &e.to_string());
+                    pending_movie = None;
+                }
+                Ok(mut new_emu) => {
+                    new_emu.cpu.bus.fill_ram(args.ram_fill.unwrap_or(config.ram_fill), args.ram_seed.unwrap_or(config.ram_seed));
+                    if let Some(path) = &args.doctor_log
+                        && let Err(e) = new_emu.cpu.start_doctor_log(path)
+                    {
+                        eprintln!("✗ Couldn't create '{}': {}", path, e);
+                    }
+                    match args.instr_trace() {
+                        Some(Ok(trace)) => new_emu.cpu.start_trace(trace),
+                        Some(Err(e)) => eprintln!("✗ Couldn't create the trace file: {}", e),
+                        None => {}
+                    }
+                    if let Some(path) = &args.coverage {
+                        new_emu.cpu.start_coverage(Coverage::new(path, new_emu.cpu.bus.rom.len()));
+                    }
+                    if let Some(profiler) = args.profiler(&path) {
+                        new_emu.cpu.start_profile(profiler);
+                    }
+                    if args.uninit_reads {
+                        new_emu.cpu.bus.uninit = Some(UninitReads::default());
+                    }
+                    if std::mem::take(&mut open_debugger) {
+                        debug_repl.get_or_insert_with(DebugRepl::new).open(&new_emu);
+                    }
+                    // Unless the game has its own palette, the CGB colors DMG
+                    // games, by the buttons held while loading or the title
+                    let rom = &new_emu.cpu.bus.rom;
+                    if config.cgb_colorization
+                        && config.model_for(&game) == Model::Cgb
+                        && colorize::is_dmg_only(rom)
+                        && config.games.get(&game).and_then(|g| g.palette).is_none()
+                    {
+                        let held = !read_joypad(&window, &gilrs, &config.input, &[], false, |_, gamepad| Some(*config.pad_mapping(gamepad)));
+                        let colors = colorize::for_game(rom, held);
+                        new_emu.cpu.ppu.set_colorization(colors);
+                    }
+                    paused = false;
+                    resume_offer = false;
+                    last_auto_state = 0;
+                    state_picker = None;
+                    show_rom_info = false;
+                    game_started = SystemTime::now();
+                    pad_lost = false;
+                    osd.prompt = None;
+                    if let Some(emu) = &mut current_emulator {
+                        emu.cpu.bus.apu.stop_vgm_recording();
+                        // A hard reset or hot reload keeps the debuggers' breakpoints
+                        if emu.mmu_filename == new_emu.mmu_filename {
+                            new_emu.breakpoints = std::mem::take(&mut emu.breakpoints);
+                            new_emu.cpu.bus.event_breaks = std::mem::take(&mut emu.cpu.bus.event_breaks);
+                        } else if gdb_server.as_ref().is_some_and(|g| g.attached()) {
+                            new_emu.breakpoints = std::mem::take(&mut emu.breakpoints);
+                        }
+                    } else if gdb_server.as_ref().is_some_and(|g| g.attached()) {
+                        new_emu.breakpoints = std::mem::take(&mut gdb_breakpoints);
+                    }
+                    if !new_emu.cpu.bus.mapper_supported() {
+                        show_dialog(
+                            rfd::MessageLevel::Warning,
+                            "Unsupported cartridge",
+                            &format!("This game uses cartridge type {:#04X}, which gb_rs doesn't emulate yet. It may not start or may glitch.", new_emu.cpu.bus.mbc_type),
+                        );
+                    }
+                    game_speed = config.speed_for(&game);
+                    border = load_border(config.border_for(&game));
+                    splitter = if config.livesplit.enabled { AutoSplitter::load(&new_emu.file_stem(), &config.livesplit) } else { None };
+                    current_emulator = Some(new_emu);
+                    if args.watch || config.watch_rom {
+                        watcher = Some(RomWatcher::new(&path));
+                    }
+                    let _ = audio_tx.send(AudioCommand::Reset(config.model_for(&game)));
+                    if let Some(emu) = &mut current_emulator {
+                        emu.cpu.bus.apu.record_writes = true;
+                        if let Some(movie) = pending_movie.take() {
+                            emu.start_movie(movie);
+                        } else if let Some(state) = startup_state.take() {
+                            // --state slotN or a file path
+                            let loaded = match state.strip_prefix("slot").and_then(|n| n.parse().ok()) {
+                                Some(slot) => emu.load_slot(slot),
+                                None => emu.load_state_file(&state),
+                            };
+                            if loaded {
+                                let _ = audio_tx.send(AudioCommand::LoadState(emu.apu_snapshot()));
+                            }
+                        } else if !hot_reload && config.resume_on_launch && std::path::Path::new(&emu.exit_state_path()).exists() {
+                            resume_offer = true;
+                            osd.prompt = Some("CONTINUE? ENTER/(A) = YES, OTHER = NO".to_string());
+                        }
+                        window.set_title(&format!("Rust Game Boy - {}", emu.mmu_filename));
+                    }
+                }
             }
         }
 
+        // Optionally stop (and silence) the game while the window is in the background
+        let unfocused = config.pause_when_unfocused && !window.is_active()
+            && !link.as_mut().is_some_and(|l| l.window.is_active());
+
+        // The synth holds still while the game is paused or a menu is up
+        let debug_paused = debug_server.as_ref().is_some_and(|d| d.paused) || debug_repl.as_ref().is_some_and(|r| r.paused)
+            || gdb_server.as_ref().is_some_and(|g| g.paused);
+        let halted = paused || menu_open || unfocused || resume_offer || debug_paused;
+        if halted != audio_paused {
+            audio_paused = halted;
+            let _ = audio_tx.send(AudioCommand::SetPaused(halted));
+        }
+
+        if let Some(p) = &mut presence {
+            let state = if menu_open {
+                "In the menu"
+            } else if paused || unfocused {
+                "Paused"
+            } else if link.is_some() {
+                "Link play"
+            } else {
+                "Playing"
+            };
+            p.update(current_emulator.as_ref().map(|e| e.title()).as_deref(), state, game_started);
+        }
+
+        // With a display rate set, video sync runs the loop at that rate and
+        // emulates however many Game Boy frames came due meanwhile: 0 repeats
+        // the last frame on screen, 2+ skips presenting some of them
+        let sync_mode = if stream.is_some() { config.audio.sync_mode } else { SyncMode::Video };
+        let decoupled = sync_mode == SyncMode::Video && config.display_hz > 0.0;
+        let frames_due = if decoupled { pacer.frames_due() } else { 1 };
+
         if let Some(emu) = &mut current_emulator {
-            let mut cycles = 0;
-            while cycles < 70224 {
-                let c = emu.cpu.step() as u8;
-                emu.cpu.bus.tick(c); emu.cpu.bus.apu.tick(c); emu.ppu.tick(&mut emu.cpu.bus, c);
-                cycles += c as u32;
-                let i = emu.cpu.handle_interrupts();
-                if i > 0 { emu.cpu.bus.tick(i); emu.cpu.bus.apu.tick(i); emu.ppu.tick(&mut emu.cpu.bus, i); cycles += i as u32; }
-            }
-            let mut s = emu.cpu.bus.apu.get_samples();
-            if let Ok(mut b) = audio_buffer.lock() { if b.len() < 8192 { b.append(&mut s); } }
-            
-            // Drain Gilrs events
-            while let Some(_) = gilrs.next_event() {} 
-            
-            let mut pad = 0xFF;
+            // Start/stop VGM capture of the APU
+            if config.hotkeys.pressed(&window, Hotkey::RecordVgm) {
+                emu.toggle_vgm_recording();
+            }
+            if config.hotkeys.pressed(&window, Hotkey::DoctorLog) {
+                osd.show(&emu.toggle_doctor_log());
+            }
+            if config.hotkeys.pressed(&window, Hotkey::CoreDump) {
+                osd.show(&emu.core_dump());
+            }
+            if config.hotkeys.pressed(&window, Hotkey::ExportGraphics) {
+                osd.show(&emu.export_graphics());
+            }
+            if config.hotkeys.pressed(&window, Hotkey::Debugger) {
+                let repl = debug_repl.get_or_insert_with(DebugRepl::new);
+                if repl.open {
+                    repl.close(emu);
+                } else {
+                    repl.open(emu);
+                    osd.show("Debugger: paused, commands on the terminal");
+                }
+            }
+            if config.hotkeys.pressed(&window, Hotkey::Screenshot) || quick_hotkey == Some(Hotkey::Screenshot) {
+                emu.save_screenshot();
+            }
+
+            // State slots: number keys pick a slot (Shift+number saves straight
+            // into it), the save/load hotkeys use the picked slot
+            let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+            let mut save_slot = config.hotkeys.pressed(&window, Hotkey::SaveState) || quick_hotkey == Some(Hotkey::SaveState);
+            for (i, key) in SLOT_KEYS.iter().enumerate() {
+                if window.is_key_pressed(*key, KeyRepeat::No) {
+                    state_slot = i as u32 + 1;
+                    if shift {
+                        save_slot = true;
+                    } else {
+                        let saved = fs::metadata(emu.slot_path(state_slot)).and_then(|m| m.modified());
+                        osd.show(&match saved {
+                            Ok(t) => format!("Slot {}: {}", state_slot, savestate::format_time(t)),
+                            Err(_) => format!("Slot {}: empty", state_slot),
+                        });
+                    }
+                }
+            }
+            if save_slot && emu.save_slot(state_slot) {
+                osd.show(&format!("Saved slot {}", state_slot));
+            }
+            if config.hotkeys.pressed(&window, Hotkey::LoadState) || quick_hotkey == Some(Hotkey::LoadState) || load_slot {
+                if emu.load_slot(state_slot) {
+                    let _ = audio_tx.send(AudioCommand::LoadState(emu.apu_snapshot()));
+                    osd.show(&format!("Loaded slot {}", state_slot));
+                } else {
+                    osd.show(&format!("Can't load slot {}", state_slot));
+                }
+            }
+            if config.hotkeys.pressed(&window, Hotkey::UndoLoad) {
+                if emu.undo_load() {
+                    let _ = audio_tx.send(AudioCommand::LoadState(emu.apu_snapshot()));
+                    osd.show("Undid load state");
+                } else {
+                    osd.show("Nothing to undo");
+                }
+            }
+            if config.hotkeys.pressed(&window, Hotkey::UndoSave) {
+                match emu.undo_save() {
+                    Some(path) => osd.show(&format!("Restored {}", path)),
+                    None => osd.show("Nothing to undo"),
+                }
+            }
+
+            // TURBO: held turbo bindings press A/B on alternating phases
+            if !halted { turbo_frame = turbo_frame.wrapping_add(frames_due); }
+            let turbo_on = (turbo_frame / config.turbo_frames.max(1)).is_multiple_of(2);
+
+            // Player 2 (linked Game Boy): their own window's keys and pad,
+            // read once per frame
+            emu.cpu.bus.link_connected = link.is_some() || bgb_link.as_ref().is_some_and(|l| l.is_connected());
+            let p2_pad = link.as_ref().map_or(0xFF, |l| {
+                let pad = read_joypad(&l.window, &gilrs, &config.player2, &[], turbo_on, |id, _| {
+                    (player2_pad == Some(id)).then_some(config.player2)
+                });
+                if config.block_opposite_dirs { block_opposite_dirs(pad) } else { pad }
+            });
+
+            // Player 1: the selected pad (or all of them), minus player 2's pad.
+            // Pad events seen mid-frame are kept for the GAMEPADS section.
+            // minifb only refreshes keys on window update, so keyboard input
+            // changes once per displayed frame.
+            let mut poll = || {
+                while let Some(ev) = gilrs.next_event() { pad_events.push(ev); }
+                let pad = read_joypad(&window, &gilrs, &config.input, &config.combos, turbo_on, |id, gamepad| {
+                    if active_pad.is_some_and(|active| active != id) || player2_pad == Some(id) { return None; }
+                    Some(*config.pad_mapping(gamepad))
+                });
+                if config.block_opposite_dirs { block_opposite_dirs(pad) } else { pad }
+            };
+            let polls = config.input_polls_per_frame;
+
+            if halted {
+                // Keep the last frame on screen
+            } else if sync_mode == SyncMode::Audio && speed == 1 {
+                // Wait for the audio device to drain what we've produced, then
+                // emulate until we're a little ahead of it again
+                let deadline = Instant::now() + Duration::from_millis(50);
+                if let Some(perf) = &mut perf_overlay { perf.stop(); }
+                while emu.cpu.bus.apu.cycle_count >= audio_clock.load(Ordering::Relaxed) + AUDIO_SYNC_LEAD
+                    && Instant::now() < deadline
+                {
+                    std::thread::sleep(Duration::from_micros(500));
+                }
+                if let Some(perf) = &mut perf_overlay { perf.start(); }
+                let mut frames = 0;
+                while emu.cpu.bus.apu.cycle_count < audio_clock.load(Ordering::Relaxed) + AUDIO_SYNC_LEAD && frames < 4
+                    && emu.break_hit.is_none()
+                {
+                    emu.run_frame(polls, &mut poll);
+                    if let Some(p2) = &mut link { p2.run_frame(&mut emu.cpu.bus, p2_pad); }
+                    if let Some(l) = &mut bgb_link && let Some(message) = l.update(&mut emu.cpu.bus, emu.frame_count) { osd.show(&message); }
+                    // Every frame, so a condition that's only true for one isn't missed
+                    if let Some(s) = &mut splitter && let Some(message) = s.update(&emu.cpu.bus, emu.frame_count) { osd.show(&message); }
+                    frames += 1;
+                }
+            } else {
+                for _ in 0..speed * frames_due {
+                    emu.run_frame(polls, &mut poll);
+                    if let Some(p2) = &mut link { p2.run_frame(&mut emu.cpu.bus, p2_pad); }
+                    if let Some(l) = &mut bgb_link && let Some(message) = l.update(&mut emu.cpu.bus, emu.frame_count) { osd.show(&message); }
+                    if let Some(s) = &mut splitter && let Some(message) = s.update(&emu.cpu.bus, emu.frame_count) { osd.show(&message); }
+                    if emu.break_hit.is_some() { break; }
+                }
+            }
+            // An illegal opcode hangs the game, not the emulator: the screen
+            // stays up and a reset gets it going again
+            if emu.cpu.locked != lock_reported {
+                lock_reported = emu.cpu.locked;
+                if let Some(message) = emu.cpu.lock_message() {
+                    eprintln!("✗ {}\n{}", message, emu.write_crash_report(&message));
+                    osd.show(&message);
+                }
+            }
+            if emu.fault.is_some() != fault_reported {
+                fault_reported = emu.fault.is_some();
+                if let Some(e) = &emu.fault {
+                    eprintln!("✗ {}\n{}", e, emu.write_crash_report(&e.to_string()));
+                    osd.show(e.to_string().lines().next().unwrap_or_default());
+                }
+            }
+            // Toggle the APU oscilloscope
+            if config.hotkeys.pressed(&window, Hotkey::Oscilloscope) {
+                scope_window = if scope_window.is_some() { None } else { Some(ScopeWindow::new()) };
+            }
+            if let Some(scope) = &mut scope_window {
+                if scope.is_open() { scope.update(&emu.cpu.bus.apu); } else { scope_window = None; }
+            }
+
+            // Toggle the APU channel state panel
+            if config.hotkeys.pressed(&window, Hotkey::ApuPanel) {
+                apu_panel = if apu_panel.is_some() { None } else { Some(ApuPanel::new()) };
+            }
+            if let Some(panel) = &mut apu_panel {
+                if panel.is_open() { panel.update(&emu.cpu.bus.apu); } else { apu_panel = None; }
+            }
+
+            // Toggle the event viewer; the bus only records writes for it
+            // while it's open
+            if config.hotkeys.pressed(&window, Hotkey::EventViewer) {
+                event_viewer = if event_viewer.is_some() { None } else { Some(EventViewer::new()) };
+            }
+            match &mut event_viewer {
+                Some(viewer) if viewer.is_open() => viewer.update(emu.cpu.bus.timeline.get_or_insert_with(Timeline::default)),
+                _ => {
+                    event_viewer = None;
+                    emu.cpu.bus.timeline = None;
+                }
+            }
+
+            // Toggle the memory editor
+            if config.hotkeys.pressed(&window, Hotkey::MemoryEditor) {
+                memory_editor = if memory_editor.is_some() { None } else { Some(MemoryEditor::new()) };
+            }
+            if let Some(editor) = &mut memory_editor {
+                if editor.is_open() { editor.update(&mut emu.cpu.bus); } else { memory_editor = None; }
+            }
+
+            // Toggle the tile map viewer
+            if config.hotkeys.pressed(&window, Hotkey::TilemapViewer) {
+                tilemap_viewer = if tilemap_viewer.is_some() { None } else { Some(TilemapViewer::new()) };
+            }
+            if let Some(viewer) = &mut tilemap_viewer {
+                if viewer.is_open() { viewer.update(&emu.cpu.ppu, &emu.cpu.bus); } else { tilemap_viewer = None; }
+            }
+
+            // Hand this frame's APU writes to the audio thread. Locally generated
+            // samples only feed the scopes (oscilloscope and APU panel) and
+            // spectators, so the APU only mixes them for those.
+            emu.cpu.bus.apu.capture_samples = scope_window.is_some() || apu_panel.is_some() || (spectators.is_some() && config.spectator.audio);
+            for w in emu.cpu.bus.apu.take_writes() {
+                // Nothing drains the queue without an audio stream
+                if stream.is_some() { let _ = audio_tx.send(AudioCommand::Write(w)); }
+            }
+            // A VIN source plays through the synth, not the emulation APU
+            if stream.is_some() && let Some(vin) = emu.cpu.bus.apu.vin.take() {
+                let _ = audio_tx.send(AudioCommand::SetVin(vin));
+            }
+            // Frames without writes still move the synth's target along
+            if stream.is_some() { let _ = audio_tx.send(AudioCommand::EmulatedTo(emu.cpu.bus.apu.cycle_count)); }
+            if let Some(server) = &mut spectators {
+                for addr in server.accept() {
+                    osd.show(&format!("Spectator joined: {} ({} watching)", addr, server.viewer_count()));
+                }
+                if !halted && server.send_frame(&emu.cpu.ppu.frame_buffer, &emu.cpu.ppu.palette, &emu.cpu.bus.apu.sample_buffer) > 0 {
+                    osd.show(&format!("Spectator left ({} watching)", server.viewer_count()));
+                }
+            }
+            emu.cpu.bus.apu.sample_buffer.clear();
+            if let Some(server) = &mut debug_server
+                && let Some(message) = server.update(emu)
+            {
+                osd.show(&message);
+            }
+            if let Some(repl) = &mut debug_repl
+                && let Some(message) = repl.update(emu)
+            {
+                osd.show(&message);
+            }
+            if let Some(server) = &mut gdb_server
+                && let Some(message) = server.update(emu)
+            {
+                osd.show(&message);
+            }
+            // All the debuggers have seen it
+            emu.break_hit = None;
+            emu.cpu.bus.event_hit = None;
             
-            // KEYBOARD (Dynamic Mapping)
-            if window.is_key_down(mapping.right_key) { pad &= !(1 << 0); }
-            if window.is_key_down(mapping.left_key)  { pad &= !(1 << 1); }
-            if window.is_key_down(mapping.up_key)    { pad &= !(1 << 2); }
-            if window.is_key_down(mapping.down_key)  { pad &= !(1 << 3); }
-            if window.is_key_down(mapping.a_key)     { pad &= !(1 << 4); }
-            if window.is_key_down(mapping.b_key)     { pad &= !(1 << 5); }
-            if window.is_key_down(mapping.select_key){ pad &= !(1 << 6); }
-            if window.is_key_down(mapping.start_key) { pad &= !(1 << 7); }
             
-            // CONTROLLER (Mapped)
-            for (_id, gamepad) in gilrs.gamepads() {
-                if gamepad.is_pressed(mapping.right_btn) { pad &= !(1 << 0); }
-                if gamepad.is_pressed(mapping.left_btn)  { pad &= !(1 << 1); }
-                if gamepad.is_pressed(mapping.up_btn)    { pad &= !(1 << 2); }
-                if gamepad.is_pressed(mapping.down_btn)  { pad &= !(1 << 3); }
-                if gamepad.is_pressed(mapping.a_btn)     { pad &= !(1 << 4); }
-                if gamepad.is_pressed(mapping.b_btn)     { pad &= !(1 << 5); }
-                if gamepad.is_pressed(mapping.select_btn){ pad &= !(1 << 6); }
-                if gamepad.is_pressed(mapping.start_btn) { pad &= !(1 << 7); }
+            // Periodic auto-state (never during movies, which replay from power-on)
+            let minutes = config.auto_state.interval_for(&emu.file_stem()) as u64;
+            if minutes > 0 && emu.movie.is_none() && emu.frame_count >= last_auto_state + minutes * FRAMES_PER_MINUTE {
+                emu.save_auto_state(config.auto_state.slots);
+                last_auto_state = emu.frame_count;
             }
 
-            emu.cpu.bus.joypad_state = pad;
-            if pad != 0xFF { emu.cpu.bus.interrupt_flag |= 0x10; }
-            if last_save.elapsed() > Duration::from_secs(1) { if emu.cpu.bus.save_dirty { emu.cpu.bus.save_ram(); } last_save = Instant::now(); }
+            if last_save.elapsed() > Duration::from_secs(1) {
+                if emu.cpu.bus.save_dirty { emu.cpu.bus.save_ram(); }
+                if let Some(p2) = &mut link { p2.emu.cpu.bus.save_ram(); }
+                last_save = Instant::now();
+
+                // A failing battery save is reported once, until a write works again
+                let save_error = emu.cpu.bus.save_error.clone().or_else(|| link.as_ref().and_then(|p2| p2.emu.cpu.bus.save_error.clone()));
+                match save_error {
+                    Some(e) if !save_error_shown => {
+                        show_dialog(
+                            rfd::MessageLevel::Error,
+                            "Save failed",
+                            &format!("{}.\n\nYour progress is still in memory and saving will be retried. Check that the disk isn't full and the folder isn't read-only.", e),
+                        );
+                        save_error_shown = true;
+                    }
+                    None => save_error_shown = false,
+                    _ => {}
+                }
+            }
+        }
+
+        // Nothing paces the loop in audio sync without a ROM running
+        if (current_emulator.is_none() || halted) && sync_mode == SyncMode::Audio {
+            if let Some(perf) = &mut perf_overlay { perf.stop(); }
+            std::thread::sleep(Duration::from_micros(16600));
+            if let Some(perf) = &mut perf_overlay { perf.start(); }
         }
 
         // --- RENDER ---
         
+        let theme = Theme::get(config.high_contrast_ui);
+
         // Backgrounds
-        for i in 0..(SS_WIDTH * 2 * SS_SCALE) { window_buffer[i] = 0xFF222222; }
         let start_row2 = SS_WIDTH * 2 * SS_SCALE;
-        for i in start_row2..(SS_WIDTH * 4 * SS_SCALE) { window_buffer[i] = 0xFF111111; }
+        window_buffer[..start_row2].fill(theme.strip[0]);
+        window_buffer[start_row2..SS_WIDTH * 4 * SS_SCALE].fill(theme.strip[1]);
 
         // Text
-        let col1 = if is_hovering_load { 0xFF55FF55 } else { 0xFFFFFFFF };
-        let col2 = if is_hovering_config { 0xFF55FF55 } else { 0xFFAAAAAA };
+        let col1 = if is_hovering_load || menu_focus == Some(0) { theme.accent } else { theme.text };
+        let col2 = if is_hovering_config || menu_focus == Some(1) { theme.accent } else { theme.dim };
+        if let Some(focus) = menu_focus {
+            draw_text(&mut window_buffer, SS_WIDTH, ">", 0, focus * 2 * SS_SCALE, theme.accent, 1);
+        }
         
         draw_text(&mut window_buffer, SS_WIDTH, "LOAD ROM", 2 * SS_SCALE, 0, col1, 1);
         draw_text(&mut window_buffer, SS_WIDTH, "INPUT", 2 * SS_SCALE, 2 * SS_SCALE, col2, 1);
 
         // Game
         if let Some(emu) = &current_emulator {
-            for y in 0..144 {
-                for x in 0..160 {
-                    let pixel = emu.ppu.frame_buffer[y * 160 + x];
-                    for dy in 0..SS_SCALE {
-                        for dx in 0..SS_SCALE {
-                            let dest_x = x * SS_SCALE + dx;
-                            let dest_y = (y + MENU_HEIGHT) * SS_SCALE + dy;
-                            window_buffer[dest_y * SS_WIDTH + dest_x] = pixel;
-                        }
-                    }
-                }
+            if shown_frame[..] != emu.cpu.ppu.frame_buffer[..] {
+                shown_frame.clear();
+                shown_frame.extend_from_slice(&emu.cpu.ppu.frame_buffer);
+                scale_frame(&shown_frame, &mut scaled_frame, SS_SCALE);
             }
+            window_buffer[SS_WIDTH * MENU_HEIGHT * SS_SCALE..].copy_from_slice(&scaled_frame);
         } else {
             let start = SS_WIDTH * MENU_HEIGHT * SS_SCALE;
             for i in start..window_buffer.len() { window_buffer[i] = 0xFF000000; }
             draw_text(&mut window_buffer, SS_WIDTH, "NO ROM", 60 * SS_SCALE, 60 * SS_SCALE, 0xFF555555, 4);
         }
 
-        window.update_with_buffer(&window_buffer, SS_WIDTH, SS_HEIGHT).unwrap();
+        // Dim the frozen frame while paused
+        if (paused || unfocused) && current_emulator.is_some() {
+            let start = SS_WIDTH * MENU_HEIGHT * SS_SCALE;
+            for p in &mut window_buffer[start..] {
+                *p = 0xFF000000 | ((*p >> 1) & 0x7F7F7F);
+            }
+            let x = (SS_WIDTH - 6 * 8 * 4) / 2;
+            draw_text(&mut window_buffer, SS_WIDTH, "PAUSED", x, (MENU_HEIGHT + 24) * SS_SCALE, 0xFFFFFFFF, 4);
+            if paused && !pad_lost && menu_focus.is_none() && state_picker.is_none() {
+                match (&game_settings, &current_emulator) {
+                    (Some(page), Some(emu)) => {
+                        let game = config.games.get(&emu.game_key()).cloned().unwrap_or_default();
+                        page.draw(&mut window_buffer, SS_WIDTH, (MENU_HEIGHT + 44) * SS_SCALE, &game, theme);
+                    }
+                    _ => quick_menu.draw(&mut window_buffer, SS_WIDTH, (MENU_HEIGHT + 44) * SS_SCALE, theme),
+                }
+            }
+        }
+
+        if let Some(b) = &browser {
+            b.draw(&mut window_buffer, SS_WIDTH, MENU_HEIGHT * SS_SCALE, GB_HEIGHT * SS_SCALE, theme);
+        }
+        if let Some(picker) = &state_picker {
+            picker.draw(&mut window_buffer, SS_WIDTH, MENU_HEIGHT * SS_SCALE, GB_HEIGHT * SS_SCALE, theme);
+        }
+        if show_rom_info && let Some(emu) = &current_emulator {
+            let top = MENU_HEIGHT * SS_SCALE + 8;
+            let mut lines = emu.rom_info();
+            lines.push(String::new());
+            lines.push("C: COPY CHECKSUMS".to_string());
+            for p in &mut window_buffer[top * SS_WIDTH..(top + lines.len() * 12 + 8) * SS_WIDTH] {
+                *p = theme.backdrop(*p);
+            }
+            for (i, line) in lines.iter().enumerate() {
+                draw_text(&mut window_buffer, SS_WIDTH, line, 8, top + 4 + i * 12, theme.text, 1);
+            }
+        }
+
+        if show_ram_watch
+            && let Some(emu) = &current_emulator
+            && let Some(game) = config.games.get(&emu.game_key())
+        {
+            ram_watch::draw(&game.watches, &emu.cpu.bus, &mut window_buffer, SS_WIDTH, MENU_HEIGHT * SS_SCALE + 4, theme);
+        }
+
+        if let Some(perf) = &mut perf_overlay {
+            let audio = current_emulator.as_ref()
+                .filter(|_| stream.is_some())
+                .map(|emu| (emu.cpu.bus.apu.cycle_count.saturating_sub(audio_clock.load(Ordering::Relaxed)), &*audio_stats));
+            perf.draw(&mut window_buffer, SS_WIDTH, MENU_HEIGHT * SS_SCALE + 4, audio, theme);
+        }
+
+        osd.draw(&mut window_buffer, SS_WIDTH, SS_HEIGHT, theme);
+
+        // Everything up to here is this iteration's work; the rest is waiting
+        if let Some(perf) = &mut perf_overlay {
+            perf.iteration_done(current_emulator.as_ref().map_or(0, |emu| emu.frame_count));
+        }
+        if decoupled {
+            display_pacer.wait();
+        } else if sync_mode == SyncMode::Video {
+            pacer.wait();
+        }
+        match &border {
+            Some(b) => {
+                b.compose(&window_buffer, SS_WIDTH, &mut framed_buffer);
+                let (w, h) = b.size();
+                window.update_with_buffer(&framed_buffer, w, h).unwrap();
+            }
+            None => window.update_with_buffer(&window_buffer, SS_WIDTH, SS_HEIGHT).unwrap(),
+        }
+        if let Some(p2) = &mut link {
+            p2.present();
+        }
+    }
+
+    if let Some(p2) = &mut link {
+        p2.emu.cpu.bus.save_ram();
+    }
+
+    if let Some(emu) = &mut current_emulator {
+        emu.close(config.resume_on_launch);
     }
 }
\ No newline at end of file
@@ -0,0 +1,51 @@
+// memdump.rs
+// Memory dumps for offline analysis: a region's raw bytes or any address
+// range as the CPU sees it, and a core dump that writes every region (plus
+// the whole address space and the registers) into a directory.
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::cpu::CPU;
+use crate::mmu::MMU;
+
+pub const REGIONS: [&str; 6] = ["vram", "wram", "oam", "hram", "io", "sram"];
+
+// A region's contents by name, all of it whatever is mapped: SRAM is every
+// bank the cartridge has, I/O the registers at 0xFF00-0xFF7F as they read
+pub fn region(bus: &MMU, name: &str) -> Option<Vec<u8>> {
+    Some(match name {
+        "vram" => bus.vram.to_vec(),
+        "wram" => bus.wram.to_vec(),
+        "oam" => bus.oam.to_vec(),
+        "hram" => bus.hram.to_vec(),
+        "io" => range(bus, 0xFF00, 0x80),
+        "sram" => bus.eram[..bus.ram_size()].to_vec(),
+        _ => return None,
+    })
+}
+
+// `len` bytes from `start` through the bus, wrapping past 0xFFFF
+pub fn range(bus: &MMU, start: u16, len: usize) -> Vec<u8> {
+    (0..len).map(|i| bus.read_byte(start.wrapping_add(i as u16))).collect()
+}
+
+pub fn core_dump(cpu: &CPU, dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for name in REGIONS {
+        // No SRAM file for a cartridge without RAM
+        if let Some(bytes) = region(&cpu.bus, name)
+            && !bytes.is_empty()
+        {
+            fs::write(dir.join(format!("{}.bin", name)), bytes)?;
+        }
+    }
+    fs::write(dir.join("memory.bin"), range(&cpu.bus, 0, 0x10000))?;
+    let r = &cpu.registers;
+    let bus = &cpu.bus;
+    let registers = format!(
+        "AF {:02X}{:02X}\nBC {:02X}{:02X}\nDE {:02X}{:02X}\nHL {:02X}{:02X}\nSP {:04X}\nPC {:04X}\nIME {}\nIE {:02X}\nIF {:02X}\nROM bank {}\nRAM bank {}\n",
+        r.a, r.f, r.b, r.c, r.d, r.e, r.h, r.l, r.sp, r.pc, cpu.ime as u8, bus.interrupt_enable, bus.interrupt_flag, bus.rom_bank_at(0x4000), bus.ram_bank
+    );
+    fs::write(dir.join("registers.txt"), registers)
+}
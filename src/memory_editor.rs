@@ -0,0 +1,175 @@
+// memory_editor.rs
+// Debug window with a live hex view of the address space. Bytes that
+// changed since the last frame are drawn in red. Typing hex digits over the
+// cursor edits memory through the bus, like a write from the game (so
+// writes to ROM reach the MBC and switch banks).
+//
+//   arrows, PgUp/PgDn   move the cursor / a page
+//   Tab, Shift+Tab      jump to the next / previous area (ROM, VRAM, ...)
+//   0-9, A-F            type a byte at the cursor
+//   G then 4 hex digits go to an address
+//   Esc                 cancel a half-typed byte or address
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+
+use crate::draw_text;
+use crate::mmu::MMU;
+
+const ROWS: usize = 24;
+const PAGE: u16 = (ROWS * 16) as u16;
+const W: usize = 600;
+const H: usize = 40 + ROWS * 10 + 24;
+
+const AREAS: [(u16, &str); 9] = [
+    (0x0000, "ROM0"), (0x4000, "ROMX"), (0x8000, "VRAM"), (0xA000, "SRAM"), (0xC000, "WRAM"),
+    (0xFE00, "OAM"), (0xFF00, "I/O"), (0xFF80, "HRAM"), (0xFFFF, "IE"),
+];
+
+fn area_name(addr: u16) -> &'static str {
+    AREAS.iter().rev().find(|&&(start, _)| addr >= start).map_or("", |&(_, name)| name)
+}
+
+fn hex_digit(key: Key) -> Option<u8> {
+    let digits = [
+        Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7,
+        Key::Key8, Key::Key9, Key::A, Key::B, Key::C, Key::D, Key::E, Key::F,
+    ];
+    digits.iter().position(|&k| k == key).map(|d| d as u8)
+}
+
+pub struct MemoryEditor {
+    window: Window,
+    buffer: Vec<u32>,
+    top: u16,    // First address shown
+    cursor: u16,
+    typed: Vec<u8>, // Hex digits of the byte being typed
+    goto: Option<Vec<u8>>, // Hex digits of the address after G
+    last: Vec<u8>, // The bytes shown last frame, to see what changed
+    last_top: u16,
+}
+
+impl MemoryEditor {
+    pub fn new() -> Self {
+        let mut window = Window::new(
+            "Memory",
+            W, H,
+            WindowOptions { resize: false, ..WindowOptions::default() },
+        ).unwrap();
+        window.limit_update_rate(None);
+        Self { window, buffer: vec![0; W * H], top: 0xC000, cursor: 0xC000, typed: Vec::new(), goto: None, last: Vec::new(), last_top: 0 }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    fn move_to(&mut self, addr: u16) {
+        self.cursor = addr;
+        self.typed.clear();
+        // Keep the cursor on screen, scrolling by whole rows
+        if addr < self.top {
+            self.top = addr & 0xFFF0;
+        } else if addr as u32 >= self.top as u32 + PAGE as u32 {
+            self.top = ((addr & 0xFFF0) as u32 + 16 - PAGE as u32) as u16;
+        }
+    }
+
+    fn keys(&mut self, bus: &mut MMU) {
+        let shift = self.window.is_key_down(Key::LeftShift) || self.window.is_key_down(Key::RightShift);
+        for key in self.window.get_keys_pressed(KeyRepeat::Yes) {
+            if let Some(digits) = &mut self.goto {
+                match (key, hex_digit(key)) {
+                    (Key::Escape, _) => self.goto = None,
+                    (_, Some(d)) => {
+                        digits.push(d);
+                        if digits.len() == 4 {
+                            let addr = digits.iter().fold(0, |a, &d| a << 4 | d as u16);
+                            self.goto = None;
+                            self.top = addr & 0xFFF0;
+                            self.move_to(addr);
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            match key {
+                Key::Left => self.move_to(self.cursor.wrapping_sub(1)),
+                Key::Right => self.move_to(self.cursor.wrapping_add(1)),
+                Key::Up => self.move_to(self.cursor.wrapping_sub(16)),
+                Key::Down => self.move_to(self.cursor.wrapping_add(16)),
+                Key::PageUp => {
+                    self.top = self.top.wrapping_sub(PAGE);
+                    self.move_to(self.cursor.wrapping_sub(PAGE));
+                }
+                Key::PageDown => {
+                    self.top = self.top.wrapping_add(PAGE);
+                    self.move_to(self.cursor.wrapping_add(PAGE));
+                }
+                Key::Tab => {
+                    let i = AREAS.iter().rposition(|&(start, _)| self.cursor >= start).unwrap_or(0);
+                    let next = if shift { (i + AREAS.len() - 1) % AREAS.len() } else { (i + 1) % AREAS.len() };
+                    self.top = AREAS[next].0 & 0xFFF0;
+                    self.move_to(AREAS[next].0);
+                }
+                Key::G => self.goto = Some(Vec::new()),
+                Key::Escape => self.typed.clear(),
+                _ => {
+                    let Some(d) = hex_digit(key) else { continue };
+                    self.typed.push(d);
+                    if self.typed.len() == 2 {
+                        bus.write_byte(self.cursor, self.typed[0] << 4 | self.typed[1]);
+                        self.move_to(self.cursor.wrapping_add(1));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn update(&mut self, bus: &mut MMU) {
+        self.keys(bus);
+        for p in self.buffer.iter_mut() { *p = 0xFF101010; }
+
+        let header = format!("{:04X}  {}  ROM bank {:02X}", self.cursor, area_name(self.cursor), bus.rom_bank_at(0x4000));
+        draw_text(&mut self.buffer, W, &header, 8, 6, 0xFFFFFFFF, 1);
+        draw_text(&mut self.buffer, W, "      00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F", 8, 22, 0xFF888888, 1);
+
+        let bytes: Vec<u8> = (0..PAGE).map(|i| bus.read_byte(self.top.wrapping_add(i))).collect();
+        for row in 0..ROWS {
+            let y = 40 + row * 10;
+            let base = self.top.wrapping_add(row as u16 * 16);
+            draw_text(&mut self.buffer, W, &format!("{:04X}", base), 8, y, 0xFF888888, 1);
+            for col in 0..16 {
+                let i = row * 16 + col;
+                let addr = base.wrapping_add(col as u16);
+                let changed = self.last_top == self.top && self.last.len() == bytes.len() && self.last[i] != bytes[i];
+                let x = 8 + (6 + col * 3) * 8;
+                let text = if addr == self.cursor && !self.typed.is_empty() {
+                    format!("{:X}_", self.typed[0])
+                } else {
+                    format!("{:02X}", bytes[i])
+                };
+                if addr == self.cursor {
+                    for yy in y - 1..y + 9 {
+                        for xx in x - 1..x + 17 {
+                            self.buffer[yy * W + xx] = 0xFF3355AA;
+                        }
+                    }
+                }
+                draw_text(&mut self.buffer, W, &text, x, y, if changed { 0xFFFF5555 } else { 0xFFCCCCCC }, 1);
+                let c = bytes[i];
+                let ch = if c.is_ascii_graphic() { c as char } else { '.' };
+                draw_text(&mut self.buffer, W, &ch.to_string(), 8 + (55 + col) * 8, y, 0xFF888888, 1);
+            }
+        }
+        self.last = bytes;
+        self.last_top = self.top;
+
+        let footer = match &self.goto {
+            Some(digits) => format!("GO TO: {}", digits.iter().map(|d| format!("{:X}", d)).collect::<String>()),
+            None => "ARROWS MOVE  TAB AREA  0-F EDIT  G GO TO".to_string(),
+        };
+        draw_text(&mut self.buffer, W, &footer, 8, H - 16, 0xFF888888, 1);
+
+        self.window.update_with_buffer(&self.buffer, W, H).unwrap();
+    }
+}
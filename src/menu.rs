@@ -0,0 +1,72 @@
+// menu.rs
+// Controller navigation for the emulator's own menus: D-pad (buttons, hat
+// axes or the left stick) moves the focus, South confirms, East cancels.
+use gilrs::{Axis, Button, EventType};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NavInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Confirm,
+    Cancel,
+}
+
+// Turns gilrs events into NavInputs. Axes only fire when they cross the
+// threshold, so holding the stick doesn't scroll on every event.
+pub struct PadNav {
+    x: f32,
+    y: f32,
+}
+
+const NAV_THRESHOLD: f32 = 0.5;
+
+impl PadNav {
+    pub fn new() -> Self {
+        Self { x: 0.0, y: 0.0 }
+    }
+
+    pub fn translate(&mut self, event: &EventType) -> Option<NavInput> {
+        match *event {
+            EventType::ButtonPressed(btn, _) => match btn {
+                Button::DPadUp => Some(NavInput::Up),
+                Button::DPadDown => Some(NavInput::Down),
+                Button::DPadLeft => Some(NavInput::Left),
+                Button::DPadRight => Some(NavInput::Right),
+                Button::South | Button::Start => Some(NavInput::Confirm),
+                Button::East => Some(NavInput::Cancel),
+                _ => None,
+            },
+            EventType::AxisChanged(Axis::LeftStickX | Axis::DPadX, v, _) => {
+                let old = std::mem::replace(&mut self.x, v);
+                Self::crossed(old, v, NavInput::Right, NavInput::Left)
+            }
+            // gilrs reports up as positive Y
+            EventType::AxisChanged(Axis::LeftStickY | Axis::DPadY, v, _) => {
+                let old = std::mem::replace(&mut self.y, v);
+                Self::crossed(old, v, NavInput::Up, NavInput::Down)
+            }
+            _ => None,
+        }
+    }
+
+    fn crossed(old: f32, new: f32, positive: NavInput, negative: NavInput) -> Option<NavInput> {
+        if new > NAV_THRESHOLD && old <= NAV_THRESHOLD {
+            Some(positive)
+        } else if new < -NAV_THRESHOLD && old >= -NAV_THRESHOLD {
+            Some(negative)
+        } else {
+            None
+        }
+    }
+}
+
+// Move a focus index through `len` rows, wrapping at both ends
+pub fn move_focus(focus: usize, len: usize, nav: NavInput) -> usize {
+    match nav {
+        NavInput::Up => (focus + len - 1) % len,
+        NavInput::Down => (focus + 1) % len,
+        _ => focus,
+    }
+}
@@ -1,6 +1,40 @@
 use std::fs;
 use crate::apu;
+use crate::bus_trace::BusTrace;
+use crate::events::{Event, EventBreak};
+use crate::timeline::{RegWrite, Timeline};
+use crate::uninit::UninitReads;
+use crate::error::CoreError;
+use crate::savestate::{StateReader, StateWriter};
 use std::io::Write;
+
+// Emulated hardware revision
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Model {
+    Dmg,
+    Cgb,
+}
+
+// What WRAM, HRAM and cartridge RAM hold at power-on. Real hardware comes up
+// with leftovers that differ between units, and a few games and glitches
+// depend on them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum RamFill {
+    Zero,
+    Ones,    // 0xFF everywhere
+    Random,  // From a seed, so runs can be reproduced
+    Pattern, // Alternating runs of 0x00 and 0xFF, like many DMGs show
+}
+
+// A ROM file, checked to be big enough to hold a cartridge header
+pub fn read_rom(path: &str) -> Result<Vec<u8>, CoreError> {
+    let rom = fs::read(path).map_err(|e| CoreError::RomUnreadable { path: path.to_string(), reason: e.to_string() })?;
+    if rom.len() < 0x150 {
+        return Err(CoreError::RomTooSmall { path: path.to_string(), len: rom.len() });
+    }
+    Ok(rom)
+}
+
 pub struct MMU {
     pub rom: Vec<u8>,         // The game file
     pub vram: [u8; 0x2000],    // 8KB Video RAM (0x8000 - 0x9FFF)
@@ -45,9 +79,24 @@ pub struct MMU {
     pub save_filename: String,
     pub save_dirty: bool,
     pub has_battery: bool,
+    pub save_error: Option<String>, // Why the last battery save failed, until one succeeds
     pub apu: apu::APU,
     pub sb: u8, // 0xFF01 - Serial Transfer Data
     pub sc: u8, // 0xFF02 - Serial Transfer Control
+    pub serial_log: Option<Vec<u8>>, // When set, SB writes are collected here instead of printed
+    pub serial_cycles: u16, // Progress of an internally clocked transfer
+    debug_line: Vec<u8>, // Text written to the 0xFF7F debug port since the last line break
+    pub debug_log: Option<Vec<String>>, // When set, debug messages are collected here instead of printed
+    pub trace: Option<BusTrace>, // When set, the CPU's reads and writes are recorded (for debuggers)
+    #[cfg(feature = "sm83-tests")]
+    pub flat: Option<Vec<u8>>, // When set, the whole address space is this plain RAM (for CPU tests)
+    pub ly_stub: bool, // LY always reads 0x90, as in Gameboy Doctor's reference logs
+    pub link_connected: bool, // Another Game Boy is on the cable; see serial_exchange
+    pub event_breaks: Vec<EventBreak>, // Events the debuggers stop on
+    pub event_hit: Option<Event>, // The last of them that happened, until a debugger takes it
+    pub timeline: Option<Timeline>, // When set, LCD register writes are recorded (for the event viewer)
+    pub line_start: u64, // APU cycle count when the PPU started the current line
+    pub uninit: Option<UninitReads>, // When set, CPU reads of RAM never written are logged
 
 }
 impl MMU {
@@ -82,8 +131,47 @@ impl MMU {
                 }
             }
         }
+
+        // 3. Serial: with nothing on the cable an internally clocked transfer
+        // takes 8 bits at 8192 Hz and shifts in all ones
+        if self.sc & 0x81 == 0x81 && !self.link_connected {
+            self.serial_cycles += cycles as u16;
+            if self.serial_cycles >= 4096 {
+                self.sb = 0xFF;
+                self.finish_transfer();
+            }
+        }
     }
-    pub fn new(rom: Vec<u8>, rom_filename:&str) -> Self {
+
+    fn finish_transfer(&mut self) {
+        self.sc &= 0x7F;
+        self.serial_cycles = 0;
+        self.interrupt_flag |= 0x08; // Request Serial Interrupt (Bit 3)
+    }
+
+    // Link cable: if this side started a transfer, swap SB with the other
+    // Game Boy. The other side completes too if it was waiting on the
+    // external clock. Called once per frame for each side, so a byte takes
+    // a frame to go through.
+    pub fn serial_exchange(&mut self, other: &mut MMU) {
+        if self.sc & 0x81 != 0x81 {
+            return;
+        }
+        std::mem::swap(&mut self.sb, &mut other.sb);
+        self.finish_transfer();
+        if other.sc & 0x81 == 0x80 {
+            other.finish_transfer();
+        }
+    }
+
+    // Link cable to a Game Boy outside this process: completes the transfer
+    // in progress with the byte that came in and returns the one sent
+    pub fn external_transfer(&mut self, incoming: u8) -> u8 {
+        let outgoing = std::mem::replace(&mut self.sb, incoming);
+        self.finish_transfer();
+        outgoing
+    }
+    pub fn new(rom: Vec<u8>, rom_filename:&str, model: Model) -> Self {
     let mbc_type = rom[0x0147];
 
     let has_battery = match mbc_type {
@@ -113,6 +201,20 @@ impl MMU {
       let mut mmu=  Self {
         sb: 0,
         sc: 0,
+        serial_log: None,
+        serial_cycles: 0,
+        debug_line: Vec::new(),
+        debug_log: None,
+        trace: None,
+        #[cfg(feature = "sm83-tests")]
+        flat: None,
+        ly_stub: false,
+        link_connected: false,
+        event_breaks: Vec::new(),
+        event_hit: None,
+        timeline: None,
+        line_start: 0,
+        uninit: None,
             rom,
             mbc_type,
             has_battery,
@@ -150,16 +252,45 @@ impl MMU {
             eram: [0; 0x8000],
             save_filename,
             save_dirty: false,
+            save_error: None,
             // --- Added for MBC3 (Pokemon) ---
             rtc_registers: [0; 5],  // The five clock registers
             rtc_sel: 0,             // Register selection for 0xA000 range
-            apu: apu::APU::new(),
+            apu: apu::APU::new(model),
         };
                if mmu.has_battery {
         mmu.load_save();
     }
         mmu
     }
+    // Power-on RAM contents. Cartridge RAM loaded from a save file is kept.
+    pub fn fill_ram(&mut self, fill: RamFill, seed: u64) {
+        let mut state = seed;
+        // SplitMix64, one byte per step
+        let mut random = move || {
+            state = state.wrapping_add(0x9E3779B97F4A7C15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+            (z ^ (z >> 31)) as u8
+        };
+        let keep_eram = self.has_battery && std::path::Path::new(&self.save_filename).exists();
+        let mut regions: Vec<&mut [u8]> = vec![&mut self.wram, &mut self.hram];
+        if !keep_eram {
+            regions.push(&mut self.eram);
+        }
+        for region in regions {
+            for (i, byte) in region.iter_mut().enumerate() {
+                *byte = match fill {
+                    RamFill::Zero => 0x00,
+                    RamFill::Ones => 0xFF,
+                    RamFill::Random => random(),
+                    RamFill::Pattern => if (i / 8) % 2 == 0 { 0x00 } else { 0xFF },
+                };
+            }
+        }
+    }
+
    pub fn load_save(&mut self) {
         if let Ok(data) = fs::read(&self.save_filename) {
             let len = data.len().min(0x8000);
@@ -180,30 +311,220 @@ pub fn save_ram(&mut self) {
 
     if let Err(e) = fs::write(&self.save_filename, &self.eram[..]) {
         eprintln!("Failed to save '{}': {}", self.save_filename, e);
+        self.save_error = Some(format!("Couldn't write the save file '{}': {}", self.save_filename, e));
     } else {
         println!("✓ Save file '{}' written", self.save_filename);
         self.save_dirty = false;
+        self.save_error = None;
     }
 }
 
+    // Battery RAM the cartridge declares (within the 32KB emulated). MBC2
+    // declares none but has 512 bytes built in.
+    pub fn ram_size(&self) -> usize {
+        let size = match (self.mbc_type, self.rom[0x149]) {
+            (0x05 | 0x06, _) => 0x200,
+            (_, 1) => 0x800,
+            (_, 2) => 0x2000,
+            (_, 3) => 0x8000,
+            (_, 4) => 0x20000,
+            (_, 5) => 0x10000,
+            _ => 0,
+        };
+        size.min(self.eram.len())
+    }
+
+    // 16KB banks in the ROM, from header byte 0x148 (32KB << n). A header
+    // that doesn't fit the file falls back to the file size, rounded up to a
+    // power of two so it still works as a mask.
+    pub fn rom_banks(&self) -> usize {
+        match self.rom[0x148] {
+            n @ 0..=8 if 0x8000 << n <= self.rom.len() => 2 << n,
+            _ => (self.rom.len() / 0x4000).max(2).next_power_of_two(),
+        }
+    }
+
+    // ROM bank mapped at a 0x0000-0x7FFF address (0 for the fixed one).
+    // Out of range bank numbers wrap, as only the low address lines reach
+    // the ROM chip.
+    pub fn rom_bank_at(&self, addr: u16) -> usize {
+        if addr < 0x4000 {
+            return 0;
+        }
+        let bank = match self.mbc_type {
+            0x01..=0x03 => {
+                if self.mode == 0 { self.rom_bank as usize }
+                else { (self.rom_bank & 0x1F) as usize }
+            }
+            0x0F..=0x13 => (self.rom_bank & 0x7F) as usize,
+            0x19..=0x1E => (self.rom_bank & 0x1FF) as usize,
+            _ => self.rom_bank as usize,
+        };
+        bank & (self.rom_banks() - 1)
+    }
+
+    pub fn has_rtc(&self) -> bool {
+        matches!(self.mbc_type, 0x0F | 0x10)
+    }
+
+    // A .sav from another emulator or a flashcart. Cartridge RAM is padded
+    // or cut to the declared size; a BGB/VBA-M RTC footer (current and
+    // latched registers as u32s, then a 32 or 64 bit timestamp) after it sets
+    // the clock. Returns what was done, for the player.
+    pub fn import_save(&mut self, data: &[u8]) -> String {
+        let size = match self.ram_size() {
+            // No RAM declared (an MBC3 with only a clock, or a bad header):
+            // a RAM size plus a footer is taken as that, anything else as RAM
+            0 => [44, 48]
+                .into_iter()
+                .filter_map(|footer| data.len().checked_sub(footer))
+                .find(|ram| matches!(ram, 0 | 0x800 | 0x2000 | 0x8000))
+                .unwrap_or(data.len())
+                .min(self.eram.len()),
+            size => size,
+        };
+        let footer = data.len().checked_sub(size).filter(|&extra| extra == 44 || extra == 48);
+        let ram = &data[..data.len().min(size)];
+        self.eram = [0; 0x8000];
+        self.eram[..ram.len()].copy_from_slice(ram);
+        self.save_dirty = true;
+
+        let mut notes = Vec::new();
+        if footer.is_some() {
+            if self.has_rtc() {
+                for (i, reg) in self.rtc_registers.iter_mut().enumerate() {
+                    *reg = data[size + i * 4];
+                }
+                notes.push("clock read".to_string());
+            } else {
+                notes.push("clock data dropped".to_string());
+            }
+        } else if data.len() > size {
+            notes.push(format!("cut from {} bytes", data.len()));
+        } else if data.len() < size {
+            notes.push(format!("padded from {} bytes", data.len()));
+        }
+        match notes.is_empty() {
+            true => format!("{} bytes", size),
+            false => format!("{} bytes, {}", size, notes.join(", ")),
+        }
+    }
+
+    // Battery RAM at its declared size, with a BGB/VBA-M RTC footer for
+    // cartridges that have a clock, which other emulators and flashcarts read
+    pub fn export_save(&self) -> Vec<u8> {
+        let mut out = self.eram[..self.ram_size()].to_vec();
+        if self.has_rtc() {
+            // Current registers, then the same as latched
+            for _ in 0..2 {
+                for &reg in &self.rtc_registers {
+                    out.extend_from_slice(&(reg as u32).to_le_bytes());
+                }
+            }
+            let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+            out.extend_from_slice(&now.to_le_bytes());
+        }
+        out
+    }
+
+    // Debug text from homebrew: a line written to 0xFF7F or an LD D,D
+    // message (see CPU::debug_message). Printed like serial output.
+    pub fn debug_print(&mut self, text: &[u8]) {
+        let line = String::from_utf8_lossy(text).to_string();
+        match &mut self.debug_log {
+            Some(log) => log.push(line),
+            None => println!("[debug] {}", line),
+        }
+    }
+
+    // Something a debugger can break on happened (see events.rs)
+    pub fn event(&mut self, event: Event) {
+        if self.event_breaks.iter().any(|b| b.matches(&event)) {
+            self.event_hit = Some(event);
+        }
+    }
+
+    // Cartridge types with a banking implementation (ROM only, MBC1, MBC3, MBC5)
+    pub fn mapper_supported(&self) -> bool {
+        matches!(self.mbc_type, 0x00..=0x03 | 0x0F..=0x13 | 0x19..=0x1E)
+    }
+
+    // Everything the game can observe. The ROM, battery bookkeeping and
+    // joypad_state (driven by the frontend every frame) are left out.
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.vram);
+        w.bytes(&self.oam);
+        w.bytes(&self.wram);
+        w.bytes(&self.hram);
+        w.bytes(&self.eram);
+        w.u8(self.interrupt_flag);
+        w.u8(self.interrupt_enable);
+        w.u8(self.div); w.u8(self.tima); w.u8(self.tma); w.u8(self.tac);
+        w.u16(self.div_counter);
+        w.u32(self.tima_counter);
+        for r in [self.lcdc, self.stat, self.scy, self.scx, self.ly, self.lyc, self.bgp, self.obp0, self.obp1, self.wy, self.wx] {
+            w.u8(r);
+        }
+        w.u8(self.joyp_sel);
+        w.u16(self.rom_bank);
+        w.bool(self.ram_enabled);
+        w.u8(self.mode);
+        w.u8(self.ram_bank);
+        w.bytes(&self.rtc_registers);
+        w.u8(self.rtc_sel);
+        w.u8(self.sb);
+        w.u8(self.sc);
+        w.u16(self.serial_cycles);
+        self.apu.save_state(w);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> std::io::Result<()> {
+        r.bytes(&mut self.vram)?;
+        r.bytes(&mut self.oam)?;
+        r.bytes(&mut self.wram)?;
+        r.bytes(&mut self.hram)?;
+        r.bytes(&mut self.eram)?;
+        self.interrupt_flag = r.u8()?;
+        self.interrupt_enable = r.u8()?;
+        self.div = r.u8()?; self.tima = r.u8()?; self.tma = r.u8()?; self.tac = r.u8()?;
+        self.div_counter = r.u16()?;
+        self.tima_counter = r.u32()?;
+        for reg in [
+            &mut self.lcdc, &mut self.stat, &mut self.scy, &mut self.scx, &mut self.ly, &mut self.lyc,
+            &mut self.bgp, &mut self.obp0, &mut self.obp1, &mut self.wy, &mut self.wx,
+        ] {
+            *reg = r.u8()?;
+        }
+        self.joyp_sel = r.u8()?;
+        self.rom_bank = r.u16()?;
+        self.ram_enabled = r.bool()?;
+        self.mode = r.u8()?;
+        self.ram_bank = r.u8()?;
+        r.bytes(&mut self.rtc_registers)?;
+        self.rtc_sel = r.u8()?;
+        self.sb = r.u8()?;
+        self.sc = r.u8()?;
+        self.serial_cycles = r.u16()?;
+        self.apu.load_state(r)?;
+        // Battery RAM came from the state, so make sure it reaches the .sav too
+        self.save_dirty = true;
+        Ok(())
+    }
+
     pub fn read_byte(&self, addr: u16) -> u8 {
+    #[cfg(feature = "sm83-tests")]
+    if let Some(memory) = &self.flat {
+        return memory[addr as usize];
+    }
     match addr {
         0xFF10..=0xFF3F => self.apu.read_register(addr),
         // ROM Bank 0 (Fixed)
-        0x0000..=0x3FFF => self.rom[addr as usize],
+        // (open bus past the end of a short file)
+        0x0000..=0x3FFF => self.rom.get(addr as usize).copied().unwrap_or(0xFF),
         
         // ROM Bank 1-N (Switchable)
         0x4000..=0x7FFF => {
-            let actual_bank = match self.mbc_type {
-                0x01..=0x03 => {
-                    if self.mode == 0 { self.rom_bank as usize } 
-                    else { (self.rom_bank & 0x1F) as usize }
-                }
-                0x0F..=0x13 => (self.rom_bank & 0x7F) as usize,
-                0x19..=0x1E => (self.rom_bank & 0x1FF) as usize,
-                _ => self.rom_bank as usize,
-            };
-            let offset = actual_bank * 0x4000;
+            let offset = self.rom_bank_at(addr) * 0x4000;
             let rom_addr = offset + (addr - 0x4000) as usize;
             if rom_addr < self.rom.len() { self.rom[rom_addr] } else { 0xFF }
         }
@@ -258,6 +579,8 @@ pub fn save_ram(&mut self) {
             }
             res | low_nibble
         }
+        0xFF01 => self.sb,
+        0xFF02 => self.sc | 0x7E,
         0xFF04 => self.div,
         0xFF05 => self.tima,
         0xFF06 => self.tma,
@@ -267,6 +590,7 @@ pub fn save_ram(&mut self) {
         0xFF41 => self.stat | 0x80,
         0xFF42 => self.scy,
         0xFF43 => self.scx,
+        0xFF44 if self.ly_stub => 0x90,
         0xFF44 => self.ly,
         0xFF45 => self.lyc,
         0xFF47 => self.bgp,
@@ -285,7 +609,42 @@ pub fn save_ram(&mut self) {
 }
 
 
+    // A read by the CPU, recorded when tracing. Other readers (the PPU,
+    // debuggers) use read_byte so they don't show up in the trace.
+    pub fn read_traced(&mut self, addr: u16) -> u8 {
+        let value = self.read_byte(addr);
+        if let Some(trace) = &mut self.trace {
+            trace.record(addr, value, false, self.apu.cycle_count);
+        }
+        if let Some(uninit) = &mut self.uninit {
+            uninit.read(addr, value);
+        }
+        value
+    }
+
     pub fn write_byte(&mut self, addr: u16, val: u8) {
+    if let Some(trace) = &mut self.trace {
+        trace.record(addr, val, true, self.apu.cycle_count);
+    }
+    if let Some(uninit) = &mut self.uninit {
+        uninit.write(addr);
+    }
+    #[cfg(feature = "sm83-tests")]
+    if let Some(memory) = &mut self.flat {
+        memory[addr as usize] = val;
+        return;
+    }
+    if (0xFF40..=0xFF4B).contains(&addr)
+        && let Some(timeline) = &mut self.timeline
+    {
+        // The CPU has the PPU caught up to the write; only a debugger's write
+        // between steps can be past the end of the line
+        let dots = self.apu.cycle_count.saturating_sub(self.line_start);
+        let line = (self.ly as u64 + dots / 456) % 154;
+        timeline.record(RegWrite { line: line as u8, dot: (dots % 456) as u16, addr, value: val });
+    }
+    // Writes to the MBC can switch banks, which debuggers can break on
+    let bank_before = (addr < 0x8000).then(|| self.rom_bank_at(0x4000));
     match addr {
         0xFF10..=0xFF3F => self.apu.write_register(addr, val),
         // MBC Register: RAM Enable
@@ -408,19 +767,34 @@ pub fn save_ram(&mut self) {
     // RE-ADD THIS SECTION TO SEE TEXT AGAIN
     0xFF01 => {
         self.sb = val; // (Optional: store it if you have the field)
-        print!("{}", val as char);
-        
-        // FLUSH to force text to appear immediately in the terminal
-        use std::io::Write;
-        let _ = std::io::stdout().flush(); 
+        if let Some(log) = &mut self.serial_log {
+            log.push(val);
+        } else {
+            print!("{}", val as char);
+
+            // FLUSH to force text to appear immediately in the terminal
+            use std::io::Write;
+            let _ = std::io::stdout().flush();
+        }
     },
 
     // 0xFF02: Serial Control (SC)
     0xFF02 => {
-        // If you want to be fancy, you can handle the handshake here,
-        // but for Blargg's tests, the code above in 0xFF01 is enough.
+        // Writing bit 7 starts a transfer; see tick and serial_exchange
         self.sc = val;
+        self.serial_cycles = 0;
     },
+        // 0xFF7F: unused on real hardware, a debug print port here. A line
+        // ends at '\n' or 0 (or gets too long).
+        0xFF7F => {
+            if val == b'\n' || val == 0 || self.debug_line.len() >= 256 {
+                let line = std::mem::take(&mut self.debug_line);
+                self.debug_print(&line);
+            }
+            if val != b'\n' && val != 0 {
+                self.debug_line.push(val);
+            }
+        }
         0xFF04 => {
             self.div = 0;
             self.div_counter = 0;
@@ -438,6 +812,9 @@ pub fn save_ram(&mut self) {
         0xFF46 => {
             // DMA Transfer
             let source_base = (val as u16) << 8;
+            if let Some(uninit) = &mut self.uninit {
+                uninit.dma(source_base);
+            }
             for i in 0..0xA0 {
                 let byte = self.read_byte(source_base + i);
                 self.oam[i as usize] = byte;
@@ -456,5 +833,11 @@ pub fn save_ram(&mut self) {
         // Ignore writes to ROM
         _ => {}
     }
+    if let Some(before) = bank_before {
+        let bank = self.rom_bank_at(0x4000);
+        if bank != before {
+            self.event(Event::RomBank(bank));
+        }
+    }
 }
 }
\ No newline at end of file
@@ -0,0 +1,114 @@
+// movie.rs
+// Input movies: one joypad byte per emulated frame, starting from power-on
+// with blank cartridge RAM.
+//
+// Movies are stored as a BizHawk-style input log (the "Input Log.txt" found
+// inside a .bk2 archive), so logs can be copied between gb_rs and BizHawk:
+//
+//   [Input]
+//   LogKey:#Up|Down|Left|Right|Start|Select|B|A|Power|
+//   |UDLRSsBAP|      <- one line per frame, '.' = released
+//   [/Input]
+//
+// On import the LogKey line decides which column is which button, so logs with
+// extra or reordered columns still load. Power presses are ignored.
+
+// Joypad bit (active low, as in MMU::joypad_state) for each BizHawk button name
+pub const BUTTONS: [(&str, char, u8); 8] = [
+    ("Up", 'U', 2),
+    ("Down", 'D', 3),
+    ("Left", 'L', 1),
+    ("Right", 'R', 0),
+    ("Start", 'S', 7),
+    ("Select", 's', 6),
+    ("B", 'B', 5),
+    ("A", 'A', 4),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovieMode {
+    Recording,
+    Playing,
+}
+
+pub struct Movie {
+    pub mode: MovieMode,
+    pub frames: Vec<u8>,
+    pub pos: usize,
+}
+
+impl Movie {
+    pub fn recording() -> Self {
+        Self { mode: MovieMode::Recording, frames: Vec::new(), pos: 0 }
+    }
+
+    // Joypad state for the next frame: the recorded one when playing (None
+    // once the movie is over), otherwise the live input, which gets logged
+    pub fn next_frame(&mut self, live: u8) -> Option<u8> {
+        match self.mode {
+            MovieMode::Recording => {
+                self.frames.push(live);
+                Some(live)
+            }
+            MovieMode::Playing => {
+                let pad = self.frames.get(self.pos).copied();
+                self.pos += 1;
+                pad
+            }
+        }
+    }
+
+    pub fn to_input_log(&self) -> String {
+        let mut out = String::from("[Input]\nLogKey:#Up|Down|Left|Right|Start|Select|B|A|Power|\n");
+        for &pad in &self.frames {
+            out.push('|');
+            for (_, c, bit) in BUTTONS {
+                out.push(if pad & (1 << bit) == 0 { c } else { '.' });
+            }
+            out.push_str(".|\n");
+        }
+        out.push_str("[/Input]\n");
+        out
+    }
+
+    pub fn from_input_log(text: &str) -> Result<Self, String> {
+        // Column order from the LogKey line (defaults to our own layout)
+        let mut columns: Vec<String> = ["Up", "Down", "Left", "Right", "Start", "Select", "B", "A", "Power"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let mut frames = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim_end();
+            if let Some(key) = line.strip_prefix("LogKey:") {
+                // "#P1 Up|P1 Down|...|#Power|": groups start with '#'
+                columns = key
+                    .split(['#', '|'])
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.trim_start_matches("P1 ").to_string())
+                    .collect();
+                continue;
+            }
+            if !line.starts_with('|') { continue; }
+
+            let cells: Vec<char> = line.chars().filter(|&c| c != '|').collect();
+            if cells.len() < columns.len() {
+                return Err(format!("frame {} has {} inputs, LogKey lists {}", frames.len() + 1, cells.len(), columns.len()));
+            }
+            let mut pad = 0xFF;
+            for (name, &cell) in columns.iter().zip(&cells) {
+                if cell == '.' || cell == ' ' { continue; }
+                if let Some((_, _, bit)) = BUTTONS.iter().find(|(n, _, _)| n == name) {
+                    pad &= !(1 << bit);
+                }
+            }
+            frames.push(pad);
+        }
+
+        if frames.is_empty() {
+            return Err("no input frames found".to_string());
+        }
+        Ok(Self { mode: MovieMode::Playing, frames, pos: 0 })
+    }
+}
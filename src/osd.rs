@@ -0,0 +1,57 @@
+// osd.rs
+// Short-lived on-screen messages drawn over the bottom of the game area
+// (pad connected, state saved, ...). Messages are mirrored to stdout.
+use std::time::{Duration, Instant};
+
+use crate::draw_text;
+use crate::theme::Theme;
+
+const DURATION: Duration = Duration::from_secs(3);
+const MAX_MESSAGES: usize = 4;
+const SCALE: usize = 2;
+const LINE_H: usize = 8 * SCALE + 4;
+
+pub struct Osd {
+    messages: Vec<(String, Instant)>,
+    pub prompt: Option<String>, // Stays up (centered) until cleared
+}
+
+impl Osd {
+    pub fn new() -> Self {
+        Self { messages: Vec::new(), prompt: None }
+    }
+
+    pub fn show(&mut self, text: &str) {
+        println!("{}", text);
+        self.messages.push((text.to_string(), Instant::now()));
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.remove(0);
+        }
+    }
+
+    // Newest message at the bottom, each on a dark strip for readability
+    pub fn draw(&mut self, buffer: &mut [u32], width: usize, height: usize, theme: &Theme) {
+        self.messages.retain(|(_, t)| t.elapsed() < DURATION);
+        let count = self.messages.len();
+        for (i, (text, _)) in self.messages.iter().enumerate() {
+            let y = height - (count - i) * LINE_H - 4;
+            Self::draw_line(buffer, width, text, 4, y, theme);
+        }
+        if let Some(text) = &self.prompt {
+            let text_w = text.chars().count() * 8 * SCALE;
+            let x = width.saturating_sub(text_w) / 2;
+            Self::draw_line(buffer, width, text, x, height / 2, theme);
+        }
+    }
+
+    fn draw_line(buffer: &mut [u32], width: usize, text: &str, x: usize, y: usize, theme: &Theme) {
+        let x0 = x.saturating_sub(4);
+        let x1 = (x + text.chars().count() * 8 * SCALE + 4).min(width);
+        for row in y..y + LINE_H {
+            for p in &mut buffer[row * width + x0..row * width + x1] {
+                *p = theme.backdrop(*p);
+            }
+        }
+        draw_text(buffer, width, text, x, y + 2, theme.text, SCALE);
+    }
+}
@@ -0,0 +1,84 @@
+// pacer.rs
+// Paces video-synced emulation at the real DMG frame rate: 70224 cycles at
+// 4194304 Hz, about 59.7275 frames per second.
+//
+// Deadlines are computed from a fixed anchor (frame N is due at anchor + N
+// frame times) instead of "now + 16.6ms", so sleep overshoot and rounding
+// never accumulate over a long session.
+use std::time::{Duration, Instant};
+
+const CPU_HZ: u64 = 4_194_304;
+const FRAME_CYCLES: u64 = 70224;
+
+// Sleep until this close to the deadline, then spin for the rest
+const SPIN_MARGIN: Duration = Duration::from_micros(1500);
+// Further behind than this (a stall, a dialog, the debugger...) we resync
+// instead of running frames back to back to catch up
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+pub struct FramePacer {
+    anchor: Instant,
+    frames: u64,
+    // Frame time in ns, as a fraction so it stays exact
+    period_num: u64,
+    period_den: u64,
+}
+
+impl FramePacer {
+    // Game Boy frame rate
+    pub fn new() -> Self {
+        Self::with_period(FRAME_CYCLES * 1_000_000_000, CPU_HZ)
+    }
+
+    // Any other rate, e.g. the monitor's refresh rate
+    pub fn with_hz(hz: f64) -> Self {
+        Self::with_period(1_000_000_000 * 1000, ((hz * 1000.0) as u64).max(1))
+    }
+
+    fn with_period(period_num: u64, period_den: u64) -> Self {
+        Self { anchor: Instant::now(), frames: 0, period_num, period_den }
+    }
+
+    // Start counting from now, e.g. after pacing was off for a while
+    pub fn reset(&mut self) {
+        self.anchor = Instant::now();
+        self.frames = 0;
+    }
+
+    fn deadline(&self, frames: u64) -> Instant {
+        self.anchor + Duration::from_nanos((frames as u128 * self.period_num as u128 / self.period_den as u128) as u64)
+    }
+
+    // Block until the next frame is due
+    pub fn wait(&mut self) {
+        self.frames += 1;
+        let deadline = self.deadline(self.frames);
+        let now = Instant::now();
+        if now > deadline + MAX_LAG {
+            self.reset();
+            return;
+        }
+        if deadline > now + SPIN_MARGIN {
+            std::thread::sleep(deadline - now - SPIN_MARGIN);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    }
+
+    // Non-blocking: how many frames have come due since the last call (0 when
+    // called faster than the frame rate, 2+ when slower)
+    pub fn frames_due(&mut self) -> u32 {
+        let now = Instant::now();
+        let mut due = 0;
+        while self.deadline(self.frames + 1) <= now {
+            self.frames += 1;
+            due += 1;
+            if now > self.deadline(self.frames) + MAX_LAG {
+                self.reset();
+                return 1;
+            }
+        }
+        due
+    }
+}
@@ -0,0 +1,109 @@
+// perf_overlay.rs
+// Performance overlay, drawn over the top left of the game: emulated frames
+// per second, the host's time per main loop iteration (the work, not the
+// wait for the next frame), how much audio emulation has queued ahead of
+// the sound card, and the card's buffer size and underruns. When a game
+// slows down by itself the FPS stays at 59.7; when the emulator can't keep
+// up the host time reaches a frame's 16.7 ms and the FPS drops, and the
+// audio queue running dry means crackles. Underruns that go up while the
+// queue is full are the buffer being too small for the machine.
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::audio::{AudioStats, MAX_LAG_CYCLES};
+use crate::draw_text;
+use crate::theme::Theme;
+
+const PERIOD: Duration = Duration::from_millis(500); // How often the numbers change
+const LINE_H: usize = 10;
+const CPU_HZ: f64 = 4_194_304.0;
+
+pub struct PerfOverlay {
+    since: Instant, // Start of the current period
+    frames_then: u64, // The emulator's frame count at `since`
+    busy_from: Option<Instant>,
+    busy: Duration, // Work in this loop iteration so far
+    total: Duration, // Work in this period
+    worst: Duration, // Longest iteration in this period
+    iterations: u32,
+    // What's shown, from the last full period
+    fps: f64,
+    host_ms: f64,
+    worst_ms: f64,
+}
+
+impl PerfOverlay {
+    pub fn new() -> Self {
+        Self {
+            since: Instant::now(), frames_then: 0, busy_from: None, busy: Duration::ZERO, total: Duration::ZERO,
+            worst: Duration::ZERO, iterations: 0, fps: 0.0, host_ms: 0.0, worst_ms: 0.0,
+        }
+    }
+
+    // Work starts (or resumes after waiting on something)
+    pub fn start(&mut self) {
+        self.busy_from = Some(Instant::now());
+    }
+
+    // Work stops to wait
+    pub fn stop(&mut self) {
+        if let Some(from) = self.busy_from.take() {
+            self.busy += from.elapsed();
+        }
+    }
+
+    // End of a main loop iteration; `frame_count` is the emulator's (0
+    // without a game)
+    pub fn iteration_done(&mut self, frame_count: u64) {
+        self.stop();
+        self.total += self.busy;
+        self.worst = self.worst.max(self.busy);
+        self.busy = Duration::ZERO;
+        self.iterations += 1;
+
+        let elapsed = self.since.elapsed();
+        if elapsed < PERIOD {
+            return;
+        }
+        // A newly loaded game counts from 0 again, which reads as 0 FPS for
+        // one period
+        let frames = frame_count.saturating_sub(self.frames_then);
+        self.fps = frames as f64 / elapsed.as_secs_f64();
+        self.host_ms = self.total.as_secs_f64() * 1000.0 / self.iterations as f64;
+        self.worst_ms = self.worst.as_secs_f64() * 1000.0;
+        self.since = Instant::now();
+        self.frames_then = frame_count;
+        self.total = Duration::ZERO;
+        self.worst = Duration::ZERO;
+        self.iterations = 0;
+    }
+
+    // `audio` is how far emulation is ahead of the sound card in T-cycles and
+    // the card's stats, None without sound
+    pub fn draw(&self, buffer: &mut [u32], width: usize, top: usize, audio: Option<(u64, &AudioStats)>, theme: &Theme) {
+        let mut lines = vec![
+            format!("FPS {:.1}", self.fps),
+            format!("HOST {:.1}MS MAX {:.1}", self.host_ms, self.worst_ms),
+        ];
+        if let Some((cycles, stats)) = audio {
+            // At MAX_LAG_CYCLES the synth skips ahead and the rest is lost
+            let percent = cycles * 100 / MAX_LAG_CYCLES;
+            lines.push(format!("AUDIO {:.1}MS {}%", cycles as f64 * 1000.0 / CPU_HZ, percent));
+            lines.push(format!(
+                "BUF {} XRUN {}",
+                stats.buffer_frames.load(Ordering::Relaxed),
+                stats.underruns.load(Ordering::Relaxed)
+            ));
+        }
+        let chars = lines.iter().map(|l| l.len()).max().unwrap_or(0);
+        let right = (chars * 8 + 8).min(width);
+        for row in top..top + lines.len() * LINE_H + 4 {
+            for p in &mut buffer[row * width..row * width + right] {
+                *p = theme.backdrop(*p);
+            }
+        }
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(buffer, width, line, 4, top + 3 + i * LINE_H, theme.text, 1);
+        }
+    }
+}
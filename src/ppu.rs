@@ -1,243 +1,469 @@
+use crate::colorize::Colorization;
+use crate::events::Event;
+use crate::savestate::{StateReader, StateWriter};
+
 pub struct PPU {
     pub frame_buffer: [u32; 160 * 144],
-    pub mode_clock: u32,
+    pub palette: [u32; 4], // ARGB shades for color IDs 0-3
+    pub obj_palettes: [[u32; 4]; 2], // Sprite shades for OBP0/OBP1, the same as `palette` unless colorized
+    pub mode_clock: u32, // Dot within the current line, 0-455
     pub window_line_counter: u8,
+    pipeline: Pipeline,
 }
 
-impl PPU {
-    fn render_window(&mut self, mmu: &crate::mmu::MMU) {
-        if (mmu.lcdc & 0x20) == 0 { return; }
+impl Default for PPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        let ly = mmu.ly;
-        let wy = mmu.wy;
-        let wx = mmu.wx;
-        
-        // Don't render if we haven't reached the window Y position yet
-        if ly < wy { return; }
-        
-        // WX range: 0-166 are valid. WX=7 means window starts at screen X=0
-        if wx >= 167 { return; }
+// Mode 3 the way the hardware draws it: a fetcher reads the background or
+// window a tile (8 pixels) at a time into a FIFO, and one pixel leaves it
+// per dot, mixed with a second FIFO of sprite pixels. Registers are read
+// when the fetcher or the mixer get to them, so a write partway through a
+// line (SCX, LCDC, BGP, ...) changes the pixels after it. Every sprite on
+// the line holds the fetcher up for 6-11 dots, and SCX % 8 and the window
+// cost a few more, so mode 3 (and the STAT timing after it) gets longer
+// the way it does on a DMG. The CPU ticks the PPU every M-cycle, ahead of
+// each memory access, so a write lands on the dot it does on hardware.
+
+// A sprite the OAM scan found on the current line
+#[derive(Clone, Copy, Default)]
+struct LineSprite {
+    x: u8,    // OAM X: the screen column plus 8
+    tile: u8, // The line's tile (the lower one of an 8x16 sprite past its 8th row)
+    row: u8,  // The line's row in that tile, after Y flip
+    attributes: u8,
+    fetched: bool,
+}
 
-        let tile_map_base: u16 = if (mmu.lcdc & 0x40) != 0 { 0x9C00 } else { 0x9800 };
-        let window_ly = self.window_line_counter; 
-        let tile_row = (window_ly as u16 / 8) * 32;
+#[derive(Default)]
+struct Pipeline {
+    active: bool, // In mode 3
+    x: u8,        // Pixels sent to the screen so far on this line
+    startup: u8,  // Dots left of the first tile fetch of the line, which is thrown away
+    discard: u8,  // Pixels to drop before drawing: SCX % 8, or the window's part left of the screen
+    // BG/window FIFO: color IDs as two shift registers, leftmost pixel in bit 7
+    bg_low: u8,
+    bg_high: u8,
+    bg_len: u8,
+    // Sprite FIFO: color ID (0 for none) and attributes of the next 8 pixels
+    obj_color: [u8; 8],
+    obj_attributes: [u8; 8],
+    // Fetcher: dots into the current tile (6: its data is read), which tile
+    // of the line it's on, and what it read
+    step: u8,
+    tile_x: u8,
+    tile_id: u8,
+    data_low: u8,
+    data_high: u8,
+    window: bool,   // Fetching the window instead of the background
+    window_y: bool, // LY has matched WY this frame, so the window can start
+    sprites: [LineSprite; 10],
+    sprite_count: u8,
+    sprite_wait: u8, // Dots into a sprite fetch, 0 when there's none
+}
 
-        // Track if we actually rendered any pixels (important for counter increment)
-        let mut rendered_pixels = false;
+impl Pipeline {
+    // Mode 3 starts: the OAM scan picks the first 10 sprites on the line
+    // (in OAM order, whatever their X), and the FIFOs start out empty
+    fn start_line(&mut self, mmu: &crate::mmu::MMU) {
+        let height = if (mmu.lcdc & 0x04) != 0 { 16 } else { 8 };
+        self.sprite_count = 0;
+        for i in 0..40u16 {
+            if self.sprite_count == 10 {
+                break;
+            }
+            let oam_addr = 0xFE00 + i * 4;
+            // OAM Y is the screen row plus 16
+            let row = mmu.ly.wrapping_add(16).wrapping_sub(mmu.read_byte(oam_addr));
+            if row >= height {
+                continue;
+            }
+            let attributes = mmu.read_byte(oam_addr + 3);
+            let row = if (attributes & 0x40) != 0 { height - 1 - row } else { row };
+            let mut tile = mmu.read_byte(oam_addr + 2);
+            if height == 16 {
+                tile = (tile & 0xFE) | (row >> 3);
+            }
+            self.sprites[self.sprite_count as usize] = LineSprite { x: mmu.read_byte(oam_addr + 1), tile, row: row & 7, attributes, fetched: false };
+            self.sprite_count += 1;
+        }
+        self.active = true;
+        self.x = 0;
+        self.startup = 6;
+        self.discard = mmu.scx % 8;
+        self.bg_len = 0;
+        self.obj_color = [0; 8];
+        self.step = 0;
+        self.tile_x = 0;
+        self.window = false;
+        self.sprite_wait = 0;
+    }
 
-        for x in 0..160u8 {
-            // Window starts at WX - 7
-            // So if WX=7, window starts at x=0
-            // If WX=0, window would start at x=-7 (off screen)
-            if x + 7 < wx { continue; }
+    // The next sprite to fetch: one whose left edge is the pixel about to
+    // be drawn (or left of the screen, at pixel 0), the leftmost first and
+    // then by OAM order. X 0 hides a sprite completely.
+    fn next_sprite(&self) -> Option<usize> {
+        (0..self.sprite_count as usize)
+            .filter(|&i| {
+                let s = &self.sprites[i];
+                !s.fetched && s.x > 0 && s.x.saturating_sub(8) == self.x
+            })
+            .min_by_key(|&i| self.sprites[i].x)
+    }
 
-            rendered_pixels = true;
-            let window_x = (x + 7 - wx) as u8;
-            let tile_col = window_x as u16 / 8;
-            
-            let tile_address = tile_map_base + tile_row + tile_col;
-            let tile_id = mmu.read_byte(tile_address);
+    // A fetched sprite's row into the sprite FIFO. Pixels already there
+    // came from sprites further left or earlier in OAM, and stay on top.
+    fn merge_sprite(&mut self, mmu: &crate::mmu::MMU, i: usize) {
+        let sprite = &mut self.sprites[i];
+        sprite.fetched = true;
+        let mut ids = tile_row(mmu, 0x8000 + sprite.tile as u16 * 16 + sprite.row as u16 * 2);
+        if (sprite.attributes & 0x20) != 0 {
+            ids.reverse();
+        }
+        // Part way off the left edge: the first pixels are never drawn
+        let skip = 8usize.saturating_sub(sprite.x as usize);
+        for (slot, &color_id) in ids[skip..].iter().enumerate() {
+            if color_id != 0 && self.obj_color[slot] == 0 {
+                self.obj_color[slot] = color_id;
+                self.obj_attributes[slot] = sprite.attributes;
+            }
+        }
+    }
 
-            let tile_data_address = self.get_tile_data_addr(mmu, tile_id, window_ly % 8);
-            let byte1 = mmu.read_byte(tile_data_address);
-            let byte2 = mmu.read_byte(tile_data_address + 1);
+    // The fetcher's reads: the tile ID at step 2, then its row's two bytes
+    // at steps 4 and 6
+    fn fetch(&mut self, mmu: &crate::mmu::MMU, window_line: u8) {
+        let (map_bit, column, y) = if self.window {
+            (0x40, self.tile_x, window_line)
+        } else {
+            (0x08, (mmu.scx / 8).wrapping_add(self.tile_x), mmu.ly.wrapping_add(mmu.scy))
+        };
+        match self.step {
+            2 => {
+                let map_base: u16 = if (mmu.lcdc & map_bit) != 0 { 0x9C00 } else { 0x9800 };
+                self.tile_id = mmu.read_byte(map_base + (y as u16 / 8) * 32 + (column & 31) as u16);
+            }
+            4 => self.data_low = mmu.read_byte(tile_data_addr(mmu, self.tile_id, y % 8)),
+            6 => self.data_high = mmu.read_byte(tile_data_addr(mmu, self.tile_id, y % 8) + 1),
+            _ => {}
+        }
+    }
 
-            let bit_idx = 7 - (window_x % 8);
-            let color_id = ((byte2 >> bit_idx) & 0x01) << 1 | ((byte1 >> bit_idx) & 0x01);
+    // One dot of mode 3. Returns the pixel sent to the screen, if any: its
+    // X, BG/window color ID, and sprite color ID (0 for none) and attributes.
+    fn dot(&mut self, mmu: &crate::mmu::MMU, window_line: u8) -> Option<(u8, u8, u8, u8)> {
+        if self.startup > 0 {
+            self.startup -= 1;
+            return None;
+        }
+        // The window takes over from pixel WX - 7 to the end of the line,
+        // with the fetcher starting over on its first tile
+        if !self.window && self.window_y && (mmu.lcdc & 0x20) != 0 && self.x + 7 >= mmu.wx {
+            self.window = true;
+            self.tile_x = 0;
+            self.step = 0;
+            self.bg_len = 0;
+            self.discard = if self.x == 0 { 7u8.saturating_sub(mmu.wx) } else { 0 };
+        }
 
-            let color = self.get_color(mmu.bgp, color_id);
-            self.frame_buffer[ly as usize * 160 + x as usize] = color;
+        if self.step == 6 && self.bg_len == 0 {
+            self.bg_low = self.data_low;
+            self.bg_high = self.data_high;
+            self.bg_len = 8;
+            self.step = 0;
+            self.tile_x = self.tile_x.wrapping_add(1);
+        }
+        if self.step < 6 {
+            self.step += 1;
+            self.fetch(mmu, window_line);
         }
 
-        // CRITICAL: Only increment if we actually rendered window pixels on this scanline
-        if rendered_pixels {
-            self.window_line_counter += 1;
+        // A sprite starting here stops the pixels while it's fetched: the
+        // background tile in progress is finished first, then 6 dots for
+        // the sprite
+        if self.sprite_wait > 0 || (self.bg_len > 0 && (mmu.lcdc & 0x02) != 0 && self.next_sprite().is_some()) {
+            if self.step == 6 {
+                self.sprite_wait += 1;
+                if self.sprite_wait == 6 {
+                    self.sprite_wait = 0;
+                    if let Some(i) = self.next_sprite() {
+                        self.merge_sprite(mmu, i);
+                    }
+                }
+            }
+            return None;
+        }
+
+        if self.bg_len == 0 {
+            return None;
+        }
+        let bg_id = (self.bg_high >> 7) << 1 | (self.bg_low >> 7);
+        self.bg_low <<= 1;
+        self.bg_high <<= 1;
+        self.bg_len -= 1;
+        if self.discard > 0 {
+            self.discard -= 1;
+            return None;
         }
+        let (obj_id, attributes) = (self.obj_color[0], self.obj_attributes[0]);
+        self.obj_color.copy_within(1.., 0);
+        self.obj_attributes.copy_within(1.., 0);
+        self.obj_color[7] = 0;
+        let x = self.x;
+        self.x += 1;
+        Some((x, bg_id, obj_id, attributes))
     }
 
-    fn render_sprites(&mut self, mmu: &crate::mmu::MMU) {
-        if (mmu.lcdc & 0x02) == 0 { return; }
-        let sprite_height = if (mmu.lcdc & 0x04) != 0 { 16 } else { 8 };
+    fn save_state(&self, w: &mut StateWriter) {
+        for v in [self.x, self.startup, self.discard, self.bg_low, self.bg_high, self.bg_len, self.step, self.tile_x, self.tile_id, self.data_low, self.data_high, self.sprite_count, self.sprite_wait] {
+            w.u8(v);
+        }
+        w.bool(self.active);
+        w.bool(self.window);
+        w.bool(self.window_y);
+        w.bytes(&self.obj_color);
+        w.bytes(&self.obj_attributes);
+        for s in &self.sprites {
+            w.bytes(&[s.x, s.tile, s.row, s.attributes]);
+            w.bool(s.fetched);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> std::io::Result<()> {
+        for v in [
+            &mut self.x, &mut self.startup, &mut self.discard, &mut self.bg_low, &mut self.bg_high, &mut self.bg_len, &mut self.step,
+            &mut self.tile_x, &mut self.tile_id, &mut self.data_low, &mut self.data_high, &mut self.sprite_count, &mut self.sprite_wait,
+        ] {
+            *v = r.u8()?;
+        }
+        self.active = r.bool()?;
+        self.window = r.bool()?;
+        self.window_y = r.bool()?;
+        r.bytes(&mut self.obj_color)?;
+        r.bytes(&mut self.obj_attributes)?;
+        for s in &mut self.sprites {
+            let mut b = [0; 4];
+            r.bytes(&mut b)?;
+            *s = LineSprite { x: b[0], tile: b[1], row: b[2], attributes: b[3], fetched: r.bool()? };
+        }
+        self.sprite_count = self.sprite_count.min(10);
+        Ok(())
+    }
+}
 
-        for i in (0..40).rev() {
-            let oam_addr = 0xFE00 + (i * 4);
-            
-            let y_pos = mmu.read_byte(oam_addr).wrapping_sub(16);
-            let x_pos = mmu.read_byte(oam_addr + 1).wrapping_sub(8);
+impl PPU {
+    // A whole 32x32 tile map (at 0x9800 or 0x9C00) as 256x256 ARGB pixels,
+    // with the tile data LCDC selects and the BGP shades, for debug views
+    pub fn render_tile_map(&self, mmu: &crate::mmu::MMU, map_base: u16) -> Vec<u32> {
+        let colors = map_shades(&self.palette, mmu.bgp);
+        let mut pixels = vec![0; 256 * 256];
+        for y in 0..256usize {
+            for tile_col in 0..32usize {
+                let tile_id = mmu.read_byte(map_base + (y / 8 * 32 + tile_col) as u16);
+                let ids = tile_row(mmu, tile_data_addr(mmu, tile_id, (y % 8) as u8));
+                for (x, &color_id) in ids.iter().enumerate() {
+                    pixels[y * 256 + tile_col * 8 + x] = colors[color_id as usize];
+                }
+            }
+        }
+        pixels
+    }
+
+    // All 384 tiles in VRAM (0x8000-0x97FF), 16 to a row, as 128x192 ARGB
+    // pixels in the plain shades, no palette register applied
+    pub fn render_tileset(&self, mmu: &crate::mmu::MMU) -> Vec<u32> {
+        let mut pixels = vec![0; 128 * 192];
+        for tile in 0..384 {
+            let (left, top) = (tile % 16 * 8, tile / 16 * 8);
+            for row in 0..8 {
+                let ids = tile_row(mmu, 0x8000 + (tile * 16 + row * 2) as u16);
+                for (x, &color_id) in ids.iter().enumerate() {
+                    pixels[(top + row) * 128 + left + x] = self.palette[color_id as usize];
+                }
+            }
+        }
+        pixels
+    }
+
+    // The 40 sprites in OAM order, 8 to a row, each in a 10x18 cell (8x16
+    // and a gap; 8x8 sprites fill the top half) as 80x90 ARGB pixels. They
+    // are flipped and colored as they'd be drawn, with transparency around
+    // them.
+    pub fn render_sprite_sheet(&self, mmu: &crate::mmu::MMU) -> Vec<u32> {
+        let sprite_height = if (mmu.lcdc & 0x04) != 0 { 16 } else { 8 };
+        let obj_colors = [map_shades(&self.obj_palettes[0], mmu.obp0), map_shades(&self.obj_palettes[1], mmu.obp1)];
+        let mut pixels = vec![0; 80 * 90];
+        for i in 0..40 {
+            let oam_addr = 0xFE00 + i as u16 * 4;
             let mut tile_id = mmu.read_byte(oam_addr + 2);
             let attributes = mmu.read_byte(oam_addr + 3);
-
             if sprite_height == 16 {
                 tile_id &= 0xFE;
             }
-
-            let ly = mmu.ly;
-
-            if ly >= y_pos && ly < y_pos + sprite_height {
-                let mut row = ly - y_pos;
-                
-                if (attributes & 0x40) != 0 {
-                    row = (sprite_height - 1) - row;
+            let colors = &obj_colors[((attributes >> 4) & 1) as usize];
+            let (left, top) = (i % 8 * 10, i / 8 * 18);
+            for row in 0..sprite_height {
+                let src = if (attributes & 0x40) != 0 { sprite_height - 1 - row } else { row };
+                let addr = 0x8000 + tile_id as u16 * 16 + src as u16 * 2;
+                let mut ids = tile_row(mmu, addr);
+                if (attributes & 0x20) != 0 {
+                    ids.reverse();
                 }
-
-                let current_tile = if sprite_height == 16 && row >= 8 {
-                    tile_id + 1
-                } else {
-                    tile_id
-                };
-                
-                let tile_row = row % 8;
-                let tile_data_addr = 0x8000 + (current_tile as u16 * 16) + (tile_row as u16 * 2);
-                let byte1 = mmu.read_byte(tile_data_addr);
-                let byte2 = mmu.read_byte(tile_data_addr + 1);
-                let behind_bg = (attributes & 0x80) != 0;
-
-                for x in 0..8 {
-                    let bit_idx = if (attributes & 0x20) != 0 { x } else { 7 - x };
-                    let low_bit = (byte1 >> bit_idx) & 0x01;
-                    let high_bit = (byte2 >> bit_idx) & 0x01;
-                    let color_id = (high_bit << 1) | low_bit;
-
+                for (x, &color_id) in ids.iter().enumerate() {
                     if color_id != 0 {
-                        let screen_x = x_pos.wrapping_add(x as u8);
-                        if screen_x < 160 {
-                            let pixel_index = ly as usize * 160 + screen_x as usize;
-                            
-                            if behind_bg {
-                                let current_pixel = self.frame_buffer[pixel_index];
-                                if current_pixel != 0xFFFFFFFF {
-                                    continue;
-                                }
-                            }
-                            
-                            let palette = if (attributes & 0x10) != 0 { mmu.obp1 } else { mmu.obp0 };
-                            let color = self.get_color(palette, color_id);
-                            self.frame_buffer[pixel_index] = color;
-                        }
+                        pixels[(top + row) * 80 + left + x] = colors[color_id as usize];
                     }
                 }
             }
         }
+        pixels
     }
 
-    fn render_background(&mut self, mmu: &crate::mmu::MMU) {
-        let ly = mmu.ly;
-        let scy = mmu.scy;
-        let scx = mmu.scx;
-
-        let tile_map_base: u16 = if (mmu.lcdc & 0x08) != 0 { 0x9C00 } else { 0x9800 };
-        let y_pos = scy.wrapping_add(ly);
-        let tile_row = (y_pos as u16 / 8) * 32;
-
-        for x in 0..160u8 {
-            let x_pos = x.wrapping_add(scx);
-            let tile_col = x_pos as u16 / 8;
-            
-            let tile_address = tile_map_base + tile_row + tile_col;
-            let tile_id = mmu.read_byte(tile_address);
-
-            let tile_data_address = self.get_tile_data_addr(mmu, tile_id, y_pos % 8);
-
-            let byte1 = mmu.read_byte(tile_data_address);
-            let byte2 = mmu.read_byte(tile_data_address + 1);
-
-            let bit_idx = 7 - (x_pos % 8);
-            let low_bit = (byte1 >> bit_idx) & 0x01;
-            let high_bit = (byte2 >> bit_idx) & 0x01;
-            let color_id = (high_bit << 1) | low_bit;
-
-            let color = self.get_color(mmu.bgp, color_id);
-            self.frame_buffer[ly as usize * 160 + x as usize] = color;
-        }
-    }
-
-    fn get_tile_data_addr(&self, mmu: &crate::mmu::MMU, tile_id: u8, row: u8) -> u16 {
-        let is_signed = (mmu.lcdc & 0x10) == 0;
-        if !is_signed {
-            0x8000 + (tile_id as u16 * 16) + (row as u16 * 2)
-        } else {
-            let offset = (tile_id as i8 as i16) + 128;
-            0x8800 + (offset as u16 * 16) + (row as u16 * 2)
+    // Switch shades, recoloring the frame on screen so the change shows
+    // even while paused
+    pub fn set_palette(&mut self, palette: [u32; 4]) {
+        for p in self.frame_buffer.iter_mut() {
+            if let Some(i) = self.palette.iter().position(|c| c == p) {
+                *p = palette[i];
+            }
         }
+        self.palette = palette;
+        self.obj_palettes = [palette; 2];
     }
 
-    fn get_color(&self, palette: u8, color_id: u8) -> u32 {
-        let hi = (color_id << 1) + 1;
-        let lo = color_id << 1;
-        let actual_color = ((palette >> hi) & 0x01) << 1 | ((palette >> lo) & 0x01);
-
-        match actual_color {
-            0 => 0xFFFFFFFF, // White
-            1 => 0xFFAAAAAA, // Light Gray
-            2 => 0xFF555555, // Dark Gray
-            _ => 0xFF000000, // Black
-        }
+    // Separate background and sprite colors, as the CGB gives DMG games
+    pub fn set_colorization(&mut self, colors: Colorization) {
+        self.set_palette(colors[0]);
+        self.obj_palettes = [colors[1], colors[2]];
     }
 
     pub fn new() -> Self {
         Self {
             frame_buffer: [0xFFFFFFFF; 160 * 144],
+            palette: [0xFFFFFFFF, 0xFFAAAAAA, 0xFF555555, 0xFF000000],
+            obj_palettes: [[0xFFFFFFFF, 0xFFAAAAAA, 0xFF555555, 0xFF000000]; 2],
             mode_clock: 0,
             window_line_counter: 0,
+            pipeline: Pipeline::default(),
         }
     }
 
+    // The frame buffer is included so a loaded state shows its picture
+    // immediately instead of the previous game's last frame
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.u32(self.mode_clock);
+        w.u8(self.window_line_counter);
+        for &p in self.frame_buffer.iter() {
+            w.u32(p);
+        }
+        self.pipeline.save_state(w);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> std::io::Result<()> {
+        self.mode_clock = r.u32()?;
+        self.window_line_counter = r.u8()?;
+        for p in self.frame_buffer.iter_mut() {
+            *p = r.u32()?;
+        }
+        self.pipeline.load_state(r)
+    }
+
     pub fn tick(&mut self, mmu: &mut crate::mmu::MMU, cycles: u8) {
         if (mmu.lcdc & 0x80) == 0 {
             mmu.ly = 0;
             self.mode_clock = 0;
+            mmu.line_start = mmu.apu.cycle_count;
             mmu.stat &= 0xFC;
+            self.pipeline.active = false;
+            self.pipeline.window_y = false;
             return;
         }
-               
-        self.mode_clock += cycles as u32;    
-        let current_mode = mmu.stat & 0x03;
-
-        if self.mode_clock >= 456 {
-            self.mode_clock -= 456;
-            mmu.ly = (mmu.ly + 1) % 154;
-
-            if mmu.ly == 0 {
-                self.window_line_counter = 0;
+        // One dot at a time; `left` is how many come after this one, the
+        // bus being at the end of them already
+        for left in (0..cycles as u64).rev() {
+            if mmu.ly < 144 {
+                self.line_dot(mmu);
             }
-            
-            if mmu.ly == mmu.lyc {
-                mmu.stat |= 0x04;
-                if (mmu.stat & 0x40) != 0 { mmu.interrupt_flag |= 0x02; }
-            } else {
-                mmu.stat &= !0x04;
+            self.mode_clock += 1;
+            if self.mode_clock == 456 {
+                self.mode_clock = 0;
+                self.next_line(mmu, left);
             }
+        }
+    }
 
-            if mmu.ly >= 144 {
-                if current_mode != 1 {
-                    self.set_mode(mmu, 1);
-                    mmu.interrupt_flag |= 0x01;
+    // Modes 2, 3 and 0 of a visible line: 80 dots of OAM scan, drawing
+    // until all 160 pixels are out, then H-Blank for the rest of the 456
+    fn line_dot(&mut self, mmu: &mut crate::mmu::MMU) {
+        match self.mode_clock {
+            0 => {
+                if mmu.ly == mmu.wy {
+                    self.pipeline.window_y = true;
                 }
+                self.set_mode(mmu, 2);
             }
+            80 => {
+                self.pipeline.start_line(mmu);
+                self.set_mode(mmu, 3);
+            }
+            _ => {}
+        }
+        if !self.pipeline.active {
+            return;
         }
+        if self.pipeline.x == 160 {
+            self.pipeline.active = false;
+            if self.pipeline.window {
+                self.window_line_counter += 1;
+            }
+            self.set_mode(mmu, 0);
+            return;
+        }
+        let Some((x, bg_id, obj_id, attributes)) = self.pipeline.dot(mmu, self.window_line_counter) else { return };
+        // With LCDC bit 0 off the background and window are color 0, and
+        // sprites are always in front. Behind-BG sprites only show through
+        // color 0, whatever shade BGP gives it.
+        let bg_id = if (mmu.lcdc & 0x01) != 0 { bg_id } else { 0 };
+        let shade = if obj_id != 0 && (mmu.lcdc & 0x02) != 0 && ((attributes & 0x80) == 0 || bg_id == 0) {
+            let (shades, obp) = if (attributes & 0x10) != 0 { (&self.obj_palettes[1], mmu.obp1) } else { (&self.obj_palettes[0], mmu.obp0) };
+            shades[(obp >> (obj_id * 2)) as usize & 0x03]
+        } else {
+            self.palette[(mmu.bgp >> (bg_id * 2)) as usize & 0x03]
+        };
+        self.frame_buffer[mmu.ly as usize * 160 + x as usize] = shade;
+    }
 
-        if mmu.ly < 144 {
-            if self.mode_clock <= 80 {
-                if current_mode != 2 { 
-                    self.set_mode(mmu, 2); 
-                }
-            } else if self.mode_clock <= 80 + 172 {
-                if current_mode != 3 { 
-                    self.set_mode(mmu, 3);
-                    self.render_background(mmu);
-                    self.render_window(mmu);
-                    self.render_sprites(mmu);
-                }
-            } else {
-                if current_mode != 0 { 
-                    self.set_mode(mmu, 0); 
-                }
+    fn next_line(&mut self, mmu: &mut crate::mmu::MMU, left: u64) {
+        mmu.ly = (mmu.ly + 1) % 154;
+        mmu.event(Event::Line(mmu.ly));
+        mmu.line_start = mmu.apu.cycle_count.saturating_sub(left);
+
+        if mmu.ly == 0 {
+            self.window_line_counter = 0;
+            self.pipeline.window_y = false;
+            if let Some(timeline) = &mut mmu.timeline {
+                timeline.new_frame();
             }
         }
+
+        if mmu.ly == mmu.lyc {
+            mmu.stat |= 0x04;
+            if (mmu.stat & 0x40) != 0 { mmu.interrupt_flag |= 0x02; }
+        } else {
+            mmu.stat &= !0x04;
+        }
+
+        if mmu.ly >= 144 && (mmu.stat & 0x03) != 1 {
+            self.set_mode(mmu, 1);
+            mmu.interrupt_flag |= 0x01;
+        }
     }
 
     fn set_mode(&self, mmu: &mut crate::mmu::MMU, mode: u8) {
         mmu.stat = (mmu.stat & 0xFC) | mode;
+        mmu.event(Event::Mode { mode, ly: mmu.ly });
 
         let interrupt_requested = match mode {
             0 => (mmu.stat & 0x08) != 0, // H-Blank
@@ -250,4 +476,26 @@ impl PPU {
             mmu.interrupt_flag |= 0x02;
         }
     }
-}
\ No newline at end of file
+}
+
+// Address of a BG/window tile's row, with the tile data area LCDC selects
+fn tile_data_addr(mmu: &crate::mmu::MMU, tile_id: u8, row: u8) -> u16 {
+    let is_signed = (mmu.lcdc & 0x10) == 0;
+    if !is_signed {
+        0x8000 + (tile_id as u16 * 16) + (row as u16 * 2)
+    } else {
+        let offset = (tile_id as i8 as i16) + 128;
+        0x8800 + (offset as u16 * 16) + (row as u16 * 2)
+    }
+}
+
+// Color IDs of a row of tile data (its two bytes at `addr`), leftmost first
+fn tile_row(mmu: &crate::mmu::MMU, addr: u16) -> [u8; 8] {
+    let (byte1, byte2) = (mmu.read_byte(addr), mmu.read_byte(addr + 1));
+    std::array::from_fn(|x| ((byte2 >> (7 - x)) & 0x01) << 1 | ((byte1 >> (7 - x)) & 0x01))
+}
+
+// ARGB for each color ID through a BGP/OBP register, for the debug views
+fn map_shades(shades: &[u32; 4], palette: u8) -> [u32; 4] {
+    std::array::from_fn(|color_id| shades[(palette >> (color_id * 2)) as usize & 0x03])
+}
@@ -0,0 +1,64 @@
+// presence.rs
+// Optional Discord Rich Presence: the cartridge title, time played and what
+// the emulator is doing. Discord doesn't have to be running; connecting is
+// retried every so often and failures are otherwise ignored.
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use discord_rich_presence::activity::{Activity, Timestamps};
+use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+
+const RETRY: Duration = Duration::from_secs(15);
+
+pub struct Presence {
+    client: DiscordIpcClient,
+    connected: bool,
+    retry_at: Instant,
+    shown: Option<(String, String)>, // Last (details, state) sent
+}
+
+impl Presence {
+    pub fn new(client_id: &str) -> Self {
+        Self { client: DiscordIpcClient::new(client_id), connected: false, retry_at: Instant::now(), shown: None }
+    }
+
+    // Only talks to Discord when something changed, which also keeps us
+    // under its rate limit
+    pub fn update(&mut self, game: Option<&str>, state: &str, since: SystemTime) {
+        if !self.connected {
+            if Instant::now() < self.retry_at {
+                return;
+            }
+            self.retry_at = Instant::now() + RETRY;
+            if self.client.connect().is_err() {
+                return;
+            }
+            println!("✓ Connected to Discord");
+            self.connected = true;
+            self.shown = None;
+        }
+
+        let details = game.unwrap_or("No game loaded").to_string();
+        if self.shown.as_ref().is_some_and(|(d, s)| *d == details && s == state) {
+            return;
+        }
+        let mut activity = Activity::new().details(details.as_str()).state(state);
+        if game.is_some() {
+            let start = since.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+            activity = activity.timestamps(Timestamps::new().start(start));
+        }
+        if self.client.set_activity(activity).is_ok() {
+            self.shown = Some((details, state.to_string()));
+        } else {
+            // Discord went away; try again later
+            self.connected = false;
+        }
+    }
+}
+
+impl Drop for Presence {
+    fn drop(&mut self) {
+        if self.connected {
+            let _ = self.client.close();
+        }
+    }
+}
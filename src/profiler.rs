@@ -0,0 +1,141 @@
+// profiler.rs
+// Cycle profiler: every instruction's cycles go to the ROM bank and address
+// it ran from, and the report adds them up per routine, hottest first. The
+// routines are the labels of a symbol file (RGBDS / no$gmb .sym) when one
+// is loaded, otherwise single instructions. Cycles spent halted are counted
+// on their own, as the time the game had to spare.
+//
+//       cycles  percent    entries  routine
+//      1234567    12.3%       4096  UpdateSprites (001:4A3C)
+//
+// Entries count how often a routine's first instruction ran, about the
+// number of calls. A report file name ending in .csv gets CSV instead, to
+// sort in a spreadsheet by any column.
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+
+#[derive(Clone, Copy, Default)]
+struct Tally {
+    cycles: u64,
+    count: u64, // Instructions run
+}
+
+pub struct Profiler {
+    path: String, // Where the report goes
+    spent: HashMap<(usize, u16), Tally>, // By bank and address
+    halted: u64,
+    symbols: Vec<(usize, u16, String)>, // Sorted by bank and address
+}
+
+// The 16KB area of the address space an address is in. Symbols only cover
+// the rest of their own area, so a label at the end of bank 0 doesn't
+// swallow everything run from RAM.
+fn area(addr: u16) -> u16 {
+    addr >> 14
+}
+
+impl Profiler {
+    pub fn new(path: &str) -> Self {
+        Self { path: path.to_string(), spent: HashMap::new(), halted: 0, symbols: Vec::new() }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    // Reads `BANK:ADDR Label` lines (hex, ';' comments). Local labels
+    // (with a '.') count towards the routine they're in. Returns how many
+    // symbols there are.
+    pub fn load_symbols(&mut self, text: &str) -> usize {
+        for line in text.lines() {
+            let line = line.split(';').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            let (Some(place), Some(name)) = (words.next(), words.next()) else { continue };
+            if name.contains('.') {
+                continue;
+            }
+            let Some((bank, addr)) = place.split_once(':') else { continue };
+            if let (Ok(bank), Ok(addr)) = (usize::from_str_radix(bank, 16), u16::from_str_radix(addr, 16)) {
+                // Outside the switchable ROM area, where our banks are 0
+                let bank = if (0x4000..0x8000).contains(&addr) { bank } else { 0 };
+                self.symbols.push((bank, addr, name.to_string()));
+            }
+        }
+        self.symbols.sort_by_key(|&(bank, addr, _)| (bank, addr));
+        self.symbols.len()
+    }
+
+    // An instruction at `addr` with `bank` mapped there (0 outside
+    // 0x4000-0x7FFF) took `cycles`
+    pub fn record(&mut self, bank: usize, addr: u16, cycles: u8) {
+        let tally = self.spent.entry((bank, addr)).or_default();
+        tally.cycles += cycles as u64;
+        tally.count += 1;
+    }
+
+    pub fn record_halted(&mut self, cycles: u8) {
+        self.halted += cycles as u64;
+    }
+
+    // The symbol a place belongs to: the last one at or before it in the
+    // same bank and area
+    fn symbol(&self, bank: usize, addr: u16) -> Option<&(usize, u16, String)> {
+        let i = self.symbols.partition_point(|&(b, a, _)| (b, a) <= (bank, addr));
+        self.symbols[..i].last().filter(|&&(b, a, _)| b == bank && area(a) == area(addr))
+    }
+
+    // Per routine: its name, where it starts, cycles and entries; hottest first
+    fn routines(&self) -> Vec<(String, usize, u16, u64, u64)> {
+        let mut routines: HashMap<(usize, u16), (String, u64, u64)> = HashMap::new();
+        for (&(bank, addr), tally) in &self.spent {
+            let (name, start) = match self.symbol(bank, addr) {
+                Some((_, start, name)) => (name.clone(), *start),
+                None => (String::new(), addr),
+            };
+            let routine = routines.entry((bank, start)).or_insert((name, 0, 0));
+            routine.1 += tally.cycles;
+            if addr == start {
+                routine.2 += tally.count;
+            }
+        }
+        let mut routines: Vec<_> = routines.into_iter().map(|((bank, addr), (name, cycles, entries))| (name, bank, addr, cycles, entries)).collect();
+        routines.sort_by(|a, b| b.3.cmp(&a.3).then((a.1, a.2).cmp(&(b.1, b.2))));
+        routines
+    }
+
+    pub fn report(&self) -> String {
+        let total = self.spent.values().map(|t| t.cycles).sum::<u64>() + self.halted;
+        let percent = |n: u64| 100.0 * n as f64 / total.max(1) as f64;
+        let mut out = String::new();
+        let _ = writeln!(out, "{} cycles, {:.1}% halted", total, percent(self.halted));
+        let _ = writeln!(out);
+        let _ = writeln!(out, "      cycles  percent    entries  routine");
+        for (name, bank, addr, cycles, entries) in self.routines() {
+            let place = format!("{:03X}:{:04X}", bank, addr);
+            let routine = if name.is_empty() { place } else { format!("{} ({})", name, place) };
+            let _ = writeln!(out, "{:>12}  {:>6.1}%  {:>9}  {}", cycles, percent(cycles), entries, routine);
+        }
+        out
+    }
+
+    pub fn csv(&self) -> String {
+        let total = self.spent.values().map(|t| t.cycles).sum::<u64>() + self.halted;
+        let mut out = String::from("routine,bank,address,cycles,percent,entries\n");
+        let mut row = |name: &str, bank: String, addr: String, cycles: u64, entries: String| {
+            let percent = 100.0 * cycles as f64 / total.max(1) as f64;
+            let _ = writeln!(out, "\"{}\",{},{},{},{:.3},{}", name.replace('"', "\"\""), bank, addr, cycles, percent, entries);
+        };
+        for (name, bank, addr, cycles, entries) in self.routines() {
+            row(&name, format!("{:03X}", bank), format!("{:04X}", addr), cycles, entries.to_string());
+        }
+        row("(halted)", String::new(), String::new(), self.halted, String::new());
+        out
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        let text = if self.path.ends_with(".csv") { self.csv() } else { self.report() };
+        fs::write(&self.path, text)
+    }
+}
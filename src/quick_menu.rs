@@ -0,0 +1,83 @@
+// quick_menu.rs
+// Shown over the game while it's paused: the most common actions, picked
+// with the arrow keys/Enter or the pad, so nobody has to remember hotkeys.
+use crate::draw_text;
+use crate::hotkeys::Hotkey;
+use crate::menu::{NavInput, move_focus};
+use crate::theme::Theme;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuickAction {
+    Resume,
+    Hotkey(Hotkey), // Does what the hotkey does
+    GameSettings,   // Overrides for this game
+    ImportSave,     // Replace battery RAM with a .sav file and restart
+    ExportSave,
+    ImportState,    // Load a state exported here or by someone else
+    ExportState,    // Save a state that names its ROM, for sharing
+    QuitToLibrary,  // Close the game and open the ROM browser
+}
+
+const ITEMS: [(&str, QuickAction); 12] = [
+    ("RESUME", QuickAction::Resume),
+    ("SAVE STATE", QuickAction::Hotkey(Hotkey::SaveState)),
+    ("LOAD STATE", QuickAction::Hotkey(Hotkey::LoadState)),
+    ("SCREENSHOT", QuickAction::Hotkey(Hotkey::Screenshot)),
+    ("RESET", QuickAction::Hotkey(Hotkey::Reset)),
+    ("CHANGE PALETTE", QuickAction::Hotkey(Hotkey::Palette)),
+    ("GAME SETTINGS", QuickAction::GameSettings),
+    ("IMPORT SAVE", QuickAction::ImportSave),
+    ("EXPORT SAVE", QuickAction::ExportSave),
+    ("IMPORT STATE", QuickAction::ImportState),
+    ("EXPORT STATE", QuickAction::ExportState),
+    ("QUIT TO LIBRARY", QuickAction::QuitToLibrary),
+];
+const SCALE: usize = 2;
+const LINE_H: usize = 8 * SCALE + 8;
+
+pub struct QuickMenu {
+    focus: usize,
+}
+
+impl QuickMenu {
+    pub fn new() -> Self {
+        Self { focus: 0 }
+    }
+
+    // Cancel resumes the game
+    pub fn nav(&mut self, nav: NavInput) -> Option<QuickAction> {
+        match nav {
+            NavInput::Confirm => Some(ITEMS[self.focus].1),
+            NavInput::Cancel => Some(QuickAction::Resume),
+            _ => {
+                self.focus = move_focus(self.focus, ITEMS.len(), nav);
+                None
+            }
+        }
+    }
+
+    // Centered in the `width` wide area starting at row `top`
+    pub fn draw(&self, buffer: &mut [u32], width: usize, top: usize, theme: &Theme) {
+        let text_w = ITEMS.iter().map(|(label, _)| label.len()).max().unwrap_or(0) * 8 * SCALE;
+        let x0 = (width - text_w) / 2 - 16;
+        let x1 = x0 + text_w + 32;
+        for y in top..top + ITEMS.len() * LINE_H + 8 {
+            for p in &mut buffer[y * width + x0..y * width + x1] {
+                *p = theme.panel;
+            }
+        }
+        for (i, (label, _)) in ITEMS.iter().enumerate() {
+            let y = top + 4 + i * LINE_H;
+            let focused = i == self.focus;
+            if focused {
+                for row in y..y + LINE_H {
+                    for p in &mut buffer[row * width + x0..row * width + x1] {
+                        *p = theme.focus;
+                    }
+                }
+            }
+            let color = if focused { theme.accent } else { theme.text };
+            draw_text(buffer, width, label, x0 + 16, y + 4, color, SCALE);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+// ram_watch.rs
+// RAM watch: values read from memory every frame and drawn over the top
+// right of the game with a label, for following game state (money, RNG,
+// timers) while playing. Set per game in the config file, e.g.
+//   [[games."POKEMON SILVER 1234".watches]]
+//   label = "MONEY"
+//   addr = 0xD573
+//   format = "bcd"   # u8 (default), u16 (little-endian) or bcd
+//   len = 3          # bcd only: bytes, most significant first
+// The RAM watch hotkey hides and shows them.
+use serde::{Deserialize, Serialize};
+
+use crate::draw_text;
+use crate::mmu::MMU;
+use crate::theme::Theme;
+
+const SCALE: usize = 2;
+const LINE_H: usize = 8 * SCALE + 4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchFormat {
+    #[default]
+    U8,
+    U16,
+    Bcd,
+}
+
+fn one() -> u8 {
+    1
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RamWatch {
+    pub label: String,
+    pub addr: u16,
+    #[serde(default)]
+    pub format: WatchFormat,
+    #[serde(default = "one")]
+    pub len: u8,
+}
+
+impl RamWatch {
+    pub fn value(&self, bus: &MMU) -> String {
+        let byte = |i: u16| bus.read_byte(self.addr.wrapping_add(i));
+        match self.format {
+            WatchFormat::U8 => byte(0).to_string(),
+            WatchFormat::U16 => u16::from_le_bytes([byte(0), byte(1)]).to_string(),
+            WatchFormat::Bcd => {
+                let digits: String = (0..self.len.max(1) as u16).map(|i| format!("{:02X}", byte(i))).collect();
+                let trimmed = digits.trim_start_matches('0');
+                if trimmed.is_empty() { "0".to_string() } else { trimmed.to_string() }
+            }
+        }
+    }
+}
+
+// One line per watch on a see-through strip, right-aligned from row `top`
+pub fn draw(watches: &[RamWatch], bus: &MMU, buffer: &mut [u32], width: usize, top: usize, theme: &Theme) {
+    let lines: Vec<String> = watches.iter().map(|w| format!("{} {}", w.label, w.value(bus))).collect();
+    let chars = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let x = width.saturating_sub(chars * 8 * SCALE + 8);
+    for (i, line) in lines.iter().enumerate() {
+        let y = top + i * LINE_H;
+        if (y + LINE_H) * width > buffer.len() {
+            break;
+        }
+        for row in y..y + LINE_H {
+            for p in &mut buffer[row * width + x.saturating_sub(4)..(row + 1) * width] {
+                *p = theme.backdrop(*p);
+            }
+        }
+        draw_text(buffer, width, line, x, y + 2, theme.text, SCALE);
+    }
+}
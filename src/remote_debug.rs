@@ -0,0 +1,327 @@
+// remote_debug.rs
+// Debugger served to a browser: open http://host:port/ for the game screen,
+// disassembly, registers, memory and breakpoints of the running instance,
+// from this machine (or another one, with allow_remote in the config).
+//
+// The page talks to /ws over a WebSocket. Client -> emulator, JSON text:
+//   {"cmd":"pause"} {"cmd":"resume"} {"cmd":"step","count":N} (N up to MAX_STEP)
+//   {"cmd":"break","addr":N} (toggles) {"cmd":"memory","addr":N}
+// Emulator -> client, on every update: a JSON text message with the machine
+// state, then a binary message with the screen as 160x144 RGBA.
+//
+// Each client has its own thread; one that falls behind misses updates
+// instead of holding up the game.
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TryRecvError, TrySendError};
+use std::time::Duration;
+
+use gb_rs::breakpoint::Breakpoint;
+use gb_rs::disasm::disassemble;
+use serde::Deserialize;
+use serde_json::json;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use crate::EmulatorState;
+
+const QUEUE_LEN: usize = 4; // Updates a client may lag behind before they're dropped
+const RUNNING_EVERY: u32 = 3; // While running, clients are updated every this many frames
+const DISASM_LINES: usize = 24;
+const MEMORY_BYTES: u16 = 256;
+// Most instructions one step command runs: a frame of the shortest ones, so
+// a client can't hold up the game for longer than a frame
+const MAX_STEP: u32 = 70224 / 4;
+
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+enum DebugCommand {
+    Pause,
+    Resume,
+    Step { count: u32 },
+    Break { addr: u16 },
+    Memory { addr: u16 },
+}
+
+struct Update {
+    state: String,
+    screen: Vec<u8>,
+}
+
+struct Client {
+    commands: Receiver<DebugCommand>,
+    updates: SyncSender<Arc<Update>>,
+}
+
+pub struct DebugServer {
+    listener: TcpListener,
+    clients: Vec<Client>,
+    pub paused: bool,
+    mem_addr: u16,
+    frames: u32,
+}
+
+impl DebugServer {
+    pub fn bind(port: u16, allow_remote: bool) -> io::Result<Self> {
+        let host = if allow_remote { "0.0.0.0" } else { "127.0.0.1" };
+        let listener = TcpListener::bind((host, port))?;
+        listener.set_nonblocking(true)?;
+        println!("✓ Remote debugger on http://localhost:{}/", port);
+        Ok(Self { listener, clients: Vec::new(), paused: false, mem_addr: 0xC000, frames: 0 })
+    }
+
+    // Once per frame: takes new clients and their commands, then sends the
+    // machine state. Returns a message for the OSD when execution stopped.
+    pub fn update(&mut self, emu: &mut EmulatorState) -> Option<String> {
+        let mut changed = false;
+        while let Ok((stream, _)) = self.listener.accept() {
+            let (command_tx, commands) = mpsc::channel();
+            let (updates, update_rx) = mpsc::sync_channel(QUEUE_LEN);
+            std::thread::spawn(move || serve(stream, command_tx, update_rx));
+            self.clients.push(Client { commands, updates });
+            changed = true;
+        }
+
+        let mut stopped = None;
+        let commands: Vec<DebugCommand> = self.clients.iter().flat_map(|c| c.commands.try_iter()).collect();
+        for command in commands {
+            changed = true;
+            match command {
+                DebugCommand::Pause => {
+                    self.paused = true;
+                    stopped = Some(format!("Debugger: paused at ${:04X}", emu.cpu.registers.pc));
+                }
+                DebugCommand::Resume => self.paused = false,
+                DebugCommand::Step { count } if self.paused => {
+                    for _ in 0..count.min(MAX_STEP) {
+                        if let Err(e) = emu.step() {
+                            stopped = Some(e.to_string().lines().next().unwrap_or_default().to_string());
+                            emu.fault = Some(e);
+                            break;
+                        }
+                    }
+                }
+                DebugCommand::Step { .. } => {}
+                DebugCommand::Break { addr } => {
+                    if let Some(i) = emu.breakpoints.iter().position(|b| b.addr == addr) {
+                        emu.breakpoints.remove(i);
+                    } else {
+                        emu.breakpoints.push(Breakpoint::new(addr));
+                    }
+                }
+                DebugCommand::Memory { addr } => self.mem_addr = addr & 0xFFF0,
+            }
+        }
+        if emu.break_hit.is_some()
+            && !self.paused
+        {
+            self.paused = true;
+            changed = true;
+            stopped = Some(format!("Debugger: {}", emu.stop_reason()));
+        }
+
+        self.frames = self.frames.wrapping_add(1);
+        if changed || (!self.paused && self.frames.is_multiple_of(RUNNING_EVERY)) {
+            let update = Arc::new(Update { state: self.state_json(emu), screen: screen_rgba(&emu.cpu.ppu.frame_buffer) });
+            // Clients whose thread ended (connection closed) are dropped here
+            self.clients.retain(|c| !matches!(c.updates.try_send(update.clone()), Err(TrySendError::Disconnected(_))));
+        }
+        stopped
+    }
+
+    fn state_json(&self, emu: &EmulatorState) -> String {
+        let bus = &emu.cpu.bus;
+        let r = &emu.cpu.registers;
+        let mut addr = r.pc;
+        let mut disasm = Vec::new();
+        for _ in 0..DISASM_LINES {
+            let (text, len) = disassemble(bus, addr);
+            let bytes: Vec<String> = (0..len).map(|i| format!("{:02X}", bus.read_byte(addr.wrapping_add(i)))).collect();
+            disasm.push(json!([addr, bytes.join(" "), text]));
+            addr = addr.wrapping_add(len);
+        }
+        let memory: Vec<u8> = (0..MEMORY_BYTES).map(|i| bus.read_byte(self.mem_addr.wrapping_add(i))).collect();
+        json!({
+            "paused": self.paused,
+            "frame": emu.frame_count,
+            "regs": { "af": u16::from_be_bytes([r.a, r.f]), "bc": u16::from_be_bytes([r.b, r.c]),
+                      "de": u16::from_be_bytes([r.d, r.e]), "hl": u16::from_be_bytes([r.h, r.l]),
+                      "sp": r.sp, "pc": r.pc },
+            "ime": emu.cpu.ime,
+            "halted": emu.cpu.halted,
+            "locked": emu.cpu.locked,
+            "io": { "ie": bus.interrupt_enable, "if": bus.interrupt_flag, "ly": bus.ly, "rom_bank": bus.rom_bank },
+            "disasm": disasm,
+            "breakpoints": emu.breakpoints.iter().map(|b| b.addr).collect::<Vec<_>>(),
+            "mem_addr": self.mem_addr,
+            "memory": memory,
+        })
+        .to_string()
+    }
+}
+
+fn screen_rgba(frame: &[u32]) -> Vec<u8> {
+    frame.iter().flat_map(|&p| [(p >> 16) as u8, (p >> 8) as u8, p as u8, 0xFF]).collect()
+}
+
+// One connection: the page itself, or a WebSocket to /ws
+fn serve(stream: TcpStream, commands: Sender<DebugCommand>, updates: Receiver<Arc<Update>>) {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+    let Ok(mut writer) = stream.try_clone() else { return };
+
+    // Request line and headers; the page and the WebSocket handshake are all
+    // we answer, so nothing else needs parsing
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    let mut ws_key = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            return;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if request.is_empty() {
+            request = line.to_string();
+        } else if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("sec-websocket-key")
+        {
+            ws_key = Some(value.trim().to_string());
+        }
+    }
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+
+    match (path, ws_key) {
+        ("/ws", Some(key)) => {
+            let accept = tungstenite::handshake::derive_accept_key(key.as_bytes());
+            let response = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept
+            );
+            if writer.write_all(response.as_bytes()).is_ok() {
+                let stream = reader.into_inner();
+                let _ = stream.set_read_timeout(Some(Duration::from_millis(10)));
+                websocket(WebSocket::from_raw_socket(stream, Role::Server, None), commands, updates);
+            }
+        }
+        ("/", _) => {
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                PAGE.len(),
+                PAGE
+            );
+            let _ = writer.write_all(response.as_bytes());
+        }
+        _ => {
+            let _ = writer.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+        }
+    }
+}
+
+// Reads time out quickly so updates go out between them
+fn websocket(mut ws: WebSocket<TcpStream>, commands: Sender<DebugCommand>, updates: Receiver<Arc<Update>>) {
+    loop {
+        match ws.read() {
+            Ok(Message::Text(text)) => {
+                if let Ok(command) = serde_json::from_str(&text)
+                    && commands.send(command).is_err()
+                {
+                    return;
+                }
+            }
+            Ok(Message::Close(_)) => return,
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+            Err(_) => return,
+        }
+        loop {
+            match updates.try_recv() {
+                Ok(update) => {
+                    if ws.send(Message::Text(update.state.clone())).is_err() || ws.send(Message::Binary(update.screen.clone())).is_err() {
+                        return;
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html><head><meta charset="utf-8"><title>gb_rs debugger</title>
+<style>
+body { background: #181818; color: #ddd; font: 13px monospace; margin: 12px; }
+.row { display: flex; gap: 16px; align-items: flex-start; }
+canvas { width: 480px; height: 432px; image-rendering: pixelated; background: #000; }
+pre { margin: 0; }
+.pc { background: #304060; }
+.bp { color: #f55; }
+#disasm div { cursor: pointer; white-space: pre; }
+button { font: inherit; }
+</style></head><body>
+<div class="row">
+  <canvas id="screen" width="160" height="144"></canvas>
+  <div>
+    <div><button id="run">Pause</button> <button id="step">Step</button>
+      <input id="count" value="1" size="5"> <span id="status"></span></div>
+    <pre id="regs"></pre>
+    <p>Breakpoint: <input id="bpaddr" size="6" placeholder="0150"> <button id="bpadd">Toggle</button></p>
+    <pre id="bps"></pre>
+  </div>
+  <div id="disasm"></div>
+</div>
+<p>Memory from <input id="memaddr" value="C000" size="6"> <button id="memgo">Show</button></p>
+<pre id="memory"></pre>
+<script>
+const hex = (n, w) => n.toString(16).toUpperCase().padStart(w, "0");
+const ws = new WebSocket(`ws://${location.host}/ws`);
+ws.binaryType = "arraybuffer";
+const send = msg => ws.send(JSON.stringify(msg));
+const ctx = document.getElementById("screen").getContext("2d");
+let state = null;
+ws.onclose = () => document.getElementById("status").textContent = "disconnected";
+ws.onmessage = ev => {
+  if (typeof ev.data !== "string") {
+    ctx.putImageData(new ImageData(new Uint8ClampedArray(ev.data), 160, 144), 0, 0);
+    return;
+  }
+  state = JSON.parse(ev.data);
+  const r = state.regs, io = state.io, f = r.af & 0xFF;
+  const flags = ["Z", "N", "H", "C"].map((c, i) => f & (0x80 >> i) ? c : "-").join("");
+  document.getElementById("run").textContent = state.paused ? "Resume" : "Pause";
+  document.getElementById("status").textContent = (state.paused ? "paused" : "running") + ", frame " + state.frame;
+  document.getElementById("regs").textContent =
+    `AF ${hex(r.af, 4)}  BC ${hex(r.bc, 4)}\nDE ${hex(r.de, 4)}  HL ${hex(r.hl, 4)}\nSP ${hex(r.sp, 4)}  PC ${hex(r.pc, 4)}\n` +
+    `Flags ${flags}  IME ${+state.ime}${state.locked ? "  LOCKED" : state.halted ? "  HALTED" : ""}\nIE ${hex(io.ie, 2)}  IF ${hex(io.if, 2)}  LY ${io.ly}\nROM bank ${io.rom_bank}`;
+  document.getElementById("bps").textContent = state.breakpoints.map(b => "$" + hex(b, 4)).join("\n");
+  const disasm = document.getElementById("disasm");
+  disasm.replaceChildren(...state.disasm.map(([addr, bytes, text]) => {
+    const line = document.createElement("div");
+    const bp = state.breakpoints.includes(addr);
+    line.textContent = `${bp ? "●" : " "}${hex(addr, 4)}  ${bytes.padEnd(9)} ${text}`;
+    if (addr === r.pc) line.className = "pc";
+    else if (bp) line.className = "bp";
+    line.title = "Click to toggle a breakpoint";
+    line.onclick = () => send({ cmd: "break", addr });
+    return line;
+  }));
+  let mem = "";
+  for (let row = 0; row < state.memory.length; row += 16) {
+    const bytes = state.memory.slice(row, row + 16);
+    const ascii = bytes.map(b => b >= 32 && b < 127 ? String.fromCharCode(b) : ".").join("");
+    mem += `${hex((state.mem_addr + row) & 0xFFFF, 4)}  ${bytes.map(b => hex(b, 2)).join(" ")}  ${ascii}\n`;
+  }
+  document.getElementById("memory").textContent = mem;
+};
+const addr = id => parseInt(document.getElementById(id).value.replace(/^\$|^0x/i, ""), 16) & 0xFFFF;
+document.getElementById("run").onclick = () => send({ cmd: state && state.paused ? "resume" : "pause" });
+document.getElementById("step").onclick = () => send({ cmd: "step", count: parseInt(document.getElementById("count").value) || 1 });
+document.getElementById("bpadd").onclick = () => send({ cmd: "break", addr: addr("bpaddr") });
+document.getElementById("memgo").onclick = () => send({ cmd: "memory", addr: addr("memaddr") });
+</script></body></html>
+"#;
@@ -0,0 +1,284 @@
+// repl.rs
+// Debugger on the terminal gb_rs was started from, next to the game window.
+// `--debug` or the debugger hotkey pauses the game and reads commands from
+// standard input; the game stays paused between them until `c`.
+//
+// Commands (an empty line repeats the last one):
+//   b [ADDR]        toggle a breakpoint at ADDR (none lists them)
+//   b ADDR if EXPR  break at ADDR only when EXPR holds, e.g.
+//                   `b 4ABC if A==0x3F && [0xC123]>5` (see breakpoint.rs)
+//   b BANK:ADDR     break at ADDR only while ROM bank BANK is mapped there,
+//                   e.g. `b 12:4F00` (also with a condition)
+//   e [EVENT]       toggle breaking on an event (none lists them):
+//                   `e int vblank`, `e mode 1`, `e mode 0 line 40`,
+//                   `e line 100`, `e bank 1A` (see events.rs)
+//   c               continue until a breakpoint
+//   s [N]           step N instructions (default 1)
+//   r               show the registers
+//   m ADDR [LEN]    show LEN bytes of memory from ADDR (default 16)
+//   p ADDR BYTE...  write bytes to memory from ADDR
+//   d REGION [FILE] dump vram, wram, oam, hram, io or sram to a file
+//   d ADDR LEN [FILE]  dump LEN bytes from ADDR as the CPU sees them
+//   d all           dump every region and the registers to a directory
+//   g               export the tiles, BG map and sprites as PNGs
+//   q               leave the debugger and clear all breakpoints
+// Addresses, lengths and bytes are hex, with or without a $ or 0x prefix.
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Receiver};
+
+use gb_rs::breakpoint::{Breakpoint, Condition};
+use gb_rs::disasm::disassemble;
+use gb_rs::events::EventBreak;
+use gb_rs::memdump;
+
+use crate::EmulatorState;
+
+pub struct DebugRepl {
+    lines: Receiver<String>,
+    pub open: bool,
+    pub paused: bool,
+    last_command: String,
+}
+
+fn parse_hex(arg: Option<&str>, what: &str) -> Result<u16, String> {
+    let arg = arg.ok_or(format!("needs {}", what))?;
+    let hex = arg.trim_start_matches('$').trim_start_matches("0x");
+    u16::from_str_radix(hex, 16).map_err(|_| format!("bad {} '{}'", what, arg))
+}
+
+fn prompt() {
+    print!("(gb_rs) ");
+    let _ = io::stdout().flush();
+}
+
+impl DebugRepl {
+    // Standard input is read on its own thread for the rest of the run, so
+    // only one of these is made however often the debugger is opened
+    pub fn new() -> Self {
+        let (tx, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { lines, open: false, paused: false, last_command: String::new() }
+    }
+
+    pub fn open(&mut self, emu: &EmulatorState) {
+        // Lines typed while it was closed weren't meant for it
+        self.lines.try_iter().for_each(drop);
+        self.open = true;
+        self.paused = true;
+        println!("Debugger: paused. b ADDR, e EVENT, c, s [N], r, m ADDR [LEN], p ADDR BYTE..., d REGION, g, q");
+        self.show_next(emu);
+        prompt();
+    }
+
+    pub fn close(&mut self, emu: &mut EmulatorState) {
+        self.open = false;
+        self.paused = false;
+        emu.breakpoints.clear();
+        emu.cpu.bus.event_breaks.clear();
+        println!("Debugger closed");
+    }
+
+    // Once per frame: stops at a breakpoint the frame ran into, then runs the
+    // commands typed since. Returns a message for the OSD when it stopped.
+    pub fn update(&mut self, emu: &mut EmulatorState) -> Option<String> {
+        if !self.open {
+            return None;
+        }
+        let mut stopped = None;
+        if emu.break_hit.is_some()
+            && !self.paused
+        {
+            self.paused = true;
+            let reason = emu.stop_reason();
+            println!();
+            println!("Stopped: {}", reason);
+            self.show_next(emu);
+            prompt();
+            stopped = Some(format!("Debugger: {}", reason));
+        }
+        while self.open && let Ok(mut line) = self.lines.try_recv() {
+            if line.trim().is_empty() {
+                line = self.last_command.clone();
+            } else {
+                self.last_command = line.clone();
+            }
+            if let Err(e) = self.command(&line, emu) {
+                println!("✗ {}", e);
+            }
+            if self.open && self.paused {
+                prompt();
+            }
+        }
+        stopped
+    }
+
+    fn command(&mut self, line: &str, emu: &mut EmulatorState) -> Result<(), String> {
+        let mut words = line.split_whitespace();
+        let Some(cmd) = words.next() else { return Ok(()) };
+        match cmd {
+            "q" | "quit" => self.close(emu),
+            "c" | "continue" => {
+                self.paused = false;
+                println!("Running");
+            }
+            "s" | "step" => {
+                let count = match words.next() {
+                    Some(n) => n.parse::<u32>().map_err(|_| format!("bad count '{}'", n))?,
+                    None => 1,
+                };
+                self.paused = true;
+                // Whatever stopped the game before doesn't stop the steps
+                emu.cpu.bus.event_hit = None;
+                for _ in 0..count {
+                    if let Err(e) = emu.step() {
+                        println!("✗ {}", e.to_string().lines().next().unwrap_or_default());
+                        emu.fault = Some(e);
+                        break;
+                    }
+                    if emu.at_breakpoint() {
+                        println!("Stopped: {}", emu.stop_reason());
+                        emu.cpu.bus.event_hit = None;
+                        break;
+                    }
+                }
+                self.show_next(emu);
+            }
+            "b" | "break" => {
+                let args = line.trim_start()[cmd.len()..].trim();
+                let (addr, condition) = match args.split_once("if") {
+                    Some((addr, condition)) => (addr.trim(), Some(Condition::parse(condition)?)),
+                    None => (args, None),
+                };
+                if addr.is_empty() && condition.is_none() {
+                    if emu.breakpoints.is_empty() {
+                        println!("No breakpoints");
+                    }
+                    for b in &emu.breakpoints {
+                        println!("Breakpoint at {}", b);
+                    }
+                    return Ok(());
+                }
+                if addr.is_empty() {
+                    return Err("b needs an address".to_string());
+                }
+                let mut b = Breakpoint::parse(addr)?;
+                let existing = emu.breakpoints.iter().position(|other| other.same_place(&b));
+                match (existing, condition) {
+                    // A condition replaces whatever was there
+                    (existing, Some(condition)) => {
+                        if let Some(i) = existing {
+                            emu.breakpoints.remove(i);
+                        }
+                        b.condition = Some(condition);
+                        println!("Breakpoint at {}", b);
+                        emu.breakpoints.push(b);
+                    }
+                    (Some(i), None) => {
+                        println!("Breakpoint at {} removed", emu.breakpoints.remove(i));
+                    }
+                    (None, None) => {
+                        println!("Breakpoint at {}", b);
+                        emu.breakpoints.push(b);
+                    }
+                }
+            }
+            "e" | "event" => {
+                let args = line.trim_start()[cmd.len()..].trim();
+                let breaks = &mut emu.cpu.bus.event_breaks;
+                if args.is_empty() {
+                    if breaks.is_empty() {
+                        println!("No event breakpoints");
+                    }
+                    for e in breaks.iter() {
+                        println!("Break on {}", e);
+                    }
+                    return Ok(());
+                }
+                let e = EventBreak::parse(args)?;
+                match breaks.iter().position(|other| *other == e) {
+                    Some(i) => println!("Break on {} removed", breaks.remove(i)),
+                    None => {
+                        println!("Break on {}", e);
+                        breaks.push(e);
+                    }
+                }
+            }
+            "r" | "regs" => {
+                let r = &emu.cpu.registers;
+                let bus = &emu.cpu.bus;
+                let flags: String = ["Z", "N", "H", "C"].iter().enumerate()
+                    .map(|(i, f)| if r.f & (0x80 >> i) != 0 { *f } else { "-" })
+                    .collect();
+                let state = if emu.cpu.locked { "  LOCKED" } else if emu.cpu.halted { "  HALTED" } else { "" };
+                println!("AF {:02X}{:02X}  BC {:02X}{:02X}  DE {:02X}{:02X}  HL {:02X}{:02X}", r.a, r.f, r.b, r.c, r.d, r.e, r.h, r.l);
+                println!("SP {:04X}  PC {:04X}  Flags {}  IME {}{}", r.sp, r.pc, flags, emu.cpu.ime as u8, state);
+                println!("IE {:02X}  IF {:02X}  LY {}  ROM bank {}  Frame {}", bus.interrupt_enable, bus.interrupt_flag, bus.ly, bus.rom_bank, emu.frame_count);
+            }
+            "m" | "mem" => {
+                let addr = parse_hex(words.next(), "an address")?;
+                let len = match words.next() {
+                    Some(n) => parse_hex(Some(n), "length")?,
+                    None => 16,
+                };
+                let bus = &emu.cpu.bus;
+                for row in (0..len).step_by(16) {
+                    let base = addr.wrapping_add(row);
+                    let bytes: Vec<u8> = (0..16.min(len - row)).map(|i| bus.read_byte(base.wrapping_add(i))).collect();
+                    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+                    let ascii: String = bytes.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+                    println!("{:04X}  {:<47}  {}", base, hex.join(" "), ascii);
+                }
+            }
+            "p" | "poke" => {
+                let addr = parse_hex(words.next(), "an address")?;
+                let bytes = words.map(|b| parse_hex(Some(b), "byte").and_then(|v| u8::try_from(v).map_err(|_| format!("bad byte '{}'", b))))
+                    .collect::<Result<Vec<u8>, String>>()?;
+                if bytes.is_empty() {
+                    return Err("p needs bytes to write".to_string());
+                }
+                // Through the bus like a game's own writes, so they land
+                // where it would see them (and can switch banks)
+                for (i, &b) in bytes.iter().enumerate() {
+                    emu.cpu.bus.write_byte(addr.wrapping_add(i as u16), b);
+                }
+                println!("Wrote {} byte(s) at ${:04X}", bytes.len(), addr);
+            }
+            "d" | "dump" => {
+                let what = words.next().ok_or("d needs a region or an address")?;
+                let stem = emu.file_stem();
+                let (bytes, default_path) = if what == "all" {
+                    println!("{}", emu.core_dump());
+                    return Ok(());
+                } else if let Some(bytes) = memdump::region(&emu.cpu.bus, what) {
+                    (bytes, format!("{}.{}.bin", stem, what))
+                } else {
+                    let addr = parse_hex(Some(what), "an address")?;
+                    let len = parse_hex(words.next(), "length")? as usize;
+                    (memdump::range(&emu.cpu.bus, addr, len), format!("{}.{:04X}-{:04X}.bin", stem, addr, addr as usize + len.max(1) - 1))
+                };
+                let path = words.next().map_or(default_path, str::to_string);
+                std::fs::write(&path, &bytes).map_err(|e| format!("couldn't write '{}': {}", path, e))?;
+                println!("Wrote {} byte(s) to '{}'", bytes.len(), path);
+            }
+            "g" | "gfx" => println!("{}", emu.export_graphics()),
+            _ => return Err(format!("unknown command '{}'", cmd)),
+        }
+        Ok(())
+    }
+
+    // The instruction at PC, which runs next
+    fn show_next(&self, emu: &EmulatorState) {
+        let bus = &emu.cpu.bus;
+        let pc = emu.cpu.registers.pc;
+        let (text, len) = disassemble(bus, pc);
+        let bytes: Vec<String> = (0..len).map(|i| format!("{:02X}", bus.read_byte(pc.wrapping_add(i)))).collect();
+        println!("{:04X}  {:<9} {}", pc, bytes.join(" "), text);
+    }
+}
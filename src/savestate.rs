@@ -0,0 +1,161 @@
+// savestate.rs
+// Little-endian binary snapshot format. Each component writes its own fields
+// in a fixed order (see the save_state/load_state methods on CPU, MMU, PPU and
+// APU); the file is just a magic/version header followed by those fields.
+use std::io::{Error, ErrorKind, Result};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const MAGIC: &[u8; 4] = b"GBRS";
+pub const VERSION: u8 = 4;
+
+pub struct StateWriter {
+    pub data: Vec<u8>,
+}
+
+impl Default for StateWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        let mut data = Vec::with_capacity(64 * 1024);
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        Self { data }
+    }
+
+    pub fn u8(&mut self, v: u8) { self.data.push(v); }
+    pub fn u16(&mut self, v: u16) { self.data.extend_from_slice(&v.to_le_bytes()); }
+    pub fn u32(&mut self, v: u32) { self.data.extend_from_slice(&v.to_le_bytes()); }
+    pub fn u64(&mut self, v: u64) { self.data.extend_from_slice(&v.to_le_bytes()); }
+    pub fn bool(&mut self, v: bool) { self.data.push(v as u8); }
+    pub fn bytes(&mut self, v: &[u8]) { self.data.extend_from_slice(v); }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    // Checks the header and positions the reader at the first field
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        if data.len() < 5 || &data[0..4] != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "not a gb_rs save state"));
+        }
+        if data[4] != VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, format!("unsupported state version {}", data[4])));
+        }
+        Ok(Self { data, pos: 5 })
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.pos + n > self.data.len() {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "save state is truncated"));
+        }
+        let out = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(out)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> { Ok(self.take(1)?[0]) }
+    pub fn u16(&mut self) -> Result<u16> { Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap())) }
+    pub fn u32(&mut self) -> Result<u32> { Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap())) }
+    pub fn u64(&mut self) -> Result<u64> { Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap())) }
+    pub fn bool(&mut self) -> Result<bool> { Ok(self.u8()? != 0) }
+    pub fn bytes(&mut self, out: &mut [u8]) -> Result<()> {
+        out.copy_from_slice(self.take(out.len())?);
+        Ok(())
+    }
+}
+
+// Full machine states start with a preview (capture time and the 160x144
+// frame on screen) so pickers can show them without loading the machine
+pub struct Preview {
+    pub time: SystemTime,
+    pub frame: Vec<u32>,
+}
+
+impl Preview {
+    pub fn write(w: &mut StateWriter, frame: &[u32]) {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        w.u64(secs);
+        for &p in frame {
+            w.bytes(&p.to_le_bytes()[..3]);
+        }
+    }
+
+    pub fn read(r: &mut StateReader) -> Result<Self> {
+        let time = UNIX_EPOCH + Duration::from_secs(r.u64()?);
+        let mut rgb = vec![0; 160 * 144 * 3];
+        r.bytes(&mut rgb)?;
+        let frame = rgb.chunks(3).map(|c| 0xFF000000 | u32::from_le_bytes([c[0], c[1], c[2], 0])).collect();
+        Ok(Self { time, frame })
+    }
+
+    pub fn from_file(path: &str) -> Result<Self> {
+        let data = std::fs::read(path)?;
+        Self::read(&mut StateReader::new(&data)?)
+    }
+}
+
+// Exported states name the ROM they were made with, so a state shared with
+// someone else is checked before it loads against another revision of the
+// game: "GBRP", a version byte, the ROM's SHA-1 and the core version that
+// wrote it (u8 length, then text), then the state as saved to a slot.
+pub const PORTABLE_MAGIC: &[u8; 4] = b"GBRP";
+const PORTABLE_VERSION: u8 = 1;
+
+pub struct PortableInfo {
+    pub rom_sha1: [u8; 20],
+    pub core_version: String,
+}
+
+pub fn export_portable(state: &[u8], rom_sha1: &[u8; 20]) -> Vec<u8> {
+    let core = env!("CARGO_PKG_VERSION");
+    let mut out = PORTABLE_MAGIC.to_vec();
+    out.push(PORTABLE_VERSION);
+    out.extend_from_slice(rom_sha1);
+    out.push(core.len() as u8);
+    out.extend_from_slice(core.as_bytes());
+    out.extend_from_slice(state);
+    out
+}
+
+// None for a plain state; otherwise its header and the state inside
+pub fn import_portable(data: &[u8]) -> Result<Option<(PortableInfo, &[u8])>> {
+    if data.len() < 4 || &data[0..4] != PORTABLE_MAGIC {
+        return Ok(None);
+    }
+    let bad = |what: &str| Error::new(ErrorKind::InvalidData, what.to_string());
+    if data.len() < 26 {
+        return Err(bad("exported state is truncated"));
+    }
+    if data[4] != PORTABLE_VERSION {
+        return Err(bad(&format!("unsupported exported state version {}", data[4])));
+    }
+    let rom_sha1 = data[5..25].try_into().unwrap();
+    let end = 26 + data[25] as usize;
+    let core_version = data.get(26..end).ok_or_else(|| bad("exported state is truncated"))?;
+    let info = PortableInfo { rom_sha1, core_version: String::from_utf8_lossy(core_version).to_string() };
+    Ok(Some((info, &data[end..])))
+}
+
+// "YYYY-MM-DD HH:MM" (UTC) for showing when a state was saved
+pub fn format_time(t: SystemTime) -> String {
+    let secs = t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, rem / 3600, rem % 3600 / 60)
+}
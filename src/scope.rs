@@ -0,0 +1,117 @@
+// scope.rs
+// Debug window that draws the APU output: one waveform lane per channel plus
+// the mixed signal, a magnitude spectrum of the mix and the raw registers.
+use minifb::{Window, WindowOptions};
+
+use crate::apu::{APU, SCOPE_LEN};
+use crate::draw_text;
+
+const W: usize = 640;
+const H: usize = 420;
+const LANE_H: usize = 56;
+const WAVE_W: usize = SCOPE_LEN;
+const SPECTRUM_TOP: usize = 290;
+const SPECTRUM_H: usize = 120;
+const SPECTRUM_BINS: usize = 64;
+
+const LANE_COLORS: [u32; 5] = [0xFFFF5555, 0xFF55FF55, 0xFF5599FF, 0xFFFFDD55, 0xFFFFFFFF];
+const LANE_LABELS: [&str; 5] = ["CH1", "CH2", "CH3", "CH4", "MIX"];
+
+pub struct ScopeWindow {
+    window: Window,
+    buffer: Vec<u32>,
+}
+
+impl ScopeWindow {
+    pub fn new() -> Self {
+        let mut window = Window::new(
+            "APU Oscilloscope",
+            W, H,
+            WindowOptions { resize: false, ..WindowOptions::default() },
+        ).unwrap();
+        window.limit_update_rate(None);
+        Self { window, buffer: vec![0; W * H] }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    pub fn update(&mut self, apu: &APU) {
+        for p in self.buffer.iter_mut() { *p = 0xFF101010; }
+
+        // 1. WAVEFORMS (oldest sample on the left)
+        for lane in 0..5 {
+            let top = lane * LANE_H;
+            let color = LANE_COLORS[lane];
+            for x in 0..WAVE_W {
+                self.buffer[(top + LANE_H - 1) * W + x] = 0xFF303030;
+            }
+
+            let mut prev_y = None;
+            for x in 0..WAVE_W {
+                let sample = apu.scope[lane][(apu.scope_pos + x) % SCOPE_LEN].clamp(0.0, 1.0);
+                let y = top + 4 + ((1.0 - sample) * (LANE_H - 10) as f32) as usize;
+                // Draw a vertical segment to the previous point so edges stay connected
+                let (y0, y1) = match prev_y {
+                    Some(p) if p < y => (p, y),
+                    Some(p) => (y, p),
+                    None => (y, y),
+                };
+                for yy in y0..=y1 {
+                    self.buffer[yy * W + x] = color;
+                }
+                prev_y = Some(y);
+            }
+            draw_text(&mut self.buffer, W, LANE_LABELS[lane], 2, top + 2, color, 1);
+        }
+
+        // 2. SPECTRUM of the mixed signal (naive DFT, Hann windowed)
+        let mix: Vec<f32> = (0..SCOPE_LEN)
+            .map(|i| {
+                let s = apu.scope[4][(apu.scope_pos + i) % SCOPE_LEN];
+                let hann = 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / SCOPE_LEN as f32).cos();
+                s * hann
+            })
+            .collect();
+        let bar_w = WAVE_W / SPECTRUM_BINS;
+        for bin in 0..SPECTRUM_BINS {
+            // Skip DC (k = 0) and spread bins over the lower half of the spectrum
+            let k = (bin + 1) as f32 * (SCOPE_LEN / 2) as f32 / SPECTRUM_BINS as f32;
+            let (mut re, mut im) = (0.0f32, 0.0f32);
+            for (n, &s) in mix.iter().enumerate() {
+                let phase = 2.0 * std::f32::consts::PI * k * n as f32 / SCOPE_LEN as f32;
+                re += s * phase.cos();
+                im -= s * phase.sin();
+            }
+            let mag = (re * re + im * im).sqrt() / (SCOPE_LEN as f32 / 4.0);
+            let height = ((mag * SPECTRUM_H as f32) as usize).min(SPECTRUM_H);
+            for y in (SPECTRUM_TOP + SPECTRUM_H - height)..(SPECTRUM_TOP + SPECTRUM_H) {
+                for x in (bin * bar_w)..(bin * bar_w + bar_w - 1) {
+                    self.buffer[y * W + x] = 0xFF55AAFF;
+                }
+            }
+        }
+        draw_text(&mut self.buffer, W, "SPECTRUM", 2, SPECTRUM_TOP, 0xFFAAAAAA, 1);
+
+        // 3. REGISTERS
+        let regs = [
+            ("NR10", apu.nr10), ("NR11", apu.nr11), ("NR12", apu.nr12), ("NR13", apu.nr13), ("NR14", apu.nr14),
+            ("NR21", apu.nr21), ("NR22", apu.nr22), ("NR23", apu.nr23), ("NR24", apu.nr24),
+            ("NR30", apu.nr30), ("NR31", apu.nr31), ("NR32", apu.nr32), ("NR33", apu.nr33), ("NR34", apu.nr34),
+            ("NR41", apu.nr41), ("NR42", apu.nr42), ("NR43", apu.nr43), ("NR44", apu.nr44),
+            ("NR50", apu.nr50), ("NR51", apu.nr51), ("NR52", apu.read_register(0xFF26)),
+        ];
+        for (i, (name, val)) in regs.iter().enumerate() {
+            draw_text(&mut self.buffer, W, &format!("{} {:02X}", name, val), WAVE_W + 16, 4 + i * 11, 0xFFCCCCCC, 1);
+        }
+        let wave_top = 4 + regs.len() * 11 + 8;
+        draw_text(&mut self.buffer, W, "WAVE", WAVE_W + 16, wave_top, 0xFFCCCCCC, 1);
+        for (row, chunk) in apu.wave_ram.chunks(4).enumerate() {
+            let line: String = chunk.iter().map(|b| format!("{:02X}", b)).collect();
+            draw_text(&mut self.buffer, W, &line, WAVE_W + 16, wave_top + 12 + row * 11, 0xFFCCCCCC, 1);
+        }
+
+        self.window.update_with_buffer(&self.buffer, W, H).unwrap();
+    }
+}
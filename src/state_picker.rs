@@ -0,0 +1,82 @@
+// state_picker.rs
+// Grid of the current game's save-state slots, each showing the frame it was
+// saved on and when, drawn over the game area so the slot to load can be
+// checked first.
+use crate::draw_text;
+use crate::menu::NavInput;
+use crate::savestate::{Preview, format_time};
+use crate::theme::Theme;
+
+const COLS: usize = 4;
+const CELL_W: usize = 160;
+const CELL_H: usize = 180;
+// Thumbnails are 4/5 of the real frame size
+const THUMB_W: usize = 128;
+const THUMB_H: usize = 115;
+
+pub enum PickerResult {
+    Open,
+    Picked(u32),
+    Cancelled,
+}
+
+pub struct StatePicker {
+    slots: Vec<Option<Preview>>, // Index 0 = slot 1
+    focus: usize,
+}
+
+impl StatePicker {
+    pub fn new(paths: impl Iterator<Item = String>, slot: u32) -> Self {
+        let slots: Vec<_> = paths.map(|p| Preview::from_file(&p).ok()).collect();
+        let focus = (slot as usize).saturating_sub(1).min(slots.len() - 1);
+        Self { slots, focus }
+    }
+
+    pub fn nav(&mut self, nav: NavInput) -> PickerResult {
+        let len = self.slots.len();
+        match nav {
+            NavInput::Left => self.focus = (self.focus + len - 1) % len,
+            NavInput::Right => self.focus = (self.focus + 1) % len,
+            NavInput::Up => self.focus = self.focus.saturating_sub(COLS),
+            NavInput::Down => self.focus = (self.focus + COLS).min(len - 1),
+            NavInput::Confirm if self.slots[self.focus].is_some() => return PickerResult::Picked(self.focus as u32 + 1),
+            NavInput::Confirm => {}
+            NavInput::Cancel => return PickerResult::Cancelled,
+        }
+        PickerResult::Open
+    }
+
+    pub fn draw(&self, buffer: &mut [u32], width: usize, top: usize, height: usize, theme: &Theme) {
+        for p in &mut buffer[top * width..(top + height) * width] {
+            *p = theme.panel;
+        }
+        draw_text(buffer, width, "LOAD STATE", 8, top + 4, theme.heading, 1);
+
+        for (i, slot) in self.slots.iter().enumerate() {
+            let x0 = (i % COLS) * CELL_W;
+            let y0 = top + 16 + (i / COLS) * CELL_H;
+            if i == self.focus {
+                for y in y0..y0 + CELL_H - 4 {
+                    for p in &mut buffer[y * width + x0 + 4..y * width + x0 + CELL_W - 4] {
+                        *p = theme.focus;
+                    }
+                }
+            }
+
+            let (tx, ty) = (x0 + (CELL_W - THUMB_W) / 2, y0 + 8);
+            match slot {
+                Some(preview) => {
+                    for y in 0..THUMB_H {
+                        for x in 0..THUMB_W {
+                            let src = (y * 144 / THUMB_H) * 160 + x * 160 / THUMB_W;
+                            buffer[(ty + y) * width + tx + x] = preview.frame[src];
+                        }
+                    }
+                    draw_text(buffer, width, &format_time(preview.time), x0 + 4, ty + THUMB_H + 20, theme.dim, 1);
+                }
+                None => draw_text(buffer, width, "EMPTY", tx + (THUMB_W - 40) / 2, ty + THUMB_H / 2, theme.faint, 1),
+            }
+            draw_text(buffer, width, &format!("SLOT {}", i + 1), tx, ty + THUMB_H + 6, theme.text, 1);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+// stream.rs
+// Spectator streaming: viewers connect over TCP and receive every frame
+// (and optionally the audio) of the running game.
+//
+// Protocol, all integers little-endian:
+//   header  "GBRS" magic, u8 version (1), u8 flags (bit 0 = audio follows),
+//           u32 audio sample rate
+//   packets u8 tag, u32 payload length, payload
+//     'F'   one 160x144 frame as shades 0-3 (0 = lightest), 2 bits per
+//           pixel, 4 pixels per byte, leftmost pixel in the high bits
+//     'A'   the frame's audio as interleaved stereo i16
+//
+// Each viewer has its own writer thread; a viewer that falls behind loses
+// packets instead of holding up the game.
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+
+const VERSION: u8 = 1;
+const AUDIO_RATE: u32 = 4_194_304 / 87; // The APU makes a sample every 87 T-cycles
+const QUEUE_LEN: usize = 8; // Packets a viewer may lag behind before they're dropped
+
+pub struct SpectatorServer {
+    listener: TcpListener,
+    viewers: Vec<SyncSender<Arc<Vec<u8>>>>,
+    audio: bool,
+}
+
+impl SpectatorServer {
+    pub fn bind(port: u16, audio: bool) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        println!("✓ Spectator stream on port {}", port);
+        Ok(Self { listener, viewers: Vec::new(), audio })
+    }
+
+    // Picks up new viewers; returns their addresses for the OSD
+    pub fn accept(&mut self) -> Vec<String> {
+        let mut joined = Vec::new();
+        while let Ok((stream, addr)) = self.listener.accept() {
+            let (tx, rx) = mpsc::sync_channel(QUEUE_LEN);
+            let mut header = b"GBRS".to_vec();
+            header.push(VERSION);
+            header.push(self.audio as u8);
+            header.extend_from_slice(&AUDIO_RATE.to_le_bytes());
+            if tx.try_send(Arc::new(header)).is_ok() {
+                std::thread::spawn(move || Self::serve(stream, rx));
+                self.viewers.push(tx);
+                joined.push(addr.to_string());
+            }
+        }
+        joined
+    }
+
+    fn serve(mut stream: TcpStream, rx: Receiver<Arc<Vec<u8>>>) {
+        let _ = stream.set_nonblocking(false);
+        let _ = stream.set_nodelay(true);
+        for packet in rx {
+            if stream.write_all(&packet).is_err() {
+                break;
+            }
+        }
+    }
+
+    pub fn viewer_count(&self) -> usize {
+        self.viewers.len()
+    }
+
+    // Sends a frame (shades looked up in `palette`) and its audio samples.
+    // Returns how many viewers disconnected.
+    pub fn send_frame(&mut self, frame: &[u32], palette: &[u32; 4], samples: &[f32]) -> usize {
+        if self.viewers.is_empty() {
+            return 0;
+        }
+        let mut shades = vec![0u8; frame.len() / 4];
+        for (i, &pixel) in frame.iter().enumerate() {
+            let shade = palette.iter().position(|&c| c == pixel).unwrap_or(0) as u8;
+            shades[i / 4] |= shade << (6 - 2 * (i % 4));
+        }
+        let mut packets = vec![Arc::new(Self::packet(b'F', &shades))];
+        if self.audio {
+            let pcm: Vec<u8> = samples
+                .iter()
+                .flat_map(|&s| ((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes())
+                .collect();
+            packets.push(Arc::new(Self::packet(b'A', &pcm)));
+        }
+
+        // A full queue just skips this frame; a closed one means the viewer left
+        let before = self.viewers.len();
+        self.viewers.retain(|tx| {
+            packets.iter().all(|p| !matches!(tx.try_send(p.clone()), Err(mpsc::TrySendError::Disconnected(_))))
+        });
+        before - self.viewers.len()
+    }
+
+    fn packet(tag: u8, payload: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(payload.len() + 5);
+        out.push(tag);
+        out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        out.extend_from_slice(payload);
+        out
+    }
+}
@@ -0,0 +1,134 @@
+// test_roms.rs
+// Runs a folder of accuracy test ROMs headlessly and prints a pass/fail table.
+// Results are detected the way each suite reports them:
+//   - blargg: "Passed" / "Failed" printed over the serial port
+//   - mooneye: LD B,B with B,C,D,E,H,L = 3,5,8,13,21,34 on success or all
+//     0x42 on failure (the same bytes are also sent over serial)
+//   - anything else (dmg-acid2, ...): the final framebuffer hash, checked
+//     against a `<rom name>.hash` file next to the ROM if there is one
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use crate::EmulatorState;
+use crate::cli::Args;
+use crate::headless::hash_frame;
+use crate::mmu::Model;
+use gb_rs::error::CoreError;
+
+const FIBONACCI: [u8; 6] = [3, 5, 8, 13, 21, 34];
+// Long enough for cpu_instrs (about a minute of emulated time)
+const DEFAULT_FRAMES: u64 = 60 * 60 * 2;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Pass,
+    Fail,
+    Timeout,
+    Crash,
+    NoReference, // Hash-checked ROM without a .hash file
+}
+
+impl Outcome {
+    fn label(self) -> &'static str {
+        match self {
+            Outcome::Pass => "PASS",
+            Outcome::Fail => "FAIL",
+            Outcome::Timeout => "TIMEOUT",
+            Outcome::Crash => "CRASH",
+            Outcome::NoReference => "NO REF",
+        }
+    }
+}
+
+pub fn find_roms(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    let mut paths: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            find_roms(&path, out);
+        } else if path.extension().is_some_and(|e| e == "gb" || e == "gbc") {
+            out.push(path);
+        }
+    }
+}
+
+// Pass/fail as reported by the ROM itself, None while still running
+fn check(emu: &EmulatorState) -> Option<Outcome> {
+    let serial = emu.cpu.bus.serial_log.as_deref().unwrap_or_default();
+    let text = String::from_utf8_lossy(serial);
+    if text.contains("Passed") || serial.ends_with(&FIBONACCI) {
+        return Some(Outcome::Pass);
+    }
+    if text.contains("Failed") || serial.ends_with(&[0x42; 6]) {
+        return Some(Outcome::Fail);
+    }
+    let r = &emu.cpu.registers;
+    let regs = [r.b, r.c, r.d, r.e, r.h, r.l];
+    if regs == FIBONACCI {
+        return Some(Outcome::Pass);
+    }
+    if regs == [0x42; 6] {
+        return Some(Outcome::Fail);
+    }
+    None
+}
+
+fn run_one(path: &Path, model: Model, frames: u64) -> (Outcome, u64) {
+    let rom = path.to_string_lossy().to_string();
+    let reference = fs::read_to_string(path.with_extension("hash")).ok();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let Ok(mut emu) = EmulatorState::load_rom(&rom, model, crate::ppu::PPU::new().palette) else {
+            return (Outcome::Crash, 0);
+        };
+        emu.detach_battery();
+        emu.cpu.bus.serial_log = Some(Vec::new());
+        for frame in 1..=frames {
+            emu.run_frame(1, &mut || 0xFF);
+            emu.cpu.bus.apu.sample_buffer.clear();
+            // EmulatorState catches panics in the core itself
+            if matches!(emu.fault, Some(CoreError::Internal { .. })) {
+                return (Outcome::Crash, frame);
+            }
+            if reference.is_none() && let Some(outcome) = check(&emu) {
+                return (outcome, frame);
+            }
+        }
+        let hash = format!("{:016x}", hash_frame(&emu.cpu.ppu.frame_buffer));
+        let outcome = match &reference {
+            Some(expected) if expected.trim() == hash => Outcome::Pass,
+            Some(_) => Outcome::Fail,
+            None if emu.cpu.bus.serial_log.as_ref().is_some_and(|s| s.is_empty()) => {
+                println!("{}: final frame hash {}", rom, hash);
+                Outcome::NoReference
+            }
+            None => Outcome::Timeout,
+        };
+        (outcome, frames)
+    }));
+    result.unwrap_or((Outcome::Crash, 0))
+}
+
+pub fn run(args: &Args) -> i32 {
+    let Some(dir) = &args.test_roms else { return 2 };
+    let mut roms = Vec::new();
+    find_roms(Path::new(dir), &mut roms);
+    if roms.is_empty() {
+        eprintln!("✗ No ROMs found in '{}'", dir);
+        return 1;
+    }
+
+    let model = args.model.unwrap_or(Model::Dmg);
+    let frames = args.frames.unwrap_or(DEFAULT_FRAMES);
+    let results: Vec<_> = roms.iter().map(|path| (path, run_one(path, model, frames))).collect();
+
+    let width = results.iter().map(|(p, _)| p.to_string_lossy().len()).max().unwrap_or(0);
+    println!("\n{:<width$}  {:<7}  FRAMES", "ROM", "RESULT");
+    for (path, (outcome, frames)) in &results {
+        println!("{:<width$}  {:<7}  {}", path.to_string_lossy(), outcome.label(), frames);
+    }
+    let passed = results.iter().filter(|(_, (o, _))| *o == Outcome::Pass).count();
+    println!("\n{}/{} passed", passed, results.len());
+    if passed == results.len() { 0 } else { 1 }
+}
@@ -0,0 +1,75 @@
+// theme.rs
+// Accessibility: DMG shade presets that stay distinguishable with common
+// color-vision deficiencies, and the colors the menus and overlays are
+// drawn with (normal or high contrast).
+
+// Shades for color IDs 0-3, lightest first. Besides the hue choices every
+// preset keeps a clear brightness step between neighbouring shades.
+pub const PALETTE_PRESETS: [(&str, [u32; 4]); 5] = [
+    ("GRAY", [0xFFFFFFFF, 0xFFAAAAAA, 0xFF555555, 0xFF000000]),
+    ("DMG GREEN", [0xFFE0F8D0, 0xFF88C070, 0xFF346856, 0xFF081820]),
+    // Blue/yellow: told apart without red or green cones
+    ("PROTAN/DEUTAN", [0xFFFFF8D0, 0xFFE8B040, 0xFF3C64C8, 0xFF101438]),
+    // Red/cyan: told apart without blue cones
+    ("TRITAN", [0xFFF0FFFF, 0xFF60D0D0, 0xFFC83C3C, 0xFF280808]),
+    ("HIGH CONTRAST", [0xFFFFFFFF, 0xFFC8C8C8, 0xFF404040, 0xFF000000]),
+];
+
+// Preset after the one matching `palette` (the first one for a custom palette)
+pub fn next_palette(palette: [u32; 4]) -> (&'static str, [u32; 4]) {
+    let i = PALETTE_PRESETS.iter().position(|(_, p)| *p == palette).map_or(0, |i| (i + 1) % PALETTE_PRESETS.len());
+    PALETTE_PRESETS[i]
+}
+
+pub struct Theme {
+    pub strip: [u32; 2], // Menu strip rows
+    pub window: u32,     // Settings window background
+    pub panel: u32,      // ROM browser and state list background
+    pub focus: u32,      // Focused row or cell
+    pub heading: u32,
+    pub text: u32,
+    pub dim: u32,    // Secondary text
+    pub faint: u32,  // Placeholders and minor buttons
+    pub accent: u32, // Hovered or focused menu entries
+    pub folder: u32,
+    pub solid_backdrop: bool, // Overlay text sits on black instead of the dimmed game
+}
+
+pub const NORMAL: Theme = Theme {
+    strip: [0xFF222222, 0xFF111111],
+    window: 0xFF202020,
+    panel: 0xFF101018,
+    focus: 0xFF304060,
+    heading: 0xFFFFFF00,
+    text: 0xFFFFFFFF,
+    dim: 0xFFAAAAAA,
+    faint: 0xFF888888,
+    accent: 0xFF55FF55,
+    folder: 0xFF88CCFF,
+    solid_backdrop: false,
+};
+
+pub const HIGH_CONTRAST: Theme = Theme {
+    strip: [0xFF000000, 0xFF000000],
+    window: 0xFF000000,
+    panel: 0xFF000000,
+    focus: 0xFF0040C0,
+    heading: 0xFFFFFF00,
+    text: 0xFFFFFFFF,
+    dim: 0xFFFFFFFF,
+    faint: 0xFFD0D0D0,
+    accent: 0xFFFFFF00,
+    folder: 0xFF00FFFF,
+    solid_backdrop: true,
+};
+
+impl Theme {
+    pub fn get(high_contrast: bool) -> &'static Theme {
+        if high_contrast { &HIGH_CONTRAST } else { &NORMAL }
+    }
+
+    // Background for overlay text drawn over the game
+    pub fn backdrop(&self, pixel: u32) -> u32 {
+        if self.solid_backdrop { 0xFF000000 } else { 0xFF000000 | ((pixel >> 2) & 0x3F3F3F) }
+    }
+}
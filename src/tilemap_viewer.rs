@@ -0,0 +1,90 @@
+// tilemap_viewer.rs
+// Debug window with both 32x32 tile maps in full: the one the background
+// uses, with the 160x144 the screen shows at SCX/SCY outlined (wrapping
+// around the edges like the PPU does), and the one the window uses, with
+// the part WX/WY put on screen outlined. Scroll bugs show up as an outline
+// in the wrong place.
+use minifb::{Scale, Window, WindowOptions};
+
+use crate::draw_text;
+use crate::mmu::MMU;
+use crate::ppu::PPU;
+
+const MAP: usize = 256;
+const GAP: usize = 16;
+const W: usize = MAP * 2 + GAP * 3;
+const H: usize = 24 + MAP + 28;
+const VIEWPORT_COLOR: u32 = 0xFFFF3333;
+const WINDOW_COLOR: u32 = 0xFF33AAFF;
+
+pub struct TilemapViewer {
+    window: Window,
+    buffer: Vec<u32>,
+}
+
+impl TilemapViewer {
+    pub fn new() -> Self {
+        let mut window = Window::new(
+            "Tile Maps",
+            W, H,
+            WindowOptions { resize: false, scale: Scale::X2, ..WindowOptions::default() },
+        ).unwrap();
+        window.limit_update_rate(None);
+        Self { window, buffer: vec![0; W * H] }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    // A map's pixels with their top left corner at `left`
+    fn draw_map(&mut self, pixels: &[u32], left: usize) {
+        for y in 0..MAP {
+            let row = (24 + y) * W + left;
+            self.buffer[row..row + MAP].copy_from_slice(&pixels[y * MAP..(y + 1) * MAP]);
+        }
+    }
+
+    // Outline of a w x h rectangle at (x, y) on the map at `left`, wrapping
+    // past the right and bottom edges
+    fn outline(&mut self, left: usize, x: usize, y: usize, w: usize, h: usize, color: u32) {
+        let mut plot = |dx: usize, dy: usize| {
+            self.buffer[(24 + (y + dy) % MAP) * W + left + (x + dx) % MAP] = color;
+        };
+        for dx in 0..w {
+            plot(dx, 0);
+            plot(dx, h - 1);
+        }
+        for dy in 0..h {
+            plot(0, dy);
+            plot(w - 1, dy);
+        }
+    }
+
+    pub fn update(&mut self, ppu: &PPU, bus: &MMU) {
+        for p in self.buffer.iter_mut() { *p = 0xFF101010; }
+
+        let bg_map = if bus.lcdc & 0x08 != 0 { 0x9C00 } else { 0x9800 };
+        let win_map = if bus.lcdc & 0x40 != 0 { 0x9C00 } else { 0x9800 };
+        let (bg_left, win_left) = (GAP, GAP * 2 + MAP);
+        self.draw_map(&ppu.render_tile_map(bus, bg_map), bg_left);
+        self.draw_map(&ppu.render_tile_map(bus, win_map), win_left);
+
+        self.outline(bg_left, bus.scx as usize, bus.scy as usize, 160, 144, VIEWPORT_COLOR);
+        // The window covers the screen right of WX-7 and below WY, showing
+        // its map from the top left corner
+        let window_on = bus.lcdc & 0x20 != 0;
+        let (w, h) = (167usize.saturating_sub(bus.wx.max(7) as usize), 144usize.saturating_sub(bus.wy as usize));
+        if window_on && w > 0 && h > 0 {
+            self.outline(win_left, 0, 0, w, h, WINDOW_COLOR);
+        }
+
+        draw_text(&mut self.buffer, W, &format!("BG {:04X}", bg_map), bg_left, 8, 0xFFCCCCCC, 1);
+        draw_text(&mut self.buffer, W, &format!("WINDOW {:04X}", win_map), win_left, 8, 0xFFCCCCCC, 1);
+        draw_text(&mut self.buffer, W, &format!("SCX {:3} SCY {:3}", bus.scx, bus.scy), bg_left, 24 + MAP + 10, VIEWPORT_COLOR, 1);
+        let window_text = if window_on { format!("WX {:3} WY {:3}", bus.wx, bus.wy) } else { "WINDOW OFF".to_string() };
+        draw_text(&mut self.buffer, W, &window_text, win_left, 24 + MAP + 10, WINDOW_COLOR, 1);
+
+        self.window.update_with_buffer(&self.buffer, W, H).unwrap();
+    }
+}
@@ -0,0 +1,48 @@
+// timeline.rs
+// Register write timeline for the event viewer: each write to an LCD
+// register (0xFF40-0xFF4B) with the scanline and dot the PPU was at, so
+// raster effects (mid-frame scroll, palette or window changes) show up where
+// they land on the screen. Kept a frame at a time; the PPU starts a new one
+// when LY wraps to 0.
+#[derive(Clone, Copy, Debug)]
+pub struct RegWrite {
+    pub line: u8,
+    pub dot: u16, // 0-455 into the line
+    pub addr: u16,
+    pub value: u8,
+}
+
+#[derive(Default)]
+pub struct Timeline {
+    current: Vec<RegWrite>,
+    pub last_frame: Vec<RegWrite>, // The frame finished last, for drawing
+}
+
+impl Timeline {
+    pub fn record(&mut self, write: RegWrite) {
+        self.current.push(write);
+    }
+
+    pub fn new_frame(&mut self) {
+        self.last_frame = std::mem::take(&mut self.current);
+    }
+}
+
+// Short names for the registers recorded
+pub fn register_name(addr: u16) -> &'static str {
+    match addr {
+        0xFF40 => "LCDC",
+        0xFF41 => "STAT",
+        0xFF42 => "SCY",
+        0xFF43 => "SCX",
+        0xFF44 => "LY",
+        0xFF45 => "LYC",
+        0xFF46 => "DMA",
+        0xFF47 => "BGP",
+        0xFF48 => "OBP0",
+        0xFF49 => "OBP1",
+        0xFF4A => "WY",
+        0xFF4B => "WX",
+        _ => "?",
+    }
+}
@@ -0,0 +1,65 @@
+// uninit.rs
+// Uninitialized RAM detection. WRAM and HRAM start out "poisoned" and each
+// byte is cleared by its first write; a CPU read (or an OAM DMA) of a byte
+// that is still poisoned gets a value the game never set, which on real
+// hardware is whatever the RAM powered up with. Those reads are logged to
+// stderr with the PC and address, once for each pair, so homebrew that only
+// works because emulators zero RAM shows where it goes wrong.
+use std::collections::HashSet;
+
+const HRAM: usize = 0x2000; // Where HRAM starts in `written`, after WRAM
+
+pub struct UninitReads {
+    written: Vec<bool>, // Per byte of WRAM, then HRAM
+    reported: HashSet<(u16, u16)>, // (PC, address) pairs already logged
+    pub pc: u16, // Set by the CPU at the start of each instruction
+}
+
+// A WRAM (or echo RAM) or HRAM address's byte in `written`
+fn index(addr: u16) -> Option<usize> {
+    match addr {
+        0xC000..=0xFDFF => Some(((addr - 0xC000) & 0x1FFF) as usize),
+        0xFF80..=0xFFFE => Some(HRAM + (addr - 0xFF80) as usize),
+        _ => None,
+    }
+}
+
+impl Default for UninitReads {
+    fn default() -> Self {
+        Self { written: vec![false; HRAM + 0x7F], reported: HashSet::new(), pc: 0 }
+    }
+}
+
+impl UninitReads {
+    pub fn write(&mut self, addr: u16) {
+        if let Some(i) = index(addr) {
+            self.written[i] = true;
+        }
+    }
+
+    // Counts all of RAM as set, for when it's replaced wholesale (a state
+    // load) and what the game wrote is no longer known
+    pub fn initialize_all(&mut self) {
+        self.written.fill(true);
+    }
+
+    pub fn read(&mut self, addr: u16, value: u8) {
+        let Some(i) = index(addr) else { return };
+        if !self.written[i] && self.reported.insert((self.pc, addr)) {
+            eprintln!("⚠ Uninitialized read of ${:04X} (${:02X}) at PC ${:04X}", addr, value, self.pc);
+        }
+    }
+
+    // An OAM DMA from `source`, logged once for the whole transfer
+    pub fn dma(&mut self, source: u16) {
+        let poisoned = (source..source + 0xA0).filter(|&addr| index(addr).is_some_and(|i| !self.written[i])).count();
+        if poisoned > 0 && self.reported.insert((self.pc, source)) {
+            eprintln!("⚠ OAM DMA from ${:04X} copied {} uninitialized byte(s) at PC ${:04X}", source, poisoned, self.pc);
+        }
+    }
+
+    // Distinct (PC, address) pairs logged so far
+    pub fn count(&self) -> usize {
+        self.reported.len()
+    }
+}
@@ -0,0 +1,73 @@
+// vgm.rs
+// Records APU register writes (0xFF10-0xFF3F) into a VGM 1.61 file so the
+// music can be replayed by chiptune tools outside the emulator.
+use std::fs;
+
+const GB_CLOCK: u64 = 4_194_304;
+const VGM_RATE: u64 = 44_100;
+const HEADER_SIZE: usize = 0x100;
+
+pub struct VgmRecorder {
+    pub path: String,
+    data: Vec<u8>,
+    total_cycles: u64,    // T-cycles since recording started
+    samples_written: u64, // 44.1kHz samples already covered by wait commands
+}
+
+impl VgmRecorder {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            data: Vec::with_capacity(64 * 1024),
+            total_cycles: 0,
+            samples_written: 0,
+        }
+    }
+
+    // Called from APU::tick so timestamps follow emulated time, not host time
+    pub fn advance(&mut self, cycles: u32) {
+        self.total_cycles += cycles as u64;
+    }
+
+    pub fn log_write(&mut self, addr: u16, val: u8) {
+        if !(0xFF10..=0xFF3F).contains(&addr) { return; }
+        self.flush_wait();
+        // 0xB3 aa dd: Game Boy DMG write, aa = register offset from 0xFF10
+        self.data.push(0xB3);
+        self.data.push((addr - 0xFF10) as u8);
+        self.data.push(val);
+    }
+
+    fn flush_wait(&mut self) {
+        let target = self.total_cycles * VGM_RATE / GB_CLOCK;
+        let mut pending = target - self.samples_written;
+        while pending > 0 {
+            let n = pending.min(0xFFFF) as u16;
+            // 0x61 nnnn: wait n samples
+            self.data.push(0x61);
+            self.data.extend_from_slice(&n.to_le_bytes());
+            pending -= n as u64;
+        }
+        self.samples_written = target;
+    }
+
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.flush_wait();
+        self.data.push(0x66); // End of sound data
+
+        let mut file = vec![0u8; HEADER_SIZE];
+        file[0x00..0x04].copy_from_slice(b"Vgm ");
+        let eof_offset = (HEADER_SIZE + self.data.len() - 4) as u32;
+        file[0x04..0x08].copy_from_slice(&eof_offset.to_le_bytes());
+        file[0x08..0x0C].copy_from_slice(&0x0000_0161u32.to_le_bytes());
+        file[0x18..0x1C].copy_from_slice(&(self.samples_written as u32).to_le_bytes());
+        let data_offset = (HEADER_SIZE - 0x34) as u32; // Relative to 0x34
+        file[0x34..0x38].copy_from_slice(&data_offset.to_le_bytes());
+        file[0x80..0x84].copy_from_slice(&(GB_CLOCK as u32).to_le_bytes());
+        file.extend_from_slice(&self.data);
+
+        fs::write(&self.path, &file)?;
+        println!("✓ VGM written to '{}' ({:.1}s)", self.path, self.samples_written as f32 / VGM_RATE as f32);
+        Ok(())
+    }
+}
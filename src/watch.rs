@@ -0,0 +1,50 @@
+// watch.rs
+// Hot reload for homebrew development: notices when the loaded ROM file is
+// rewritten by an assembler or build script so it can be reloaded. Polls the
+// file's modification time and size; a change only counts once they've held
+// still for a poll, so a half-written ROM isn't picked up.
+use std::fs;
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL: Duration = Duration::from_millis(250);
+
+type Stamp = Option<(SystemTime, u64)>;
+
+pub struct RomWatcher {
+    pub path: String,
+    seen: Stamp,
+    pending: Stamp,
+    next_poll: Instant,
+}
+
+impl RomWatcher {
+    pub fn new(path: &str) -> Self {
+        Self { path: path.to_string(), seen: Self::stamp(path), pending: None, next_poll: Instant::now() + POLL }
+    }
+
+    fn stamp(path: &str) -> Stamp {
+        let meta = fs::metadata(path).ok()?;
+        Some((meta.modified().ok()?, meta.len()))
+    }
+
+    // True once per finished rewrite of the file
+    pub fn changed(&mut self) -> bool {
+        if Instant::now() < self.next_poll {
+            return false;
+        }
+        self.next_poll = Instant::now() + POLL;
+        let stamp = Self::stamp(&self.path);
+        if stamp.is_none() || stamp == self.seen {
+            // Missing (mid-rewrite) or untouched
+            self.pending = None;
+            return false;
+        }
+        if self.pending == stamp {
+            self.seen = stamp;
+            self.pending = None;
+            return true;
+        }
+        self.pending = stamp;
+        false
+    }
+}
@@ -0,0 +1,90 @@
+// ppu_raster.rs
+// Mid-scanline register writes: a small program waits for line 60, writes
+// BGP or SCX partway through drawing it and puts the old value back on line
+// 61. Only the pixels after the write change, so line 60 is the line above
+// it up to some column and the changed version from there on. The column
+// follows from the cycle counts of the polling loop and the NOPs; with the
+// PPU only catching up after each instruction it came out 12-16 pixels
+// early, as the write showed up before the instruction's dots were drawn.
+use gb_rs::cpu::CPU;
+use gb_rs::mmu::{MMU, Model};
+
+const LINE: u8 = 60;
+const FRAME_CYCLES: u64 = 70224;
+
+// DI; set `reg` to `before`, then each frame: wait for LINE, NOPs to get
+// into mode 3, set `reg` to `during`, wait for the next line and set it back
+fn raster_rom(reg: u8, before: u8, during: u8) -> Vec<u8> {
+    let mut code = vec![0xF3, 0x3E, before, 0xE0, reg];
+    let wait = |code: &mut Vec<u8>, line: u8| code.extend_from_slice(&[0xF0, 0x44, 0xFE, line, 0x20, 0xFA]);
+    let top = 0x100 + code.len();
+    wait(&mut code, LINE);
+    code.extend_from_slice(&[0x00; 20]);
+    code.extend_from_slice(&[0x3E, during, 0xE0, reg]);
+    wait(&mut code, LINE + 1);
+    code.extend_from_slice(&[0x3E, before, 0xE0, reg]);
+    let back = top as i32 - (0x100 + code.len() as i32 + 2);
+    code.extend_from_slice(&[0x18, back as i8 as u8]);
+
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x100 + code.len()].copy_from_slice(&code);
+    rom
+}
+
+fn run(rom: Vec<u8>, setup: impl FnOnce(&mut MMU)) -> CPU {
+    let mut mmu = MMU::new(rom, "raster.gb", Model::Dmg);
+    setup(&mut mmu);
+    let mut cpu = CPU::new(mmu);
+    let mut cycles = 0;
+    while cycles < FRAME_CYCLES * 3 {
+        cycles += cpu.step().expect("only known opcodes") as u64;
+        cycles += cpu.handle_interrupts() as u64;
+    }
+    cpu
+}
+
+fn line(cpu: &CPU, ly: u8) -> &[u32] {
+    &cpu.ppu.frame_buffer[ly as usize * 160..(ly as usize + 1) * 160]
+}
+
+// Where line 60 starts matching `after`, having matched line 59 before it
+fn split(cpu: &CPU, after: impl Fn(usize) -> u32) -> usize {
+    let above = line(cpu, LINE - 1);
+    let changed = line(cpu, LINE);
+    let x = (0..160).find(|&x| changed[x] != above[x]).expect("the write changed line 60");
+    for (i, &p) in changed.iter().enumerate().skip(x) {
+        assert_eq!(p, after(i), "column {} after the write", i);
+    }
+    x
+}
+
+#[test]
+fn bgp_write_mid_line() {
+    // Every pixel is color 0: black through BGP $FF, white through $00
+    let cpu = run(raster_rom(0x47, 0xFF, 0x00), |_| {});
+    let [white, _, _, black] = cpu.ppu.palette;
+    assert_eq!(split(&cpu, |_| white), 52);
+    assert!(line(&cpu, LINE - 1).iter().all(|&p| p == black));
+    assert!(line(&cpu, LINE + 1).iter().all(|&p| p == black));
+}
+
+#[test]
+fn scx_write_mid_line() {
+    // Alternating white (tile 0) and black (tile 1) columns of tiles; SCX 8
+    // swaps them from the next tile the fetcher reads
+    let cpu = run(raster_rom(0x43, 0x00, 0x08), |mmu| {
+        for addr in 0x8010..0x8020 {
+            mmu.write_byte(addr, 0xFF);
+        }
+        for i in 0..0x400u16 {
+            mmu.write_byte(0x9800 + i, (i & 1) as u8);
+        }
+        mmu.write_byte(0xFF47, 0xE4);
+    });
+    let [white, _, _, black] = cpu.ppu.palette;
+    let column = |x: usize| if (x / 8).is_multiple_of(2) { white } else { black };
+    // The fetcher picks up SCX a tile at a time, so the split is on a tile
+    // edge past where the write was
+    assert_eq!(split(&cpu, |x| column(x + 8)), 64);
+    assert!(line(&cpu, LINE + 1).iter().enumerate().all(|(x, &p)| p == column(x)));
+}
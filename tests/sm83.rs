@@ -0,0 +1,144 @@
+// sm83.rs
+// The community SM83 single-step tests (SingleStepTests/sm83 on GitHub: one
+// JSON file per opcode, each with a thousand random cases) against the CPU
+// on flat 64 KiB RAM. After each instruction the registers, flags, IME,
+// memory and cycle count are compared, which catches flag bugs in
+// DAA/SBC/ADC and the like without a test ROM.
+//
+// The tests aren't in the repository, so the test is ignored by default.
+// Point SM83_TESTS at their v1 directory and ask for it, with the feature
+// that gives the MMU its flat memory:
+//
+//   SM83_TESTS=path/to/sm83/v1 cargo test --features sm83-tests --test sm83 -- --ignored
+//
+// Only the cycle count is checked, not which address each cycle touched.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use gb_rs::cpu::CPU;
+use gb_rs::mmu::{MMU, Model};
+
+#[derive(Deserialize)]
+struct State {
+    pc: u16,
+    sp: u16,
+    a: u8,
+    b: u8,
+    c: u8,
+    d: u8,
+    e: u8,
+    f: u8,
+    h: u8,
+    l: u8,
+    ime: Option<u8>,
+    ram: Vec<(u16, u8)>,
+}
+
+#[derive(Deserialize)]
+struct Test {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    cycles: Vec<serde_json::Value>, // One per M-cycle
+}
+
+fn find_tests(dir: &Path) -> Vec<PathBuf> {
+    let entries = fs::read_dir(dir).unwrap_or_else(|e| panic!("can't read '{}': {}", dir.display(), e));
+    let mut paths: Vec<_> = entries.flatten().map(|e| e.path()).filter(|p| p.extension().is_some_and(|e| e == "json")).collect();
+    paths.sort();
+    paths
+}
+
+fn memory(cpu: &mut CPU) -> &mut Vec<u8> {
+    cpu.bus.flat.as_mut().expect("set up with flat memory")
+}
+
+// What differs from the expected state after the test, None if it passed
+fn run_test(cpu: &mut CPU, test: &Test) -> Option<String> {
+    let init = &test.initial;
+    let r = &mut cpu.registers;
+    (r.a, r.b, r.c, r.d, r.e, r.f, r.h, r.l, r.pc, r.sp) = (init.a, init.b, init.c, init.d, init.e, init.f, init.h, init.l, init.pc, init.sp);
+    cpu.ime = init.ime == Some(1);
+    cpu.halted = false;
+    cpu.locked = false;
+    cpu.interrupt_enable_delay = false;
+    for &(addr, value) in &init.ram {
+        memory(cpu)[addr as usize] = value;
+    }
+
+    let result = cpu.step();
+
+    let want = &test.expected;
+    let r = &cpu.registers;
+    let mut diffs = Vec::new();
+    for (name, got, expected) in [("A", r.a, want.a), ("F", r.f, want.f), ("B", r.b, want.b), ("C", r.c, want.c), ("D", r.d, want.d), ("E", r.e, want.e), ("H", r.h, want.h), ("L", r.l, want.l)] {
+        if got != expected {
+            diffs.push(format!("{} ${:02X}, expected ${:02X}", name, got, expected));
+        }
+    }
+    for (name, got, expected) in [("PC", r.pc, want.pc), ("SP", r.sp, want.sp)] {
+        if got != expected {
+            diffs.push(format!("{} ${:04X}, expected ${:04X}", name, got, expected));
+        }
+    }
+    if let Some(ime) = want.ime && cpu.ime != (ime == 1) {
+        diffs.push(format!("IME {}, expected {}", cpu.ime as u8, ime));
+    }
+    for &(addr, expected) in &want.ram {
+        let got = memory(cpu)[addr as usize];
+        if got != expected {
+            diffs.push(format!("(${:04X}) ${:02X}, expected ${:02X}", addr, got, expected));
+        }
+    }
+    match result {
+        Ok(cycles) if cycles as usize != test.cycles.len() * 4 => {
+            diffs.push(format!("{} cycles, expected {}", cycles, test.cycles.len() * 4));
+        }
+        Ok(_) => {}
+        Err(e) => diffs = vec![e.to_string().lines().next().unwrap_or_default().to_string()],
+    }
+
+    // Clean for the next test
+    for &(addr, _) in init.ram.iter().chain(&want.ram) {
+        memory(cpu)[addr as usize] = 0;
+    }
+    (!diffs.is_empty()).then(|| diffs.join(", "))
+}
+
+// The first failing case in an opcode file, None if they all pass
+fn run_file(cpu: &mut CPU, path: &Path) -> Option<String> {
+    let text = fs::read_to_string(path).unwrap_or_else(|e| panic!("can't read '{}': {}", path.display(), e));
+    let tests: Vec<Test> = serde_json::from_str(&text).unwrap_or_else(|e| panic!("can't parse '{}': {}", path.display(), e));
+    let mut first_failure = None;
+    let mut failures = 0;
+    for test in &tests {
+        if let Some(diff) = run_test(cpu, test) {
+            failures += 1;
+            first_failure.get_or_insert_with(|| format!("{}: {}", test.name, diff));
+        }
+    }
+    first_failure.map(|failure| format!("{}/{} failed, first: {}", failures, tests.len(), failure))
+}
+
+#[test]
+#[ignore = "needs the SM83 single-step tests, see SM83_TESTS above"]
+fn sm83_single_step() {
+    let dir = std::env::var("SM83_TESTS").expect("SM83_TESTS should be the path to the tests' v1 directory");
+    let files = find_tests(Path::new(&dir));
+    assert!(!files.is_empty(), "no .json tests found in '{}'", dir);
+
+    let mut mmu = MMU::new(vec![0; 0x8000], "sm83.gb", Model::Dmg);
+    mmu.flat = Some(vec![0; 0x10000]);
+    let mut cpu = CPU::new(mmu);
+    let failed: Vec<String> = files
+        .iter()
+        .filter_map(|path| {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            run_file(&mut cpu, path).map(|failure| format!("{}: {}", name, failure))
+        })
+        .collect();
+    assert!(failed.is_empty(), "{}/{} opcodes failed:\n{}", failed.len(), files.len(), failed.join("\n"));
+}